@@ -0,0 +1,149 @@
+// the code every rollout & explore runs through. `cargo bench` compares against the last run, so
+// a change that slows these down shows up before it shows up as fewer rollouts per move
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use hanabi::env::{Env, HasEnd};
+use hanabi::flat::{policy, rollout_single_determinization};
+use hanabi::hanabi_env::{determinize_hints, ActionList, CardCollection, HanabiEnv};
+use hanabi::position::Position;
+use hanabi::rollout_rng::{RngKind, RolloutRng};
+use rand::prelude::SliceRandom;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// clued hands make determinize_hints reject & restart, open hands never do
+const CLUED: &str = "R1.B2.Y3.G4.W5 R.RB.?.B.- ?.?.?.?.? 22222 - 8/3 -";
+
+fn opening() -> HanabiEnv {
+    HanabiEnv::random(&mut StdRng::seed_from_u64(0))
+}
+
+fn bench_pop(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    c.bench_function("CardCollection::pop", |b| {
+        b.iter_batched_ref(
+            CardCollection::starting_deck,
+            |deck| deck.pop(&mut rng),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_determinize_hints(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let open = opening();
+    let open = Position {
+        public_info: open.public_info(),
+        private_info: open.private_info(open.current_player()),
+    };
+    let clued = Position::parse(CLUED).unwrap();
+    let mut group = c.benchmark_group("determinize_hints");
+    for (name, position) in [("open", &open), ("clued", &clued)].iter() {
+        let public_info = &position.public_info;
+        // the cards the player could be holding, as sampling sees them
+        let mut unseen = CardCollection::starting_deck();
+        unseen.subtract(&public_info.discard);
+        unseen.remove_fireworks(&public_info.fireworks);
+        unseen.remove_hand(&position.private_info.opponent_hand);
+        group.bench_function(*name, |b| {
+            b.iter_batched_ref(
+                || unseen,
+                |deck| determinize_hints(deck, &public_info.player_hints, &mut rng),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_step(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut action_rng = StdRng::seed_from_u64(1);
+    let env = opening();
+    let actions = env.actions();
+    c.bench_function("HanabiEnv::step", |b| {
+        b.iter_batched_ref(
+            || (env.clone(), *actions.choose(&mut action_rng).unwrap()),
+            |(env, action)| env.step(action, &mut rng),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_actions(c: &mut Criterion) {
+    let env = opening();
+    let mut actions = ActionList::new();
+    c.bench_function("HanabiEnv::actions", |b| {
+        b.iter(|| black_box(&env).actions())
+    });
+    c.bench_function("HanabiEnv::actions_into", |b| {
+        b.iter(|| black_box(&env).actions_into(&mut actions))
+    });
+}
+
+fn bench_rng(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gen_range");
+    for (name, kind) in [("std", RngKind::Std), ("small", RngKind::Small)].iter() {
+        let mut rng = RolloutRng::with_kind(*kind, 0);
+        group.bench_function(*name, |b| b.iter(|| rng.gen_range(0, black_box(20))));
+    }
+    group.finish();
+}
+
+// the same rollouts drawing from ChaCha and from the small rng searches use by default
+fn bench_rollout(c: &mut Criterion) {
+    let env = opening();
+    let mut group = c.benchmark_group("random rollout");
+    for (name, kind) in [("std", RngKind::Std), ("small", RngKind::Small)].iter() {
+        let mut rng = RolloutRng::with_kind(*kind, 0);
+        group.bench_function(*name, |b| {
+            b.iter_batched_ref(
+                || env.clone(),
+                |env| {
+                    while !env.is_over() {
+                        let action = env.random_action(&mut rng);
+                        env.step(&action, &mut rng);
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_policy(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let env = opening();
+    let mut group = c.benchmark_group("policy");
+    group.sample_size(10);
+    for &num_rollouts in [100, 1_000, 10_000].iter() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_rollouts),
+            &num_rollouts,
+            |b, &num_rollouts| {
+                b.iter(|| {
+                    policy(
+                        black_box(env.public_info()),
+                        env.private_info(env.current_player()),
+                        &rollout_single_determinization,
+                        num_rollouts,
+                        &mut rng,
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_pop,
+    bench_determinize_hints,
+    bench_step,
+    bench_actions,
+    bench_rng,
+    bench_rollout,
+    bench_policy
+);
+criterion_main!(benches);
@@ -0,0 +1,182 @@
+use crate::env::HasKey;
+use crate::hanabi_env::{zobrist_key, Card, CardCollection, Fireworks, PublicInfo, RuleSet};
+
+// maps public states to the key used to look up tree nodes, states sharing a key share a node
+pub trait Abstraction<P> {
+    fn key(&self, public_info: &P) -> u64;
+}
+
+// a card that can never be played: its rank is already on the fireworks, or a lower
+// rank of its color has every copy in the discard
+pub fn is_trash(card: Card, fireworks: &Fireworks, discard: &CardCollection) -> bool {
+    is_trash_with(&RuleSet::default(), card, fireworks, discard)
+}
+
+// a card that can still be played but has no other copy left outside the discard
+pub fn is_critical(card: Card, fireworks: &Fireworks, discard: &CardCollection) -> bool {
+    is_critical_with(&RuleSet::default(), card, fireworks, discard)
+}
+
+// is_trash for a game with a different deck, e.g. one with the black suit
+pub fn is_trash_with(
+    rules: &RuleSet,
+    card: Card,
+    fireworks: &Fireworks,
+    discard: &CardCollection,
+) -> bool {
+    let color = card.color_id();
+    let played = fireworks.0[color as usize];
+    if card.suit_id() < played {
+        return true;
+    }
+    let starting = rules.deck();
+    (played..card.suit_id()).any(|suit| {
+        let id = Card::parts_id(color, suit) as usize;
+        discard.counts[id] == starting.counts[id]
+    })
+}
+
+pub fn is_critical_with(
+    rules: &RuleSet,
+    card: Card,
+    fireworks: &Fireworks,
+    discard: &CardCollection,
+) -> bool {
+    let id = card.id() as usize;
+    !is_trash_with(rules, card, fireworks, discard)
+        && discard.counts[id] + 1 == rules.deck().counts[id]
+}
+
+// what a card is still worth to the fireworks, given what's played & discarded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CardClass {
+    // already played, or a lower rank of its color has every copy in the discard
+    Trash,
+    // still needed, and the last copy outside the discard
+    Critical,
+    // still needed, with another copy left to fall back on
+    EventuallyPlayable,
+}
+
+pub fn classify_with(
+    rules: &RuleSet,
+    card: Card,
+    fireworks: &Fireworks,
+    discard: &CardCollection,
+) -> CardClass {
+    let id = card.id() as usize;
+    if is_trash_with(rules, card, fireworks, discard) {
+        CardClass::Trash
+    } else if discard.counts[id] + 1 == rules.deck().counts[id] {
+        CardClass::Critical
+    } else {
+        CardClass::EventuallyPlayable
+    }
+}
+
+// merges states whose discards only differ in which trash cards were thrown away.
+// the number of trash discards is kept since it changes how many cards are left to draw
+pub struct TrashDiscards;
+
+impl Abstraction<PublicInfo> for TrashDiscards {
+    fn key(&self, public_info: &PublicInfo) -> u64 {
+        let mut merged = public_info.clone();
+        let mut num_trash = 0;
+        for id in 0..25 {
+            let card = Card::from_id(id as u8);
+            if is_trash_with(
+                &public_info.rules,
+                card,
+                &public_info.fireworks,
+                &public_info.discard,
+            ) {
+                num_trash += merged.discard.counts[id];
+                merged.discard.counts[id] = 0;
+            }
+        }
+        merged.key() ^ zobrist_key(54, num_trash as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::hanabi_env::{Color, HanabiEnv, Suit};
+    use crate::mcts::MCTS;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_trash_discards_share_keys() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let mut public_info = env.public_info();
+        public_info.fireworks.0[Color::Red as usize] = 2;
+
+        let mut a = public_info.clone();
+        a.discard.add(Card::new(Color::Red, Suit::One));
+        let mut b = public_info.clone();
+        b.discard.add(Card::new(Color::Red, Suit::Two));
+        let mut c = public_info.clone();
+        c.discard.add(Card::new(Color::Red, Suit::Three));
+
+        assert_ne!(a.key(), b.key());
+        assert_eq!(TrashDiscards.key(&a), TrashDiscards.key(&b));
+        assert_ne!(TrashDiscards.key(&a), TrashDiscards.key(&c));
+        assert_ne!(TrashDiscards.key(&a), TrashDiscards.key(&public_info));
+
+        // the last blue 2 is gone, so every blue card above it is trash too
+        let mut discard = CardCollection::empty();
+        discard.add(Card::new(Color::Blue, Suit::Two));
+        discard.add(Card::new(Color::Blue, Suit::Two));
+        assert!(is_trash(
+            Card::new(Color::Blue, Suit::Four),
+            &public_info.fireworks,
+            &discard
+        ));
+        assert!(!is_trash(
+            Card::new(Color::Blue, Suit::One),
+            &public_info.fireworks,
+            &discard
+        ));
+        assert!(is_critical(
+            Card::new(Color::Yellow, Suit::Five),
+            &public_info.fireworks,
+            &discard
+        ));
+        discard.add(Card::new(Color::Green, Suit::Three));
+        assert!(is_critical(
+            Card::new(Color::Green, Suit::Three),
+            &public_info.fireworks,
+            &discard
+        ));
+        assert!(!is_critical(
+            Card::new(Color::Blue, Suit::Four),
+            &public_info.fireworks,
+            &discard
+        ));
+        let class = |color, suit| {
+            let rules = RuleSet::default();
+            classify_with(
+                &rules,
+                Card::new(color, suit),
+                &public_info.fireworks,
+                &discard,
+            )
+        };
+        assert_eq!(class(Color::Blue, Suit::Four), CardClass::Trash);
+        assert_eq!(class(Color::Green, Suit::Three), CardClass::Critical);
+        assert_eq!(class(Color::Blue, Suit::One), CardClass::EventuallyPlayable);
+
+        let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            500,
+            0,
+        )
+        .with_abstraction(Box::new(TrashDiscards));
+        mcts.explore_n(500);
+        assert!(env.actions().contains(&mcts.best_action()));
+    }
+}
@@ -0,0 +1,219 @@
+// machine readable facts about an action from the mover's point of view, so front ends can
+// show rich move lists without re-deriving the rules
+use crate::abstraction::{is_critical_with, is_trash_with};
+use crate::advisor::Suggestion;
+use crate::beliefs::Beliefs;
+use crate::hanabi_env::{apply_clue, Action, Card, PrivateInfo, PublicInfo};
+use serde_json::{json, Value};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionInfo {
+    pub action: Action,
+    // the own slot a play/discard acts on, or the partner's slots a clue touches
+    pub slots: Vec<usize>,
+    // "color" or "rank" for clues
+    pub clue_type: Option<&'static str>,
+    // chance a play misfires
+    pub bomb_probability: Option<f32>,
+    // chance a discard throws away the last copy of a card that's still needed
+    pub critical_probability: Option<f32>,
+    // a clue touching the last copy of a card that's still needed
+    pub touches_critical: bool,
+    // a clue touching a card that can never be played
+    pub touches_trash: bool,
+    // bits of uncertainty a clue takes off the partner's view of their hand
+    pub info_gain: Option<f32>,
+}
+
+impl ActionInfo {
+    pub fn new(public_info: &PublicInfo, private_info: &PrivateInfo, action: &Action) -> Self {
+        let rules = &public_info.rules;
+        let mut info = Self {
+            action: *action,
+            slots: Vec::new(),
+            clue_type: None,
+            bomb_probability: None,
+            critical_probability: None,
+            touches_critical: false,
+            touches_trash: false,
+            info_gain: None,
+        };
+        match *action {
+            Action::ColorHint(_) | Action::SuitHint(_) => {
+                info.clue_type = Some(match action {
+                    Action::ColorHint(_) => "color",
+                    _ => "rank",
+                });
+                let touches = |c: &Card| match *action {
+                    Action::ColorHint(color) => c.color() == color,
+                    Action::SuitHint(suit) => c.suit() == suit,
+                    _ => false,
+                };
+                let hand = &private_info.opponent_hand;
+                info.slots = (0..5)
+                    .filter(|&i| hand[i].is_some() && touches(&hand[i]))
+                    .collect();
+                info.touches_critical = info.slots.iter().any(|&i| {
+                    is_critical_with(rules, hand[i], &public_info.fireworks, &public_info.discard)
+                });
+                info.touches_trash = info.slots.iter().any(|&i| {
+                    is_trash_with(rules, hand[i], &public_info.fireworks, &public_info.discard)
+                });
+                let mut after = public_info.clone();
+                apply_clue(hand, &mut after.opponent_hints, action);
+                info.info_gain = Some(
+                    Beliefs::partner(public_info).entropy() - Beliefs::partner(&after).entropy(),
+                );
+            }
+            Action::Play(slot) | Action::Discard(slot) => {
+                info.slots = vec![slot];
+                let beliefs = Beliefs::new(public_info, private_info);
+                let probability = |event: &dyn Fn(Card) -> bool| {
+                    (0..25)
+                        .filter(|&id| event(Card::from_id(id as u8)))
+                        .map(|id| beliefs.slots[slot][id])
+                        .sum::<f32>()
+                };
+                if let &Action::Play(_) = action {
+                    info.bomb_probability =
+                        Some(probability(&|card| !public_info.fireworks.accepts(card)));
+                } else {
+                    info.critical_probability = Some(probability(&|card| {
+                        is_critical_with(rules, card, &public_info.fireworks, &public_info.discard)
+                    }));
+                }
+            }
+        }
+        info
+    }
+
+    // e.g. "slots=[0, 2] bomb=12%"
+    pub fn summary(&self) -> String {
+        let mut text = format!("slots={:?}", self.slots);
+        if let Some(p) = self.bomb_probability {
+            text += &format!(" bomb={:.0}%", 100.0 * p);
+        }
+        if let Some(p) = self.critical_probability {
+            text += &format!(" critical={:.0}%", 100.0 * p);
+        }
+        if self.touches_critical {
+            text += " touches-critical";
+        }
+        if self.touches_trash {
+            text += " touches-trash";
+        }
+        if let Some(bits) = self.info_gain {
+            text += &format!(" info={:.1}bits", bits);
+        }
+        text
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        let kind = match self.action {
+            Action::Play(_) => "play",
+            Action::Discard(_) => "discard",
+            Action::ColorHint(_) | Action::SuitHint(_) => "clue",
+        };
+        let mut value = json!({
+            "action": format!("{:?}", self.action),
+            "kind": kind,
+            "slots": self.slots,
+        });
+        let map = value.as_object_mut().unwrap();
+        if let Some(clue_type) = self.clue_type {
+            map.insert("clue_type".to_string(), json!(clue_type));
+            map.insert("touched".to_string(), json!(self.slots.len()));
+            map.insert("touches_critical".to_string(), json!(self.touches_critical));
+            map.insert("touches_trash".to_string(), json!(self.touches_trash));
+            map.insert("info_gain".to_string(), json!(self.info_gain));
+        }
+        if let Some(p) = self.bomb_probability {
+            map.insert("bomb_probability".to_string(), json!(p));
+        }
+        if let Some(p) = self.critical_probability {
+            map.insert("critical_probability".to_string(), json!(p));
+        }
+        value
+    }
+}
+
+// search suggestions with each action's metadata, as a JSON list
+pub fn suggestions_to_json(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    suggestions: &[Suggestion],
+) -> Value {
+    Value::Array(
+        suggestions
+            .iter()
+            .map(|s| {
+                let mut value =
+                    ActionInfo::new(public_info, private_info, &s.action).to_json_value();
+                let map = value.as_object_mut().unwrap();
+                map.insert("visits".to_string(), json!(s.visits));
+                map.insert("mean_reward".to_string(), json!(s.mean_reward));
+                value
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::hanabi_env::{Color, HanabiEnv, Suit};
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_action_info() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.opponent_hand = [
+            Card::new(Color::Red, Suit::One),
+            Card::new(Color::Blue, Suit::Five),
+            Card::new(Color::Red, Suit::Three),
+            Card::new(Color::Green, Suit::One),
+            Card::new(Color::White, Suit::Two),
+        ];
+        env.fireworks.0[Color::Green as usize] = 1;
+        let public_info = env.public_info();
+        let private_info = env.private_info(env.current_player());
+
+        let red = ActionInfo::new(&public_info, &private_info, &Action::ColorHint(Color::Red));
+        assert_eq!(red.slots, vec![0, 2]);
+        assert_eq!(red.clue_type, Some("color"));
+        assert!(!red.touches_critical && !red.touches_trash);
+
+        let ones = ActionInfo::new(&public_info, &private_info, &Action::SuitHint(Suit::One));
+        assert_eq!(ones.clue_type, Some("rank"));
+        assert!(ones.touches_trash);
+        let fives = ActionInfo::new(&public_info, &private_info, &Action::SuitHint(Suit::Five));
+        assert!(fives.touches_critical);
+        // touching two cards of one color says more than touching a single five
+        assert!(red.info_gain.unwrap() > 0.0 && fives.info_gain.unwrap() > 0.0);
+        assert!(red.info_gain.unwrap() > fives.info_gain.unwrap());
+
+        // nothing is known about our own hand
+        let play = ActionInfo::new(&public_info, &private_info, &Action::Play(2));
+        assert_eq!(play.slots, vec![2]);
+        let bomb = play.bomb_probability.unwrap();
+        assert!(bomb > 0.5 && bomb < 1.0);
+        let discard = ActionInfo::new(&public_info, &private_info, &Action::Discard(2));
+        assert!(discard.critical_probability.unwrap() > 0.0);
+
+        let value = suggestions_to_json(
+            &public_info,
+            &private_info,
+            &[Suggestion {
+                action: Action::ColorHint(Color::Red),
+                visits: 10.0,
+                mean_reward: 0.5,
+            }],
+        );
+        assert_eq!(value[0]["kind"], "clue");
+        assert_eq!(value[0]["touched"], 2);
+        assert_eq!(value[0]["visits"], 10.0);
+    }
+}
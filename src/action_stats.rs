@@ -0,0 +1,215 @@
+// action values averaged over many searches and kept between runs, so what earlier self-play
+// learned about a kind of position steers later searches. positions are keyed by a few public
+// features after relabeling colors canonically: both players' hints, the fireworks & the tokens.
+// the discard and the deck size are left out, so positions from different games share entries.
+// MCTS reads them back as a progressive bias, a bonus fading as an edge gets visits. saved as
+//
+//   key: feature key u64 | action code   value: total value f32 | count u32
+//
+// all little endian, in any KvStore
+use crate::env::{Env, HasEnd, HasSymmetry};
+use crate::hanabi_env::{zobrist_key, Action, HanabiEnv, PublicInfo};
+use crate::kv_store::{FileStore, KvStore};
+use crate::mcts::MCTS;
+use crate::opening_book::encode_action;
+use crate::rand::rngs::StdRng;
+use crate::rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// the key of a position in its canonical colors, and the action in them too
+fn feature_key(public_info: &PublicInfo, action: &Action) -> (u64, u8) {
+    let symmetry = public_info.canonical_symmetry();
+    let canonical = public_info.apply(&symmetry);
+    let mut key = zobrist_key(0, canonical.blue_tokens.get() as u64)
+        ^ zobrist_key(1, canonical.black_tokens.get() as u64);
+    for color in 0..5 {
+        key ^= zobrist_key(2 + color as u64, canonical.fireworks.0[color] as u64);
+    }
+    for i in 0..5 {
+        let (player, opponent) = (canonical.player_hints[i], canonical.opponent_hints[i]);
+        key ^= zobrist_key(8 + i as u64, player.color_mask() as u64)
+            ^ zobrist_key(13 + i as u64, player.suit_mask() as u64)
+            ^ zobrist_key(18 + i as u64, opponent.color_mask() as u64)
+            ^ zobrist_key(23 + i as u64, opponent.suit_mask() as u64);
+    }
+    (
+        key,
+        encode_action(&PublicInfo::apply_to_action(&symmetry, action)),
+    )
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ActionStats {
+    // (total value, count) per feature key & action code
+    entries: HashMap<(u64, u8), (f32, u32)>,
+}
+
+impl ActionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn record(&mut self, public_info: &PublicInfo, action: &Action, value: f32) {
+        let entry = self
+            .entries
+            .entry(feature_key(public_info, action))
+            .or_insert((0.0, 0));
+        entry.0 += value;
+        entry.1 += 1;
+    }
+
+    // each root action's mean value from a search, as MCTS::root_stats lists them
+    pub fn record_search(&mut self, public_info: &PublicInfo, root_stats: &[(Action, f32, f32)]) {
+        for &(action, _, value) in root_stats.iter() {
+            self.record(public_info, &action, value);
+        }
+    }
+
+    // (mean value, count) of `action` in positions like `public_info`
+    pub fn mean(&self, public_info: &PublicInfo, action: &Action) -> Option<(f32, u32)> {
+        let &(total, count) = self.entries.get(&feature_key(public_info, action))?;
+        Some((total / count as f32, count))
+    }
+
+    pub fn merge(&mut self, other: &ActionStats) {
+        for (key, &(total, count)) in other.entries.iter() {
+            let entry = self.entries.entry(*key).or_insert((0.0, 0));
+            entry.0 += total;
+            entry.1 += count;
+        }
+    }
+
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mut stats = Self::new();
+        for (key, value) in FileStore::open(path)?.entries()?.iter() {
+            if key.len() != 9 || value.len() != 8 {
+                return Err(format!("{}: malformed entry", path));
+            }
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&key[..8]);
+            let feature = u64::from_le_bytes(bytes);
+            let mut total = [0; 4];
+            let mut count = [0; 4];
+            total.copy_from_slice(&value[..4]);
+            count.copy_from_slice(&value[4..]);
+            stats.entries.insert(
+                (feature, key[8]),
+                (f32::from_le_bytes(total), u32::from_le_bytes(count)),
+            );
+        }
+        Ok(stats)
+    }
+
+    pub fn save(&self, path: &str) -> Result<usize, String> {
+        let mut store = FileStore::open(path)?;
+        for (&(feature, code), &(total, count)) in self.entries.iter() {
+            let mut key = feature.to_le_bytes().to_vec();
+            key.push(code);
+            let mut value = total.to_le_bytes().to_vec();
+            value.extend_from_slice(&count.to_le_bytes());
+            store.put(&key, &value)?;
+        }
+        store.flush()?;
+        Ok(self.entries.len())
+    }
+}
+
+// has `mcts` add the mean value `stats` have for an edge to its UCT score, fading with visits
+pub fn with_stats_bias(mcts: MCTS<HanabiEnv>, stats: Arc<ActionStats>) -> MCTS<HanabiEnv> {
+    mcts.with_progressive_bias(Box::new(move |public_info, action| {
+        stats
+            .mean(public_info, action)
+            .map_or(0.0, |(mean, _)| mean)
+    }))
+}
+
+// self-play games of MCTS seeded from `seed`, recording every root search into new stats.
+// `prior` biases the searches, so stats can be learned over several rounds
+pub fn learn_stats(
+    num_games: usize,
+    num_explores: usize,
+    seed: u64,
+    prior: Option<Arc<ActionStats>>,
+) -> ActionStats {
+    let mut stats = ActionStats::new();
+    for game in 0..num_games as u64 {
+        let mut rng = StdRng::seed_from_u64(seed + game);
+        let mut env = HanabiEnv::random(&mut rng);
+        while !env.is_over() {
+            let public_info = env.public_info();
+            let private_info = env.private_info(env.current_player());
+            let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+                &public_info,
+                &private_info,
+                num_explores,
+                rng.gen(),
+            );
+            if let Some(prior) = prior.as_ref() {
+                mcts = with_stats_bias(mcts, prior.clone());
+            }
+            mcts.explore_n(num_explores);
+            stats.record_search(&public_info, &mcts.root_stats());
+            let action = mcts.best_action();
+            env.step(&action, &mut rng);
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hanabi_symmetry::ColorPermutation;
+
+    #[test]
+    fn test_action_stats() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let public_info = env.public_info();
+        let mut stats = ActionStats::new();
+        stats.record(&public_info, &Action::Play(0), 0.2);
+        stats.record(&public_info, &Action::Play(0), 0.4);
+        let (mean, count) = stats.mean(&public_info, &Action::Play(0)).unwrap();
+        assert!((mean - 0.3).abs() < 1e-6);
+        assert_eq!(count, 2);
+        assert_eq!(stats.mean(&public_info, &Action::Discard(0)), None);
+
+        // positions with the colors swapped share entries
+        let swap = ColorPermutation([1, 0, 2, 3, 4]);
+        let clue = Action::ColorHint(env.opponent_hand[0].color());
+        stats.record(&public_info, &clue, 1.0);
+        assert_eq!(
+            stats.mean(&public_info.apply(&swap), &swap.action(&clue)),
+            Some((1.0, 1))
+        );
+
+        let learned = learn_stats(1, 50, 0, None);
+        assert!(!learned.is_empty());
+        let mut merged = stats.clone();
+        merged.merge(&learned);
+        assert!(merged.len() >= learned.len());
+        // a biased search still plays legal moves
+        let biased = learn_stats(1, 50, 1, Some(Arc::new(learned.clone())));
+        assert!(!biased.is_empty());
+
+        let path = std::env::temp_dir().join(format!("hanabi-stats-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(merged.save(path).unwrap(), merged.len());
+        let reopened = ActionStats::open(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(reopened.len(), merged.len());
+        assert_eq!(
+            reopened.mean(&public_info, &Action::Play(0)),
+            merged.mean(&public_info, &Action::Play(0))
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::mcts::MCTS;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub action: Action,
+    pub visits: f32,
+    pub mean_reward: f32,
+}
+
+// searches from the partner's seat using the partner's own observation (their hints as
+// `player_hints`, our hand as `opponent_hand`), so nothing the partner can't see leaks
+// into the advice. suggestions are ordered from most to least visited
+pub fn advise(
+    partner_public_info: &PublicInfo,
+    partner_private_info: &PrivateInfo,
+    num_explores: usize,
+    seed: u64,
+) -> Vec<Suggestion> {
+    let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+        partner_public_info,
+        partner_private_info,
+        num_explores,
+        seed,
+    );
+    mcts.explore_n(num_explores);
+    suggestions(&mcts)
+}
+
+// the root statistics of a search so far, most visited first
+pub fn suggestions(mcts: &MCTS<HanabiEnv>) -> Vec<Suggestion> {
+    let mut suggestions: Vec<Suggestion> = mcts
+        .root_stats()
+        .into_iter()
+        .map(|(action, visits, mean_reward)| Suggestion {
+            action,
+            visits,
+            mean_reward,
+        })
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.visits
+            .partial_cmp(&a.visits)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_advice_is_for_the_partner() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        let action = env.actions()[0];
+        env.step(&action, &mut rng);
+
+        // after our move the env's "player" is the partner
+        let suggestions = advise(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            200,
+            0,
+        );
+        let legal = env.search_actions();
+        assert_eq!(suggestions.len(), legal.len());
+        assert!(suggestions.iter().all(|s| legal.contains(&s.action)));
+        assert!(suggestions
+            .windows(2)
+            .all(|pair| pair[0].visits >= pair[1].visits));
+    }
+}
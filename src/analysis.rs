@@ -0,0 +1,144 @@
+// the numbers Hanabi players track to judge a position, worked out from public information only:
+// - pace: how many more cards can be discarded before the max score is out of reach. each card
+//   drawn is a turn, and the last round gives each player one more. at 0 every remaining turn
+//   has to be a play or a clue
+// - efficiency: clues there are still to give per card that has to be played. tokens in hand,
+//   one per discard the pace allows, and one per 5 still to play. under 1 the players have to
+//   get cards played with less than a clue each
+// - critical: cards still in play that are the last copy the firework needs
+// - dead: cards still in play that can never be played, the trash
+use crate::abstraction::{is_critical_with, is_trash_with};
+use crate::hanabi_env::{Card, PublicInfo};
+
+pub fn pace(public_info: &PublicInfo) -> i16 {
+    public_info.fireworks.total() as i16
+        + public_info.deck_total() as i16
+        + public_info.num_players() as i16
+        - public_info.max_score as i16
+}
+
+// the cards of every color & rank still in a hand or the deck, as (card, copies)
+fn cards_in_play(public_info: &PublicInfo) -> Vec<(Card, u8)> {
+    let rules = &public_info.rules;
+    let deck = rules.deck();
+    let mut cards = Vec::new();
+    for color in 0..rules.colors {
+        for suit in 0..rules.ranks {
+            let card = Card::from_parts(color, suit);
+            let id = card.id() as usize;
+            let played = (public_info.fireworks.0[color as usize] > suit) as u8;
+            let copies = deck.counts[id] - public_info.discard.counts[id] - played;
+            if copies > 0 {
+                cards.push((card, copies));
+            }
+        }
+    }
+    cards
+}
+
+pub fn num_critical(public_info: &PublicInfo) -> u8 {
+    let (rules, fireworks, discard) = (
+        &public_info.rules,
+        &public_info.fireworks,
+        &public_info.discard,
+    );
+    cards_in_play(public_info)
+        .iter()
+        .filter(|(card, _)| is_critical_with(rules, *card, fireworks, discard))
+        .count() as u8
+}
+
+pub fn num_dead(public_info: &PublicInfo) -> u8 {
+    let (rules, fireworks, discard) = (
+        &public_info.rules,
+        &public_info.fireworks,
+        &public_info.discard,
+    );
+    cards_in_play(public_info)
+        .iter()
+        .filter(|(card, _)| is_trash_with(rules, *card, fireworks, discard))
+        .map(|&(_, copies)| copies)
+        .sum()
+}
+
+// the clues there are left to give, see the top of the file
+pub fn clues_available(public_info: &PublicInfo) -> u16 {
+    let rules = &public_info.rules;
+    let (fireworks, discard) = (&public_info.fireworks, &public_info.discard);
+    let fives = (0..rules.colors)
+        .map(|color| Card::from_parts(color, rules.ranks - 1))
+        .filter(|&card| !is_trash_with(rules, card, fireworks, discard))
+        .count() as u8;
+    (public_info.blue_tokens.get() as i16 + pace(public_info).max(0) + fives as i16) as u16
+}
+
+// None once there's nothing left to play
+pub fn efficiency(public_info: &PublicInfo) -> Option<f32> {
+    match public_info.max_score - public_info.fireworks.total() {
+        0 => None,
+        plays => Some(clues_available(public_info) as f32 / plays as f32),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub pace: i16,
+    pub efficiency: Option<f32>,
+    pub critical: u8,
+    pub dead: u8,
+}
+
+pub fn analyze(public_info: &PublicInfo) -> Analysis {
+    Analysis {
+        pace: pace(public_info),
+        efficiency: efficiency(public_info),
+        critical: num_critical(public_info),
+        dead: num_dead(public_info),
+    }
+}
+
+impl std::fmt::Display for Analysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "pace={} efficiency=", self.pace)?;
+        match self.efficiency {
+            Some(efficiency) => write!(f, "{:.2}", efficiency)?,
+            None => write!(f, "-")?,
+        }
+        write!(f, " critical={} dead={}", self.critical, self.dead)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_analyze() {
+        let start = Position::parse("R1.B2.Y3.G4.W5 ?.?.?.?.? ?.?.?.?.? 00000 - 8/3 -").unwrap();
+        let analysis = analyze(&start.public_info);
+        // 40 cards to draw, 2 more turns in the last round and 25 to play
+        assert_eq!(analysis.pace, 17);
+        assert_eq!(analysis.critical, 5);
+        assert_eq!(analysis.dead, 0);
+        assert_eq!(analysis.efficiency, Some((8 + 17 + 5) as f32 / 25.0));
+
+        // both R2s gone kill the rest of red, and leave R1 one copy
+        let late =
+            Position::parse("R3.R4.B3.Y4.G5 ?.?.?.?.? ?.?.?.?.? 50234 R1R1R2R2 5/3 -").unwrap();
+        let public_info = &late.public_info;
+        assert_eq!(public_info.max_score, 21);
+        assert_eq!(public_info.deck_total(), 22);
+        let analysis = analyze(public_info);
+        assert_eq!(analysis.pace, 14 + 22 + 2 - 21);
+        // R3-R5, and the copies of what's been played
+        assert_eq!(analysis.dead, 5 + 3 + 4 + 5 + 5);
+        assert_eq!(analysis.critical, 4);
+        // B5, Y5 & G5 still give a clue back
+        assert_eq!(analysis.efficiency, Some((5 + 17 + 3) as f32 / 7.0));
+        assert_eq!(
+            analysis.to_string(),
+            "pace=17 efficiency=3.57 critical=4 dead=22"
+        );
+    }
+}
@@ -0,0 +1,320 @@
+// splits the points a game fell short of 25 between luck and bad play, so engine work goes to
+// the bucket that's actually losing points:
+// - `deal`: points a player seeing every card, the deck order included, still doesn't score
+// - `bombs`: points misplays cost, through discarding the card or bombing out
+// - `decisions`: the value the search says other moves were dropping, once the game is reviewed
+// - `hidden`: the rest of the gap to the double-dummy ceiling, put down to not seeing the cards
+// - `unattributed`: that rest when some move has no value from a review, or there's no review,
+//   as it can't be told apart from a decision error then
+// the ceiling comes from a greedy player with full information rather than an exact solver. it
+// can only undershoot perfect play, so `deal` errs high & the other buckets low
+use crate::abstraction::{is_critical_with, is_trash_with};
+use crate::env::{Env, HasEnd};
+use crate::hanabi_env::{Action, Card, Deck, HanabiEnv, RuleSet};
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+use crate::record::GameRecord;
+use crate::replay::{DecisionReview, Replay};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Attribution {
+    pub score: u8,
+    pub ceiling: u8,
+    pub deal: f32,
+    pub hidden: f32,
+    pub bombs: f32,
+    pub decisions: f32,
+    pub unattributed: f32,
+}
+
+impl Attribution {
+    // points nobody at the table could have saved
+    pub fn luck(&self) -> f32 {
+        self.deal + self.hidden
+    }
+
+    pub fn errors(&self) -> f32 {
+        self.bombs + self.decisions
+    }
+}
+
+// which hand card the full information player gives up: trash first, then a card with another
+// copy in either hand, then the highest rank that isn't the last copy
+fn discard_choice(env: &HanabiEnv) -> usize {
+    let slots: Vec<usize> = (0..5).filter(|&i| env.player_hand[i].is_some()).collect();
+    let public_info = env.public_info();
+    let (fireworks, discard) = (&public_info.fireworks, &public_info.discard);
+    let duplicated = |i: usize| {
+        let card = env.player_hand[i];
+        (0..5).any(|j| j != i && env.player_hand[j] == card) || env.opponent_hand.contains(&card)
+    };
+    let score = |i: usize| {
+        let card = env.player_hand[i];
+        if is_trash_with(&env.rules, card, fireworks, discard) {
+            0
+        } else if duplicated(i) {
+            1
+        } else if !is_critical_with(&env.rules, card, fireworks, discard) {
+            7 - card.suit_id() as usize
+        } else {
+            13 - card.suit_id() as usize
+        }
+    };
+    *slots.iter().min_by_key(|&&i| score(i)).unwrap()
+}
+
+fn is_trash_at(env: &HanabiEnv, i: usize) -> bool {
+    is_trash_with(&env.rules, env.player_hand[i], &env.fireworks, &env.discard)
+}
+
+// what the full information player does: play the lowest playable card, throw trash away, stall
+// with a clue while there are tokens, and otherwise discard the least useful card
+fn double_dummy_action(env: &HanabiEnv) -> Action {
+    let playable = (0..5)
+        .filter(|&i| env.player_hand[i].is_some() && env.fireworks.accepts(env.player_hand[i]))
+        .min_by_key(|&i| env.player_hand[i].suit_id());
+    if let Some(i) = playable {
+        return Action::Play(i);
+    }
+    let trash = (0..5).find(|&i| env.player_hand[i].is_some() && is_trash_at(env, i));
+    match trash {
+        Some(i) if env.blue_tokens < env.rules.clue_tokens => return Action::Discard(i),
+        _ => {}
+    }
+    let clue = env
+        .action_list()
+        .iter()
+        .find(|a| matches!(a, Action::ColorHint(_) | Action::SuitHint(_)))
+        .cloned();
+    match clue {
+        Some(clue) if env.blue_tokens > 0 => clue,
+        _ => Action::Discard(discard_choice(env)),
+    }
+}
+
+// the double-dummy estimate: what the full information player scores on a deck order
+pub fn double_dummy_score(rules: &RuleSet, order: &[Card]) -> u8 {
+    let mut env = HanabiEnv::from_deck_order_with(*rules, order);
+    // the deck is ordered, the rng never decides anything
+    let mut rng = StdRng::seed_from_u64(0);
+    while !env.is_over() {
+        let action = double_dummy_action(&env);
+        env.step(&action, &mut rng);
+    }
+    env.fireworks.total()
+}
+
+// the whole deal of a game. a deck drawn from an rng as the game went is only known as far as
+// it was drawn, Replay::from_game puts the rest on the bottom in a fixed order
+pub fn deal_order(initial: &HanabiEnv, record: &GameRecord) -> Vec<Card> {
+    match &initial.deck {
+        Deck::Ordered(cards) => {
+            let hands = initial
+                .player_hand
+                .iter()
+                .chain(initial.opponent_hand.iter());
+            hands.chain(cards.iter().rev()).cloned().collect()
+        }
+        Deck::Multiset(_) => Replay::from_game(initial, record).deck,
+    }
+}
+
+// `initial` is the env before the first move of `record`. `reviews`, one per turn as
+// Replay::review returns them, fill in the `decisions` bucket and split the rest off as hidden
+// information. without them, or with a move the search didn't value, what isn't a bomb is left
+// unattributed
+pub fn attribute(
+    initial: &HanabiEnv,
+    record: &GameRecord,
+    reviews: Option<&[DecisionReview]>,
+) -> Result<Attribution, String> {
+    if let Some(reviews) = reviews {
+        if reviews.len() != record.turns.len() {
+            return Err(format!(
+                "{} reviews for {} turns",
+                reviews.len(),
+                record.turns.len()
+            ));
+        }
+    }
+    let mut env = initial.clone();
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut bomb_loss = 0;
+    let mut drops = 0.0;
+    let mut unvalued = 0;
+    for (i, turn) in record.turns.iter().enumerate() {
+        let max_score = env.max_score;
+        env.try_step(&turn.action, &mut rng)
+            .map_err(|e| format!("turn {}: {}", i, e))?;
+        let bombed = matches!(turn.action, Action::Play(_)) && !turn.successful_play;
+        if bombed {
+            bomb_loss += max_score - env.max_score;
+        } else if let Some(review) = reviews.map(|r| &r[i]) {
+            match review.played_value {
+                // rewards are on a 0..1 scale of the score
+                Some(played_value) => drops += 25.0 * (review.best_value - played_value).max(0.0),
+                None => unvalued += 1,
+            }
+        }
+    }
+    if env.black_tokens == 1 {
+        // bombing out gives up everything still reachable
+        bomb_loss += env.max_score - env.fireworks.total();
+    }
+
+    let score = env.fireworks.total();
+    let order = deal_order(initial, record);
+    // the game itself shows what's reachable when the greedy player falls short of it
+    let ceiling = double_dummy_score(&initial.rules, &order).max(score);
+    let shortfall = (ceiling - score) as f32;
+    let bombs = (bomb_loss as f32).min(shortfall);
+    let decisions = drops.min(shortfall - bombs);
+    let rest = shortfall - bombs - decisions;
+    let (hidden, unattributed) = match reviews.is_some() && unvalued == 0 {
+        true => (rest, 0.0),
+        false => (0.0, rest),
+    };
+    Ok(Attribution {
+        score,
+        ceiling,
+        deal: (initial.rules.perfect_score() - ceiling) as f32,
+        hidden,
+        bombs,
+        decisions,
+        unattributed,
+    })
+}
+
+// mean points per game in each bucket
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct AttributionSummary {
+    pub num_games: usize,
+    pub mean: Attribution,
+    pub mean_ceiling: f32,
+}
+
+pub fn summarize_attributions(attributions: &[Attribution]) -> AttributionSummary {
+    let n = attributions.len().max(1) as f32;
+    let mean_of = |f: fn(&Attribution) -> f32| attributions.iter().map(f).sum::<f32>() / n;
+    AttributionSummary {
+        num_games: attributions.len(),
+        mean: Attribution {
+            score: 0,
+            ceiling: 0,
+            deal: mean_of(|a| a.deal),
+            hidden: mean_of(|a| a.hidden),
+            bombs: mean_of(|a| a.bombs),
+            decisions: mean_of(|a| a.decisions),
+            unattributed: mean_of(|a| a.unattributed),
+        },
+        mean_ceiling: mean_of(|a| a.ceiling as f32),
+    }
+}
+
+impl std::fmt::Display for AttributionSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "lost points per game: luck={:.2} (deal={:.2} hidden={:.2}) errors={:.2} (bombs={:.2} \
+             decisions={:.2}) unattributed={:.2} | double-dummy ceiling={:.2}",
+            self.mean.luck(),
+            self.mean.deal,
+            self.mean.hidden,
+            self.mean.errors(),
+            self.mean.bombs,
+            self.mean.decisions,
+            self.mean.unattributed,
+            self.mean_ceiling
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::heuristic_policy;
+    use crate::record::GameRecord;
+    use crate::runner::deal;
+
+    fn play(seed: u64, blunder: bool) -> (HanabiEnv, GameRecord) {
+        let initial = deal(seed);
+        let mut env = initial.clone();
+        let mut record = GameRecord::new(["heuristic".to_string(), "heuristic".to_string()]);
+        let mut rng = StdRng::seed_from_u64(seed);
+        while !env.is_over() {
+            let action = match blunder {
+                // always plays the newest card
+                true => Action::Play(4),
+                false => {
+                    heuristic_policy(&env.public_info(), &env.private_info(env.current_player()))
+                }
+            };
+            record.step(&mut env, &action, &mut rng);
+        }
+        (initial, record)
+    }
+
+    #[test]
+    fn test_double_dummy_score() {
+        let record = GameRecord::new(["a".to_string(), "b".to_string()]);
+        let scores: Vec<u8> = (0..10)
+            .map(|seed| double_dummy_score(&RuleSet::default(), &deal_order(&deal(seed), &record)))
+            .collect();
+        let mean = scores.iter().map(|&s| s as f32).sum::<f32>() / scores.len() as f32;
+        assert!(mean > 22.0, "{:?}", scores);
+    }
+
+    #[test]
+    fn test_attribute() {
+        let mut attributions = Vec::new();
+        for seed in 0..5 {
+            for &blunder in [false, true].iter() {
+                let (initial, record) = play(seed, blunder);
+                let a = attribute(&initial, &record, None).unwrap();
+                assert!(a.deal >= 0.0 && a.unattributed >= 0.0 && a.bombs >= 0.0);
+                // unreviewed, nothing is put down to hidden information or decisions
+                assert_eq!(a.decisions, 0.0);
+                assert_eq!(a.hidden, 0.0);
+                let lost = a.luck() + a.errors() + a.unattributed;
+                assert!((lost - (25 - a.score) as f32).abs() < 1e-4);
+                if blunder {
+                    // bombing out loses everything to bombs
+                    assert_eq!(a.unattributed, 0.0);
+                    assert!(a.bombs > 0.0);
+                }
+                attributions.push(a);
+            }
+        }
+        let summary = summarize_attributions(&attributions);
+        assert_eq!(summary.num_games, 10);
+        assert!(summary.to_string().starts_with("lost points per game"));
+
+        // reviews split the losses that aren't bombs into decisions & hidden information
+        let (initial, record) = play(0, false);
+        let mut reviews: Vec<DecisionReview> = record
+            .turns
+            .iter()
+            .enumerate()
+            .map(|(turn, t)| DecisionReview {
+                turn,
+                played: t.action,
+                best: t.action,
+                played_value: Some(0.0),
+                best_value: 0.01,
+            })
+            .collect();
+        let plain = attribute(&initial, &record, None).unwrap();
+        let reviewed = attribute(&initial, &record, Some(&reviews)).unwrap();
+        assert!(reviewed.decisions > 0.0 || plain.unattributed == 0.0);
+        assert_eq!(reviewed.unattributed, 0.0);
+        assert!((reviewed.decisions + reviewed.hidden - plain.unattributed).abs() < 1e-4);
+        assert!(attribute(&initial, &record, Some(&reviews[1..])).is_err());
+
+        // a move the search never tried has no value, so what's left isn't hidden information
+        let last = reviews.len() - 1;
+        reviews[last].played_value = None;
+        let partial = attribute(&initial, &record, Some(&reviews)).unwrap();
+        assert_eq!(partial.hidden, 0.0);
+        assert!((partial.decisions + partial.unattributed - plain.unattributed).abs() < 1e-4);
+    }
+}
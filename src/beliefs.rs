@@ -0,0 +1,262 @@
+use crate::abstraction::{classify_with, CardClass};
+use crate::endgame::unseen_cards;
+use crate::hanabi_env::{apply_clue, Action, Card, Color, Hint, PrivateInfo, PublicInfo};
+use serde_json::{json, Value};
+
+// the chance a slot's card can be played right now, and the chance of each CardClass: it can
+// never be played, it's the last copy of one that still can, or it's needed but has a copy to
+// spare. all zero for an empty slot
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SlotSummary {
+    pub playable: f32,
+    pub trash: f32,
+    pub critical: f32,
+    pub eventually_playable: f32,
+}
+
+// per slot probability of each card id for the player's own hand.
+// note: slots are treated independently, so cards shared between slots are double counted
+#[derive(Clone, Debug)]
+pub struct Beliefs {
+    pub slots: [[f32; 25]; 5],
+}
+
+impl Beliefs {
+    pub fn new(public_info: &PublicInfo, private_info: &PrivateInfo) -> Self {
+        let unseen = unseen_cards(public_info, private_info);
+        let mut counts = [0.0; 25];
+        for (count, &unseen) in counts.iter_mut().zip(unseen.counts.iter()) {
+            *count = unseen as f32;
+        }
+        Self::from_counts(&public_info.player_hints, &counts)
+    }
+
+    // each slot of a hand with `hints` in proportion to how many of each card, `counts`, are
+    // left that the hints allow
+    fn from_counts(hints: &[Hint; 5], counts: &[f32; 25]) -> Self {
+        let mut slots = [[0.0; 25]; 5];
+        for i in 0..5 {
+            let hint = hints[i];
+            if !hint.is_some() {
+                continue;
+            }
+            let possible = hint.card_mask();
+            let mut total = 0.0;
+            for id in 0..25 {
+                if possible & 1 << id != 0 {
+                    slots[i][id] = counts[id];
+                    total += slots[i][id];
+                }
+            }
+            if total > 0.0 {
+                for p in slots[i].iter_mut() {
+                    *p /= total;
+                }
+            }
+        }
+        Self { slots }
+    }
+
+    // the partner's beliefs about their own hand. which cards they can't see depends on our
+    // hand, so only the discard & fireworks are taken out
+    pub fn partner(public_info: &PublicInfo) -> Self {
+        Self::partner_seeing(public_info, &[Card::none(); 5])
+    }
+
+    // the partner's exact beliefs, when our hand, the one they see, is known
+    pub fn partner_seeing(public_info: &PublicInfo, our_hand: &[Card; 5]) -> Self {
+        let mut partner_info = public_info.clone();
+        partner_info.player_hints = public_info.opponent_hints;
+        partner_info.opponent_hints = public_info.player_hints;
+        Self::new(
+            &partner_info,
+            &PrivateInfo {
+                opponent_hand: *our_hand,
+            },
+        )
+    }
+
+    // the partner's beliefs as the player to move expects them: the cards the partner can't see
+    // are the ones we can't, their own hand, less what we think our hand holds. it only takes
+    // what the mover knows, so it's the same in every determinization
+    pub fn partner_expected(public_info: &PublicInfo, private_info: &PrivateInfo) -> Self {
+        let ours = Self::new(public_info, private_info);
+        let unseen = unseen_cards(public_info, private_info);
+        let mut counts = [0.0; 25];
+        for id in 0..25 {
+            let expected: f32 = ours.slots.iter().map(|slot| slot[id]).sum();
+            counts[id] = (unseen.counts[id] as f32 - expected).max(0.0);
+        }
+        for card in private_info.opponent_hand.iter().filter(|c| c.is_some()) {
+            counts[card.id() as usize] += 1.0;
+        }
+        Self::from_counts(&public_info.opponent_hints, &counts)
+    }
+
+    pub fn summary(&self, public_info: &PublicInfo) -> [SlotSummary; 5] {
+        let (fireworks, discard) = (&public_info.fireworks, &public_info.discard);
+        let mut summary = [SlotSummary::default(); 5];
+        for (i, slot) in self.slots.iter().enumerate() {
+            for (id, &p) in slot.iter().enumerate().filter(|(_, &p)| p > 0.0) {
+                let card = Card::from_id(id as u8);
+                if fireworks.accepts(card) {
+                    summary[i].playable += p;
+                }
+                match classify_with(&public_info.rules, card, fireworks, discard) {
+                    CardClass::Trash => summary[i].trash += p,
+                    CardClass::Critical => summary[i].critical += p,
+                    CardClass::EventuallyPlayable => summary[i].eventually_playable += p,
+                }
+            }
+        }
+        summary
+    }
+
+    pub fn slot_entropy(&self, i: usize) -> f32 {
+        self.slots[i]
+            .iter()
+            .filter(|&&p| p > 0.0)
+            .map(|&p| -p * p.log2())
+            .sum()
+    }
+
+    // total entropy in bits over all slots
+    pub fn entropy(&self) -> f32 {
+        (0..5).map(|i| self.slot_entropy(i)).sum()
+    }
+
+    // entropy relative to knowing nothing about a full hand, between 0 and 1
+    pub fn entropy_fraction(&self) -> f32 {
+        self.entropy() / (5.0 * 25f32.log2())
+    }
+
+    // slot `i` as a grid, rows by color in W R B Y G order & columns by rank 1 to 5
+    pub fn heatmap(&self, i: usize) -> [[f32; 5]; 5] {
+        let mut grid = [[0.0; 5]; 5];
+        for id in 0..25 {
+            let card = Card::from_id(id as u8);
+            grid[card.color_id() as usize][card.suit_id() as usize] = self.slots[i][id];
+        }
+        grid
+    }
+
+    // one grid per slot, an empty slot is all zeros
+    pub fn to_json_value(&self) -> Value {
+        let slots: Vec<Value> = (0..5)
+            .map(|i| {
+                let rows: Vec<Vec<f32>> = self.heatmap(i).iter().map(|row| row.to_vec()).collect();
+                json!(rows)
+            })
+            .collect();
+        json!(slots)
+    }
+
+    // "whose,slot,color,rank,probability" rows for every slot & card, `whose` labels the rows
+    pub fn csv_rows(&self, whose: &str) -> Vec<String> {
+        let mut rows = Vec::with_capacity(125);
+        for i in 0..5 {
+            for (color, row) in self.heatmap(i).iter().enumerate() {
+                for (rank, p) in row.iter().enumerate() {
+                    rows.push(format!(
+                        "{},{},{},{},{}",
+                        whose,
+                        i,
+                        Color::from_id(color as u8).short_name(),
+                        rank + 1,
+                        p
+                    ));
+                }
+            }
+        }
+        rows
+    }
+}
+
+pub const HEATMAP_CSV_HEADER: &str = "whose,slot,color,rank,probability";
+
+// bits a clue takes off the partner's uncertainty about their hand, as the player to move
+// expects it, see Beliefs::partner_expected
+pub fn hint_info_gain(public_info: &PublicInfo, private_info: &PrivateInfo, clue: &Action) -> f32 {
+    let mut after = public_info.clone();
+    apply_clue(&private_info.opponent_hand, &mut after.opponent_hints, clue);
+    Beliefs::partner_expected(public_info, private_info).entropy()
+        - Beliefs::partner_expected(&after, private_info).entropy()
+}
+
+// our beliefs about our own hand & the partner's modeled beliefs about theirs, for plotting
+pub fn heatmaps_to_json(public_info: &PublicInfo, private_info: &PrivateInfo) -> Value {
+    json!({
+        "colors": ["W", "R", "B", "Y", "G"],
+        "ranks": [1, 2, 3, 4, 5],
+        "mine": Beliefs::new(public_info, private_info).to_json_value(),
+        "partner": Beliefs::partner(public_info).to_json_value(),
+    })
+}
+
+pub fn heatmaps_to_csv(public_info: &PublicInfo, private_info: &PrivateInfo) -> String {
+    let mut lines = vec![HEATMAP_CSV_HEADER.to_string()];
+    lines.extend(Beliefs::new(public_info, private_info).csv_rows("mine"));
+    lines.extend(Beliefs::partner(public_info).csv_rows("partner"));
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_heatmaps() {
+        // our first card is known to be R1, the partner's first card is known to be a 5
+        let position =
+            Position::parse("B4.Y3.G2.W4.Y4 R1.?.?.?.- 5.?.?.?.? 00000 - 6/3 -").unwrap();
+        let (public_info, private_info) = (&position.public_info, &position.private_info);
+        let mine = Beliefs::new(public_info, private_info);
+        assert_eq!(mine.heatmap(0)[1][0], 1.0);
+        assert_eq!(mine.heatmap(4), [[0.0; 5]; 5]);
+        let total: f32 = mine.heatmap(1).iter().flatten().sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        let value = heatmaps_to_json(public_info, private_info);
+        assert_eq!(value["mine"][0][1][0], 1.0);
+        let partner_fives: f64 = (0..5)
+            .map(|c| value["partner"][0][c][4].as_f64().unwrap())
+            .sum();
+        assert!((partner_fives - 1.0).abs() < 1e-5);
+
+        let csv = heatmaps_to_csv(public_info, private_info);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], HEATMAP_CSV_HEADER);
+        assert_eq!(lines.len(), 1 + 2 * 125);
+        assert_eq!(lines[1 + 5], "mine,0,R,1,1");
+    }
+
+    #[test]
+    fn test_summary() {
+        // the R1 is playable. with W2 down the unseen whites are two W1, a W2, two W3, a W4 & the
+        // W5, and a known 5 is always the last copy
+        let position =
+            Position::parse("B4.Y3.G2.W4.Y4 R1.W.5.?.- ?.?.?.?.? 20000 - 6/3 -").unwrap();
+        let (public_info, private_info) = (&position.public_info, &position.private_info);
+        let summary = Beliefs::new(public_info, private_info).summary(public_info);
+        assert_eq!(
+            summary[0],
+            SlotSummary {
+                playable: 1.0,
+                trash: 0.0,
+                critical: 0.0,
+                eventually_playable: 1.0,
+            }
+        );
+        assert!((summary[1].playable - 2.0 / 7.0).abs() < 1e-5);
+        assert!((summary[1].trash - 3.0 / 7.0).abs() < 1e-5);
+        assert!((summary[1].critical - 1.0 / 7.0).abs() < 1e-5);
+        assert!((summary[1].eventually_playable - 3.0 / 7.0).abs() < 1e-5);
+        assert!((summary[2].critical - 1.0).abs() < 1e-5);
+        assert_eq!(summary[4], SlotSummary::default());
+        for s in summary.iter().take(4) {
+            assert!(s.playable + s.trash <= 1.0 + 1e-5);
+            assert!((s.trash + s.critical + s.eventually_playable - 1.0).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+// the `report` command: every results file under a directory in one table, so runs from
+// different days & configs can be lined up without copying numbers around. files written by
+// `evaluate --out`, `--json` and `--csv` are recognized, anything else is listed as skipped
+// instead of failing the whole report
+use crate::results::{read_results, summarize, GameResult, Summary};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug)]
+pub struct Run {
+    // the agent a json file names, otherwise the file name without its extension
+    pub config: String,
+    pub path: PathBuf,
+    pub summary: Summary,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Dashboard {
+    // best mean first
+    pub runs: Vec<Run>,
+    // files that couldn't be read as results, with why
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+fn read_json(path: &Path) -> Result<(String, Vec<GameResult>), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    let agent = value["agent"].as_str().ok_or("missing 'agent'")?;
+    let games = value["games"].as_array().ok_or("missing 'games'")?;
+    let mut results = Vec::with_capacity(games.len());
+    for game in games.iter() {
+        match (game["seed"].as_u64(), game["score"].as_u64()) {
+            (Some(seed), Some(score)) => results.push(GameResult {
+                seed,
+                score: score as u8,
+            }),
+            _ => return Err("expected games of {seed, score}".to_string()),
+        }
+    }
+    Ok((agent.to_string(), results))
+}
+
+fn read_csv(path: &Path) -> Result<Vec<GameResult>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut lines = text.lines();
+    if lines.next().map(|l| l.trim()) != Some("seed,score") {
+        return Err("expected a 'seed,score' header".to_string());
+    }
+    let mut results = Vec::new();
+    for (line_no, line) in lines.enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let mut parts = line.split(',');
+        let seed = parts.next().and_then(|p| p.trim().parse().ok());
+        let score = parts.next().and_then(|p| p.trim().parse().ok());
+        match (seed, score) {
+            (Some(seed), Some(score)) => results.push(GameResult { seed, score }),
+            _ => return Err(format!("line {}: expected 'seed,score'", line_no + 2)),
+        }
+    }
+    Ok(results)
+}
+
+fn read_run(path: &Path) -> Result<Run, String> {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (config, results) = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => read_json(path)?,
+        Some("csv") => (stem, read_csv(path)?),
+        _ => (
+            stem,
+            read_results(&path.to_string_lossy()).map_err(|e| e.to_string())?,
+        ),
+    };
+    if results.is_empty() {
+        return Err("no games".to_string());
+    }
+    Ok(Run {
+        config,
+        path: path.to_path_buf(),
+        summary: summarize(&results),
+    })
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// every file under `dir`, subdirectories included. only an unreadable `dir` is an error
+pub fn scan(dir: &str) -> Result<Dashboard, String> {
+    let mut files = Vec::new();
+    collect_files(Path::new(dir), &mut files)?;
+    files.sort();
+    let mut dashboard = Dashboard::default();
+    for path in files {
+        match read_run(&path) {
+            Ok(run) => dashboard.runs.push(run),
+            Err(e) => dashboard.skipped.push((path, e)),
+        }
+    }
+    dashboard.runs.sort_by(|a, b| {
+        b.summary
+            .mean
+            .partial_cmp(&a.summary.mean)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Ok(dashboard)
+}
+
+impl Dashboard {
+    // the run with the best mean, `lines` notes when the runner up's ci reaches it
+    pub fn best(&self) -> Option<&Run> {
+        self.runs.first()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let width = self
+            .runs
+            .iter()
+            .map(|r| r.config.len())
+            .max()
+            .unwrap_or(0)
+            .max(6);
+        let mut lines = vec![format!(
+            "{:<width$} {:>6} {:>7} {:>17} {:>8}  file",
+            "config",
+            "games",
+            "mean",
+            "ci95",
+            "perfect",
+            width = width
+        )];
+        for run in self.runs.iter() {
+            let (low, high) = run.summary.ci95();
+            lines.push(format!(
+                "{:<width$} {:>6} {:>7.3} {:>17} {:>7.1}%  {}",
+                run.config,
+                run.summary.num_games,
+                run.summary.mean,
+                format!("[{:.3}, {:.3}]", low, high),
+                100.0 * run.summary.perfect_rate(),
+                run.path.display(),
+                width = width
+            ));
+        }
+        if let Some(best) = self.best() {
+            let (low, high) = best.summary.ci95();
+            let clear = self
+                .runs
+                .get(1)
+                .is_none_or(|next| next.summary.ci95().1 < low);
+            lines.push(String::new());
+            lines.push(format!(
+                "best: {} mean={:.3} ci95=[{:.3}, {:.3}]{}",
+                best.config,
+                best.summary.mean,
+                low,
+                high,
+                if clear {
+                    ""
+                } else {
+                    " (within the runner up's ci)"
+                }
+            ));
+        }
+        for (path, why) in self.skipped.iter() {
+            lines.push(format!("skipped {}: {}", path.display(), why));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results::{write_csv, write_json, write_results};
+
+    fn results(scores: &[u8]) -> Vec<GameResult> {
+        scores
+            .iter()
+            .enumerate()
+            .map(|(seed, &score)| GameResult {
+                seed: seed as u64,
+                score,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scan() {
+        let dir = std::env::temp_dir().join(format!("hanabi-dashboard-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("tuning")).unwrap();
+        let file = |name: &str| dir.join(name).to_string_lossy().to_string();
+        write_results(&file("flat-mc.txt"), &results(&[20, 22, 24])).unwrap();
+        write_json(&file("run.json"), "mcts", &results(&[24, 25, 25])).unwrap();
+        write_csv(&file("tuning/c-0.5.csv"), &results(&[10, 12])).unwrap();
+        std::fs::write(file("notes.md"), "# not results\n").unwrap();
+
+        let dashboard = scan(&dir.to_string_lossy()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let configs: Vec<&str> = dashboard.runs.iter().map(|r| r.config.as_str()).collect();
+        assert_eq!(configs, vec!["mcts", "flat-mc", "c-0.5"]);
+        assert_eq!(dashboard.runs[1].summary.mean, 22.0);
+        assert_eq!(dashboard.skipped.len(), 1);
+        assert!(dashboard.skipped[0].0.ends_with("notes.md"));
+        assert_eq!(dashboard.best().unwrap().config, "mcts");
+
+        let lines = dashboard.lines();
+        assert!(lines[0].starts_with("config"));
+        assert!(lines.iter().any(|l| l.starts_with("best: mcts")));
+        assert!(lines.last().unwrap().starts_with("skipped"));
+
+        assert!(scan("/no/such/dir").is_err());
+    }
+}
@@ -0,0 +1,200 @@
+// deck orders built to meet constraints, for testing particular situations (e.g. every 5 drawn
+// late to exercise the endgame) and for practice puzzle sets. orders are laid out the way
+// HanabiEnv::from_deck_order reads them: the player's hand, the partner's hand, then the draws
+use crate::hanabi_env::{Card, CardCollection};
+use crate::notation::parse_card;
+use crate::rand::seq::SliceRandom;
+use crate::rand::Rng;
+
+pub const DECK_SIZE: usize = 50;
+
+// the first 10 cards are dealt into the two starting hands
+const DEALT: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DealConstraint {
+    // every 5 among the last `n` cards of the deck, i.e. drawn as late as possible
+    FivesInBottom(usize),
+    // neither starting hand holds a 1, so nobody has an opening play
+    NoStartingOnes,
+    // `card` at position `i` of the order
+    CardAt(usize, Card),
+}
+
+impl DealConstraint {
+    // "fives-bottom:<n>", "no-starting-ones" or "card-at:<i>:<card>", e.g. "card-at:0:R5"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split(':').collect();
+        let index = |part: &str| {
+            part.parse::<usize>()
+                .ok()
+                .filter(|&i| i < DECK_SIZE)
+                .ok_or_else(|| format!("invalid deck position '{}'", part))
+        };
+        match parts.as_slice() {
+            ["fives-bottom", n] => match n.parse::<usize>() {
+                Ok(n) if n <= DECK_SIZE => Ok(DealConstraint::FivesInBottom(n)),
+                _ => Err(format!("invalid card count '{}'", n)),
+            },
+            ["no-starting-ones"] => Ok(DealConstraint::NoStartingOnes),
+            ["card-at", i, card] => {
+                let card = parse_card(card).ok_or_else(|| format!("invalid card '{}'", card))?;
+                Ok(DealConstraint::CardAt(index(i)?, card))
+            }
+            _ => Err(format!(
+                "unknown constraint '{}', expected fives-bottom:<n>, no-starting-ones or \
+                 card-at:<i>:<card>",
+                text
+            )),
+        }
+    }
+
+    // whether this constraint lets `card` go at position `i`
+    fn allows(&self, card: Card, i: usize) -> bool {
+        match self {
+            &DealConstraint::FivesInBottom(n) => card.suit_id() != 4 || i + n >= DECK_SIZE,
+            DealConstraint::NoStartingOnes => card.suit_id() != 0 || i >= DEALT,
+            &DealConstraint::CardAt(at, wanted) => i != at || card == wanted,
+        }
+    }
+}
+
+// every constraint is a rule about where single cards may go, so a deal is built by pinning the
+// CardAt cards, then placing the most restricted cards first into random positions they're
+// allowed in, starting over when a card is left with nowhere to go
+#[derive(Clone, Debug)]
+pub struct DealGenerator {
+    pub constraints: Vec<DealConstraint>,
+    pub max_attempts: usize,
+}
+
+impl Default for DealGenerator {
+    fn default() -> Self {
+        Self {
+            constraints: Vec::new(),
+            max_attempts: 1_000,
+        }
+    }
+}
+
+impl DealGenerator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, constraint: DealConstraint) -> Self {
+        self.constraints.push(constraint);
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn allows(&self, card: Card, i: usize) -> bool {
+        self.constraints.iter().all(|c| c.allows(card, i))
+    }
+
+    pub fn satisfies(&self, order: &[Card]) -> bool {
+        order.len() == DECK_SIZE && order.iter().enumerate().all(|(i, &c)| self.allows(c, i))
+    }
+
+    fn attempt<R: Rng>(&self, rng: &mut R) -> Option<Vec<Card>> {
+        let starting = CardCollection::starting_deck();
+        let mut cards: Vec<Card> = (0..25)
+            .flat_map(|id| (0..starting.counts[id]).map(move |_| Card::from_id(id as u8)))
+            .collect();
+        let mut order = vec![Card::none(); DECK_SIZE];
+        for constraint in self.constraints.iter() {
+            if let &DealConstraint::CardAt(i, card) = constraint {
+                let copy = cards.iter().position(|&c| c == card)?;
+                if order[i].is_some() || !self.allows(card, i) {
+                    return None;
+                }
+                order[i] = cards.swap_remove(copy);
+            }
+        }
+        cards.shuffle(rng);
+        // a stable sort keeps the shuffle among cards with as many options
+        let options = |card: Card| (0..DECK_SIZE).filter(|&i| self.allows(card, i)).count();
+        cards.sort_by_key(|&card| options(card));
+
+        for card in cards {
+            let free: Vec<usize> = (0..DECK_SIZE)
+                .filter(|&i| !order[i].is_some() && self.allows(card, i))
+                .collect();
+            order[*free.choose(rng)?] = card;
+        }
+        Some(order)
+    }
+
+    // a deck order meeting every constraint
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> Result<Vec<Card>, String> {
+        for _ in 0..self.max_attempts {
+            if let Some(order) = self.attempt(rng) {
+                debug_assert!(self.satisfies(&order));
+                return Ok(order);
+            }
+        }
+        Err(format!(
+            "no deal met {:?} in {} attempts",
+            self.constraints, self.max_attempts
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasEnd};
+    use crate::hanabi_env::HanabiEnv;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            DealConstraint::parse("fives-bottom:10"),
+            Ok(DealConstraint::FivesInBottom(10))
+        );
+        assert_eq!(
+            DealConstraint::parse("card-at:0:R5"),
+            Ok(DealConstraint::CardAt(0, parse_card("R5").unwrap()))
+        );
+        assert!(DealConstraint::parse("card-at:50:R5").is_err());
+        assert!(DealConstraint::parse("fives-top:3").is_err());
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let generator = DealGenerator::new()
+            .with(DealConstraint::FivesInBottom(10))
+            .with(DealConstraint::NoStartingOnes)
+            .with(DealConstraint::CardAt(0, parse_card("W4").unwrap()));
+        for _ in 0..20 {
+            let order = generator.generate(&mut rng).unwrap();
+            assert!(generator.satisfies(&order));
+            assert!(order[40..].iter().filter(|c| c.suit_id() == 4).count() == 5);
+            assert!(order[..10].iter().all(|c| c.suit_id() != 0));
+            assert_eq!(order[0], parse_card("W4").unwrap());
+
+            let mut counts = CardCollection::empty();
+            order.iter().for_each(|&c| counts.add(c));
+            assert_eq!(counts.counts, CardCollection::starting_deck().counts);
+
+            let mut env = HanabiEnv::from_deck_order(&order);
+            while !env.is_over() {
+                let action = env.random_action(&mut rng);
+                env.step(&action, &mut rng);
+            }
+        }
+
+        // five 5s don't fit in the bottom four cards
+        let impossible = DealGenerator::new()
+            .with(DealConstraint::FivesInBottom(4))
+            .with_max_attempts(3);
+        assert!(impossible.generate(&mut rng).is_err());
+    }
+}
@@ -0,0 +1,441 @@
+use crate::env::{Env, HasEnd, HasReward};
+use crate::hanabi_env::{
+    Action, Card, CardCollection, Deck, Fireworks, HanabiEnv, PrivateInfo, PublicInfo, RuleSet,
+};
+use crate::rand::Rng;
+use crate::table::{write_table, Table};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// with this many cards or fewer left in the deck the game is in its endgame, where the sparse
+// agents search and build-endgame-table starts solving
+pub const ENDGAME_DECK_SIZE: u8 = 2;
+
+// hands, deck, fireworks, tokens & last round info... the discard pile is whatever is left over
+type StateKey = ([u8; 5], [u8; 5], [u8; 25], [u8; 5], u8, u8, bool, u8);
+
+// a StateKey as the fixed size bytes stored in endgame tables
+pub const STATE_KEY_LEN: usize = 44;
+
+pub struct EndgameSolver {
+    cache: HashMap<StateKey, f32>,
+    // values solved in earlier runs, looked up before solving anything
+    table: Option<Arc<Table>>,
+}
+
+fn state_key(env: &HanabiEnv) -> StateKey {
+    let mut player_hand = [0; 5];
+    let mut opponent_hand = [0; 5];
+    for i in 0..5 {
+        player_hand[i] = env.player_hand[i].id();
+        opponent_hand[i] = env.opponent_hand[i].id();
+    }
+    (
+        player_hand,
+        opponent_hand,
+        env.deck.cards().counts,
+        env.fireworks.0,
+        env.blue_tokens.get(),
+        env.black_tokens.get(),
+        env.last_round,
+        env.last_round_turns_taken,
+    )
+}
+
+fn key_bytes(key: &StateKey) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(STATE_KEY_LEN);
+    bytes.extend_from_slice(&key.0);
+    bytes.extend_from_slice(&key.1);
+    bytes.extend_from_slice(&key.2);
+    bytes.extend_from_slice(&key.3);
+    bytes.extend_from_slice(&[key.4, key.5, key.6 as u8, key.7]);
+    bytes
+}
+
+// cards the player can't see: the deck plus their own hand
+pub fn unseen_cards(public_info: &PublicInfo, private_info: &PrivateInfo) -> CardCollection {
+    let mut unseen = public_info.rules.deck();
+    unseen.subtract(&public_info.discard);
+    unseen.remove_fireworks(&public_info.fireworks);
+    unseen.remove_hand(&private_info.opponent_hand);
+    unseen
+}
+
+// every assignment of the player's hand consistent with their hints, weighted by how many ways it can be dealt
+pub fn enumerate_hands(public_info: &PublicInfo, unseen: &CardCollection) -> Vec<([Card; 5], f32)> {
+    let mut hands = Vec::new();
+    let mut hand = [Card::none(); 5];
+    let mut unseen = *unseen;
+    enumerate_slot(public_info, &mut unseen, &mut hand, 0, 1.0, &mut hands);
+    hands
+}
+
+fn enumerate_slot(
+    public_info: &PublicInfo,
+    unseen: &mut CardCollection,
+    hand: &mut [Card; 5],
+    i: usize,
+    weight: f32,
+    hands: &mut Vec<([Card; 5], f32)>,
+) {
+    if i == 5 {
+        hands.push((*hand, weight));
+        return;
+    }
+    if !public_info.player_hints[i].is_some() {
+        enumerate_slot(public_info, unseen, hand, i + 1, weight, hands);
+        return;
+    }
+    for id in 0..25 {
+        let card = Card::from_id(id);
+        let count = unseen.counts[id as usize];
+        if count > 0 && public_info.player_hints[i].matches(card) {
+            hand[i] = unseen.remove(card);
+            enumerate_slot(
+                public_info,
+                unseen,
+                hand,
+                i + 1,
+                weight * count as f32,
+                hands,
+            );
+            unseen.add(card);
+        }
+    }
+    hand[i] = Card::none();
+}
+
+// where the next card of each suit is, as the player to move sees it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuitLock {
+    // all five are played
+    Complete,
+    // every copy of the next card is discarded, the suit can't advance again
+    Dead,
+    // the partner holds the next card, a clue or their own play advances the suit
+    Partner,
+    // the player's hints pin one of their slots down to the next card
+    Known,
+    // the next card is in the deck or somewhere in the player's hand they can't tell, so the
+    // suit stays put until it's drawn or clued
+    Locked,
+}
+
+// the card the suit needs next, none once it's complete
+pub fn next_card(fireworks: &Fireworks, color: usize) -> Option<Card> {
+    match fireworks.0[color] {
+        5 => None,
+        suit => Some(Card::from_parts(color as u8, suit)),
+    }
+}
+
+fn is_dead(rules: &RuleSet, card: Card, discard: &CardCollection) -> bool {
+    let id = card.id() as usize;
+    discard.counts[id] == rules.deck().counts[id]
+}
+
+pub fn suit_locks(public_info: &PublicInfo, private_info: &PrivateInfo) -> [SuitLock; 5] {
+    let unseen = unseen_cards(public_info, private_info);
+    let mut locks = [SuitLock::Complete; 5];
+    for (color, lock) in locks.iter_mut().enumerate() {
+        let next = match next_card(&public_info.fireworks, color) {
+            Some(card) => card,
+            None => continue,
+        };
+        let known = public_info.player_hints.iter().any(|hint| {
+            hint.is_some()
+                && (0..25).map(Card::from_id).all(|card| {
+                    card == next || unseen.counts[card.id() as usize] == 0 || !hint.matches(card)
+                })
+        });
+        *lock = if is_dead(&public_info.rules, next, &public_info.discard) {
+            SuitLock::Dead
+        } else if private_info.opponent_hand.contains(&next) {
+            SuitLock::Partner
+        } else if known {
+            SuitLock::Known
+        } else {
+            SuitLock::Locked
+        };
+    }
+    locks
+}
+
+// how many of the partner's cards play one after another from the current fireworks, e.g. R2 & R3
+// on R1 count for two
+pub fn partner_plays(public_info: &PublicInfo, private_info: &PrivateInfo) -> usize {
+    let mut fireworks = public_info.fireworks;
+    let mut plays = 0;
+    for color in 0..5 {
+        while let Some(next) = next_card(&fireworks, color) {
+            if !private_info.opponent_hand.contains(&next) {
+                break;
+            }
+            fireworks.0[color] += 1;
+            plays += 1;
+        }
+    }
+    plays
+}
+
+// once every suit is complete or dead nothing left in the game changes the reward
+fn nothing_left_to_play(env: &HanabiEnv) -> bool {
+    (0..5).all(|color| match next_card(&env.fireworks, color) {
+        Some(next) => is_dead(&env.rules, next, &env.discard),
+        None => true,
+    })
+}
+
+impl Default for EndgameSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EndgameSolver {
+    pub fn new() -> Self {
+        Self {
+            cache: HashMap::new(),
+            table: None,
+        }
+    }
+
+    pub fn with_table(mut self, table: Arc<Table>) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    pub fn cache_len(&self) -> usize {
+        self.cache.len()
+    }
+
+    // writes every state solved so far, along with the table's entries when `merge` is set
+    pub fn save_table(&self, path: &str, merge: Option<&Table>) -> Result<(), String> {
+        let mut entries: Vec<(Vec<u8>, f32)> = self
+            .cache
+            .iter()
+            .map(|(key, &value)| (key_bytes(key), value))
+            .collect();
+        if let Some(table) = merge {
+            if table.key_len() != STATE_KEY_LEN {
+                return Err(format!("table keys are {} bytes", table.key_len()));
+            }
+            entries.extend((0..table.len()).map(|i| {
+                let (key, value) = table.record(i);
+                (key.to_vec(), value)
+            }));
+        }
+        write_table(path, STATE_KEY_LEN, entries)
+    }
+
+    // a determinized upper bound on each action's value, not the value itself: each world is
+    // solved with both hands visible and the values are averaged over the worlds, so later moves
+    // may differ from world to world where the player couldn't tell them apart (strategy fusion).
+    // hints carry no information there and only act as a way to stall
+    pub fn upper_bound_values<R: Rng>(
+        &mut self,
+        public_info: &PublicInfo,
+        private_info: &PrivateInfo,
+        rng: &mut R,
+    ) -> Vec<(Action, f32)> {
+        let unseen = unseen_cards(public_info, private_info);
+        let hands = enumerate_hands(public_info, &unseen);
+        let total_weight = hands.iter().map(|(_, w)| w).sum::<f32>();
+
+        let mut values: Vec<(Action, f32)> = Vec::new();
+        for (hand, weight) in hands.iter() {
+            let opponent_info = PrivateInfo {
+                opponent_hand: *hand,
+            };
+            let env = HanabiEnv::new(
+                public_info,
+                &public_info.by_seat(private_info.clone(), opponent_info),
+            );
+            if values.is_empty() {
+                values = env.actions().into_iter().map(|a| (a, 0.0)).collect();
+            }
+            for (action, value) in values.iter_mut() {
+                *value += weight * self.chance_value(&env, action, rng) / total_weight;
+            }
+        }
+        values
+    }
+
+    fn state_value<R: Rng>(&mut self, env: &HanabiEnv, rng: &mut R) -> f32 {
+        if env.is_over() || nothing_left_to_play(env) {
+            return env.reward();
+        }
+
+        let key = state_key(env);
+        if let Some(&value) = self.cache.get(&key) {
+            return value;
+        }
+        if let Some(value) = self.table.as_ref().and_then(|t| t.get(&key_bytes(&key))) {
+            return value;
+        }
+
+        let mut best = f32::NEG_INFINITY;
+        for i in 0..5 {
+            if env.player_hand[i].is_some() {
+                best = best.max(self.chance_value(env, &Action::Play(i), rng));
+                if env.blue_tokens < env.rules.clue_tokens {
+                    best = best.max(self.chance_value(env, &Action::Discard(i), rng));
+                }
+            }
+        }
+        // every clue leads to the same state key: with both hands visible the hints are never
+        // read, and a clue only spends a token & passes the turn. so one of them stands for all
+        if env.blue_tokens > 0 {
+            let stall = env
+                .actions()
+                .into_iter()
+                .find(|a| matches!(a, Action::ColorHint(_) | Action::SuitHint(_)));
+            if let Some(stall) = stall {
+                best = best.max(self.chance_value(env, &stall, rng));
+            }
+        }
+
+        self.cache.insert(key, best);
+        best
+    }
+
+    fn chance_value<R: Rng>(&mut self, env: &HanabiEnv, action: &Action, rng: &mut R) -> f32 {
+        let deck = env.deck.cards();
+        let draws = match action {
+            &Action::Play(_) | &Action::Discard(_) => deck.total > 0,
+            _ => false,
+        };
+        if !draws {
+            let mut next = env.clone();
+            next.step(action, rng);
+            return self.state_value(&next, rng);
+        }
+
+        let mut value = 0.0;
+        for id in 0..25 {
+            let count = deck.counts[id];
+            if count == 0 {
+                continue;
+            }
+            let card = Card::from_id(id as u8);
+            let mut rest = deck;
+            rest.remove(card);
+
+            let mut next = env.clone();
+            next.deck = Deck::Ordered(vec![card]);
+            next.step(action, rng);
+            next.deck = Deck::Multiset(rest);
+
+            value += count as f32 / deck.total as f32 * self.state_value(&next, rng);
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hanabi_env::Hint;
+    use crate::rand::prelude::SliceRandom;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_enumerated_hands_match_unseen_cards() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        while env.deck.remaining() > ENDGAME_DECK_SIZE {
+            // never play so the game can't bomb out before the endgame
+            let actions: Vec<Action> = env
+                .actions()
+                .into_iter()
+                .filter(|a| !matches!(a, Action::Play(_)))
+                .collect();
+            env.step(actions.choose(&mut rng).unwrap(), &mut rng);
+        }
+
+        let public_info = env.public_info();
+        let private_info = env.private_info(env.current_player());
+        assert_eq!(public_info.deck_total(), env.deck.remaining());
+
+        let hands = enumerate_hands(&public_info, &unseen_cards(&public_info, &private_info));
+        assert!(hands.iter().any(|(hand, _)| *hand == env.player_hand));
+
+        // the clues are all the same stall to the clairvoyant solve
+        let values = EndgameSolver::new().upper_bound_values(&public_info, &private_info, &mut rng);
+        assert!(values.iter().all(|(a, _)| env.actions().contains(a)));
+        let clues: Vec<f32> = values
+            .iter()
+            .filter(|(a, _)| matches!(a, Action::ColorHint(_) | Action::SuitHint(_)))
+            .map(|&(_, value)| value)
+            .collect();
+        assert!(clues.len() > 1);
+        assert!(clues.iter().all(|&value| value == clues[0]), "{:?}", values);
+    }
+
+    // one card left in the deck, the partner holds B4 & B5 clued and Y2/G2 are nowhere visible
+    const STALL: &str = "B4.B5.G3.Y3.W1 ?.?.?.?.? B4.B5.?.?.? 55311 \
+        W1W2W3W4R1R1R2R3R4B1B1B2B3B4Y1Y1Y2Y3Y4G1G1G2G3G4 3/2 -";
+
+    #[test]
+    fn test_suit_locks() {
+        use crate::position::Position;
+
+        let mut position = Position::parse(STALL).unwrap();
+        assert_eq!(position.public_info.deck_total(), 1);
+        let locks = |p: &Position| suit_locks(&p.public_info, &p.private_info);
+        assert_eq!(
+            locks(&position),
+            [
+                SuitLock::Complete,
+                SuitLock::Complete,
+                SuitLock::Partner,
+                SuitLock::Locked,
+                SuitLock::Locked,
+            ]
+        );
+        assert_eq!(
+            partner_plays(&position.public_info, &position.private_info),
+            2
+        );
+
+        position.public_info.player_hints[0] = Hint::from_masks(1 << 3, 1 << 1);
+        position.public_info.discard.add(Card::from_parts(4, 1));
+        assert_eq!(locks(&position)[3], SuitLock::Known);
+        assert_eq!(locks(&position)[4], SuitLock::Dead);
+    }
+
+    #[test]
+    fn test_solver_reads_saved_table() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut env = HanabiEnv::random(&mut rng);
+        while env.deck.remaining() > ENDGAME_DECK_SIZE {
+            let actions: Vec<Action> = env
+                .actions()
+                .into_iter()
+                .filter(|a| !matches!(a, Action::Play(_)))
+                .collect();
+            env.step(actions.choose(&mut rng).unwrap(), &mut rng);
+        }
+        let public_info = env.public_info();
+        let private_info = env.private_info(env.current_player());
+
+        let mut solver = EndgameSolver::new();
+        let values = solver.upper_bound_values(&public_info, &private_info, &mut rng);
+        assert!(solver.cache_len() > 0);
+        let path = std::env::temp_dir().join(format!("hanabi-endgame-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        solver.save_table(path, None).unwrap();
+
+        let table = Arc::new(Table::open(path).unwrap());
+        assert_eq!(table.len(), solver.cache_len());
+        let mut from_table = EndgameSolver::new().with_table(table);
+        assert_eq!(
+            from_table.upper_bound_values(&public_info, &private_info, &mut rng),
+            values
+        );
+        // everything below the root came from the table
+        assert_eq!(from_table.cache_len(), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+}
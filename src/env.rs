@@ -1,4 +1,6 @@
-use crate::rand::Rng;
+use crate::rand::prelude::SliceRandom;
+use crate::rand::rngs::StdRng;
+use crate::rand::{Rng, SeedableRng};
 
 pub trait HasEnd {
     fn is_over(&self) -> bool;
@@ -9,31 +11,119 @@ pub trait HasReward {
     fn reward(&self) -> Self::Reward;
 }
 
+pub trait HasKey {
+    fn key(&self) -> u64;
+}
+
+pub trait HasSymmetry<A>: HasKey + Sized {
+    type Symmetry: Copy;
+
+    // the symmetry that maps self onto its canonical representative
+    fn canonical_symmetry(&self) -> Self::Symmetry;
+    fn apply(&self, symmetry: &Self::Symmetry) -> Self;
+    fn apply_to_action(symmetry: &Self::Symmetry, action: &A) -> A;
+    fn invert(symmetry: &Self::Symmetry) -> Self::Symmetry;
+
+    fn canonical_key(&self) -> u64 {
+        self.apply(&self.canonical_symmetry()).key()
+    }
+}
+
 pub trait Env: HasEnd + HasReward + Clone {
     type PublicInfo: HasEnd + HasReward + Clone;
     type PrivateInfo: Clone;
     type Action;
+    // an action with only its chance event left, see `apply_action`
+    type Outcome: Clone;
+    type Chance: Clone;
 
     fn random<R: Rng>(rng: &mut R) -> Self;
 
-    fn new(
-        public_info: &Self::PublicInfo,
-        player_private_info: &Self::PrivateInfo,
-        opponent_private_info: &Self::PrivateInfo,
-    ) -> Self;
+    // seats are numbered from 0 in turn order. `private_infos[seat]` is what that seat sees
+    fn new(public_info: &Self::PublicInfo, private_infos: &[Self::PrivateInfo]) -> Self;
 
-    fn sample_opponent_info<R: Rng>(
+    // what every seat sees, by seat, with whatever the player to move can't see sampled to fit
+    // `player_private_info`. returns the natural log of the weight the sample carries too, 0
+    // when every sample is drawn as likely as it is in the game
+    fn sample_private_infos<R: Rng>(
         public_info: &Self::PublicInfo,
         player_private_info: &Self::PrivateInfo,
         rng: &mut R,
-    ) -> (Self::PrivateInfo, f32);
+    ) -> (Vec<Self::PrivateInfo>, f32);
+
+    fn num_players(&self) -> usize {
+        2
+    }
+
+    // a key of what a player holding `private_info` sees that the public info doesn't show, the
+    // partner's hand in Hanabi. 0 when there's nothing, MCTS files nodes under it & the public key
+    fn view_key(_private_info: &Self::PrivateInfo) -> u64 {
+        0
+    }
+
+    // the symmetry that maps the state, as a player holding `private_info` sees it, onto its
+    // canonical representative, and the view key of `private_info` under it. the public info's
+    // own symmetry for games where what the player sees can't tell symmetric states apart
+    fn canonical_view(
+        public_info: &Self::PublicInfo,
+        private_info: &Self::PrivateInfo,
+    ) -> (
+        <Self::PublicInfo as HasSymmetry<Self::Action>>::Symmetry,
+        u64,
+    )
+    where
+        Self::PublicInfo: HasSymmetry<Self::Action>,
+    {
+        (
+            public_info.canonical_symmetry(),
+            Self::view_key(private_info),
+        )
+    }
+
+    // the seat to move
+    fn current_player(&self) -> usize;
 
     fn public_info(&self) -> Self::PublicInfo;
-    fn private_info(&self, player_perspective: bool) -> Self::PrivateInfo;
+
+    // what `player`'s seat sees
+    fn private_info(&self, player: usize) -> Self::PrivateInfo;
 
     fn actions(&self) -> Vec<Self::Action>;
+
+    // a uniformly random legal action. envs override it where `actions` allocating would show
+    // up in rollouts
+    fn random_action<R: Rng>(&self, rng: &mut R) -> Self::Action
+    where
+        Self::Action: Clone,
+    {
+        self.actions().choose(rng).unwrap().clone()
+    }
+
+    // the actions a search branches on: `actions` without any the env can show are never better
+    // than another one
+    fn search_actions(&self) -> Vec<Self::Action> {
+        self.actions()
+    }
+
     fn step<R: Rng>(&mut self, action: &Self::Action, rng: &mut R);
 
+    // `step` split at the chance event, so a search can branch on what gets drawn: `apply_action`
+    // is the mover's choice and changes nothing, `resolve_chance` finishes the step
+    fn apply_action(&self, action: &Self::Action) -> Self::Outcome;
+
+    // what the chance event can come out as & how likely each is, summing to 1. empty when the
+    // outcome has nothing left to chance
+    fn chance_outcomes(&self, outcome: &Self::Outcome) -> Vec<(Self::Chance, f32)>;
+
+    fn resolve_chance<R: Rng>(&mut self, outcome: &Self::Outcome, rng: &mut R);
+
+    // fails without changing the env when `chance` isn't possible
+    fn resolve_chance_with(
+        &mut self,
+        outcome: &Self::Outcome,
+        chance: &Self::Chance,
+    ) -> Result<(), String>;
+
     fn determinize<R: Rng>(
         public_info: &Self::PublicInfo,
         player_private_info: &Self::PrivateInfo,
@@ -42,10 +132,320 @@ pub trait Env: HasEnd + HasReward + Clone {
     where
         Self: std::marker::Sized,
     {
-        let (opponent_private_info, probability) =
-            Self::sample_opponent_info(public_info, player_private_info, &mut rng);
-        let env = Self::new(public_info, player_private_info, &opponent_private_info);
+        let (private_infos, log_prob) =
+            Self::sample_private_infos(public_info, player_private_info, &mut rng);
+        (Self::new(public_info, &private_infos), log_prob)
+    }
+}
+
+// the mean of `value` over the positions `action` can lead to, branching on every chance outcome
+// instead of sampling one
+pub fn chance_expectation<E: Env, F: Fn(&E) -> f32>(env: &E, action: &E::Action, value: F) -> f32 {
+    let outcome = env.apply_action(action);
+    let chances = env.chance_outcomes(&outcome);
+    if chances.is_empty() {
+        // nothing is drawn, so the rng is never used
+        let mut next = env.clone();
+        next.resolve_chance(&outcome, &mut StdRng::seed_from_u64(0));
+        value(&next)
+    } else {
+        chances
+            .iter()
+            .map(|(chance, p)| {
+                let mut next = env.clone();
+                next.resolve_chance_with(&outcome, chance).unwrap();
+                p * value(&next)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guess_env::GuessEnv;
+    use crate::hanabi_env::HanabiEnv;
+    use crate::mcts::MCTS;
+    use std::fmt::Debug;
+
+    // what every env has to agree on, checked over random games
+    fn check_env<E>(num_games: u64)
+    where
+        E: Env + HasReward<Reward = f32>,
+        E::PublicInfo: HasKey,
+        E::PrivateInfo: PartialEq + Debug,
+        E::Action: Clone + Debug,
+    {
+        for seed in 0..num_games {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut env = E::random(&mut rng);
+            let mut num_steps = 0;
+            while !env.is_over() {
+                let (public_info, private_info) =
+                    (env.public_info(), env.private_info(env.current_player()));
+                assert_eq!(public_info.is_over(), env.is_over());
+
+                // rebuilding the env from every seat's private info gives it back
+                let seats: Vec<usize> = (0..env.num_players()).collect();
+                let private_infos: Vec<E::PrivateInfo> =
+                    seats.iter().map(|&seat| env.private_info(seat)).collect();
+                let rebuilt = E::new(&public_info, &private_infos);
+                assert_eq!(rebuilt.public_info().key(), public_info.key());
+                assert_eq!(rebuilt.current_player(), env.current_player());
+                for &seat in seats.iter() {
+                    assert_eq!(rebuilt.private_info(seat), private_infos[seat]);
+                }
+
+                // a determinization only changes what the mover can't see
+                let (world, log_prob) = E::determinize(&public_info, &private_info, &mut rng);
+                assert!(log_prob <= 0.0);
+                assert_eq!(world.public_info().key(), public_info.key());
+                assert_eq!(world.current_player(), env.current_player());
+                assert_eq!(world.private_info(world.current_player()), private_info);
+
+                let actions = env.actions();
+                assert!(!actions.is_empty());
+                let action = actions.choose(&mut rng).unwrap().clone();
+                let outcome = env.apply_action(&action);
+                let chances = env.chance_outcomes(&outcome);
+                if !chances.is_empty() {
+                    let total: f32 = chances.iter().map(|(_, p)| p).sum();
+                    assert!((total - 1.0).abs() < 1e-4, "{:?}: {}", action, total);
+                    for (chance, _) in chances.iter() {
+                        let mut next = env.clone();
+                        next.resolve_chance_with(&outcome, chance).unwrap();
+                    }
+                }
+                env.resolve_chance(&outcome, &mut rng);
+                num_steps += 1;
+                assert!(num_steps < 1_000, "seed {} never ends", seed);
+            }
+            assert!((0.0..=1.0).contains(&env.reward()));
+        }
+    }
+
+    // a three seat game, so the contract & the search are checked with more than two seats:
+    // every seat holds a hidden bit the others can see, and on their turn either calls their
+    // own bit, scoring a point when right and drawing a new one, or passes
+    const ROUND_SEATS: usize = 3;
+    const ROUND_TURNS: u8 = 9;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct RoundPublicInfo {
+        turn: u8,
+        score: u8,
+    }
+
+    // the bits of every seat but the viewer's
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct RoundPrivateInfo {
+        bits: [Option<bool>; ROUND_SEATS],
+    }
+
+    #[derive(Copy, Clone, Debug)]
+    struct RoundEnv {
+        bits: [bool; ROUND_SEATS],
+        public: RoundPublicInfo,
+    }
+
+    impl HasEnd for RoundPublicInfo {
+        fn is_over(&self) -> bool {
+            self.turn == ROUND_TURNS
+        }
+    }
+
+    impl HasReward for RoundPublicInfo {
+        type Reward = f32;
+
+        fn reward(&self) -> f32 {
+            self.score as f32 / ROUND_TURNS as f32
+        }
+    }
+
+    impl HasKey for RoundPublicInfo {
+        fn key(&self) -> u64 {
+            self.turn as u64 | (self.score as u64) << 8
+        }
+    }
+
+    impl HasSymmetry<Option<bool>> for RoundPublicInfo {
+        type Symmetry = ();
+
+        fn canonical_symmetry(&self) {}
+
+        fn apply(&self, _symmetry: &()) -> Self {
+            *self
+        }
+
+        fn apply_to_action(_symmetry: &(), action: &Option<bool>) -> Option<bool> {
+            *action
+        }
+
+        fn invert(_symmetry: &()) {}
+    }
+
+    impl HasEnd for RoundEnv {
+        fn is_over(&self) -> bool {
+            self.public.is_over()
+        }
+    }
+
+    impl HasReward for RoundEnv {
+        type Reward = f32;
+
+        fn reward(&self) -> f32 {
+            self.public.reward()
+        }
+    }
+
+    impl RoundEnv {
+        // `drawn` replaces the mover's bit after a call
+        fn apply(&mut self, action: &Option<bool>, drawn: bool) {
+            if let Some(call) = *action {
+                let mover = self.current_player();
+                self.public.score += (self.bits[mover] == call) as u8;
+                self.bits[mover] = drawn;
+            }
+            self.public.turn += 1;
+        }
+    }
+
+    // a call is Some(bit), a pass None
+    impl Env for RoundEnv {
+        type PublicInfo = RoundPublicInfo;
+        type PrivateInfo = RoundPrivateInfo;
+        type Action = Option<bool>;
+        type Outcome = Option<bool>;
+        type Chance = bool;
+
+        fn random<R: Rng>(rng: &mut R) -> Self {
+            Self {
+                bits: [rng.gen(), rng.gen(), rng.gen()],
+                public: RoundPublicInfo { turn: 0, score: 0 },
+            }
+        }
+
+        fn new(public_info: &RoundPublicInfo, private_infos: &[RoundPrivateInfo]) -> Self {
+            assert_eq!(private_infos.len(), ROUND_SEATS);
+            let mut bits = [false; ROUND_SEATS];
+            for (seat, bit) in bits.iter_mut().enumerate() {
+                *bit = private_infos[(seat + 1) % ROUND_SEATS].bits[seat].unwrap();
+            }
+            Self {
+                bits,
+                public: *public_info,
+            }
+        }
+
+        fn sample_private_infos<R: Rng>(
+            public_info: &RoundPublicInfo,
+            player_private_info: &RoundPrivateInfo,
+            rng: &mut R,
+        ) -> (Vec<RoundPrivateInfo>, f32) {
+            let mut env = Self {
+                bits: [false; ROUND_SEATS],
+                public: *public_info,
+            };
+            for seat in 0..ROUND_SEATS {
+                env.bits[seat] = player_private_info.bits[seat].unwrap_or_else(|| rng.gen());
+            }
+            let seats = (0..ROUND_SEATS).map(|seat| env.private_info(seat));
+            (seats.collect(), 0.0)
+        }
+
+        fn num_players(&self) -> usize {
+            ROUND_SEATS
+        }
+
+        fn current_player(&self) -> usize {
+            self.public.turn as usize % ROUND_SEATS
+        }
+
+        fn public_info(&self) -> RoundPublicInfo {
+            self.public
+        }
+
+        fn private_info(&self, player: usize) -> RoundPrivateInfo {
+            let mut bits = [None; ROUND_SEATS];
+            for seat in (0..ROUND_SEATS).filter(|&seat| seat != player) {
+                bits[seat] = Some(self.bits[seat]);
+            }
+            RoundPrivateInfo { bits }
+        }
+
+        fn actions(&self) -> Vec<Option<bool>> {
+            match self.is_over() {
+                true => Vec::new(),
+                false => vec![Some(false), Some(true), None],
+            }
+        }
+
+        fn step<R: Rng>(&mut self, action: &Option<bool>, rng: &mut R) {
+            let outcome = self.apply_action(action);
+            self.resolve_chance(&outcome, rng);
+        }
+
+        fn apply_action(&self, action: &Option<bool>) -> Option<bool> {
+            *action
+        }
+
+        fn chance_outcomes(&self, outcome: &Option<bool>) -> Vec<(bool, f32)> {
+            match outcome {
+                Some(_) => vec![(false, 0.5), (true, 0.5)],
+                None => Vec::new(),
+            }
+        }
+
+        fn resolve_chance<R: Rng>(&mut self, outcome: &Option<bool>, rng: &mut R) {
+            self.apply(outcome, rng.gen());
+        }
+
+        fn resolve_chance_with(
+            &mut self,
+            outcome: &Option<bool>,
+            chance: &bool,
+        ) -> Result<(), String> {
+            self.apply(outcome, *chance);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_env_contract() {
+        check_env::<HanabiEnv>(5);
+        check_env::<GuessEnv>(50);
+        check_env::<RoundEnv>(20);
+
+        // the search & its rave statistics run on three seats, a seat's own moves are every
+        // third one
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = RoundEnv::random(&mut rng);
+        let mut mcts = MCTS::<RoundEnv>::with_capacity(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            500,
+            0,
+        )
+        .with_rave(100.0);
+        mcts.explore_n(500);
+        assert!(env.actions().contains(&mcts.best_action()));
+        let root = &mcts.nodes[mcts.root];
+        assert!(root.edges.iter().all(|e| e.amaf_visits <= root.num_visits));
+    }
+
+    #[test]
+    #[should_panic(expected = "HanabiEnv seats exactly 2 players")]
+    fn test_hanabi_env_has_two_seats() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let seat = env.private_info(0);
+        HanabiEnv::new(&env.public_info(), &[seat.clone(), seat.clone(), seat]);
+    }
 
-        (env, probability)
+    #[test]
+    #[should_panic(expected = "HanabiEnv seats exactly 2 players")]
+    fn test_hanabi_env_has_no_third_hand() {
+        let mut rng = StdRng::seed_from_u64(0);
+        HanabiEnv::random(&mut rng).private_info(2);
     }
 }
@@ -0,0 +1,265 @@
+use crate::abstraction::{classify_with, CardClass};
+use crate::beliefs::Beliefs;
+use crate::endgame::{partner_plays, unseen_cards};
+use crate::env::{Env, HasReward};
+use crate::hanabi_env::{Action, Card, HanabiEnv, Hint, PrivateInfo, PublicInfo};
+
+pub trait Evaluator<E: Env> {
+    // value of the position for the player to move, and a prior for each of `actions`
+    fn evaluate(
+        &mut self,
+        public_info: &E::PublicInfo,
+        private_info: &E::PrivateInfo,
+        actions: &[E::Action],
+    ) -> (f32, Vec<f32>);
+}
+
+// uses the shaped reward as the value. priors come from the player's beliefs about their own
+// hand: plays by how likely the card is playable, discards by how likely it's trash and away from
+// cards that may be the last copy
+pub struct HeuristicEvaluator;
+
+// cards the player might be holding in a slot with this hint
+pub fn possible_cards(
+    hint: &Hint,
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+) -> Vec<Card> {
+    let mut possible = hint.card_mask() & unseen_cards(public_info, private_info).card_mask();
+    let mut cards = Vec::with_capacity(possible.count_ones() as usize);
+    while possible != 0 {
+        cards.push(Card::from_id(possible.trailing_zeros() as u8));
+        possible &= possible - 1;
+    }
+    cards
+}
+
+impl Evaluator<HanabiEnv> for HeuristicEvaluator {
+    fn evaluate(
+        &mut self,
+        public_info: &PublicInfo,
+        private_info: &PrivateInfo,
+        actions: &[Action],
+    ) -> (f32, Vec<f32>) {
+        let summary = Beliefs::new(public_info, private_info).summary(public_info);
+        let mut priors: Vec<f32> = actions
+            .iter()
+            .map(|action| match *action {
+                // a certain play gets 5 times the weight of an unlikely one
+                Action::Play(i) => 1.0 + 4.0 * summary[i].playable,
+                Action::Discard(i) => (1.0 + summary[i].trash) * (1.0 - 0.5 * summary[i].critical),
+                _ => 1.0,
+            })
+            .collect();
+        let total = priors.iter().sum::<f32>();
+        for p in priors.iter_mut() {
+            *p /= total;
+        }
+        (public_info.reward(), priors)
+    }
+}
+
+// another slot in the partner's hand holding the same card as slot `i` that a clue touched
+fn touched_duplicate(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    i: usize,
+) -> Option<usize> {
+    let card = private_info.opponent_hand[i];
+    let touched = public_info.hint_history.touched(false);
+    (0..5).find(|&j| j != i && private_info.opponent_hand[j] == card && touched & 1 << j != 0)
+}
+
+// a clue touching `card` that only stalls, by color unless the rules forbid its color
+fn stall_clue(public_info: &PublicInfo, card: Card) -> Action {
+    let clue = Action::ColorHint(card.color());
+    match public_info.rules.allows_clue(&clue) {
+        true => clue,
+        false => Action::SuitHint(card.suit()),
+    }
+}
+
+// a fast rule based agent: play certain cards, clue playable cards, otherwise discard
+pub fn heuristic_policy(public_info: &PublicInfo, private_info: &PrivateInfo) -> Action {
+    let hints = &public_info.player_hints;
+    for i in (0..5).filter(|&i| hints[i].is_some()) {
+        let cards = possible_cards(&hints[i], public_info, private_info);
+        if !cards.is_empty() && cards.iter().all(|&c| public_info.fireworks.accepts(c)) {
+            return Action::Play(i);
+        }
+    }
+
+    if public_info.blue_tokens > 0 {
+        for (i, &card) in private_info.opponent_hand.iter().enumerate() {
+            if !card.is_some() || !public_info.fireworks.accepts(card) {
+                continue;
+            }
+            let hint = public_info.opponent_hints[i];
+            let touched = public_info.hint_history.touched(false) & 1 << i != 0;
+            // when the partner holds duplicates, only the copy that's already touched gets clued
+            if !touched && touched_duplicate(public_info, private_info, i).is_some() {
+                continue;
+            }
+            let color_clue = Action::ColorHint(card.color());
+            if hint.color_mask() != 1 << card.color_id()
+                && public_info.rules.allows_clue(&color_clue)
+            {
+                return color_clue;
+            }
+            if hint.suit_mask() != 1 << card.suit_id() {
+                return Action::SuitHint(card.suit());
+            }
+        }
+    }
+
+    // drawing the last card leaves one more turn each, so while the partner has more plays lined
+    // up than that, burn a clue rather than discard
+    if public_info.blue_tokens > 0
+        && public_info.deck_total() == 1
+        && partner_plays(public_info, private_info) > 1
+    {
+        if let Some(&card) = private_info.opponent_hand.iter().find(|c| c.is_some()) {
+            return stall_clue(public_info, card);
+        }
+    }
+
+    let present: Vec<usize> = (0..5).filter(|&i| hints[i].is_some()).collect();
+    if public_info.blue_tokens < public_info.rules.clue_tokens {
+        // a card that's trash whatever it is goes first, then one nobody has clued
+        let (rules, fireworks, discard) = (
+            &public_info.rules,
+            &public_info.fireworks,
+            &public_info.discard,
+        );
+        let known_trash = present.iter().find(|&&i| {
+            let cards = possible_cards(&hints[i], public_info, private_info);
+            let trash = |&c: &Card| classify_with(rules, c, fireworks, discard) == CardClass::Trash;
+            !cards.is_empty() && cards.iter().all(trash)
+        });
+        let touched = public_info.hint_history.touched(true);
+        let untouched = present.iter().find(|&&i| touched & 1 << i == 0);
+        return Action::Discard(*known_trash.or(untouched).unwrap_or(&present[0]));
+    }
+
+    // every token and nothing useful to clue, so any clue is a free pass
+    match private_info.opponent_hand.iter().find(|c| c.is_some()) {
+        Some(&card) => stall_clue(public_info, card),
+        None => Action::Play(present[0]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::MCTS;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_puct_with_heuristic_evaluator() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            500,
+            0,
+        )
+        .with_evaluator(Box::new(HeuristicEvaluator));
+        mcts.explore_n(500);
+
+        let root = &mcts.nodes[mcts.root];
+        let total_prior = root.edges.iter().map(|e| e.prior).sum::<f32>();
+        assert!((total_prior - 1.0).abs() < 1e-4);
+        assert_eq!(root.num_visits, 500.0);
+        assert!(env.actions().contains(&mcts.best_action()));
+    }
+
+    #[test]
+    fn test_priors_follow_own_hand_beliefs() {
+        use crate::position::Position;
+
+        // slot 0 is a known R1, slot 1 a known 5 and slot 2 a 1 of a color already started
+        let position =
+            Position::parse("B4.Y3.G2.W4.Y4 R1.5.W1.?.? ?.?.?.?.? 10000 - 6/3 -").unwrap();
+        let actions = [
+            Action::Play(0),
+            Action::Play(3),
+            Action::Discard(1),
+            Action::Discard(2),
+            Action::Discard(3),
+        ];
+        let (_, priors) =
+            HeuristicEvaluator.evaluate(&position.public_info, &position.private_info, &actions);
+        assert!(priors[0] > priors[1]);
+        assert!(priors[3] > priors[4] && priors[4] > priors[2]);
+    }
+
+    #[test]
+    fn test_heuristic_policy_clues_touched_duplicate() {
+        use crate::hanabi_env::Suit;
+        use crate::scenarios::touched_and_drawn_copy;
+
+        // the untouched R1 in slot 1 comes first, but the touched copy in slot 3 gets finished
+        let env = touched_and_drawn_copy();
+        assert_eq!(
+            heuristic_policy(&env.public_info(), &env.private_info(env.current_player())),
+            Action::SuitHint(Suit::One)
+        );
+    }
+
+    #[test]
+    fn test_heuristic_policy_keeps_touched_cards() {
+        use crate::scenarios::clued_twos;
+
+        // every slot has information from the clue, only 0 & 3 were touched by it
+        let env = clued_twos();
+        assert!(env.public_info().player_hints[1] != Hint::empty());
+        assert_eq!(
+            heuristic_policy(&env.public_info(), &env.private_info(env.current_player())),
+            Action::Discard(1)
+        );
+    }
+
+    #[test]
+    fn test_heuristic_policy_stalls() {
+        use crate::hanabi_env::Color;
+        use crate::position::Position;
+
+        // discarding would draw the last card with B4 & B5 still to play, so it burns a clue
+        let mut position = Position::parse(
+            "B4.B5.G3.Y3.W1 ?.?.?.?.? B4.B5.?.?.? 55311 \
+             W1W2W3W4R1R1R2R3R4B1B1B2B3B4Y1Y1Y2Y3Y4G1G1G2G3G4 3/2 -",
+        )
+        .unwrap();
+        assert_eq!(
+            heuristic_policy(&position.public_info, &position.private_info),
+            Action::ColorHint(Color::Blue)
+        );
+
+        // with nothing lined up past the partner's final turn it discards as usual
+        position.private_info.opponent_hand[1] = Card::from_parts(0, 0);
+        position.public_info.discard.remove(Card::from_parts(0, 0));
+        position.public_info.discard.add(Card::from_parts(2, 4));
+        match heuristic_policy(&position.public_info, &position.private_info) {
+            Action::Discard(_) => {}
+            action => panic!("expected a discard, got {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_heuristic_policy_is_legal() {
+        use crate::env::HasEnd;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut env = HanabiEnv::random(&mut rng);
+            while !env.is_over() {
+                let action =
+                    heuristic_policy(&env.public_info(), &env.private_info(env.current_player()));
+                assert!(env.actions().contains(&action));
+                env.step(&action, &mut rng);
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+// why a search picked its move: the chosen action next to the best few alternatives, each with
+// its value and what the action risks or tells the partner, under the pace & efficiency of the
+// position
+use crate::action_info::ActionInfo;
+use crate::advisor::Suggestion;
+use crate::analysis::{analyze, Analysis};
+use crate::hanabi_env::{PrivateInfo, PublicInfo};
+
+const NUM_ALTERNATIVES: usize = 3;
+
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    pub chosen: (Suggestion, ActionInfo),
+    // the next best actions by visits
+    pub alternatives: Vec<(Suggestion, ActionInfo)>,
+    pub analysis: Analysis,
+}
+
+// `suggestions` most visited first, as `advisor::suggestions` returns them. the most visited
+// action is the one the search plays
+pub fn explain(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    suggestions: &[Suggestion],
+) -> Option<Explanation> {
+    let mut explained = suggestions.iter().map(|s| {
+        (
+            s.clone(),
+            ActionInfo::new(public_info, private_info, &s.action),
+        )
+    });
+    let chosen = explained.next()?;
+    Some(Explanation {
+        chosen,
+        alternatives: explained.take(NUM_ALTERNATIVES).collect(),
+        analysis: analyze(public_info),
+    })
+}
+
+fn describe(
+    f: &mut std::fmt::Formatter<'_>,
+    (suggestion, info): &(Suggestion, ActionInfo),
+) -> std::fmt::Result {
+    write!(
+        f,
+        "{:?}: value={:.3} visits={}",
+        suggestion.action, suggestion.mean_reward, suggestion.visits
+    )?;
+    if let Some(p) = info.bomb_probability {
+        write!(
+            f,
+            " | playable {:.0}%, bomb risk {:.0}%",
+            100.0 * (1.0 - p),
+            100.0 * p
+        )?;
+    }
+    if let Some(p) = info.critical_probability {
+        write!(f, " | discards a critical card {:.0}%", 100.0 * p)?;
+    }
+    if let Some(bits) = info.info_gain {
+        write!(
+            f,
+            " | touches {} card(s), partner learns {:.1} bits",
+            info.slots.len(),
+            bits
+        )?;
+        if info.touches_critical {
+            write!(f, ", saves a critical card")?;
+        }
+        if info.touches_trash {
+            write!(f, ", touches trash")?;
+        }
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "chose ")?;
+        describe(f, &self.chosen)?;
+        for alternative in self.alternatives.iter() {
+            write!(f, "\n  vs ")?;
+            describe(f, alternative)?;
+            write!(
+                f,
+                " ({:+.3} from the chosen action)",
+                alternative.0.mean_reward - self.chosen.0.mean_reward
+            )?;
+        }
+        write!(f, "\n  position: {}", self.analysis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisor::advise;
+    use crate::env::Env;
+    use crate::hanabi_env::HanabiEnv;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_explain() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let suggestions = advise(&public_info, &private_info, 2_000, 0);
+        let explanation = explain(&public_info, &private_info, &suggestions).unwrap();
+        assert_eq!(explanation.chosen.0, suggestions[0]);
+        assert_eq!(explanation.alternatives.len(), 3);
+        assert_eq!(explanation.alternatives[0].0, suggestions[1]);
+
+        let text = explanation.to_string();
+        assert!(text.starts_with("chose "));
+        assert_eq!(text.lines().count(), 5);
+        assert!(text.contains("partner learns"));
+        assert!(text.contains("position: pace=17"));
+        assert!(explain(&public_info, &private_info, &[]).is_none());
+    }
+}
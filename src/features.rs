@@ -0,0 +1,190 @@
+// the features positions & actions are described to learned models with: `encode` is the input
+// of the policy/value network, see onnx_evaluator.rs, and `action_features` what the NRPA rollout
+// policy weighs, see nrpa.rs. both are built from the same beliefs & hint encodings
+use crate::beliefs::Beliefs;
+use crate::hanabi_env::{Action, Card, CardCollection, Hint, PrivateInfo, PublicInfo};
+
+// fireworks one-hot (5x6), discard counts (25), tokens (2), last round (1), deck size (1),
+// opponent hand one-hot (5x25), opponent hints (5x10), own hand beliefs (5x25), own hints (5x10),
+// own slots playable/trash/critical (5x3)
+pub const INPUT_SIZE: usize = 30 + 25 + 2 + 1 + 1 + 125 + 50 + 125 + 50 + 15;
+
+// color hints (5), suit hints (5), play slot (5), discard slot (5)
+pub const POLICY_SIZE: usize = 20;
+
+fn encode_hint(hint: &Hint, features: &mut Vec<f32>) {
+    for bit in 0..5 {
+        features.push(((hint.color_mask() >> bit) & 1) as f32);
+    }
+    for bit in 0..5 {
+        features.push(((hint.suit_mask() >> bit) & 1) as f32);
+    }
+}
+
+fn encode_card(card: Card, features: &mut Vec<f32>) {
+    for id in 0..25 {
+        features.push(if card.is_some() && card.id() == id {
+            1.0
+        } else {
+            0.0
+        });
+    }
+}
+
+pub fn encode(public_info: &PublicInfo, private_info: &PrivateInfo) -> Vec<f32> {
+    let mut features = Vec::with_capacity(INPUT_SIZE);
+    for color in 0..5 {
+        for level in 0..6 {
+            features.push(if public_info.fireworks.0[color] == level {
+                1.0
+            } else {
+                0.0
+            });
+        }
+    }
+    let starting = CardCollection::starting_deck();
+    for id in 0..25 {
+        features.push(public_info.discard.counts[id] as f32 / starting.counts[id] as f32);
+    }
+    features.push(public_info.blue_tokens.get() as f32 / 8.0);
+    features.push(public_info.black_tokens.get() as f32 / 4.0);
+    features.push(if public_info.last_round { 1.0 } else { 0.0 });
+    features.push(public_info.deck_total() as f32 / 40.0);
+
+    for i in 0..5 {
+        encode_card(private_info.opponent_hand[i], &mut features);
+    }
+    for hint in public_info.opponent_hints.iter() {
+        encode_hint(hint, &mut features);
+    }
+
+    let beliefs = Beliefs::new(public_info, private_info);
+    for slot in beliefs.slots.iter() {
+        features.extend_from_slice(slot);
+    }
+    for hint in public_info.player_hints.iter() {
+        encode_hint(hint, &mut features);
+    }
+    for slot in beliefs.summary(public_info).iter() {
+        features.extend_from_slice(&[slot.playable, slot.trash, slot.critical]);
+    }
+
+    assert_eq!(features.len(), INPUT_SIZE);
+    features
+}
+
+// index into the policy output, play & discard use the best scoring slot holding that hint
+pub fn policy_indices(public_info: &PublicInfo, action: &Action) -> Vec<usize> {
+    let hints = &public_info.player_hints;
+    let slots =
+        |slot: usize| -> Vec<usize> { (0..5).filter(|&i| hints[i] == hints[slot]).collect() };
+    match *action {
+        Action::ColorHint(color) => vec![color as usize],
+        Action::SuitHint(suit) => vec![5 + suit as usize],
+        Action::Play(slot) => slots(slot).iter().map(|i| 10 + i).collect(),
+        Action::Discard(slot) => slots(slot).iter().map(|i| 15 + i).collect(),
+    }
+}
+
+// action kind one-hot (play, discard, color hint, suit hint), then by kind
+// - play: the chance the slot is playable
+// - discard: the chance the slot is trash, critical & playable, and the clue tokens left
+// - clue: the partner's cards it touches, the playable ones, the ones it tells something new,
+//   and the clue tokens left
+// so a linear score can weigh the same thing differently for each kind
+pub const ACTION_FEATURES: usize = 4 + 1 + 4 + 4;
+
+// the features of each of `actions`, for the player to move
+pub fn action_features(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    actions: &[Action],
+) -> Vec<[f32; ACTION_FEATURES]> {
+    let summary = Beliefs::new(public_info, private_info).summary(public_info);
+    let fireworks = &public_info.fireworks;
+    let hand = &private_info.opponent_hand;
+    let hints = &public_info.opponent_hints;
+    let tokens = public_info.blue_tokens.get() as f32 / 8.0;
+    actions
+        .iter()
+        .map(|action| {
+            let mut features = [0.0; ACTION_FEATURES];
+            let touches: Box<dyn Fn(usize) -> bool> = match *action {
+                Action::Play(slot) => {
+                    features[0] = 1.0;
+                    features[4] = summary[slot].playable;
+                    return features;
+                }
+                Action::Discard(slot) => {
+                    features[1] = 1.0;
+                    features[5] = summary[slot].trash;
+                    features[6] = summary[slot].critical;
+                    features[7] = summary[slot].playable;
+                    features[8] = tokens;
+                    return features;
+                }
+                Action::ColorHint(color) => {
+                    features[2] = 1.0;
+                    Box::new(move |i| hand[i].is_some() && hand[i].color() == color)
+                }
+                Action::SuitHint(suit) => {
+                    features[3] = 1.0;
+                    Box::new(move |i| hand[i].is_some() && hand[i].suit() == suit)
+                }
+            };
+            for i in (0..5).filter(|&i| touches(i)) {
+                let card = hand[i];
+                features[9] += 0.2;
+                if fireworks.0[card.color_id() as usize] == card.suit_id() {
+                    features[10] += 0.2;
+                }
+                let known = match action {
+                    Action::ColorHint(_) => hints[i].color_mask().count_ones() == 1,
+                    _ => hints[i].suit_mask().count_ones() == 1,
+                };
+                if !known {
+                    features[11] += 0.2;
+                }
+            }
+            features[12] = tokens;
+            features
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::hanabi_env::HanabiEnv;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_encode_size() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let features = encode(&env.public_info(), &env.private_info(env.current_player()));
+        assert_eq!(features.len(), INPUT_SIZE);
+        assert!(features.iter().all(|&f| (0.0..=1.0).contains(&f)));
+
+        let actions = env.actions();
+        let action_features = action_features(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            &actions,
+        );
+        assert_eq!(action_features.len(), actions.len());
+        for (action, features) in actions.iter().zip(action_features.iter()) {
+            assert_eq!(features[..4].iter().sum::<f32>(), 1.0);
+            assert!(features.iter().all(|&f| (0.0..=1.0 + 1e-6).contains(&f)));
+            // a clue touches at least one card
+            if let Action::ColorHint(_) | Action::SuitHint(_) = action {
+                assert!(features[9] > 0.0);
+            }
+            let indices = policy_indices(&env.public_info(), action);
+            assert!(!indices.is_empty());
+            assert!(indices.iter().all(|&i| i < POLICY_SIZE));
+        }
+    }
+}
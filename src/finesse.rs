@@ -0,0 +1,218 @@
+// finesses in the partner plausibility model. a clue on a card that isn't playable yet, with the
+// one card it waits on nowhere in sight, makes sense as a finesse: the partner is telling us the
+// missing card is in our finesse position, the untouched slot at the other end from the chop.
+// sampled deals that put it there are weighted up by the configured factor, 1 leaves the
+// sampling as it is. clues aren't remembered, so every clued card in the hand is read as one.
+// deals where two touched cards are the same card are weighted down by the same factor: a
+// partner keeping to good touch wouldn't have clued the second copy
+use crate::hanabi_env::{Card, PrivateInfo, PublicInfo};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// a factor that favors finesse deals without ruling the others out
+pub const FINESSE_WEIGHT: f32 = 4.0;
+
+// the f32 bits of the weight, 1.0 by default
+static WEIGHT_BITS: AtomicU32 = AtomicU32::new(0x3f80_0000);
+
+pub fn set_finesse_weight(weight: f32) {
+    WEIGHT_BITS.store(weight.to_bits(), Ordering::Relaxed);
+}
+
+pub fn finesse_weight() -> f32 {
+    f32::from_bits(WEIGHT_BITS.load(Ordering::Relaxed))
+}
+
+// the last slot holding a card no clue has touched, render::chop is the first
+pub fn finesse_slot(public_info: &PublicInfo) -> Option<usize> {
+    let touched = public_info.hint_history.touched(true);
+    (0..5)
+        .rev()
+        .find(|&i| public_info.player_hints[i].is_some() && touched & 1 << i == 0)
+}
+
+// the card a clued `card` waits on when that's the only one missing below it
+fn connecting_card(public_info: &PublicInfo, card: Card) -> Option<Card> {
+    let level = public_info.fireworks.0[card.color_id() as usize];
+    match card.suit_id() == level + 1 {
+        true => Some(Card::from_parts(card.color_id(), level)),
+        false => None,
+    }
+}
+
+// true when `hand`, a sampled deal of the player's cards, holds the card some clue in it is
+// finessing in the finesse position
+pub fn is_finesse(public_info: &PublicInfo, private_info: &PrivateInfo, hand: &[Card; 5]) -> bool {
+    let slot = match finesse_slot(public_info) {
+        Some(slot) => slot,
+        None => return false,
+    };
+    let touched = public_info.hint_history.touched(true);
+    let clued = |i: usize| touched & 1 << i != 0;
+    let visible = |card: Card| {
+        private_info.opponent_hand.contains(&card) || (0..5).any(|j| clued(j) && hand[j] == card)
+    };
+    (0..5)
+        .filter(|&i| clued(i))
+        .filter_map(|i| connecting_card(public_info, hand[i]))
+        .any(|card| hand[slot] == card && !visible(card))
+}
+
+// true when two of the cards clues touched in `hand`, a sampled deal of the player's cards, are
+// the same card
+pub fn touches_duplicate(public_info: &PublicInfo, hand: &[Card; 5]) -> bool {
+    let touched = public_info.hint_history.touched(true);
+    let clued = |i: usize| touched & 1 << i != 0;
+    (0..5).any(|i| clued(i) && (i + 1..5).any(|j| clued(j) && hand[j] == hand[i]))
+}
+
+// what to add to a sampled deal's log probability
+pub fn finesse_log_weight(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    hand: &[Card; 5],
+    weight: f32,
+) -> f32 {
+    if weight == 1.0 {
+        return 0.0;
+    }
+    let mut log_weight = 0.0;
+    if is_finesse(public_info, private_info, hand) {
+        log_weight += weight.ln();
+    }
+    if touches_duplicate(public_info, hand) {
+        log_weight -= weight.ln();
+    }
+    log_weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::hanabi_env::{Action, Color, HanabiEnv, Hint, Suit};
+    use crate::notation::parse_card;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+    use crate::scenarios::{game, two_touched_reds};
+
+    fn hand(cards: &str) -> [Card; 5] {
+        let mut hand = [Card::none(); 5];
+        for (i, card) in cards.split('.').enumerate() {
+            hand[i] = parse_card(card).unwrap();
+        }
+        hand
+    }
+
+    const TWO: Action = Action::SuitHint(Suit::Two);
+    // a clue we give the partner to pass the turn back
+    const BLUE: Action = Action::ColorHint(Color::Blue);
+
+    // the opening self-finesse: nothing played, the partner clues 2 onto our R2. our finesse
+    // position should be the R1
+    fn self_finesse() -> HanabiEnv {
+        game("B4.Y3.G2.W4.Y4.R2.W1.B1.G3.W3", &[TWO])
+    }
+
+    #[test]
+    fn test_finesse_slot() {
+        // the clue leaves the other slots with negative information, but they're untouched
+        let env = self_finesse();
+        assert_ne!(env.public_info().player_hints[4], Hint::empty());
+        assert_eq!(finesse_slot(&env.public_info()), Some(4));
+
+        let deal = "B4.Y3.G3.W4.Y4.R2.G2.B5.Y1.W5";
+        let five = Action::SuitHint(Suit::Five);
+        let one = Action::SuitHint(Suit::One);
+        let env = game(deal, &[TWO, BLUE, five]);
+        assert_eq!(finesse_slot(&env.public_info()), Some(3));
+        let env = game(deal, &[TWO, BLUE, five, BLUE, one]);
+        assert_eq!(finesse_slot(&env.public_info()), None);
+    }
+
+    #[test]
+    fn test_is_finesse() {
+        let check = |env: &HanabiEnv, cards: &str| {
+            let (public_info, private_info) =
+                (env.public_info(), env.private_info(env.current_player()));
+            is_finesse(&public_info, &private_info, &hand(cards))
+        };
+        let env = self_finesse();
+        assert!(check(&env, "R2.W1.B1.G3.R1"));
+        assert!(!check(&env, "R2.R1.B1.G3.W1"));
+        // the same clue once the partner has played R1 is a plain play clue
+        let env = game(
+            "R1.Y3.G2.W4.Y4.R2.W1.B1.G3.W3.B4",
+            &[Action::Play(0), Action::ColorHint(Color::Yellow), TWO],
+        );
+        assert!(!check(&env, "R2.W1.B1.G3.R1"));
+        // the partner holds the R1 themselves, so they'll play it first
+        let env = game("R1.Y3.G2.W4.Y4.R2.W1.B1.G3.W3", &[TWO]);
+        assert!(!check(&env, "R2.W1.B1.G3.R1"));
+        // a clued 3 over an empty firework waits on two cards, a layered finesse isn't modeled
+        let env = game(
+            "B4.Y4.G2.W4.Y4.R3.W1.B1.G2.W2",
+            &[Action::SuitHint(Suit::Three)],
+        );
+        assert!(!check(&env, "R3.W1.B1.G2.R1"));
+        assert!(!check(&env, "R3.W1.B1.G2.R2"));
+
+        let env = self_finesse();
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let finesse = hand("R2.W1.B1.G3.R1");
+        assert_eq!(
+            finesse_log_weight(&public_info, &private_info, &finesse, 1.0),
+            0.0
+        );
+        assert_eq!(
+            finesse_log_weight(&public_info, &private_info, &finesse, FINESSE_WEIGHT),
+            FINESSE_WEIGHT.ln()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_touches_are_unlikely() {
+        let env = two_touched_reds();
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        assert!(touches_duplicate(&public_info, &hand("R1.B3.R1.W4.Y3")));
+        assert!(!touches_duplicate(&public_info, &hand("R2.B3.R1.W4.Y3")));
+        // the untouched slots may hold copies of a touched card
+        assert!(!touches_duplicate(&public_info, &hand("R2.R2.R1.W4.Y3")));
+        assert_eq!(
+            finesse_log_weight(
+                &public_info,
+                &private_info,
+                &hand("R1.B3.R1.W4.Y3"),
+                FINESSE_WEIGHT
+            ),
+            -FINESSE_WEIGHT.ln()
+        );
+    }
+
+    #[test]
+    fn test_weighting_favors_the_finesse() {
+        let env = self_finesse();
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let mut rng = StdRng::seed_from_u64(0);
+        // weighted share of the sampled deals with the 1 the clued 2 waits on in the finesse
+        // position
+        let share = |weight: f32, rng: &mut StdRng| {
+            let (mut hits, mut total) = (0.0, 0.0);
+            for _ in 0..2_000 {
+                let (env, _) = HanabiEnv::determinize(&public_info, &private_info, rng);
+                let w = finesse_log_weight(&public_info, &private_info, &env.player_hand, weight);
+                total += w.exp();
+                let hand = env.player_hand;
+                if hand[4] == Card::from_parts(hand[0].color_id(), 0) {
+                    hits += w.exp();
+                }
+            }
+            hits / total
+        };
+        let plain = share(1.0, &mut rng);
+        let weighted = share(FINESSE_WEIGHT, &mut rng);
+        assert!(weighted > 2.0 * plain, "{} vs {}", weighted, plain);
+    }
+}
@@ -0,0 +1,418 @@
+// flat Monte Carlo search: random rollouts from sampled deals, played out to the end, and the
+// first action with the best total reward. the endgame is solved clairvoyantly instead, for an
+// upper bound on each action's value over the deals
+use crate::env::{Env, HasEnd};
+use crate::evaluator::heuristic_policy;
+use crate::finesse::{finesse_log_weight, finesse_weight};
+use crate::hanabi_env::{
+    Action, HanabiEnv, PrivateInfo, PublicInfo, RewardShaping, RewardSwitch, Utility,
+};
+use crate::opening_book::OpeningBook;
+use crate::rand::rngs::StdRng;
+use crate::rand::seq::SliceRandom;
+use crate::rand::Rng;
+use crate::rollout_rng::RolloutRng;
+use crate::search::{Budget, VarianceReduction, WeightedReward};
+use crate::telemetry::{self, ActionStat, Decision};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+// rollouts play `first` at the root when it's given, a random legal action otherwise
+pub fn rollout_single_determinization<R: Rng>(
+    public_info: &PublicInfo,
+    my_private: &PrivateInfo,
+    first: Option<Action>,
+    rng: &mut R,
+) -> (Action, f32, f32) {
+    rollout_shaped(public_info, my_private, first, &RewardShaping::full(), rng)
+}
+
+pub fn rollout_shaped<R: Rng>(
+    public_info: &PublicInfo,
+    my_private: &PrivateInfo,
+    first: Option<Action>,
+    shaping: &RewardShaping,
+    rng: &mut R,
+) -> (Action, f32, f32) {
+    let switch = match REWARD_SWITCH.get() {
+        Some(switch) => *switch,
+        None => Some(RewardSwitch::default()),
+    };
+    rollout_switching(
+        public_info,
+        my_private,
+        first,
+        shaping,
+        switch.as_ref(),
+        rng,
+    )
+}
+
+// scores the rollout with `switch`'s endgame reward instead of the shaped one when the searched
+// position is close enough to the end
+pub fn rollout_switching<R: Rng>(
+    public_info: &PublicInfo,
+    my_private: &PrivateInfo,
+    first: Option<Action>,
+    shaping: &RewardShaping,
+    switch: Option<&RewardSwitch>,
+    mut rng: &mut R,
+) -> (Action, f32, f32) {
+    let (mut env, log_prob) = HanabiEnv::determinize(public_info, my_private, &mut rng);
+    let log_prob =
+        log_prob + finesse_log_weight(public_info, my_private, &env.player_hand, finesse_weight());
+    let action = first.unwrap_or_else(|| *env.search_actions().choose(&mut rng).unwrap());
+    env.step(&action, &mut rng);
+
+    while !env.is_over() {
+        let action = env.random_action(&mut rng);
+        env.step(&action, &mut rng);
+    }
+
+    let reward = match (UTILITY.get(), switch.filter(|s| s.applies(public_info))) {
+        (Some(&utility), _) => env.public_info().utility(utility),
+        (None, Some(switch)) => env.public_info().endgame_reward(switch.reward),
+        (None, None) => env.public_info().shaped_reward(shaping),
+    };
+    (action, log_prob, reward)
+}
+
+// rollout_single_determinization with the heuristic playing every move after the first in place
+// of random ones, scored by the final score. random play after the first move bombs out so fast
+// that what the first move was worth is lost in it, and more rollouts don't bring it back
+pub fn rollout_heuristic<R: Rng>(
+    public_info: &PublicInfo,
+    my_private: &PrivateInfo,
+    first: Option<Action>,
+    mut rng: &mut R,
+) -> (Action, f32, f32) {
+    let (mut env, log_prob) = HanabiEnv::determinize(public_info, my_private, &mut rng);
+    let log_prob =
+        log_prob + finesse_log_weight(public_info, my_private, &env.player_hand, finesse_weight());
+    let action = first.unwrap_or_else(|| *env.search_actions().choose(&mut rng).unwrap());
+    env.step(&action, &mut rng);
+
+    while !env.is_over() {
+        let private_info = env.private_info(env.current_player());
+        let action = heuristic_policy(&env.public_info(), &private_info);
+        env.step(&action, &mut rng);
+    }
+    (action, log_prob, env.fireworks.total() as f32)
+}
+
+// how many standard errors better than the heuristic's move another move has to look before
+// improvement_policy plays it
+pub const IMPROVEMENT_MARGIN: f32 = 1.0;
+
+// one step of policy improvement over the heuristic: every search action is rolled out with
+// rollout_heuristic in the same worlds, `num_rollouts` over them all, and the heuristic's move is
+// kept unless another beats it by IMPROVEMENT_MARGIN standard errors of the difference. picking
+// the best mean outright loses to the heuristic, the noise in a few dozen rollouts a move is
+// bigger than most of the differences
+pub fn improvement_policy(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    num_rollouts: usize,
+    rng: &mut StdRng,
+) -> Action {
+    let heuristic = heuristic_policy(public_info, private_info);
+    let actions = HanabiEnv::determinize(public_info, private_info, rng)
+        .0
+        .search_actions();
+    let base = match actions.iter().position(|&a| a == heuristic) {
+        Some(base) => base,
+        None => return heuristic,
+    };
+    let num_worlds = (num_rollouts / actions.len()).max(2);
+    // the sum & sum of squares of each action's score less the heuristic move's, world by world
+    let mut sums = vec![(0.0, 0.0); actions.len()];
+    for _ in 0..num_worlds {
+        let seed = rng.gen();
+        let scores: Vec<f32> = actions
+            .iter()
+            .map(|&action| {
+                let mut world_rng = RolloutRng::seed_from_u64(seed);
+                rollout_heuristic(public_info, private_info, Some(action), &mut world_rng).2
+            })
+            .collect();
+        for (sum, score) in sums.iter_mut().zip(scores.iter()) {
+            let diff = score - scores[base];
+            sum.0 += diff;
+            sum.1 += diff * diff;
+        }
+    }
+    let n = num_worlds as f32;
+    let lower_bound = |&(sum, squares): &(f32, f32)| {
+        let mean = sum / n;
+        let variance = (squares / n - mean * mean).max(0.0);
+        mean - IMPROVEMENT_MARGIN * (variance / n).sqrt()
+    };
+    let bounds: Vec<f32> = sums.iter().map(lower_bound).collect();
+    match (0..actions.len()).max_by(|&i, &j| bounds[i].partial_cmp(&bounds[j]).unwrap()) {
+        Some(i) if bounds[i] > 0.0 => actions[i],
+        _ => heuristic,
+    }
+}
+
+// set once from the command line, `--reward-switch`. unset rollouts switch to
+// RewardSwitch::default(), None keeps the shaped reward to the end
+pub static REWARD_SWITCH: OnceLock<Option<RewardSwitch>> = OnceLock::new();
+
+// set once from the command line, `--utility`. rollouts are scored by it over the final score in
+// place of the shaped reward & the reward switch. the endgame solve still maximizes the expected
+// score
+pub static UTILITY: OnceLock<Utility> = OnceLock::new();
+
+// set once from the command line, `--opening-book`, and read before any rollouts
+pub static OPENING_BOOK: OnceLock<OpeningBook> = OnceLock::new();
+
+// rollouts draw from their own RolloutRng seeded from `rng`
+pub fn policy<F>(
+    public_info: PublicInfo,
+    private_info: PrivateInfo,
+    rollout_fn: &F,
+    num_rollouts: usize,
+    rng: &mut StdRng,
+) -> Action
+where
+    F: Fn(&PublicInfo, &PrivateInfo, Option<Action>, &mut RolloutRng) -> (Action, f32, f32),
+{
+    let budget = Budget::Iterations(num_rollouts);
+    let variance = VarianceReduction::None;
+    policy_with_budget(public_info, private_info, rollout_fn, budget, variance, rng)
+}
+
+// with common random numbers every rollout of a world counts against the budget, and a world is
+// always finished, so the budget can run over by the number of legal actions
+pub fn policy_with_budget<F>(
+    public_info: PublicInfo,
+    private_info: PrivateInfo,
+    rollout_fn: &F,
+    budget: Budget,
+    variance: VarianceReduction,
+    mut rng: &mut StdRng,
+) -> Action
+where
+    F: Fn(&PublicInfo, &PrivateInfo, Option<Action>, &mut RolloutRng) -> (Action, f32, f32),
+{
+    if let Some(action) = OPENING_BOOK
+        .get()
+        .and_then(|book| book.lookup(&public_info, &private_info))
+    {
+        return action;
+    }
+    let seed = rng.gen();
+    let mut rollout_rng = RolloutRng::seed_from_u64(seed);
+    let start = Instant::now();
+    let mut actions = Vec::new();
+    let mut rewards: Vec<WeightedReward> = Vec::new();
+    let mut upper = f32::NEG_INFINITY;
+    let mut lower = f32::INFINITY;
+    let mut child_upper = Vec::new();
+    let mut child_lower = Vec::new();
+    let mut visits = Vec::new();
+
+    // every legal action is rolled out in each world, the legal actions only depend on what's public
+    let root_actions = match variance {
+        VarianceReduction::None => Vec::new(),
+        VarianceReduction::CommonRandomNumbers => {
+            HanabiEnv::determinize(&public_info, &private_info, &mut rng)
+                .0
+                .search_actions()
+        }
+    };
+
+    let mut num_rollouts = 0;
+    let mut record = |action: Action, log_prob: f32, reward: f32| {
+        num_rollouts += 1;
+        if upper < reward {
+            upper = reward;
+        }
+        if lower > reward {
+            lower = reward;
+        }
+
+        match actions.iter().position(|&a| a == action) {
+            Some(i) => {
+                rewards[i].add(log_prob, reward);
+                visits[i] += 1;
+                if child_upper[i] < reward {
+                    child_upper[i] = reward;
+                }
+                if child_lower[i] > reward {
+                    child_lower[i] = reward;
+                }
+            }
+            None => {
+                actions.push(action);
+                let mut weighted_reward = WeightedReward::new();
+                weighted_reward.add(log_prob, reward);
+                rewards.push(weighted_reward);
+                child_lower.push(reward);
+                child_upper.push(reward);
+                visits.push(1);
+            }
+        }
+        num_rollouts
+    };
+
+    let mut done = 0;
+    while !budget.exhausted(done, start) {
+        match variance {
+            VarianceReduction::None => {
+                let (action, log_prob, reward) =
+                    rollout_fn(&public_info, &private_info, None, &mut rollout_rng);
+                done = record(action, log_prob, reward);
+            }
+            VarianceReduction::CommonRandomNumbers => {
+                // one seed per world, so every action sees the same deal & the same draws after it
+                let seed = rollout_rng.gen();
+                for &first in root_actions.iter() {
+                    let mut world_rng = RolloutRng::seed_from_u64(seed);
+                    let (action, log_prob, reward) =
+                        rollout_fn(&public_info, &private_info, Some(first), &mut world_rng);
+                    done = record(action, log_prob, reward);
+                }
+            }
+        }
+    }
+    let num_rollouts = done;
+
+    let mut best_i = 0;
+    let mut best_score = f32::NEG_INFINITY;
+    for (i, reward) in rewards.iter().enumerate() {
+        // the weighted mean: a total favors whichever action was rolled out most, and with the root
+        // action picked at random that's noise a few hundred rollouts don't even out
+        let total_reward = reward.mean();
+        // let mean_reward = total_reward / visits[i] as f32;
+        // // let ugape = child_upper[i] - lower;
+        // let mut B = std::f32::NEG_INFINITY;
+        // for j in 0..rewards.len() {
+        //     if i == j {
+        //         continue;
+        //     }
+        //     let ugap = child_upper[j] - child_lower[i];
+        //     if ugap > B {
+        //         B = ugap;
+        //     }
+        // }
+        // println!(
+        //     "{:?}: {} / {} = {} | [{} {}]  | {}",
+        //     actions[i], total_reward, visits[i], mean_reward, child_lower[i], child_upper[i], B,
+        // );
+        if total_reward > best_score {
+            best_score = total_reward;
+            best_i = i;
+        }
+        // if ugape > best_score {
+        //     best_i = i;
+        //     best_score = ugape;
+        // }
+    }
+
+    if telemetry::enabled() {
+        telemetry::record(&Decision {
+            searcher: "flat-mc",
+            legal_actions: telemetry::legal_actions(&public_info, &private_info, seed),
+            budget: num_rollouts,
+            actions: (0..actions.len())
+                .map(|i| ActionStat {
+                    action: actions[i],
+                    visits: visits[i] as f32,
+                    value: rewards[i].mean(),
+                })
+                .collect(),
+            chosen: actions[best_i],
+            millis: start.elapsed().as_secs_f32() * 1000.0,
+        });
+    }
+    actions[best_i]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_common_random_numbers() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+
+        // the same seed deals the same world whichever action goes first
+        let mut log_probs = Vec::new();
+        for &first in env.actions().iter() {
+            let mut world_rng = RolloutRng::seed_from_u64(7);
+            let (action, log_prob, _) = rollout_single_determinization(
+                &public_info,
+                &private_info,
+                Some(first),
+                &mut world_rng,
+            );
+            assert_eq!(action, first);
+            log_probs.push(log_prob);
+        }
+        assert!(log_probs.iter().all(|&p| p == log_probs[0]));
+
+        let action = policy_with_budget(
+            public_info,
+            private_info,
+            &rollout_single_determinization,
+            Budget::Iterations(200),
+            VarianceReduction::CommonRandomNumbers,
+            &mut rng,
+        );
+        assert!(env.actions().contains(&action));
+    }
+
+    #[test]
+    fn test_telemetry_leaves_the_game_rng_alone() {
+        let env = HanabiEnv::random(&mut StdRng::seed_from_u64(0));
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let next_draw = || {
+            let mut rng = StdRng::seed_from_u64(1);
+            let rollout_fn = rollout_single_determinization;
+            policy(
+                public_info.clone(),
+                private_info.clone(),
+                &rollout_fn,
+                20,
+                &mut rng,
+            );
+            rng.gen::<u64>()
+        };
+        let unlogged = next_draw();
+        let path =
+            std::env::temp_dir().join(format!("hanabi-telemetry-{}.jsonl", std::process::id()));
+        telemetry::open(path.to_str().unwrap()).unwrap();
+        let logged = next_draw();
+        telemetry::close().unwrap();
+        let lines = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(lines.contains("\"searcher\":\"flat-mc\""));
+        assert_eq!(logged, unlogged);
+    }
+
+    #[test]
+    fn test_improvement_policy() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let improve = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            improvement_policy(&public_info, &private_info, 100, &mut rng)
+        };
+        let action = improve(1);
+        assert!(env.actions().contains(&action));
+        assert_eq!(improve(1), action);
+
+        // a heuristic rollout plays the game out, so it's scored by what reached the fireworks
+        let mut rollout_rng = RolloutRng::seed_from_u64(0);
+        let (_, _, score) = rollout_heuristic(&public_info, &private_info, None, &mut rollout_rng);
+        assert!((0.0..=25.0).contains(&score) && score.fract() == 0.0);
+    }
+}
@@ -0,0 +1,367 @@
+// a tiny cooperative card game with hidden information, so the Env trait has a second game to be
+// checked against besides Hanabi. the two players each hold one card from a deck of VALUES
+// values with COPIES of each, see their partner's card but not their own, and on their turn
+// either
+// - clue whether the partner's card is some value, spending a clue token, or
+// - guess their own card, scoring a point when right. the card is shown and replaced from the
+//   deck, which is the chance event
+// the game ends after MAX_TURNS turns, or once a player's guessed card can't be replaced. like
+// HanabiEnv the env holds the player to move as `player` and swaps the seats every step
+use crate::env::{Env, HasEnd, HasKey, HasReward, HasSymmetry};
+use crate::rand::Rng;
+
+pub const VALUES: usize = 4;
+pub const COPIES: u8 = 2;
+pub const MAX_TURNS: u8 = 6;
+pub const CLUE_TOKENS: u8 = 2;
+
+const ALL_VALUES: u8 = (1 << VALUES) - 1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GuessAction {
+    Clue(u8),
+    Guess(u8),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GuessPublicInfo {
+    // the values each player's own card can still be, a bit per value. 0 once there's no card
+    pub player_mask: u8,
+    pub opponent_mask: u8,
+    // the cards guesses have shown, per value
+    pub shown: [u8; VALUES],
+    pub clue_tokens: u8,
+    pub score: u8,
+    pub turns_left: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GuessPrivateInfo {
+    pub opponent_card: Option<u8>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GuessEnv {
+    pub player_card: Option<u8>,
+    pub opponent_card: Option<u8>,
+    // cards left to draw, per value
+    pub deck: [u8; VALUES],
+    pub public: GuessPublicInfo,
+}
+
+// the cards not shown and not in `hand`, per value
+fn unseen(shown: &[u8; VALUES], hand: Option<u8>) -> [u8; VALUES] {
+    let mut counts = [COPIES; VALUES];
+    for v in 0..VALUES {
+        counts[v] -= shown[v];
+    }
+    if let Some(card) = hand {
+        counts[card as usize] -= 1;
+    }
+    counts
+}
+
+fn draw<R: Rng>(deck: &mut [u8; VALUES], rng: &mut R) -> Option<u8> {
+    let total: u8 = deck.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let mut index = rng.gen_range(0, total);
+    for (v, count) in deck.iter_mut().enumerate() {
+        if index < *count {
+            *count -= 1;
+            return Some(v as u8);
+        }
+        index -= *count;
+    }
+    unreachable!()
+}
+
+impl GuessPublicInfo {
+    pub fn current_player(&self) -> usize {
+        (MAX_TURNS - self.turns_left) as usize % 2
+    }
+}
+
+impl HasEnd for GuessPublicInfo {
+    fn is_over(&self) -> bool {
+        self.turns_left == 0 || self.player_mask == 0 || self.opponent_mask == 0
+    }
+}
+
+impl HasReward for GuessPublicInfo {
+    type Reward = f32;
+
+    fn reward(&self) -> f32 {
+        self.score as f32 / MAX_TURNS as f32
+    }
+}
+
+impl HasKey for GuessPublicInfo {
+    // every field fits in a few bits, so the key is exact
+    fn key(&self) -> u64 {
+        let mut key = self.player_mask as u64 | (self.opponent_mask as u64) << 8;
+        for v in 0..VALUES {
+            key |= (self.shown[v] as u64) << (16 + 4 * v);
+        }
+        key | (self.clue_tokens as u64) << 32
+            | (self.score as u64) << 40
+            | (self.turns_left as u64) << 48
+    }
+}
+
+impl HasSymmetry<GuessAction> for GuessPublicInfo {
+    // the values aren't interchangeable the way Hanabi's colors are, so there's only the identity
+    type Symmetry = ();
+
+    fn canonical_symmetry(&self) {}
+
+    fn apply(&self, _symmetry: &()) -> Self {
+        *self
+    }
+
+    fn apply_to_action(_symmetry: &(), action: &GuessAction) -> GuessAction {
+        *action
+    }
+
+    fn invert(_symmetry: &()) {}
+}
+
+impl HasEnd for GuessEnv {
+    fn is_over(&self) -> bool {
+        self.public.is_over()
+    }
+}
+
+impl HasReward for GuessEnv {
+    type Reward = f32;
+
+    fn reward(&self) -> f32 {
+        self.public.reward()
+    }
+}
+
+impl GuessEnv {
+    fn apply(&mut self, action: &GuessAction, drawn: Option<u8>) {
+        let public = &mut self.public;
+        match *action {
+            GuessAction::Clue(v) => {
+                public.opponent_mask = match self.opponent_card == Some(v) {
+                    true => 1 << v,
+                    false => public.opponent_mask & !(1 << v),
+                };
+                public.clue_tokens -= 1;
+            }
+            GuessAction::Guess(v) => {
+                let card = self.player_card.expect("guessed without a card");
+                public.shown[card as usize] += 1;
+                public.score += (card == v) as u8;
+                self.player_card = drawn;
+                public.player_mask = drawn.map_or(0, |_| ALL_VALUES);
+            }
+        }
+        public.turns_left -= 1;
+        std::mem::swap(&mut self.player_card, &mut self.opponent_card);
+        std::mem::swap(&mut public.player_mask, &mut public.opponent_mask);
+    }
+}
+
+impl Env for GuessEnv {
+    type PublicInfo = GuessPublicInfo;
+    type PrivateInfo = GuessPrivateInfo;
+    type Action = GuessAction;
+    type Outcome = GuessAction;
+    type Chance = u8;
+
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        let mut deck = [COPIES; VALUES];
+        let player_card = draw(&mut deck, rng);
+        let opponent_card = draw(&mut deck, rng);
+        Self {
+            player_card,
+            opponent_card,
+            deck,
+            public: GuessPublicInfo {
+                player_mask: ALL_VALUES,
+                opponent_mask: ALL_VALUES,
+                shown: [0; VALUES],
+                clue_tokens: CLUE_TOKENS,
+                score: 0,
+                turns_left: MAX_TURNS,
+            },
+        }
+    }
+
+    fn new(public_info: &GuessPublicInfo, private_infos: &[GuessPrivateInfo]) -> Self {
+        assert_eq!(private_infos.len(), 2, "GuessEnv seats exactly 2 players");
+        let mover = public_info.current_player();
+        let player_private_info = &private_infos[mover];
+        let opponent_private_info = &private_infos[1 - mover];
+        let mut deck = unseen(&public_info.shown, player_private_info.opponent_card);
+        if let Some(card) = opponent_private_info.opponent_card {
+            deck[card as usize] -= 1;
+        }
+        Self {
+            player_card: opponent_private_info.opponent_card,
+            opponent_card: player_private_info.opponent_card,
+            deck,
+            public: *public_info,
+        }
+    }
+
+    fn sample_private_infos<R: Rng>(
+        public_info: &GuessPublicInfo,
+        player_private_info: &GuessPrivateInfo,
+        rng: &mut R,
+    ) -> (Vec<GuessPrivateInfo>, f32) {
+        let mut candidates = unseen(&public_info.shown, player_private_info.opponent_card);
+        for (v, count) in candidates.iter_mut().enumerate() {
+            if public_info.player_mask & 1 << v == 0 {
+                *count = 0;
+            }
+        }
+        let total: u8 = candidates.iter().sum();
+        let card = draw(&mut candidates, rng);
+        let log_prob = match card {
+            Some(v) => ((candidates[v as usize] + 1) as f32 / total as f32).ln(),
+            None => 0.0,
+        };
+        let mut private_infos = vec![
+            *player_private_info,
+            GuessPrivateInfo {
+                opponent_card: card,
+            },
+        ];
+        if public_info.current_player() == 1 {
+            private_infos.reverse();
+        }
+        (private_infos, log_prob)
+    }
+
+    fn current_player(&self) -> usize {
+        self.public.current_player()
+    }
+
+    fn public_info(&self) -> GuessPublicInfo {
+        self.public
+    }
+
+    fn private_info(&self, player: usize) -> GuessPrivateInfo {
+        GuessPrivateInfo {
+            opponent_card: match player == self.current_player() {
+                true => self.opponent_card,
+                false => self.player_card,
+            },
+        }
+    }
+
+    fn actions(&self) -> Vec<GuessAction> {
+        let mut actions = Vec::new();
+        if self.is_over() {
+            return actions;
+        }
+        let public = &self.public;
+        for v in 0..VALUES as u8 {
+            if public.player_mask & 1 << v != 0 {
+                actions.push(GuessAction::Guess(v));
+            }
+        }
+        // a clue that can't narrow the partner's card down is a wasted token
+        if public.clue_tokens > 0 && public.opponent_mask.count_ones() > 1 {
+            for v in 0..VALUES as u8 {
+                if public.opponent_mask & 1 << v != 0 {
+                    actions.push(GuessAction::Clue(v));
+                }
+            }
+        }
+        actions
+    }
+
+    fn step<R: Rng>(&mut self, action: &GuessAction, rng: &mut R) {
+        let outcome = self.apply_action(action);
+        self.resolve_chance(&outcome, rng);
+    }
+
+    fn apply_action(&self, action: &GuessAction) -> GuessAction {
+        *action
+    }
+
+    fn chance_outcomes(&self, outcome: &GuessAction) -> Vec<(u8, f32)> {
+        let total: u8 = self.deck.iter().sum();
+        match outcome {
+            GuessAction::Guess(_) if total > 0 => (0..VALUES)
+                .filter(|&v| self.deck[v] > 0)
+                .map(|v| (v as u8, self.deck[v] as f32 / total as f32))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn resolve_chance<R: Rng>(&mut self, outcome: &GuessAction, rng: &mut R) {
+        let drawn = match outcome {
+            GuessAction::Guess(_) => draw(&mut self.deck, rng),
+            GuessAction::Clue(_) => None,
+        };
+        self.apply(outcome, drawn);
+    }
+
+    fn resolve_chance_with(&mut self, outcome: &GuessAction, chance: &u8) -> Result<(), String> {
+        if let GuessAction::Clue(_) = outcome {
+            self.apply(outcome, None);
+            return Ok(());
+        }
+        match self.deck.get(*chance as usize) {
+            Some(&n) if n > 0 => {
+                self.deck[*chance as usize] -= 1;
+                self.apply(outcome, Some(*chance));
+                Ok(())
+            }
+            _ => Err(format!("{} isn't in the deck", chance)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::MCTS;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_guess_env() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = GuessEnv::random(&mut rng);
+        let partner = env.opponent_card.unwrap();
+        env.step(&GuessAction::Clue(partner), &mut rng);
+        // the partner is to move and knows their card now
+        assert_eq!(env.public.player_mask, 1 << partner);
+        assert!(env.actions().contains(&GuessAction::Guess(partner)));
+        assert_eq!(env.public.clue_tokens, CLUE_TOKENS - 1);
+
+        // on the last turn the searcher guesses the card it's been told about
+        let mut last_turn = env;
+        last_turn.public.turns_left = 1;
+        let mcts_action = {
+            let mut mcts = MCTS::<GuessEnv>::with_capacity(
+                &last_turn.public_info(),
+                &last_turn.private_info(last_turn.current_player()),
+                500,
+                0,
+            );
+            mcts.explore_n(500);
+            mcts.best_action()
+        };
+        assert_eq!(mcts_action, GuessAction::Guess(partner));
+
+        env.step(&GuessAction::Guess(partner), &mut rng);
+        assert_eq!(env.public.score, 1);
+        assert_eq!(env.public.shown[partner as usize], 1);
+        assert_eq!(env.public.opponent_mask, ALL_VALUES);
+        while !env.is_over() {
+            let action = env.random_action(&mut rng);
+            env.step(&action, &mut rng);
+        }
+        assert!(env.reward() <= 1.0);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::env::{Env, HasEnd};
+use crate::evaluator::heuristic_policy;
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::hle::{
+    encode_observation, legal_move_mask, legal_moves_from_mask, move_from_uid, HLE_NUM_MOVES,
+};
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+use crate::record::GameRecord;
+
+#[derive(Clone, Debug)]
+pub struct Observation {
+    // HLE observation bits for the agent's seat
+    pub bits: Vec<u8>,
+    // legal HLE move uids, and the same as a bitmask with bit `uid` set for each
+    pub legal_moves: [bool; HLE_NUM_MOVES],
+    pub legal_mask: u32,
+    pub score: u8,
+}
+
+pub type Partner = Box<dyn FnMut(&PublicInfo, &PrivateInfo) -> Action>;
+
+// reset/step wrapper for RL frameworks. the agent always moves first and acts with HLE
+// move uids, the partner seat is played by `partner` in between agent moves
+pub struct GymEnv {
+    env: HanabiEnv,
+    record: GameRecord,
+    rng: StdRng,
+    partner: Partner,
+}
+
+impl GymEnv {
+    pub fn new(partner: Partner) -> Self {
+        let mut rng = StdRng::seed_from_u64(0);
+        Self {
+            env: HanabiEnv::random(&mut rng),
+            record: GameRecord::new(["agent".to_string(), "partner".to_string()]),
+            rng,
+            partner,
+        }
+    }
+
+    pub fn with_heuristic_partner() -> Self {
+        Self::new(Box::new(
+            |public_info: &PublicInfo, private_info: &PrivateInfo| {
+                heuristic_policy(public_info, private_info)
+            },
+        ))
+    }
+
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.env = HanabiEnv::random(&mut self.rng);
+        self.record = GameRecord::new(["agent".to_string(), "partner".to_string()]);
+        self.observation()
+    }
+
+    // reward is the change in score over the agent's move and the partner's reply
+    pub fn step(&mut self, uid: usize) -> (Observation, f32, bool) {
+        assert!(
+            uid < HLE_NUM_MOVES && self.legal_mask() & (1 << uid) != 0,
+            "move {} is illegal, check Observation::legal_moves",
+            uid
+        );
+        let score_before = self.env.fireworks.total();
+
+        let action = move_from_uid(uid);
+        self.record.step(&mut self.env, &action, &mut self.rng);
+        if !self.env.is_over() {
+            let action = (self.partner)(
+                &self.env.public_info(),
+                &self.env.private_info(self.env.current_player()),
+            );
+            self.record.step(&mut self.env, &action, &mut self.rng);
+        }
+
+        let reward = self.env.fireworks.total() as f32 - score_before as f32;
+        (self.observation(), reward, self.env.is_over())
+    }
+
+    pub fn legal_mask(&self) -> u32 {
+        legal_move_mask(
+            &self.env.public_info(),
+            &self.env.private_info(self.env.current_player()),
+        )
+    }
+
+    pub fn legal_moves(&self) -> [bool; HLE_NUM_MOVES] {
+        legal_moves_from_mask(self.legal_mask())
+    }
+
+    fn observation(&self) -> Observation {
+        let legal_mask = self.legal_mask();
+        Observation {
+            bits: encode_observation(
+                &self.env.public_info(),
+                &self.env.private_info(self.env.current_player()),
+                self.record.turns.last(),
+            ),
+            legal_moves: legal_moves_from_mask(legal_mask),
+            legal_mask,
+            score: self.env.fireworks.total(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::prelude::SliceRandom;
+
+    #[test]
+    fn test_gym_episode() {
+        let mut gym = GymEnv::with_heuristic_partner();
+        let mut rng = StdRng::seed_from_u64(0);
+        for seed in 0..5 {
+            let mut obs = gym.reset(seed);
+            let mut total_reward = 0.0;
+            let mut done = false;
+            while !done {
+                let legal: Vec<usize> =
+                    (0..HLE_NUM_MOVES).filter(|&u| obs.legal_moves[u]).collect();
+                assert_eq!(legal.len(), obs.legal_mask.count_ones() as usize);
+                let (next, reward, is_done) = gym.step(*legal.choose(&mut rng).unwrap());
+                total_reward += reward;
+                obs = next;
+                done = is_done;
+            }
+            assert_eq!(total_reward, obs.score as f32);
+        }
+
+        // the same seed deals the same game
+        let a = gym.reset(3);
+        let b = gym.reset(3);
+        assert_eq!(a.bits, b.bits);
+    }
+}
@@ -0,0 +1,664 @@
+use crate::hanabi_env::{Action, Card, Color, HanabiEnv, Suit};
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+use crate::record::GameRecord;
+use crate::replay::Replay;
+use crate::state_json::parse;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::time::Duration;
+
+// hanab.live "No Variant" suits are red, yellow, green, blue & purple, purple plays the part of white
+const SUITS: [Color; 5] = [
+    Color::Red,
+    Color::Yellow,
+    Color::Green,
+    Color::Blue,
+    Color::White,
+];
+
+const PLAY: u64 = 0;
+const DISCARD: u64 = 1;
+const COLOR_CLUE: u64 = 2;
+const RANK_CLUE: u64 = 3;
+const GAME_OVER: u64 = 4;
+
+#[derive(Clone, Debug)]
+pub struct HanabLiveGame {
+    pub players: Vec<String>,
+    pub replay: Replay,
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, String> {
+    match &value[name] {
+        Value::Null => Err(format!("missing '{}' in {}", name, value)),
+        v => Ok(v),
+    }
+}
+
+fn index_field(value: &Value, name: &str) -> Result<usize, String> {
+    field(value, name)?
+        .as_u64()
+        .map(|v| v as usize)
+        .ok_or_else(|| format!("'{}' should be a number in {}", name, value))
+}
+
+fn parse_card(value: &Value) -> Result<Card, String> {
+    let suit = index_field(value, "suitIndex")?;
+    let rank = index_field(value, "rank")?;
+    if suit >= 5 || !(1..=5).contains(&rank) {
+        return Err(format!("unsupported card {}", value));
+    }
+    Ok(Card::new(SUITS[suit], Suit::from_id(rank as u8 - 1)))
+}
+
+// parses a two player, no variant game from the hanab.live JSON export
+// ({"players": [...], "deck": [{"suitIndex", "rank"}...], "actions": [{"type", "target", "value"}...]})
+pub fn parse_export(text: &str) -> Result<HanabLiveGame, String> {
+    parse_game(&parse(text)?)
+}
+
+fn parse_game(value: &Value) -> Result<HanabLiveGame, String> {
+    let players: Vec<String> = field(value, "players")?
+        .as_array()
+        .ok_or("'players' should be a list")?
+        .iter()
+        .map(|p| p.as_str().unwrap_or("?").to_string())
+        .collect();
+    if players.len() != 2 {
+        return Err(format!(
+            "only 2 player games are supported, found {}",
+            players.len()
+        ));
+    }
+    if let Some(variant) = value["options"]["variant"].as_str() {
+        if variant != "No Variant" {
+            return Err(format!("unsupported variant '{}'", variant));
+        }
+    }
+    let deck = field(value, "deck")?
+        .as_array()
+        .ok_or("'deck' should be a list")?
+        .iter()
+        .map(parse_card)
+        .collect::<Result<Vec<Card>, String>>()?;
+    if deck.len() != 50 {
+        return Err(format!(
+            "expected 50 cards in the deck, found {}",
+            deck.len()
+        ));
+    }
+
+    // cards are addressed by their position in the deck, so track which deck index is in each slot
+    let mut env = HanabiEnv::from_deck_order(&deck);
+    let initial = env.clone();
+    let mut orders: [[Option<usize>; 5]; 2] = [[None; 5]; 2];
+    for (seat, seat_orders) in orders.iter_mut().enumerate() {
+        for (slot, order) in seat_orders.iter_mut().enumerate() {
+            *order = Some(seat * 5 + slot);
+        }
+    }
+    let mut next_order = 10;
+    let mut record = GameRecord::new([players[0].clone(), players[1].clone()]);
+    // the deck order decides every draw, so the rng is never used
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let actions = field(value, "actions")?
+        .as_array()
+        .ok_or("'actions' should be a list")?;
+    for action in actions.iter() {
+        let seat = record.current_seat();
+        let kind = index_field(action, "type")? as u64;
+        let (action, slot) = match kind {
+            PLAY | DISCARD => {
+                let order = index_field(action, "target")?;
+                let slot = orders[seat]
+                    .iter()
+                    .position(|&o| o == Some(order))
+                    .ok_or_else(|| format!("card {} isn't in P{}'s hand", order, seat + 1))?;
+                let action = if kind == PLAY {
+                    Action::Play(slot)
+                } else {
+                    Action::Discard(slot)
+                };
+                (action, Some(slot))
+            }
+            COLOR_CLUE => {
+                let color = index_field(action, "value")?;
+                (
+                    Action::ColorHint(*SUITS.get(color).ok_or("invalid color")?),
+                    None,
+                )
+            }
+            RANK_CLUE => {
+                let rank = index_field(action, "value")?;
+                if !(1..=5).contains(&rank) {
+                    return Err(format!("invalid rank clue {}", rank));
+                }
+                (Action::SuitHint(Suit::from_id(rank as u8 - 1)), None)
+            }
+            GAME_OVER => break,
+            _ => return Err(format!("unknown action type {}", kind)),
+        };
+
+        env.check_action(&action)
+            .map_err(|e| format!("action {}: {}", record.turns.len(), e))?;
+        record.step(&mut env, &action, &mut rng);
+        if let Some(slot) = slot {
+            orders[seat][slot] = if next_order < deck.len() {
+                next_order += 1;
+                Some(next_order - 1)
+            } else {
+                None
+            };
+        }
+    }
+
+    Ok(HanabLiveGame {
+        players,
+        replay: Replay::from_game(&initial, &record),
+    })
+}
+
+// which games of a database dump to keep. only 2 player "No Variant" games can be parsed, so those
+// are the defaults, other values just pick games to count as filtered
+#[derive(Clone, Debug)]
+pub struct ImportFilter {
+    pub variant: String,
+    pub num_players: usize,
+    // the final fireworks, games that bombed out keep what they had played
+    pub min_score: u8,
+    pub max_score: u8,
+}
+
+impl Default for ImportFilter {
+    fn default() -> Self {
+        Self {
+            variant: "No Variant".to_string(),
+            num_players: 2,
+            min_score: 0,
+            max_score: 25,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImportStats {
+    pub num_read: usize,
+    pub num_imported: usize,
+    pub num_filtered: usize,
+    // games the filter kept that couldn't be parsed, with why
+    pub failed: Vec<(usize, String)>,
+}
+
+// calls `f` with each game of a dump as it's read, so only one game is in memory at a time: the
+// elements of a top level JSON list, or the non-empty lines of one export per line. a read error
+// or a list cut short ends the dump with an error
+fn for_each_entry<R: BufRead>(mut reader: R, mut f: impl FnMut(Result<Value, String>)) {
+    let first = loop {
+        match reader.fill_buf() {
+            Ok(buf) => match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(i) => break Some(buf[i]),
+                None if buf.is_empty() => break None,
+                None => {
+                    let len = buf.len();
+                    reader.consume(len);
+                }
+            },
+            Err(e) => return f(Err(e.to_string())),
+        }
+    };
+    if first != Some(b'[') {
+        for line in reader.lines() {
+            match line {
+                Ok(line) if line.trim().is_empty() => {}
+                Ok(line) => f(parse(&line)),
+                Err(e) => return f(Err(e.to_string())),
+            }
+        }
+        return;
+    }
+
+    // the bytes of the element so far, how deep in brackets it is and where in a string
+    let mut element = Vec::new();
+    let (mut depth, mut in_string, mut escaped) = (0, false, false);
+    let mut bytes = reader
+        .bytes()
+        .skip_while(|b| b.as_ref().is_ok_and(|&b| b != b'['));
+    bytes.next();
+    for byte in bytes {
+        let byte = match byte {
+            Ok(byte) => byte,
+            Err(e) => return f(Err(e.to_string())),
+        };
+        if in_string {
+            match (escaped, byte) {
+                (true, _) => escaped = false,
+                (false, b'\\') => escaped = true,
+                (false, b'"') => in_string = false,
+                _ => {}
+            }
+        } else {
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' if depth > 0 => depth -= 1,
+                b',' | b']' if depth == 0 => {
+                    if element.iter().any(|b: &u8| !b.is_ascii_whitespace()) {
+                        f(serde_json::from_slice(&element)
+                            .map_err(|e| format!("invalid json: {}", e)));
+                    }
+                    element.clear();
+                    if byte == b']' {
+                        return;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        element.push(byte);
+    }
+    f(Err("the list of games isn't closed".to_string()))
+}
+
+// imports a hanab.live database dump, either a JSON list of game exports or one export per line,
+// a game at a time. games are numbered from 0 in the order they appear
+pub fn import_dump<R: BufRead>(
+    reader: R,
+    filter: &ImportFilter,
+) -> (Vec<HanabLiveGame>, ImportStats) {
+    let mut games = Vec::new();
+    let mut stats = ImportStats::default();
+    for_each_entry(reader, |entry| {
+        let i = stats.num_read;
+        stats.num_read += 1;
+        let value = match entry {
+            Ok(value) => value,
+            Err(e) => {
+                stats.failed.push((i, e));
+                return;
+            }
+        };
+        let variant = value["options"]["variant"].as_str().unwrap_or("No Variant");
+        let num_players = value["players"].as_array().map_or(0, |p| p.len());
+        if variant != filter.variant || num_players != filter.num_players {
+            stats.num_filtered += 1;
+            return;
+        }
+        let game = match parse_game(&value) {
+            Ok(game) => game,
+            Err(e) => {
+                stats.failed.push((i, e));
+                return;
+            }
+        };
+        let last = game.replay.env_at(game.replay.turns.len() + 1);
+        let score = last.map_or(0, |env| env.fireworks.total());
+        if score < filter.min_score || score > filter.max_score {
+            stats.num_filtered += 1;
+            return;
+        }
+        stats.num_imported += 1;
+        games.push(game);
+    });
+    (games, stats)
+}
+
+// the turn bookkeeping of a bot seated at a live table, kept apart from the connection so it
+// can be driven by any transport and tested without one. the server is the only source of
+// truth: moves count once it has broadcast them, they may arrive late or out of order and are
+// held until the gap before them fills, and our own move is only sent when the confirmed
+// history says it's our turn. a move the server doesn't confirm in time is sent again, and a
+// rejection or a history that contradicts ours asks the server for the whole game again
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LiveMove {
+    pub turn: usize,
+    pub seat: usize,
+    pub action: Action,
+}
+
+// what the session wants sent to the server
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Outgoing {
+    Submit { turn: usize, action: Action },
+    RequestResync,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SubmitState {
+    Idle,
+    // sent and waiting to see it confirmed, `waited` since the last send
+    Submitted {
+        turn: usize,
+        action: Action,
+        attempts: usize,
+        waited: Duration,
+    },
+    // nothing is sent until the server's full history arrives
+    Resyncing,
+}
+
+#[derive(Clone, Debug)]
+pub struct LiveSession {
+    pub seat: usize,
+    pub num_players: usize,
+    // moves the server confirmed, in turn order with no gaps
+    pub confirmed: Vec<LiveMove>,
+    // moves that arrived ahead of one still missing, by turn
+    pending: BTreeMap<usize, LiveMove>,
+    pub state: SubmitState,
+    pub retry_after: Duration,
+    pub max_attempts: usize,
+}
+
+impl LiveSession {
+    pub fn new(seat: usize, num_players: usize) -> Self {
+        Self {
+            seat,
+            num_players,
+            confirmed: Vec::new(),
+            pending: BTreeMap::new(),
+            state: SubmitState::Idle,
+            retry_after: Duration::from_secs(5),
+            max_attempts: 3,
+        }
+    }
+
+    pub fn with_retries(mut self, retry_after: Duration, max_attempts: usize) -> Self {
+        self.retry_after = retry_after;
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn next_turn(&self) -> usize {
+        self.confirmed.len()
+    }
+
+    // our move is due and nothing of ours is waiting on the server
+    pub fn is_our_turn(&self) -> bool {
+        self.state == SubmitState::Idle && self.next_turn() % self.num_players == self.seat
+    }
+
+    fn resync(&mut self) -> Option<Outgoing> {
+        self.pending.clear();
+        self.state = SubmitState::Resyncing;
+        Some(Outgoing::RequestResync)
+    }
+
+    // a move the server broadcast
+    pub fn receive(&mut self, m: LiveMove) -> Option<Outgoing> {
+        if self.state == SubmitState::Resyncing {
+            return None;
+        }
+        if m.seat != m.turn % self.num_players {
+            return self.resync();
+        }
+        if let Some(known) = self.confirmed.get(m.turn) {
+            // a repeat of something we have, unless it disagrees with it
+            return if *known == m { None } else { self.resync() };
+        }
+        if self.pending.get(&m.turn).is_some_and(|p| *p != m) {
+            return self.resync();
+        }
+        self.pending.insert(m.turn, m);
+        while let Some(next) = self.pending.remove(&self.next_turn()) {
+            self.confirmed.push(next);
+            if let SubmitState::Submitted { turn, .. } = self.state {
+                // confirmed, or the server moved on without it, e.g. a timeout move
+                if turn == next.turn {
+                    self.state = SubmitState::Idle;
+                }
+            }
+        }
+        None
+    }
+
+    // sends our move, only when it's due
+    pub fn submit(&mut self, action: Action) -> Result<Outgoing, String> {
+        if !self.is_our_turn() {
+            return Err(format!(
+                "not our turn to move: turn {} in state {:?}",
+                self.next_turn(),
+                self.state
+            ));
+        }
+        let turn = self.next_turn();
+        self.state = SubmitState::Submitted {
+            turn,
+            action,
+            attempts: 1,
+            waited: Duration::from_secs(0),
+        };
+        Ok(Outgoing::Submit { turn, action })
+    }
+
+    // the server refused our move, our view of the game can't be trusted
+    pub fn rejected(&mut self) -> Option<Outgoing> {
+        match self.state {
+            SubmitState::Submitted { .. } => self.resync(),
+            _ => None,
+        }
+    }
+
+    // the server's full history, replacing ours
+    pub fn resynced(&mut self, moves: Vec<LiveMove>) -> Result<(), String> {
+        for (turn, m) in moves.iter().enumerate() {
+            if m.turn != turn || m.seat != turn % self.num_players {
+                return Err(format!("move {:?} out of order in the history", m));
+            }
+        }
+        self.confirmed = moves;
+        self.pending.clear();
+        self.state = SubmitState::Idle;
+        Ok(())
+    }
+
+    // time passing with no word from the server, sends our move again once it's overdue
+    pub fn tick(&mut self, elapsed: Duration) -> Option<Outgoing> {
+        if let SubmitState::Submitted {
+            turn,
+            action,
+            attempts,
+            waited,
+        } = self.state
+        {
+            let waited = waited + elapsed;
+            if waited < self.retry_after {
+                self.state = SubmitState::Submitted {
+                    turn,
+                    action,
+                    attempts,
+                    waited,
+                };
+                return None;
+            }
+            if attempts >= self.max_attempts {
+                return self.resync();
+            }
+            self.state = SubmitState::Submitted {
+                turn,
+                action,
+                attempts: attempts + 1,
+                waited: Duration::from_secs(0),
+            };
+            return Some(Outgoing::Submit { turn, action });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::notation::Move;
+
+    fn export(actions: &str) -> String {
+        let mut deck = Vec::new();
+        for suit in 0..5 {
+            for (rank, copies) in [3, 2, 2, 2, 1].iter().enumerate() {
+                for _ in 0..*copies {
+                    deck.push(format!(
+                        "{{\"suitIndex\": {}, \"rank\": {}}}",
+                        suit,
+                        rank + 1
+                    ));
+                }
+            }
+        }
+        format!(
+            "{{\"players\": [\"alice\", \"bob\"], \"deck\": [{}], \"actions\": [{}], \"options\": {{\"variant\": \"No Variant\"}}}}",
+            deck.join(", "),
+            actions
+        )
+    }
+
+    #[test]
+    fn test_parse_export() {
+        // alice holds R1 R1 R1 R2 R2 and bob R3 R3 R4 R4 R5 (cards 0-9), the next draws are Y1 Y1
+        let text = export(
+            "{\"type\": 0, \"target\": 0, \"value\": 0}, \
+             {\"type\": 3, \"target\": 0, \"value\": 1}, \
+             {\"type\": 0, \"target\": 10, \"value\": 0}, \
+             {\"type\": 1, \"target\": 9, \"value\": 0}, \
+             {\"type\": 4, \"target\": 0, \"value\": 0}",
+        );
+        let game = parse_export(&text).unwrap();
+        assert_eq!(game.players, vec!["alice".to_string(), "bob".to_string()]);
+
+        let turns = &game.replay.turns;
+        assert_eq!(turns.len(), 4);
+        // card 10 was drawn into the slot alice played from
+        assert_eq!(
+            turns[2].mv,
+            Move::Play {
+                seat: 0,
+                slot: Some(0),
+                card: Some(Card::new(Color::Yellow, Suit::One)),
+                success: Some(true),
+            }
+        );
+        assert_eq!(
+            turns[3].mv,
+            Move::Discard {
+                seat: 1,
+                slot: Some(4),
+                card: Some(Card::new(Color::Red, Suit::Five)),
+            }
+        );
+        let env = game.replay.env_at(5).unwrap();
+        assert_eq!(env.fireworks.total(), 2);
+        assert_eq!(env.public_info().discard.total, 1);
+
+        assert!(parse_export("{\"players\": [\"a\"]}").is_err());
+        // discarding with all 8 clue tokens is illegal
+        assert!(parse_export(&export("{\"type\": 1, \"target\": 0, \"value\": 0}")).is_err());
+    }
+
+    #[test]
+    fn test_import_dump() {
+        // scores 1 & 2
+        let one = export("{\"type\": 0, \"target\": 0, \"value\": 0}");
+        let two = export(
+            "{\"type\": 0, \"target\": 0, \"value\": 0}, \
+             {\"type\": 3, \"target\": 0, \"value\": 1}, \
+             {\"type\": 0, \"target\": 10, \"value\": 0}",
+        );
+        let variant = one.replace("No Variant", "Rainbow (6 Suits)");
+        let illegal = export("{\"type\": 1, \"target\": 0, \"value\": 0}");
+        let dump = [one.as_str(), &two, &variant, &illegal, "{not json"].join("\n");
+
+        let (games, stats) = import_dump(dump.as_bytes(), &ImportFilter::default());
+        assert_eq!(games.len(), 2);
+        assert_eq!(stats.num_read, 5);
+        assert_eq!(stats.num_imported, 2);
+        assert_eq!(stats.num_filtered, 1);
+        let failed: Vec<usize> = stats.failed.iter().map(|f| f.0).collect();
+        assert_eq!(failed, vec![3, 4]);
+
+        let filter = ImportFilter {
+            min_score: 2,
+            ..ImportFilter::default()
+        };
+        let list = format!("[{}, {}]", one, two);
+        let (games, stats) = import_dump(list.as_bytes(), &filter);
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].replay.turns.len(), 3);
+        assert_eq!(stats.num_filtered, 1);
+
+        // brackets & commas in strings don't split a game, a list cut short fails at the end
+        let named = one.replace("alice", r#"a, [b]\""#);
+        let list = format!("\n [{},\n{}", named, two);
+        let (games, stats) = import_dump(list.as_bytes(), &ImportFilter::default());
+        assert_eq!(stats.num_read, 2);
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].players[0], "a, [b]\"");
+        assert_eq!(stats.failed.len(), 1);
+    }
+
+    #[test]
+    fn test_live_session() {
+        let m = |turn: usize, action: Action| LiveMove {
+            turn,
+            seat: turn % 2,
+            action,
+        };
+        let clue = Action::SuitHint(Suit::One);
+        let mut session = LiveSession::new(1, 2).with_retries(Duration::from_millis(100), 2);
+        assert!(!session.is_our_turn());
+        assert!(session.submit(clue).is_err());
+
+        // turn 2 arrives before turn 0, nothing moves until the gap fills
+        assert_eq!(session.receive(m(2, Action::Play(0))), None);
+        assert_eq!(session.next_turn(), 0);
+        assert_eq!(session.receive(m(0, Action::Discard(4))), None);
+        assert_eq!(session.next_turn(), 1);
+        assert!(session.is_our_turn());
+        // a repeated broadcast changes nothing
+        assert_eq!(session.receive(m(0, Action::Discard(4))), None);
+
+        assert_eq!(
+            session.submit(clue),
+            Ok(Outgoing::Submit {
+                turn: 1,
+                action: clue
+            })
+        );
+        assert!(session.submit(clue).is_err());
+        // unconfirmed moves are sent again, then given up on
+        assert_eq!(session.tick(Duration::from_millis(60)), None);
+        assert_eq!(
+            session.tick(Duration::from_millis(60)),
+            Some(Outgoing::Submit {
+                turn: 1,
+                action: clue
+            })
+        );
+        assert_eq!(
+            session.tick(Duration::from_millis(100)),
+            Some(Outgoing::RequestResync)
+        );
+        assert_eq!(session.state, SubmitState::Resyncing);
+        assert!(session.resynced(vec![m(1, clue)]).is_err());
+        session
+            .resynced(vec![m(0, Action::Discard(4)), m(1, clue)])
+            .unwrap();
+        assert_eq!(session.next_turn(), 2);
+
+        // the buffered turn 2 went with the resync, it comes again
+        assert_eq!(session.receive(m(2, Action::Play(0))), None);
+        assert!(session.is_our_turn());
+        session.submit(Action::Play(1)).unwrap();
+        assert_eq!(session.rejected(), Some(Outgoing::RequestResync));
+
+        // a broadcast contradicting the history asks for the game again
+        let mut session = LiveSession::new(0, 2);
+        session.receive(m(0, clue));
+        assert_eq!(
+            session.receive(m(0, Action::Play(0))),
+            Some(Outgoing::RequestResync)
+        );
+    }
+}
@@ -1,6 +1,10 @@
-use crate::env::{Env, HasEnd, HasReward};
+use crate::abstraction::{is_critical_with, is_trash_with};
+use crate::beliefs::hint_info_gain;
+use crate::env::{Env, HasEnd, HasKey, HasReward};
+use crate::hanabi_symmetry::{canonical_symmetry_seeing, ColorPermutation};
 use crate::rand::seq::SliceRandom;
 use crate::rand::Rng;
+use std::sync::Arc;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Color {
@@ -20,32 +24,332 @@ pub enum Suit {
     Five = 4,
 }
 
-const COLORS: [Color; 5] = [
+pub const COLORS: [Color; 5] = [
     Color::White,
     Color::Red,
     Color::Blue,
     Color::Yellow,
     Color::Green,
 ];
-const SUITS: [Suit; 5] = [Suit::One, Suit::Two, Suit::Three, Suit::Four, Suit::Five];
+pub const SUITS: [Suit; 5] = [Suit::One, Suit::Two, Suit::Three, Suit::Four, Suit::Five];
 
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Card {
     id: u8,
 }
 
+// the color mask in the low byte & the suit mask in the high byte. bit 5 of each marks an empty slot
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Hint {
-    color: u8,
-    suit: u8,
+    masks: u16,
 }
 
+// bit `id` set for each card id, 25 bits
+pub type CardMask = u32;
+
+pub const ALL_CARDS: CardMask = (1 << 25) - 1;
+
+// plays & discards name the slot they act on
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Action {
     ColorHint(Color),
     SuitHint(Suit),
-    Discard(Hint),
-    Play(Hint),
+    Discard(usize),
+    Play(usize),
+}
+
+impl Action {
+    pub fn slot(&self) -> Option<usize> {
+        match self {
+            &Action::Play(slot) | &Action::Discard(slot) => Some(slot),
+            _ => None,
+        }
+    }
+}
+
+// how Env::search_actions narrows the legal actions down. it's carried by the env & its public
+// info, so every determinization of a position is searched the same way
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SearchPruning {
+    // slots whose cards carry the same hint look the same to the player, so by default only the
+    // first of them is played or discarded. turning this off branches on every slot
+    pub collapse_slots: bool,
+    // which moves the player's own knowledge rules out, see DominanceRules
+    pub dominance: DominanceRules,
+    // clues telling the partner less than this many bits are left out, so rollouts aren't spent
+    // on clues that only stall. 0 keeps every clue
+    pub min_hint_info: f32,
+}
+
+impl Default for SearchPruning {
+    fn default() -> Self {
+        Self {
+            collapse_slots: true,
+            dominance: DominanceRules::default(),
+            min_hint_info: 0.0,
+        }
+    }
+}
+
+// what a step changed, enough for HanabiEnv::undo to take it back, see
+// HanabiEnv::step_with_undo
+#[derive(Copy, Clone)]
+pub struct UndoToken {
+    action: Action,
+    // the card played or discarded, Card::none() for a clue
+    card: Card,
+    // a play that landed on the fireworks rather than in the discard
+    played: bool,
+    // the card drawn to replace a played or discarded one
+    drawn: Option<Card>,
+    // the hints of the hand the action changed: the partner's for a clue, the mover's otherwise
+    hints: [Hint; 5],
+    blue_tokens: ClueTokens,
+    black_tokens: StrikeTokens,
+    max_score: u8,
+    last_round: bool,
+    last_round_turns_taken: u8,
+    // the clues that touched & held the played or discarded card, see HintHistory::forget_slot
+    forgotten: (u128, u128),
+}
+
+// why an action can't be applied to an env, see HanabiEnv::check_action
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IllegalAction {
+    GameOver,
+    NoClueTokens(Action),
+    // the black color can't be clued
+    ClueNotInVariant(Action),
+    NoCardOfColor(Color),
+    NoCardOfRank(Suit),
+    // a play or discard of a slot past the hand or without a card
+    EmptySlot(Action),
+    DiscardAtMaxTokens,
+}
+
+impl std::fmt::Display for IllegalAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IllegalAction::GameOver => write!(f, "the game is over"),
+            IllegalAction::NoClueTokens(action) => {
+                write!(f, "{:?} needs a clue token but none are left", action)
+            }
+            IllegalAction::ClueNotInVariant(action) => {
+                write!(f, "{:?} isn't a clue in this variant", action)
+            }
+            IllegalAction::NoCardOfColor(color) => {
+                write!(f, "{:?} clue touches no cards", color)
+            }
+            IllegalAction::NoCardOfRank(suit) => write!(f, "{:?} clue touches no cards", suit),
+            IllegalAction::EmptySlot(action) => write!(f, "{:?} names no card", action),
+            IllegalAction::DiscardAtMaxTokens => {
+                write!(f, "can't discard with every clue token available")
+            }
+        }
+    }
+}
+
+impl From<IllegalAction> for String {
+    fn from(e: IllegalAction) -> Self {
+        e.to_string()
+    }
+}
+
+// clue tokens left: a clue spends one, a discard or a finished color wins one back
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ClueTokens(u8);
+
+impl ClueTokens {
+    pub fn new(tokens: u8) -> Self {
+        Self(tokens)
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    // one token fewer, None when there's none to spend
+    pub fn spend(self) -> Option<Self> {
+        self.0.checked_sub(1).map(Self)
+    }
+
+    // one token more, up to `max`
+    pub fn gain(self, max: u8) -> Self {
+        Self((self.0 + 1).min(max))
+    }
+}
+
+impl PartialEq<u8> for ClueTokens {
+    fn eq(&self, tokens: &u8) -> bool {
+        self.0 == *tokens
+    }
+}
+
+impl PartialOrd<u8> for ClueTokens {
+    fn partial_cmp(&self, tokens: &u8) -> Option<std::cmp::Ordering> {
+        self.0.partial_cmp(tokens)
+    }
+}
+
+impl std::fmt::Display for ClueTokens {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// strikes the team can still take, plus one: a misplay spends one and the game is lost at 1
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StrikeTokens(u8);
+
+impl StrikeTokens {
+    pub fn new(tokens: u8) -> Self {
+        Self(tokens)
+    }
+
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    // one token fewer, None when there's none to spend
+    pub fn spend(self) -> Option<Self> {
+        self.0.checked_sub(1).map(Self)
+    }
+}
+
+impl PartialEq<u8> for StrikeTokens {
+    fn eq(&self, tokens: &u8) -> bool {
+        self.0 == *tokens
+    }
+}
+
+impl PartialOrd<u8> for StrikeTokens {
+    fn partial_cmp(&self, tokens: &u8) -> Option<std::cmp::Ordering> {
+        self.0.partial_cmp(tokens)
+    }
+}
+
+impl std::fmt::Display for StrikeTokens {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// moves the player's own knowledge already rules out, left out of Env::search_actions so no
+// search spends visits on them: playing a card that's trash or can't be played yet whatever it
+// is, discarding a card that's playable whatever it is, and discarding a card that's critical
+// whatever it is while another slot is known trash. a slot's knowledge is its hint narrowed to
+// the cards the player can't see, which is the same in every determinization
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DominanceRules {
+    pub never_play_trash: bool,
+    pub never_discard_playable: bool,
+    pub never_play_unplayable: bool,
+    pub never_discard_critical_over_trash: bool,
+}
+
+impl DominanceRules {
+    pub fn none() -> Self {
+        Self {
+            never_play_trash: false,
+            never_discard_playable: false,
+            never_play_unplayable: false,
+            never_discard_critical_over_trash: false,
+        }
+    }
+}
+
+// bit masks of the slots whose card the player knows to be each of these, see
+// HanabiEnv::known_slots
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct KnownSlots {
+    pub trash: u8,
+    pub playable: u8,
+    pub unplayable: u8,
+    pub critical: u8,
+}
+
+impl DominanceRules {
+    // whether `action` is ruled out by what the player knows about their slots
+    pub fn prunes(&self, action: &Action, known: &KnownSlots) -> bool {
+        match *action {
+            Action::Play(i) => {
+                (self.never_play_trash && known.trash & 1 << i != 0)
+                    || (self.never_play_unplayable && known.unplayable & 1 << i != 0)
+            }
+            Action::Discard(i) => {
+                (self.never_discard_playable && known.playable & 1 << i != 0)
+                    || (self.never_discard_critical_over_trash
+                        && known.critical & 1 << i != 0
+                        && known.trash != 0)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for DominanceRules {
+    fn default() -> Self {
+        Self {
+            never_play_trash: true,
+            never_discard_playable: true,
+            never_play_unplayable: true,
+            never_discard_critical_over_trash: true,
+        }
+    }
+}
+
+// 5 plays, 5 discards, 5 color & 5 rank clues
+pub const MAX_ACTIONS: usize = 20;
+
+// an action with everything but the draw decided, see HanabiEnv::resolve
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PendingOutcome {
+    pub action: Action,
+    // the slot played or discarded from, the replacement is drawn into it. None for clues
+    pub slot: Option<usize>,
+    // the card played or discarded
+    pub card: Option<Card>,
+    // whether a play lands on its firework
+    pub success: Option<bool>,
+}
+
+// the legal actions of a position, kept on the stack so rollouts never allocate for them
+#[derive(Copy, Clone)]
+pub struct ActionList {
+    actions: [Action; MAX_ACTIONS],
+    len: usize,
+}
+
+impl Default for ActionList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActionList {
+    pub fn new() -> Self {
+        Self {
+            actions: [Action::Play(0); MAX_ACTIONS],
+            len: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn push(&mut self, action: Action) {
+        self.actions[self.len] = action;
+        self.len += 1;
+    }
+}
+
+impl std::ops::Deref for ActionList {
+    type Target = [Action];
+
+    fn deref(&self) -> &[Action] {
+        &self.actions[..self.len]
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -54,22 +358,189 @@ pub struct CardCollection {
     pub counts: [u8; 25],
 }
 
+#[derive(Clone)]
+pub enum Deck {
+    // order-agnostic, draws are sampled from the remaining counts
+    Multiset(CardCollection),
+    // concrete draw order, the next card drawn is the last one
+    Ordered(Vec<Card>),
+}
+
 #[derive(Copy, Clone)]
 pub struct Fireworks(pub [u8; 5]);
 
+// the parts of the game variants & house rules change. the default is the standard game
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RuleSet {
+    // cards in each hand, at most 5. the slots past it stay empty
+    pub hand_size: usize,
+    pub clue_tokens: u8,
+    // misplays that end the game
+    pub strikes: u8,
+    // copies of each rank in every color, 1s first
+    pub copies: [u8; 5],
+    // turns taken once the last card is drawn
+    pub final_turns: u8,
+    // hanab.live's "Black (5 Suits)": the black suit in place of BLACK_COLOR, one copy of each
+    // rank and no clue of its color. cards have five colors, so the six suit variant that adds
+    // black as a sixth suit can't be played
+    pub black: bool,
+    // the first `colors` colors and `ranks` ranks are in the deck, fewer of both make a mini
+    // game small enough to solve exactly (see mini.rs)
+    pub colors: u8,
+    pub ranks: u8,
+}
+
+// the color that plays the black suit, the one hanab.live's 5 suit black variant swaps out just
+// as purple maps to it in No Variant
+pub const BLACK_COLOR: Color = Color::White;
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            hand_size: 5,
+            clue_tokens: 8,
+            strikes: 3,
+            copies: [3, 2, 2, 2, 1],
+            final_turns: 2,
+            black: false,
+            colors: 5,
+            ranks: 5,
+        }
+    }
+}
+
+impl RuleSet {
+    // why a game can't be played under these rules
+    pub fn check(&self) -> Result<(), String> {
+        if self.hand_size == 0 || self.hand_size > 5 {
+            return Err(format!(
+                "hand size {} isn't between 1 and 5",
+                self.hand_size
+            ));
+        }
+        if self.strikes == 0 || self.final_turns == 0 {
+            return Err("strikes and final turns must be at least 1".to_string());
+        }
+        if self.copies.iter().any(|&n| n == 0 || n > 4) {
+            return Err(format!(
+                "copies {:?} must each be between 1 and 4",
+                self.copies
+            ));
+        }
+        if self.colors == 0 || self.colors > 5 || self.ranks == 0 || self.ranks > 5 {
+            return Err(format!(
+                "{} colors and {} ranks, both must be between 1 and 5",
+                self.colors, self.ranks
+            ));
+        }
+        if (self.deck_size() as usize) < 2 * self.hand_size {
+            return Err(format!(
+                "a deck of {} cards can't deal two hands of {}",
+                self.deck_size(),
+                self.hand_size
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn deck(&self) -> CardCollection {
+        let mut deck = CardCollection::empty();
+        for id in 0..25 {
+            let card = Card::from_id(id as u8);
+            let copies = if card.color_id() >= self.colors || card.suit_id() >= self.ranks {
+                0
+            } else if self.black && card.color() == BLACK_COLOR {
+                1
+            } else {
+                self.copies[id % 5]
+            };
+            deck.counts[id] = copies;
+            deck.total += copies;
+        }
+        deck
+    }
+
+    pub fn deck_size(&self) -> u8 {
+        self.deck().total
+    }
+
+    pub fn perfect_score(&self) -> u8 {
+        self.colors * self.ranks
+    }
+
+    // false for a color clue of the black suit
+    pub fn allows_clue(&self, action: &Action) -> bool {
+        !(self.black && *action == Action::ColorHint(BLACK_COLOR))
+    }
+
+    // comma separated changes to the standard rules, e.g. "hand=4,clues=6,strikes=2,
+    // copies=3.2.2.2.1,final-turns=2,black5,colors=2,ranks=3"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut rules = Self::default();
+        for part in text.split(',').filter(|p| !p.is_empty()) {
+            let mut kv = part.splitn(2, '=');
+            let (key, value) = (kv.next().unwrap(), kv.next().unwrap_or(""));
+            let number = |value: &str| {
+                value
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid number '{}' for '{}'", value, key))
+            };
+            match key {
+                "hand" => rules.hand_size = number(value)? as usize,
+                "clues" => rules.clue_tokens = number(value)?,
+                "strikes" => rules.strikes = number(value)?,
+                "final-turns" => rules.final_turns = number(value)?,
+                "black5" if value.is_empty() => rules.black = true,
+                "black" | "black6" => {
+                    return Err(
+                        "the black suit only replaces white (black5), there's no sixth suit"
+                            .to_string(),
+                    )
+                }
+                "colors" => rules.colors = number(value)?,
+                "ranks" => rules.ranks = number(value)?,
+                "copies" => {
+                    let copies: Vec<&str> = value.split('.').collect();
+                    if copies.len() != 5 {
+                        return Err(format!("expected 5 copy counts, found '{}'", value));
+                    }
+                    for (i, n) in copies.iter().enumerate() {
+                        rules.copies[i] = number(n)?;
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "unknown rule '{}', expected hand, clues, strikes, copies, final-turns, \
+                         black5, colors or ranks",
+                        key
+                    ))
+                }
+            }
+        }
+        rules.check()?;
+        Ok(rules)
+    }
+}
+
 #[derive(Clone)]
 pub struct HanabiEnv {
     pub player_hand: [Card; 5],
     pub player_hints: [Hint; 5],
     pub opponent_hand: [Card; 5],
     pub opponent_hints: [Hint; 5],
-    pub deck: CardCollection,
+    pub deck: Deck,
     pub discard: CardCollection,
-    pub blue_tokens: u8,
-    pub black_tokens: u8,
+    pub blue_tokens: ClueTokens,
+    pub black_tokens: StrikeTokens,
     pub fireworks: Fireworks,
+    // the best score still reachable, kept up to date as cards are played & discarded
+    pub max_score: u8,
     pub last_round: bool,
     pub last_round_turns_taken: u8,
+    pub hint_history: HintHistory,
+    pub rules: RuleSet,
+    pub pruning: SearchPruning,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -82,15 +553,203 @@ pub struct PublicInfo {
     pub player_hints: [Hint; 5],
     pub opponent_hints: [Hint; 5],
     pub discard: CardCollection,
-    pub blue_tokens: u8,
-    pub black_tokens: u8,
+    pub blue_tokens: ClueTokens,
+    pub black_tokens: StrikeTokens,
     pub fireworks: Fireworks,
+    // see HanabiEnv::max_score, `max_score` works it out for positions built by hand
+    pub max_score: u8,
     pub last_round: bool,
     pub last_round_turns_taken: u8,
+    // left out of the key, two positions with the same hints share a node whatever the order
+    // they were given in
+    pub hint_history: HintHistory,
+    // left out of the key too, it's the same for every position of a game
+    pub rules: RuleSet,
+    // left out of the key as well, it only changes what the searches try
+    pub pruning: SearchPruning,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HintEvent {
+    // the turn it was given on, counting from 0
+    pub turn: u16,
+    pub clue: Action,
+    // bit i is set while the card the clue touched in slot i is still held
+    pub touched: u8,
+    // bit i is set while the card in slot i when the clue was given is still held, touched or not
+    pub held: u8,
+}
+
+impl HintEvent {
+    pub fn touched_slots(&self) -> Vec<usize> {
+        (0..5).filter(|&i| self.touched & (1 << i) != 0).collect()
+    }
+
+    pub fn held_slots(&self) -> Vec<usize> {
+        (0..5).filter(|&i| self.held & (1 << i) != 0).collect()
+    }
+
+    // narrows `hint` for slot `i` by this clue: to the clue for a touched card, away from it
+    // for a card that was in the hand but not touched
+    pub fn narrow(&self, i: usize, hint: &mut Hint) {
+        if self.held & (1 << i) == 0 {
+            return;
+        }
+        let touched = self.touched & (1 << i) != 0;
+        match self.clue {
+            Action::ColorHint(color) if touched => hint.set_true_color(color),
+            Action::ColorHint(color) => hint.disable_color(color),
+            Action::SuitHint(suit) if touched => hint.set_true_suit(suit),
+            Action::SuitHint(suit) => hint.disable_suit(suit),
+            _ => {}
+        }
+    }
+}
+
+// every clue of the game newest first, with the turns they were given on so they read the same
+// from either seat: the player to move got a clue when it was given on a turn of the other
+// parity. the events live behind an Arc so envs & public infos stay small and cloning one for a
+// determinization doesn't copy them, they're only copied when a clone records or forgets
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HintHistory {
+    events: Arc<Vec<HintEvent>>,
+    // turns taken so far
+    pub turn: u16,
+    // the clues before some point are missing, e.g. in a state saved before there was a history
+    pub lost: bool,
+}
+
+impl HintHistory {
+    pub fn iter(&self) -> impl Iterator<Item = &HintEvent> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn received_by_player(&self, event: &HintEvent) -> bool {
+        event.turn % 2 != self.turn % 2
+    }
+
+    pub fn turns_ago(&self, event: &HintEvent) -> u16 {
+        self.turn - event.turn
+    }
+
+    // the newest clue the player to move received
+    pub fn latest_received(&self) -> Option<&HintEvent> {
+        self.iter().find(|e| self.received_by_player(e))
+    }
+
+    // the newest clue that touched a card the player to move still holds in `slot`
+    pub fn touch_on(&self, slot: usize) -> Option<&HintEvent> {
+        self.iter()
+            .find(|e| self.received_by_player(e) && e.touched & (1 << slot) != 0)
+    }
+
+    // the slots of the player to move's hand, or the partner's when `player` is false, holding a
+    // card a clue touched. negative information doesn't count, unlike a hint that isn't empty
+    pub fn touched(&self, player: bool) -> u8 {
+        self.iter()
+            .filter(|e| self.received_by_player(e) == player)
+            .fold(0, |bits, e| bits | e.touched)
+    }
+
+    // the clue the player to move is giving this turn, touching `touched` of the `held` cards in
+    // the partner's hand
+    pub fn record(&mut self, clue: Action, touched: u8, held: u8) {
+        let event = HintEvent {
+            turn: self.turn,
+            clue,
+            touched,
+            held,
+        };
+        Arc::make_mut(&mut self.events).insert(0, event);
+    }
+
+    // the player to move played or discarded `slot`, so the clues they got no longer apply to it.
+    // returns the clues, bit i for the i-th newest, that touched & held the slot's card so
+    // `remember_slot` can put them back
+    pub fn forget_slot(&mut self, slot: usize) -> (u128, u128) {
+        // a game gives fewer clues than this: the tokens it starts with, one back for each
+        // discard and one for each finished color
+        assert!(self.events.len() <= 128, "too many clues to undo");
+        let (mut touched, mut held) = (0u128, 0u128);
+        for (i, event) in self.events.iter().enumerate() {
+            if event.turn % 2 != self.turn % 2 {
+                touched |= ((event.touched >> slot & 1) as u128) << i;
+                held |= ((event.held >> slot & 1) as u128) << i;
+            }
+        }
+        if held != 0 {
+            for (i, event) in Arc::make_mut(&mut self.events).iter_mut().enumerate() {
+                if held & 1 << i != 0 {
+                    event.touched &= !(1 << slot);
+                    event.held &= !(1 << slot);
+                }
+            }
+        }
+        (touched, held)
+    }
+
+    // takes back forget_slot(slot), which returned `forgotten`
+    pub fn remember_slot(&mut self, slot: usize, forgotten: (u128, u128)) {
+        let (touched, held) = forgotten;
+        if held == 0 {
+            return;
+        }
+        for (i, event) in Arc::make_mut(&mut self.events).iter_mut().enumerate() {
+            event.touched |= ((touched >> i & 1) as u8) << slot;
+            event.held |= ((held >> i & 1) as u8) << slot;
+        }
+    }
+
+    // takes back the newest clue, the last thing recorded
+    pub fn unrecord(&mut self) {
+        Arc::make_mut(&mut self.events).remove(0);
+    }
+
+    // what the clues say about each slot of the player to move's hand, or the partner's when
+    // `player` is false: every clue a held card got, touched or not. `present` are the slots
+    // holding a card, the others come back as Hint::none(). only matches the env's hints while
+    // nothing is `lost`
+    pub fn knowledge(&self, player: bool, present: &[bool; 5]) -> [Hint; 5] {
+        let mut hints = [Hint::none(); 5];
+        for i in (0..5).filter(|&i| present[i]) {
+            hints[i] = Hint::empty();
+            for event in self.iter() {
+                if self.received_by_player(event) == player {
+                    event.narrow(i, &mut hints[i]);
+                }
+            }
+        }
+        hints
+    }
+
+    // the same history with every clue passed through `f`, e.g. to relabel colors
+    pub fn map_clues<F: Fn(&Action) -> Action>(&self, f: F) -> Self {
+        let mut mapped = self.clone();
+        for event in Arc::make_mut(&mut mapped.events).iter_mut() {
+            event.clue = f(&event.clue);
+        }
+        mapped
+    }
+
+    pub fn push_event(&mut self, event: HintEvent) -> Result<(), String> {
+        if event.turn >= self.turn || self.iter().any(|e| e.turn <= event.turn) {
+            return Err(format!("clue on turn {} is out of order", event.turn));
+        }
+        Arc::make_mut(&mut self.events).push(event);
+        Ok(())
+    }
 }
 
 impl Color {
-    fn from_id(id: u8) -> Self {
+    pub fn from_id(id: u8) -> Self {
         match id {
             0 => Color::White,
             1 => Color::Red,
@@ -101,7 +760,7 @@ impl Color {
         }
     }
 
-    fn short_name(&self) -> &str {
+    pub fn short_name(&self) -> &str {
         match self {
             Color::White => "W",
             Color::Blue => "B",
@@ -113,7 +772,7 @@ impl Color {
 }
 
 impl Suit {
-    fn from_id(id: u8) -> Self {
+    pub fn from_id(id: u8) -> Self {
         match id {
             0 => Suit::One,
             1 => Suit::Two,
@@ -126,7 +785,7 @@ impl Suit {
 }
 
 impl Card {
-    fn new(color: Color, suit: Suit) -> Self {
+    pub fn new(color: Color, suit: Suit) -> Self {
         Card::from_parts(color as u8, suit as u8)
     }
 
@@ -137,10 +796,10 @@ impl Card {
     }
 
     pub fn from_id(id: u8) -> Card {
-        Card { id: id }
+        Card { id }
     }
 
-    fn none() -> Card {
+    pub fn none() -> Card {
         Card { id: 26 }
     }
 
@@ -160,18 +819,18 @@ impl Card {
         !self.is_none()
     }
 
-    fn color_id(&self) -> u8 {
+    pub fn color_id(&self) -> u8 {
         self.id / 5
     }
-    fn suit_id(&self) -> u8 {
+    pub fn suit_id(&self) -> u8 {
         self.id % 5
     }
 
-    fn color(&self) -> Color {
+    pub fn color(&self) -> Color {
         Color::from_id(self.color_id())
     }
 
-    fn suit(&self) -> Suit {
+    pub fn suit(&self) -> Suit {
         Suit::from_id(self.suit_id())
     }
 }
@@ -208,6 +867,84 @@ impl std::fmt::Debug for Hint {
     }
 }
 
+impl std::fmt::Display for Fireworks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for color_i in 0..5 {
+            if color_i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(
+                f,
+                "{}{}",
+                Color::from_id(color_i).short_name(),
+                self.0[color_i as usize]
+            )?;
+        }
+        write!(f, " ({}/25)", self.total())
+    }
+}
+
+// the cards in id order, e.g. "W1 W1 R3", or "-" when empty
+impl std::fmt::Display for CardCollection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.total == 0 {
+            return f.write_str("-");
+        }
+        let mut first = true;
+        for id in 0..25 {
+            for _ in 0..self.counts[id] {
+                if !first {
+                    f.write_str(" ")?;
+                }
+                first = false;
+                write!(f, "{:?}", Card::from_id(id as u8))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for PublicInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Deck=|{}| Discard=|{}| Fireworks={:?} Blue={} Black={} FutureReward={}",
+            self.deck_total(),
+            self.discard.total,
+            self.fireworks,
+            self.blue_tokens,
+            self.black_tokens,
+            self.max_score - self.fireworks.total(),
+        )?;
+        writeln!(f, "Discarded: {}", self.discard)?;
+        writeln!(f, "----- Me -----")?;
+        writeln!(f, "{:?}", self.player_hints)?;
+        writeln!(f, "----- Op -----")?;
+        writeln!(f, "{:?}", self.opponent_hints)
+    }
+}
+
+impl std::fmt::Display for HanabiEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Deck=|{}| Discard=|{}| Fireworks={:?} Blue={} Black={} FutureReward={}",
+            self.deck.remaining(),
+            self.discard.total,
+            self.fireworks,
+            self.blue_tokens,
+            self.black_tokens,
+            self.max_score - self.fireworks.total(),
+        )?;
+        writeln!(f, "----- Me -----")?;
+        writeln!(f, "{:?}", self.player_hand)?;
+        writeln!(f, "{:?}", self.player_hints)?;
+        writeln!(f, "----- Op -----")?;
+        writeln!(f, "{:?}", self.opponent_hand)?;
+        writeln!(f, "{:?}", self.opponent_hints)
+    }
+}
+
 impl std::fmt::Debug for Fireworks {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str("[")?;
@@ -239,7 +976,7 @@ impl Fireworks {
         self.0.iter().sum::<u8>()
     }
 
-    fn accepts(&self, card: Card) -> bool {
+    pub fn accepts(&self, card: Card) -> bool {
         self.0[card.color_id() as usize] == card.suit_id()
     }
 
@@ -247,29 +984,33 @@ impl Fireworks {
         assert!(self.accepts(card));
         self.0[card.color_id() as usize] = card.suit_id() + 1;
     }
-
-    fn is_color_complete(&self, color: Color) -> bool {
-        self.0[color as usize] == 5
-    }
 }
 
 impl Hint {
-    pub fn empty() -> Self {
+    pub fn from_masks(color: u8, suit: u8) -> Self {
         Self {
-            color: 0b011111,
-            suit: 0b011111,
+            masks: color as u16 | (suit as u16) << 8,
         }
     }
 
+    pub fn color_mask(&self) -> u8 {
+        self.masks as u8
+    }
+
+    pub fn suit_mask(&self) -> u8 {
+        (self.masks >> 8) as u8
+    }
+
+    pub fn empty() -> Self {
+        Self::from_masks(0b011111, 0b011111)
+    }
+
     fn none() -> Self {
-        Self {
-            color: 0b100000,
-            suit: 0b100000,
-        }
+        Self::from_masks(0b100000, 0b100000)
     }
 
     fn is_none(&self) -> bool {
-        self.color == 0b100000 || self.suit == 0b100000
+        self.color_mask() == 0b100000 || self.suit_mask() == 0b100000
     }
 
     pub fn is_some(&self) -> bool {
@@ -277,38 +1018,51 @@ impl Hint {
     }
 
     pub fn set_true_color(&mut self, color: Color) {
-        self.color = 1 << color as usize;
+        self.masks = self.masks & 0xff00 | 1 << color as u16;
     }
 
     pub fn disable_color(&mut self, color: Color) {
-        self.color &= !(1 << color as usize);
+        self.masks &= !(1 << color as u16);
     }
 
     pub fn set_true_suit(&mut self, suit: Suit) {
-        self.suit = 1 << suit as usize;
+        self.masks = self.masks & 0x00ff | 1 << (8 + suit as u16);
     }
 
     pub fn disable_suit(&mut self, suit: Suit) {
-        self.suit &= !(1 << suit as usize);
+        self.masks &= !(1 << (8 + suit as u16));
     }
 
     fn matches_color(&self, color: Color) -> bool {
-        let color_bit = 1 << color as u8;
-        self.color & color_bit == color_bit
+        self.masks & 1 << color as u16 != 0
     }
 
     fn matches_suit(&self, suit: Suit) -> bool {
-        let suit_bit = 1 << suit as u8;
-        self.suit & suit_bit == suit_bit
+        self.masks & 1 << (8 + suit as u16) != 0
     }
 
     pub fn matches(&self, card: Card) -> bool {
         self.matches_color(card.color()) && self.matches_suit(card.suit())
     }
+
+    // the card ids the slot could still be, nothing for an empty slot
+    pub fn card_mask(&self) -> CardMask {
+        if self.is_none() {
+            return 0;
+        }
+        let suits = (self.suit_mask() & 0b11111) as CardMask;
+        let mut mask = 0;
+        for color in 0..5 {
+            if self.masks & 1 << color != 0 {
+                mask |= suits << (5 * color);
+            }
+        }
+        mask
+    }
 }
 
 impl CardCollection {
-    fn empty() -> Self {
+    pub fn empty() -> Self {
         Self {
             total: 0,
             counts: [0; 25],
@@ -328,12 +1082,12 @@ impl CardCollection {
         }
     }
 
-    fn add(&mut self, card: Card) {
+    pub fn add(&mut self, card: Card) {
         self.total += 1;
         self.counts[card.id() as usize] += 1;
     }
 
-    fn remove(&mut self, card: Card) -> Card {
+    pub fn remove(&mut self, card: Card) -> Card {
         self.total -= 1;
         self.counts[card.id() as usize] -= 1;
         card
@@ -362,7 +1116,7 @@ impl CardCollection {
         }
     }
 
-    fn pop<R: Rng>(&mut self, rng: &mut R) -> Card {
+    pub fn pop<R: Rng>(&mut self, rng: &mut R) -> Card {
         if self.total > 0 {
             let card_index = rng.gen_range(0, self.total);
             let mut total = 0;
@@ -376,105 +1130,276 @@ impl CardCollection {
         Card::none()
     }
 
-    fn pop_match<R: Rng>(&mut self, hint: &Hint, mut rng: &mut R) -> Option<(Card, f32)> {
-        let mut matches = self.clone();
+    // the ids with at least one card left
+    pub fn card_mask(&self) -> CardMask {
+        let mut mask = 0;
         for i in 0..25 {
-            let card = Card::from_id(i as u8);
-            if self.counts[i] > 0 && !hint.matches(card) {
-                matches.total -= matches.counts[i];
-                matches.counts[i] = 0;
-            }
-        }
-        let matched_card = matches.pop(&mut rng);
-        if matched_card.is_some() {
-            let num_matches = matches.counts[matched_card.id() as usize] + 1;
-            Some((
-                self.remove(matched_card),
-                (num_matches as f32) / (matches.total + 1) as f32, // TODO for theory of mind, change this probabilty based on what they play
-            ))
-        } else {
-            None
+            if self.counts[i] > 0 {
+                mask |= 1 << i;
+            }
         }
+        mask
     }
 
-    fn num_of_suit(&self, suit: Suit) -> u8 {
-        let mut num = 0;
-        for color in 0..5 {
-            num += self.counts[5 * color + suit as usize];
+    // how many of the cards have an id in `mask`
+    pub fn count_in(&self, mask: CardMask) -> u8 {
+        let mut count = 0;
+        let mut bits = mask & ALL_CARDS;
+        while bits != 0 {
+            count += self.counts[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
         }
-        num
+        count
     }
 
-    fn num_of_color(&self, color: Color) -> u8 {
-        let mut num = 0;
-        for suit in 0..5 {
-            num += self.counts[5 * (color as usize) + suit];
+    fn pop_match<R: Rng>(&mut self, hint: &Hint, rng: &mut R) -> Option<Card> {
+        let mask = hint.card_mask();
+        let total = self.count_in(mask);
+        if total == 0 {
+            return None;
+        }
+        let card_index = rng.gen_range(0, total);
+        let mut seen = 0;
+        let mut bits = mask;
+        loop {
+            let id = bits.trailing_zeros() as usize;
+            let count = self.counts[id];
+            if card_index < seen + count {
+                return Some(self.remove(Card::from_id(id as u8)));
+            }
+            seen += count;
+            bits &= bits - 1;
         }
-        num
     }
 }
 
-fn determinize_hints<R: Rng>(
-    deck: &mut CardCollection,
-    hints: &[Hint; 5],
-    mut rng: &mut R,
-) -> ([Card; 5], f32) {
-    // go to first card
-    let mut i = 0;
-    while hints[i].is_none() && i < 5 {
-        i += 1;
+impl Deck {
+    pub fn shuffled<R: Rng>(rng: &mut R) -> Self {
+        let mut cards = Vec::with_capacity(50);
+        let deck = CardCollection::starting_deck();
+        for i in 0..25 {
+            for _ in 0..deck.counts[i] {
+                cards.push(Card::from_id(i as u8));
+            }
+        }
+        cards.shuffle(rng);
+        Deck::Ordered(cards)
     }
 
-    let mut cards = [Card::none(); 5];
-    let mut prob = 1.0;
-    while i < 5 {
-        match deck.pop_match(&hints[i], &mut rng) {
-            Some((card, p)) => {
-                cards[i] = card;
-                prob *= p;
-            }
-            None => {
-                // there are no matching cards! start over
-                // remove any cards we've set
-                // TODO optimize this so we don't throw away good work!
-                prob = 1.0;
-                for j in 0..5 {
-                    if cards[j].is_some() {
-                        deck.add(cards[j]);
-                        cards[j] = Card::none();
-                    }
-                }
+    // `order[0]` is the first card drawn
+    pub fn from_order(order: &[Card]) -> Self {
+        Deck::Ordered(order.iter().rev().cloned().collect())
+    }
+
+    pub fn pop<R: Rng>(&mut self, rng: &mut R) -> Card {
+        match self {
+            Deck::Multiset(cards) => cards.pop(rng),
+            Deck::Ordered(cards) => cards.pop().unwrap_or(Card::none()),
+        }
+    }
 
-                // go to first card
-                i = 0;
-                while hints[i].is_none() && i < 5 {
-                    i += 1;
+    // draws `card` itself, wherever it is in an ordered deck
+    pub fn take(&mut self, card: Card) -> Result<Card, String> {
+        match self {
+            Deck::Multiset(cards) if cards.counts[card.id() as usize] > 0 => Ok(cards.remove(card)),
+            Deck::Ordered(cards) => match cards.iter().rposition(|&c| c == card) {
+                Some(i) => Ok(cards.remove(i)),
+                None => Err(format!("{:?} isn't in the deck", card)),
+            },
+            _ => Err(format!("{:?} isn't in the deck", card)),
+        }
+    }
+
+    pub fn peek_known(&self) -> Option<Card> {
+        match self {
+            Deck::Multiset(cards) => {
+                // only known when every remaining card is the same
+                let i = cards.counts.iter().position(|&c| c > 0)?;
+                if cards.counts[i] == cards.total {
+                    Some(Card::from_id(i as u8))
+                } else {
+                    None
                 }
-                continue;
             }
+            Deck::Ordered(cards) => cards.last().cloned(),
         }
+    }
 
-        // go to next card
-        loop {
-            i += 1;
-            if i == 5 || hints[i].is_some() {
-                break;
+    pub fn remaining(&self) -> u8 {
+        match self {
+            Deck::Multiset(cards) => cards.total,
+            Deck::Ordered(cards) => cards.len() as u8,
+        }
+    }
+
+    pub fn cards(&self) -> CardCollection {
+        match self {
+            Deck::Multiset(cards) => *cards,
+            Deck::Ordered(cards) => {
+                let mut collection = CardCollection::empty();
+                for &card in cards.iter() {
+                    collection.add(card);
+                }
+                collection
             }
         }
     }
+}
 
-    (cards, prob)
+// what a clue tells the holder of `hand` about each of its cards, plays & discards change nothing
+pub fn apply_clue(hand: &[Card; 5], hints: &mut [Hint; 5], action: &Action) {
+    for i in 0..5 {
+        if !hand[i].is_some() {
+            continue;
+        }
+        match *action {
+            Action::ColorHint(color) if hand[i].color() == color => hints[i].set_true_color(color),
+            Action::ColorHint(color) => hints[i].disable_color(color),
+            Action::SuitHint(suit) if hand[i].suit() == suit => hints[i].set_true_suit(suit),
+            Action::SuitHint(suit) => hints[i].disable_suit(suit),
+            _ => {}
+        }
+    }
+}
+
+// complete deals determinize_hints tries before keeping one whatever its chance
+pub const MAX_DEAL_ATTEMPTS: usize = 1000;
+
+// deals a hand fitting `hints` out of `deck`, with every such hand as likely as in a real deal.
+// drawing slot by slot from the cards each hint allows favors cards that fit few of the hints,
+// so a dealt hand is kept with the chance of the matching totals it was drawn from over the
+// most they could be, and dealing starts over when it isn't or a slot runs out of matches.
+// the kept hands then need no weighting, the log weight returned is 0. after MAX_DEAL_ATTEMPTS
+// the next complete hand is kept anyway, weighted by its chance over the mean chance of the
+// attempts so far, and hints no hand of the deck fits are an error
+pub fn determinize_hints<R: Rng>(
+    deck: &mut CardCollection,
+    hints: &[Hint; 5],
+    mut rng: &mut R,
+) -> Result<([Card; 5], f32, usize), String> {
+    let slots: Vec<usize> = (0..5).filter(|&i| hints[i].is_some()).collect();
+    let most: Vec<f32> = slots
+        .iter()
+        .map(|&i| deck.count_in(hints[i].card_mask()) as f32)
+        .collect();
+    let mut cards = [Card::none(); 5];
+    let mut restarts = 0;
+    // the chances of every attempt, 0 for those that ran out of matches
+    let mut total_keep = 0.0;
+    loop {
+        let mut keep = 1.0;
+        let mut dealt = true;
+        for (k, &i) in slots.iter().enumerate() {
+            let total = deck.count_in(hints[i].card_mask()) as f32;
+            match deck.pop_match(&hints[i], &mut rng) {
+                Some(card) => {
+                    cards[i] = card;
+                    keep *= total / most[k];
+                }
+                None => {
+                    dealt = false;
+                    break;
+                }
+            }
+        }
+        if dealt {
+            total_keep += keep;
+            if restarts >= MAX_DEAL_ATTEMPTS {
+                let mean_keep = total_keep / (restarts + 1) as f32;
+                return Ok((cards, keep.ln() - mean_keep.ln(), restarts));
+            }
+            if rng.gen::<f32>() < keep {
+                return Ok((cards, 0.0, restarts));
+            }
+        }
+        restarts += 1;
+        for card in cards.iter_mut().filter(|c| c.is_some()) {
+            deck.add(*card);
+            *card = Card::none();
+        }
+        if restarts >= 2 * MAX_DEAL_ATTEMPTS {
+            return Err(format!("no hand of the deck fits the hints {:?}", hints));
+        }
+    }
 }
 
 impl HanabiEnv {
+    fn with_hands(
+        rules: RuleSet,
+        player_hand: [Card; 5],
+        opponent_hand: [Card; 5],
+        deck: Deck,
+    ) -> Self {
+        let mut hints = [Hint::none(); 5];
+        for hint in hints.iter_mut().take(rules.hand_size) {
+            *hint = Hint::empty();
+        }
+        Self {
+            player_hand,
+            opponent_hand,
+            player_hints: hints,
+            opponent_hints: hints,
+            deck,
+            discard: CardCollection::empty(),
+            blue_tokens: ClueTokens::new(rules.clue_tokens),
+            black_tokens: StrikeTokens::new(rules.strikes + 1),
+            fireworks: Fireworks::empty(),
+            max_score: rules.perfect_score(),
+            last_round: false,
+            last_round_turns_taken: 0,
+            hint_history: HintHistory::default(),
+            rules,
+            pruning: SearchPruning::default(),
+        }
+    }
+
+    // deals the first 5 cards to the player and the next 5 to the opponent, the rest are drawn in order
+    pub fn from_deck_order(order: &[Card]) -> Self {
+        Self::from_deck_order_with(RuleSet::default(), order)
+    }
+
+    // from_deck_order dealing hands of `rules.hand_size`
+    pub fn from_deck_order_with(rules: RuleSet, order: &[Card]) -> Self {
+        let n = rules.hand_size;
+        let mut player_hand = [Card::none(); 5];
+        let mut opponent_hand = [Card::none(); 5];
+        player_hand[..n].copy_from_slice(&order[0..n]);
+        opponent_hand[..n].copy_from_slice(&order[n..2 * n]);
+        Self::with_hands(
+            rules,
+            player_hand,
+            opponent_hand,
+            Deck::from_order(&order[2 * n..]),
+        )
+    }
+
+    // Env::random under other rules
+    pub fn random_with<R: Rng>(rules: RuleSet, mut rng: &mut R) -> Self {
+        let mut deck = rules.deck();
+        let mut player_hand = [Card::none(); 5];
+        let mut opponent_hand = [Card::none(); 5];
+        for card in player_hand.iter_mut().take(rules.hand_size) {
+            *card = deck.pop(&mut rng);
+        }
+        for card in opponent_hand.iter_mut().take(rules.hand_size) {
+            *card = deck.pop(&mut rng);
+        }
+        Self::with_hands(rules, player_hand, opponent_hand, Deck::Multiset(deck))
+    }
+
     fn discard_at(&mut self, i: usize) {
+        // only the color of the discarded card can lose reachable points
+        let color = self.player_hand[i].color_id();
+        let copies = self.rules.deck();
+        let reach = color_reach(&copies, &self.fireworks, &self.discard, color);
         self.discard.add(self.player_hand[i]);
+        self.max_score -= reach - color_reach(&copies, &self.fireworks, &self.discard, color);
         self.player_hand[i] = Card::none();
         self.player_hints[i] = Hint::none();
     }
 
-    fn draw_into<R: Rng>(&mut self, mut rng: &mut R, i: usize) {
-        let card = self.deck.pop(&mut rng);
+    fn draw_into<F: FnOnce(&mut Deck) -> Card>(&mut self, draw: F, i: usize) {
+        let card = draw(&mut self.deck);
         self.player_hand[i] = card;
         if card.is_some() {
             self.player_hints[i] = Hint::empty();
@@ -485,31 +1410,483 @@ impl HanabiEnv {
     }
 
     pub fn describe(&self) {
-        println!(
-            "Deck=|{}| Discard=|{}| Fireworks={:?} Blue={} Black={} FutureReward={}",
-            self.deck.total,
-            self.discard.total,
-            self.fireworks,
-            self.blue_tokens,
-            self.black_tokens,
-            possible_future_rewards(&self.fireworks, &self.discard),
-        );
-        println!("----- Me -----");
-        println!("{:?}", self.player_hand);
-        println!("{:?}", self.player_hints);
-        println!("----- Op -----");
-        println!("{:?}", self.opponent_hand);
-        println!("{:?}", self.opponent_hints);
+        self.describe_to(&mut std::io::stdout()).unwrap();
     }
 
-    fn hint_matches(&self, hints: &[Hint; 5], hint: &Hint) -> Vec<usize> {
-        hints
+    pub fn describe_to<W: std::io::Write>(&self, out: &mut W) -> std::io::Result<()> {
+        write!(out, "{}", self)
+    }
+
+    pub fn touched_slots(&self, action: &Action) -> Vec<usize> {
+        let touches = |c: &Card| match *action {
+            Action::ColorHint(color) => c.color() == color,
+            Action::SuitHint(suit) => c.suit() == suit,
+            _ => false,
+        };
+        (0..5)
+            .filter(|&i| self.opponent_hand[i].is_some() && touches(&self.opponent_hand[i]))
+            .collect()
+    }
+
+    // the deck plus the player's own hand, the cards sampling deals the player's hand from
+    fn unseen_deck(public_info: &PublicInfo, player_private_info: &PrivateInfo) -> CardCollection {
+        let mut deck = public_info.rules.deck();
+        deck.subtract(&public_info.discard);
+        deck.remove_fireworks(&public_info.fireworks);
+        deck.remove_hand(&player_private_info.opponent_hand);
+        deck
+    }
+
+    // the mover's hand as the partner sees it, sampled like Env::sample_private_infos, and how many
+    // times sampling had to start over
+    pub fn sample_counting_restarts<R: Rng>(
+        public_info: &PublicInfo,
+        player_private_info: &PrivateInfo,
+        mut rng: &mut R,
+    ) -> (PrivateInfo, f32, usize) {
+        let mut deck = Self::unseen_deck(public_info, player_private_info);
+        let (player_hand, log_prob, restarts) =
+            determinize_hints(&mut deck, &public_info.player_hints, &mut rng)
+                .unwrap_or_else(|e| panic!("{}", e));
+        (
+            PrivateInfo {
+                opponent_hand: player_hand,
+            },
+            log_prob,
+            restarts,
+        )
+    }
+
+    // same as Env::determinize, but also returns how many times sampling had to start over
+    pub fn determinize_counting_restarts<R: Rng>(
+        public_info: &PublicInfo,
+        player_private_info: &PrivateInfo,
+        rng: &mut R,
+    ) -> (Self, f32, usize) {
+        // what's left after dealing the player's hand is the deck, so it isn't worked out again
+        let mut deck = Self::unseen_deck(public_info, player_private_info);
+        let (player_hand, log_prob, restarts) =
+            determinize_hints(&mut deck, &public_info.player_hints, rng)
+                .unwrap_or_else(|e| panic!("{}", e));
+        let env = Self {
+            player_hand,
+            player_hints: public_info.player_hints,
+            opponent_hand: player_private_info.opponent_hand,
+            opponent_hints: public_info.opponent_hints,
+            deck: Deck::Multiset(deck),
+            discard: public_info.discard,
+            blue_tokens: public_info.blue_tokens,
+            black_tokens: public_info.black_tokens,
+            fireworks: public_info.fireworks,
+            max_score: public_info.max_score,
+            last_round: public_info.last_round,
+            last_round_turns_taken: public_info.last_round_turns_taken,
+            hint_history: public_info.hint_history.clone(),
+            rules: public_info.rules,
+            pruning: public_info.pruning,
+        };
+        (env, log_prob, restarts)
+    }
+
+    // the same actions in the same order as Env::actions, written into `actions`
+    pub fn actions_into(&self, actions: &mut ActionList) {
+        self.actions_into_with(actions, false);
+    }
+
+    // with `collapse` plays & discards only act on the first slot of each distinct hint
+    pub fn actions_into_with(&self, actions: &mut ActionList, collapse: bool) {
+        actions.clear();
+
+        for i in 0..5 {
+            if !self.player_hand[i].is_some() {
+                continue;
+            }
+            if !collapse || self.first_alike(i) == i {
+                actions.push(Action::Play(i));
+                if self.blue_tokens < self.rules.clue_tokens {
+                    actions.push(Action::Discard(i));
+                }
+            }
+        }
+
+        if self.blue_tokens > 0 {
+            let (mut colors, mut suits) = (0u8, 0u8);
+            for card in self.opponent_hand.iter().filter(|c| c.is_some()) {
+                colors |= 1 << card.color_id();
+                suits |= 1 << card.suit_id();
+            }
+            for (i, &color) in COLORS.iter().enumerate() {
+                if colors & (1 << i) != 0 && self.rules.allows_clue(&Action::ColorHint(color)) {
+                    actions.push(Action::ColorHint(color));
+                }
+            }
+            for (i, &suit) in SUITS.iter().enumerate() {
+                if suits & (1 << i) != 0 {
+                    actions.push(Action::SuitHint(suit));
+                }
+            }
+        }
+    }
+
+    // bits `clue` tells the partner about their hand as the mover reckons it. the mover's own
+    // cards are left out, so every determinization of their information set prunes alike
+    pub fn hint_info_gain(&self, clue: &Action) -> f32 {
+        hint_info_gain(
+            &self.public_info(),
+            &self.private_info(self.current_player()),
+            clue,
+        )
+    }
+
+    // drops the clues in `actions` telling the partner less than `min_bits`. when that's every
+    // clue the most informative one stays, so there's still a way to stall
+    fn prune_uninformative_hints(&self, actions: &mut Vec<Action>, min_bits: f32) {
+        let is_clue = |a: &Action| matches!(a, Action::ColorHint(_) | Action::SuitHint(_));
+        let gains: Vec<(Action, f32)> = actions
             .iter()
-            .enumerate()
-            .filter(|&(_, h)| h == hint)
-            .map(|(i, _)| i)
+            .filter(|a| is_clue(a))
+            .map(|a| (*a, self.hint_info_gain(a)))
+            .collect();
+        let best = gains
+            .iter()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|g| g.0);
+        actions.retain(|a| {
+            !is_clue(a) || Some(*a) == best || gains.iter().any(|g| g.0 == *a && g.1 >= min_bits)
+        });
+    }
+
+    // the slots the player knows hold trash, a playable card, a card that can't be played yet
+    // and a critical card, from their hints & the cards they can't see
+    pub fn known_slots(&self) -> KnownSlots {
+        let mut unseen = self.rules.deck();
+        unseen.subtract(&self.discard);
+        unseen.remove_fireworks(&self.fireworks);
+        unseen.remove_hand(&self.opponent_hand);
+        let mut known = KnownSlots::default();
+        let (rules, fireworks, discard) = (&self.rules, &self.fireworks, &self.discard);
+        for i in 0..5 {
+            let mask = self.player_hints[i].card_mask();
+            let possible =
+                (0..25u8).filter(|&id| mask & 1 << id != 0 && unseen.counts[id as usize] > 0);
+            let (mut all_trash, mut all_playable, mut any) = (true, true, false);
+            let (mut all_unplayable, mut all_critical) = (true, true);
+            for card in possible.map(Card::from_id) {
+                any = true;
+                all_trash &= is_trash_with(rules, card, fireworks, discard);
+                all_playable &= fireworks.accepts(card);
+                all_unplayable &= !fireworks.accepts(card);
+                all_critical &= is_critical_with(rules, card, fireworks, discard);
+            }
+            known.trash |= ((any && all_trash) as u8) << i;
+            known.playable |= ((any && all_playable) as u8) << i;
+            known.unplayable |= ((any && all_unplayable) as u8) << i;
+            known.critical |= ((any && all_critical) as u8) << i;
+        }
+        known
+    }
+
+    // the first slot holding a card with the same hint as slot `i`
+    fn first_alike(&self, i: usize) -> usize {
+        let hint = self.player_hints[i];
+        (0..i)
+            .find(|&j| self.player_hand[j].is_some() && self.player_hints[j] == hint)
+            .unwrap_or(i)
+    }
+
+    // the action standing in for `action` among collapsed actions, a play or discard of the
+    // first slot that looks the same
+    pub fn collapsed(&self, action: &Action) -> Action {
+        match *action {
+            Action::Play(i) if i < 5 => Action::Play(self.first_alike(i)),
+            Action::Discard(i) if i < 5 => Action::Discard(self.first_alike(i)),
+            _ => *action,
+        }
+    }
+
+    pub fn action_list(&self) -> ActionList {
+        let mut actions = ActionList::new();
+        self.actions_into(&mut actions);
+        actions
+    }
+
+    // why `action` can't be applied, e.g. when it comes from an import or another program rather
+    // than from `actions`
+    pub fn check_action(&self, action: &Action) -> Result<(), IllegalAction> {
+        if self.is_over() {
+            return Err(IllegalAction::GameOver);
+        }
+        match *action {
+            Action::ColorHint(_) | Action::SuitHint(_) => {
+                if self.blue_tokens == 0 {
+                    return Err(IllegalAction::NoClueTokens(*action));
+                }
+                if !self.rules.allows_clue(action) {
+                    return Err(IllegalAction::ClueNotInVariant(*action));
+                }
+                let cards = self.opponent_hand.iter().filter(|c| c.is_some());
+                match *action {
+                    Action::ColorHint(color) if cards.clone().all(|c| c.color() != color) => {
+                        return Err(IllegalAction::NoCardOfColor(color))
+                    }
+                    Action::SuitHint(suit) if cards.clone().all(|c| c.suit() != suit) => {
+                        return Err(IllegalAction::NoCardOfRank(suit))
+                    }
+                    _ => {}
+                }
+            }
+            Action::Play(i) | Action::Discard(i) => {
+                if i >= 5 || !self.player_hand[i].is_some() {
+                    return Err(IllegalAction::EmptySlot(*action));
+                }
+                if let Action::Discard(_) = action {
+                    if self.blue_tokens >= self.rules.clue_tokens {
+                        return Err(IllegalAction::DiscardAtMaxTokens);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // checked version of Env::step, which assumes the action came from `actions` so rollouts
+    // don't pay for the checks
+    pub fn try_step<R: Rng>(&mut self, action: &Action, rng: &mut R) -> Result<(), IllegalAction> {
+        self.check_action(action)?;
+        self.step(action, rng);
+        Ok(())
+    }
+
+    fn spend_clue_token(&mut self) {
+        self.blue_tokens = self
+            .blue_tokens
+            .spend()
+            .expect("gave a clue with no clue tokens left, see HanabiEnv::try_step");
+    }
+
+    // the first half of a step, everything an action does before the card drawn to replace a
+    // played or discarded one is known. nothing changes until `apply_chance`
+    pub fn resolve(&self, action: &Action) -> PendingOutcome {
+        let slot = action.slot();
+        let card = slot.map(|i| self.player_hand[i]);
+        let success = match action {
+            &Action::Play(_) => card.map(|c| self.fireworks.accepts(c)),
+            _ => None,
+        };
+        PendingOutcome {
+            action: *action,
+            slot,
+            card,
+            success,
+        }
+    }
+
+    // the cards the pending draw could be & how many copies of each are left in the deck, empty
+    // when nothing is drawn
+    pub fn draw_counts(&self, outcome: &PendingOutcome) -> Vec<(Card, u8)> {
+        if outcome.slot.is_none() {
+            return Vec::new();
+        }
+        let deck = self.deck.cards();
+        (0..25)
+            .filter(|&id| deck.counts[id] > 0)
+            .map(|id| (Card::from_id(id as u8), deck.counts[id]))
             .collect()
     }
+
+    // the second half of a step, draws the replacement from the deck with `rng`
+    pub fn apply_chance<R: Rng>(&mut self, outcome: &PendingOutcome, mut rng: &mut R) {
+        self.apply_outcome(outcome, |deck| deck.pop(&mut rng));
+    }
+
+    // Env::step, returning what `undo` needs to put the env back exactly as it was: the action,
+    // the card it moved & the card drawn, and the counters & hints it changed
+    pub fn step_with_undo<R: Rng>(&mut self, action: &Action, rng: &mut R) -> UndoToken {
+        let outcome = self.resolve(action);
+        let mut token = UndoToken {
+            action: *action,
+            card: outcome.card.unwrap_or(Card::none()),
+            played: outcome.success == Some(true),
+            drawn: None,
+            hints: match outcome.slot {
+                Some(_) => self.player_hints,
+                None => self.opponent_hints,
+            },
+            blue_tokens: self.blue_tokens,
+            black_tokens: self.black_tokens,
+            max_score: self.max_score,
+            last_round: self.last_round,
+            last_round_turns_taken: self.last_round_turns_taken,
+            forgotten: (0, 0),
+        };
+        let remaining = self.deck.remaining();
+        token.forgotten = self.apply_outcome(&outcome, |deck| deck.pop(rng));
+        // the mover's hand is the partner's now
+        if let (Some(i), true) = (outcome.slot, self.deck.remaining() < remaining) {
+            token.drawn = Some(self.opponent_hand[i]);
+        }
+        token
+    }
+
+    // takes back the step `token` came from, which must be the last one taken
+    pub fn undo(&mut self, token: UndoToken) {
+        std::mem::swap(&mut self.player_hand, &mut self.opponent_hand);
+        std::mem::swap(&mut self.player_hints, &mut self.opponent_hints);
+        self.hint_history.turn -= 1;
+        match token.action {
+            Action::ColorHint(_) | Action::SuitHint(_) => {
+                self.hint_history.unrecord();
+                self.opponent_hints = token.hints;
+            }
+            Action::Play(i) | Action::Discard(i) => {
+                if let Some(card) = token.drawn {
+                    match &mut self.deck {
+                        Deck::Multiset(cards) => cards.add(card),
+                        Deck::Ordered(cards) => cards.push(card),
+                    }
+                }
+                if token.played {
+                    self.fireworks.0[token.card.color_id() as usize] -= 1;
+                } else {
+                    self.discard.remove(token.card);
+                }
+                self.player_hand[i] = token.card;
+                self.player_hints = token.hints;
+                self.hint_history.remember_slot(i, token.forgotten);
+            }
+        }
+        self.blue_tokens = token.blue_tokens;
+        self.black_tokens = token.black_tokens;
+        self.max_score = token.max_score;
+        self.last_round = token.last_round;
+        self.last_round_turns_taken = token.last_round_turns_taken;
+    }
+
+    // the second half of a step with the drawn card known, e.g. from a replay or a test. fails
+    // without changing anything when `drawn` isn't in the deck. `drawn` is ignored for clues and
+    // should be Card::none() once the deck is empty
+    pub fn apply_chance_with(
+        &mut self,
+        outcome: &PendingOutcome,
+        drawn: Card,
+    ) -> Result<(), String> {
+        let drawn = match outcome.slot {
+            Some(_) if drawn.is_some() => Some(self.deck.take(drawn)?),
+            Some(_) if self.deck.remaining() > 0 => {
+                return Err("the deck isn't empty, a card must be drawn".to_string())
+            }
+            _ => None,
+        };
+        self.apply_outcome(outcome, |_| drawn.unwrap_or(Card::none()));
+        Ok(())
+    }
+
+    // returns what forgetting the played or discarded slot took out of the hint history
+    fn apply_outcome<F: FnOnce(&mut Deck) -> Card>(
+        &mut self,
+        outcome: &PendingOutcome,
+        draw: F,
+    ) -> (u128, u128) {
+        let action = &outcome.action;
+        let i = outcome.slot.unwrap_or(0);
+        let forgotten = match action {
+            &Action::ColorHint(_) | &Action::SuitHint(_) => {
+                let mask = |slots: Vec<usize>| slots.iter().fold(0, |m, &i| m | 1 << i);
+                let held = (0..5)
+                    .filter(|&i| self.opponent_hand[i].is_some())
+                    .collect();
+                self.hint_history
+                    .record(*action, mask(self.touched_slots(action)), mask(held));
+                apply_clue(&self.opponent_hand, &mut self.opponent_hints, action);
+                self.spend_clue_token();
+                (0, 0)
+            }
+            &Action::Play(_) => {
+                let card = self.player_hand[i];
+
+                if self.fireworks.accepts(card) {
+                    self.fireworks.add_card(card);
+                    if self.fireworks.0[card.color_id() as usize] == self.rules.ranks {
+                        self.blue_tokens = self.blue_tokens.gain(self.rules.clue_tokens);
+                    }
+                } else {
+                    self.discard_at(i);
+                    self.black_tokens = self
+                        .black_tokens
+                        .spend()
+                        .expect("misplayed with no black tokens left, see HanabiEnv::try_step");
+                }
+                self.draw_into(draw, i);
+                self.hint_history.forget_slot(i)
+            }
+            &Action::Discard(_) => {
+                self.discard_at(i);
+                self.draw_into(draw, i);
+                self.blue_tokens = self.blue_tokens.gain(self.rules.clue_tokens);
+                self.hint_history.forget_slot(i)
+            }
+        };
+        self.hint_history.turn += 1;
+
+        if self.last_round {
+            self.last_round_turns_taken += 1;
+        }
+
+        std::mem::swap(&mut self.player_hand, &mut self.opponent_hand);
+        std::mem::swap(&mut self.player_hints, &mut self.opponent_hints);
+        forgotten
+    }
+}
+
+impl PublicInfo {
+    // the seats at the table, see Env::num_players
+    pub fn num_players(&self) -> usize {
+        2
+    }
+
+    // the seat to move, see Env::current_player
+    pub fn current_player(&self) -> usize {
+        self.hint_history.turn as usize % self.num_players()
+    }
+
+    // the private infos by seat, as Env::new takes them, from the mover's & the partner's
+    pub fn by_seat(&self, player: PrivateInfo, opponent: PrivateInfo) -> Vec<PrivateInfo> {
+        match self.current_player() {
+            0 => vec![player, opponent],
+            _ => vec![opponent, player],
+        }
+    }
+
+    // the player's & partner's hints worked out again from the hint history, positive and
+    // negative information alike. None once a clue that still applies has dropped out of it
+    pub fn hints_from_history(&self) -> Option<([Hint; 5], [Hint; 5])> {
+        if self.hint_history.lost {
+            return None;
+        }
+        let present = |hints: &[Hint; 5]| {
+            let mut present = [false; 5];
+            for i in 0..5 {
+                present[i] = hints[i].is_some();
+            }
+            present
+        };
+        Some((
+            self.hint_history
+                .knowledge(true, &present(&self.player_hints)),
+            self.hint_history
+                .knowledge(false, &present(&self.opponent_hints)),
+        ))
+    }
+
+    pub fn deck_total(&self) -> u8 {
+        let num_player_cards = self.player_hints.iter().filter(|h| h.is_some()).count() as u8;
+        let num_opponent_cards = self.opponent_hints.iter().filter(|h| h.is_some()).count() as u8;
+        self.rules.deck_size()
+            - self.discard.total
+            - self.fireworks.total()
+            - num_player_cards
+            - num_opponent_cards
+    }
 }
 
 impl HasEnd for PublicInfo {
@@ -518,10 +1895,11 @@ impl HasEnd for PublicInfo {
         let num_opponent_cards = self.opponent_hints.iter().filter(|h| h.is_some()).count() as u8;
         let num_fireworks = self.fireworks.total();
         self.black_tokens == 1
-            || num_fireworks == 25
-            || (self.discard.total + num_player_cards + num_opponent_cards + num_fireworks == 50
+            || num_fireworks == self.rules.perfect_score()
+            || (self.discard.total + num_player_cards + num_opponent_cards + num_fireworks
+                == self.rules.deck_size()
                 && self.last_round
-                && self.last_round_turns_taken == 2)
+                && self.last_round_turns_taken == self.rules.final_turns)
     }
 }
 
@@ -534,31 +1912,231 @@ impl HasEnd for HanabiEnv {
     }
 }
 
-fn possible_future_rewards(fireworks: &Fireworks, discard: &CardCollection) -> u8 {
-    let mut cards_in_play = CardCollection::starting_deck();
-    cards_in_play.subtract(discard);
+// the level a color can still reach, stopping below the first card with every copy discarded
+fn color_reach(
+    copies: &CardCollection,
+    fireworks: &Fireworks,
+    discard: &CardCollection,
+    color: u8,
+) -> u8 {
+    let mut level = fireworks.0[color as usize];
+    while level < 5 {
+        let id = Card::parts_id(color, level) as usize;
+        if discard.counts[id] == copies.counts[id] {
+            break;
+        }
+        level += 1;
+    }
+    level
+}
+
+fn possible_future_rewards(
+    copies: &CardCollection,
+    fireworks: &Fireworks,
+    discard: &CardCollection,
+) -> u8 {
+    (0..5)
+        .map(|color| color_reach(copies, fireworks, discard, color) - fireworks.0[color as usize])
+        .sum()
+}
+
+// what HanabiEnv::max_score tracks, from scratch
+pub fn max_score(fireworks: &Fireworks, discard: &CardCollection) -> u8 {
+    max_score_with(&RuleSet::default(), fireworks, discard)
+}
+
+pub fn max_score_with(rules: &RuleSet, fireworks: &Fireworks, discard: &CardCollection) -> u8 {
+    fireworks.total() + possible_future_rewards(&rules.deck(), fireworks, discard)
+}
+
+// splitmix64 of (feature, value) stands in for a table of random zobrist keys
+pub fn zobrist_key(feature: u64, value: u64) -> u64 {
+    let mut z = (feature << 8 | value).wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
 
-    let mut future_rewards = 0;
-    for color in 0..5 {
-        let played_suit = fireworks.0[color];
-        for suit in played_suit..5 {
-            if cards_in_play.counts[Card::parts_id(color as u8, suit as u8) as usize] == 0 {
-                break;
+impl HasKey for PublicInfo {
+    fn key(&self) -> u64 {
+        let mut key = 0;
+        for i in 0..5 {
+            let player_hint = self.player_hints[i];
+            let opponent_hint = self.opponent_hints[i];
+            key ^= zobrist_key(i as u64, player_hint.color_mask() as u64);
+            key ^= zobrist_key(5 + i as u64, player_hint.suit_mask() as u64);
+            key ^= zobrist_key(10 + i as u64, opponent_hint.color_mask() as u64);
+            key ^= zobrist_key(15 + i as u64, opponent_hint.suit_mask() as u64);
+            key ^= zobrist_key(20 + i as u64, self.fireworks.0[i] as u64);
+        }
+        for i in 0..25 {
+            key ^= zobrist_key(25 + i as u64, self.discard.counts[i] as u64);
+        }
+        key ^= zobrist_key(50, self.blue_tokens.get() as u64);
+        key ^= zobrist_key(51, self.black_tokens.get() as u64);
+        key ^= zobrist_key(52, self.last_round as u64);
+        key ^= zobrist_key(53, self.last_round_turns_taken as u64);
+        // the hints are the mover's & the partner's, the same hints with the seats swapped are
+        // another state
+        key ^= zobrist_key(60, self.current_player() as u64);
+        key
+    }
+}
+
+// which terms of the shaped reward are used. the future reward term is scaled by the token term,
+// with only one of the two on it is added by itself
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RewardShaping {
+    pub score: bool,
+    pub future: bool,
+    pub tokens: bool,
+}
+
+impl RewardShaping {
+    pub fn full() -> Self {
+        Self {
+            score: true,
+            future: true,
+            tokens: true,
+        }
+    }
+}
+
+// what rollouts score once the searched position is this close to the end. with only a few cards
+// left to draw the future reward term counts cards there won't be turns to play, so the raw
+// score, or just whether the game is perfect, is the better target
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EndgameReward {
+    Score,
+    Perfect,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RewardSwitch {
+    // switch when the deck has at most this many cards
+    pub deck_size: u8,
+    pub reward: EndgameReward,
+}
+
+// what rollouts switch to without a `--reward-switch`: the final score once the deck is down to
+// five cards. the score ties the shaped reward wherever both apply, so it costs nothing where the
+// shaping is still right
+impl Default for RewardSwitch {
+    fn default() -> Self {
+        Self {
+            deck_size: 5,
+            reward: EndgameReward::Score,
+        }
+    }
+}
+
+// how much of the score the perfect endgame reward adds. a perfect game is still worth more than
+// any other, but moves that never reach one aren't all worth 0, which left the search with
+// nothing to rank them by and it took the first
+pub const PERFECT_TIE_BREAK: f32 = 0.1;
+
+impl RewardSwitch {
+    // "score:<deck size>" or "perfect:<deck size>"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.split(':');
+        let reward = match parts.next() {
+            Some("score") => EndgameReward::Score,
+            Some("perfect") => EndgameReward::Perfect,
+            _ => {
+                return Err(format!(
+                    "invalid reward switch '{}', expected score:<n> or perfect:<n>",
+                    text
+                ))
+            }
+        };
+        match (parts.next().map(|n| n.parse::<u8>()), parts.next()) {
+            (Some(Ok(deck_size)), None) => Ok(Self { deck_size, reward }),
+            _ => Err(format!(
+                "invalid reward switch '{}', expected score:<n> or perfect:<n>",
+                text
+            )),
+        }
+    }
+
+    pub fn applies(&self, public_info: &PublicInfo) -> bool {
+        public_info.deck_total() <= self.deck_size
+    }
+}
+
+// what a finished game is worth to the searcher, as a function of its final score. the expected
+// score is what every searcher maximizes by default, a target makes it maximize the chance of
+// reaching the target instead, e.g. of a perfect game
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Utility {
+    Expected,
+    AtLeast(u8),
+}
+
+impl Utility {
+    // "expected" or "at-least:<score>"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.splitn(2, ':');
+        match (parts.next(), parts.next().map(|n| n.parse::<u8>())) {
+            (Some("expected"), None) => Ok(Utility::Expected),
+            (Some("at-least"), Some(Ok(target))) => Ok(Utility::AtLeast(target)),
+            _ => Err(format!(
+                "invalid utility '{}', expected expected or at-least:<score>",
+                text
+            )),
+        }
+    }
+
+    // on the same 0..1 scale as the rewards
+    pub fn of(&self, score: u8, perfect_score: u8) -> f32 {
+        match *self {
+            Utility::Expected => score as f32 / perfect_score as f32,
+            Utility::AtLeast(target) => (score >= target) as u8 as f32,
+        }
+    }
+}
+
+impl PublicInfo {
+    // rewards are scaled by this so a perfect game scores 1 under any rules
+    fn perfect_score(&self) -> f32 {
+        self.rules.perfect_score() as f32
+    }
+
+    pub fn endgame_reward(&self, reward: EndgameReward) -> f32 {
+        match reward {
+            EndgameReward::Score => self.fireworks.total() as f32 / self.perfect_score(),
+            EndgameReward::Perfect => {
+                (self.fireworks.total() == self.rules.perfect_score()) as u8 as f32
+                    + PERFECT_TIE_BREAK * self.endgame_reward(EndgameReward::Score)
             }
-            future_rewards += 1;
         }
     }
-    future_rewards
+
+    pub fn utility(&self, utility: Utility) -> f32 {
+        utility.of(self.fireworks.total(), self.rules.perfect_score())
+    }
+
+    pub fn shaped_reward(&self, shaping: &RewardShaping) -> f32 {
+        let reward = if shaping.score {
+            (self.fireworks.total() as f32) / self.perfect_score()
+        } else {
+            0.0
+        };
+        let black_tokens = (self.black_tokens.get() as f32 - 1.0) / self.rules.strikes as f32;
+        let future_reward = (self.max_score - self.fireworks.total()) as f32 / self.perfect_score();
+        match (shaping.future, shaping.tokens) {
+            (true, true) => reward + black_tokens * future_reward,
+            (true, false) => reward + future_reward,
+            (false, true) => reward + black_tokens,
+            (false, false) => reward,
+        }
+    }
 }
 
 impl HasReward for PublicInfo {
     type Reward = f32;
 
     fn reward(&self) -> Self::Reward {
-        let reward = (self.fireworks.total() as f32) / 25.0;
-        let black_tokens = (self.black_tokens as f32 - 1.0) / 3.0;
-        let future_reward = possible_future_rewards(&self.fireworks, &self.discard) as f32 / 25.0;
-        reward + black_tokens * future_reward
+        self.shaped_reward(&RewardShaping::full())
     }
 }
 
@@ -570,17 +2148,20 @@ impl HasReward for HanabiEnv {
     }
 }
 
-impl Env for HanabiEnv {
-    type PublicInfo = PublicInfo;
-    type PrivateInfo = PrivateInfo;
-    type Action = Action;
-
-    fn new(
-        public_info: &Self::PublicInfo,
-        player_private_info: &Self::PrivateInfo,
-        opponent_private_info: &Self::PrivateInfo,
-    ) -> Self {
-        let mut deck = CardCollection::starting_deck();
+impl Env for HanabiEnv {
+    type PublicInfo = PublicInfo;
+    type PrivateInfo = PrivateInfo;
+    type Action = Action;
+    type Outcome = PendingOutcome;
+    type Chance = Card;
+
+    fn new(public_info: &Self::PublicInfo, private_infos: &[Self::PrivateInfo]) -> Self {
+        // the env holds the mover's hand & their partner's, the 3-5 player game isn't in it
+        assert_eq!(private_infos.len(), 2, "HanabiEnv seats exactly 2 players");
+        let mover = public_info.current_player();
+        let player_private_info = &private_infos[mover];
+        let opponent_private_info = &private_infos[1 - mover];
+        let mut deck = public_info.rules.deck();
         deck.subtract(&public_info.discard);
         deck.remove_fireworks(&public_info.fireworks);
         deck.remove_hand(&player_private_info.opponent_hand);
@@ -590,83 +2171,95 @@ impl Env for HanabiEnv {
             player_hints: public_info.player_hints,
             opponent_hand: player_private_info.opponent_hand,
             opponent_hints: public_info.opponent_hints,
-            deck: deck,
+            deck: Deck::Multiset(deck),
             discard: public_info.discard,
             blue_tokens: public_info.blue_tokens,
             black_tokens: public_info.black_tokens,
             fireworks: public_info.fireworks,
+            max_score: public_info.max_score,
             last_round: public_info.last_round,
             last_round_turns_taken: public_info.last_round_turns_taken,
+            hint_history: public_info.hint_history.clone(),
+            rules: public_info.rules,
+            pruning: public_info.pruning,
         }
     }
 
-    fn random<R: Rng>(mut rng: &mut R) -> Self {
-        let mut deck = CardCollection::starting_deck();
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        Self::random_with(RuleSet::default(), rng)
+    }
 
-        let player_hand = [
-            deck.pop(&mut rng),
-            deck.pop(&mut rng),
-            deck.pop(&mut rng),
-            deck.pop(&mut rng),
-            deck.pop(&mut rng),
-        ];
-        let opponent_hand = [
-            deck.pop(&mut rng),
-            deck.pop(&mut rng),
-            deck.pop(&mut rng),
-            deck.pop(&mut rng),
-            deck.pop(&mut rng),
-        ];
+    fn sample_private_infos<R: Rng>(
+        public_info: &Self::PublicInfo,
+        player_private_info: &Self::PrivateInfo,
+        rng: &mut R,
+    ) -> (Vec<Self::PrivateInfo>, f32) {
+        let (opponent_info, log_prob, _) =
+            HanabiEnv::sample_counting_restarts(public_info, player_private_info, rng);
+        let private_infos = public_info.by_seat(player_private_info.clone(), opponent_info);
+        (private_infos, log_prob)
+    }
 
-        Self {
-            player_hand: player_hand,
-            opponent_hand: opponent_hand,
-            player_hints: [Hint::empty(); 5],
-            opponent_hints: [Hint::empty(); 5],
-            deck: deck,
-            discard: CardCollection::empty(),
-            blue_tokens: 8,
-            black_tokens: 4,
-            fireworks: Fireworks::empty(),
-            last_round: false,
-            last_round_turns_taken: 0,
-        }
+    fn current_player(&self) -> usize {
+        self.hint_history.turn as usize % 2
     }
 
-    fn sample_opponent_info<R: Rng>(
+    fn determinize<R: Rng>(
         public_info: &Self::PublicInfo,
         player_private_info: &Self::PrivateInfo,
-        mut rng: &mut R,
-    ) -> (Self::PrivateInfo, f32) {
-        let mut deck = CardCollection::starting_deck();
-        deck.subtract(&public_info.discard);
-        deck.remove_fireworks(&public_info.fireworks);
-        deck.remove_hand(&player_private_info.opponent_hand);
-        let (player_hand, prob) = determinize_hints(&mut deck, &public_info.player_hints, &mut rng);
-        (
-            PrivateInfo {
-                opponent_hand: player_hand,
-            },
-            prob,
-        )
+        rng: &mut R,
+    ) -> (Self, f32) {
+        let (env, log_prob, _) =
+            Self::determinize_counting_restarts(public_info, player_private_info, rng);
+        (env, log_prob)
     }
 
     fn public_info(&self) -> Self::PublicInfo {
         PublicInfo {
             player_hints: self.player_hints,
             opponent_hints: self.opponent_hints,
-            discard: self.discard.clone(),
+            discard: self.discard,
             blue_tokens: self.blue_tokens,
             black_tokens: self.black_tokens,
             fireworks: self.fireworks,
+            max_score: self.max_score,
             last_round: self.last_round,
             last_round_turns_taken: self.last_round_turns_taken,
+            hint_history: self.hint_history.clone(),
+            rules: self.rules,
+            pruning: self.pruning,
         }
     }
 
-    fn private_info(&self, player_perspective: bool) -> Self::PrivateInfo {
+    // the partner's hand. with the public key it tells the mover's information sets apart, so its
+    // features come after every one the public key & the abstraction use
+    fn view_key(private_info: &PrivateInfo) -> u64 {
+        (0..5).fold(0, |key, i| {
+            key ^ zobrist_key(55 + i as u64, private_info.opponent_hand[i].id() as u64)
+        })
+    }
+
+    // the colors are ordered by what the mover sees of them in the partner's hand too, a clue
+    // of red isn't a clue of blue when the partner holds a red card and no blue one
+    fn canonical_view(
+        public_info: &PublicInfo,
+        private_info: &PrivateInfo,
+    ) -> (ColorPermutation, u64) {
+        let symmetry = canonical_symmetry_seeing(public_info, &private_info.opponent_hand);
+        (
+            symmetry,
+            Self::view_key(&symmetry.private_info(private_info)),
+        )
+    }
+
+    fn private_info(&self, player: usize) -> Self::PrivateInfo {
+        assert!(
+            player < 2,
+            "HanabiEnv seats exactly 2 players, not seat {}",
+            player
+        );
         PrivateInfo {
-            opponent_hand: if player_perspective {
+            opponent_hand: if player == self.current_player() {
                 self.opponent_hand
             } else {
                 self.player_hand
@@ -675,210 +2268,903 @@ impl Env for HanabiEnv {
     }
 
     fn actions(&self) -> Vec<Self::Action> {
-        let mut actions = Vec::new();
+        self.action_list().to_vec()
+    }
 
-        // play & discard actions
-        for i in 0..5 {
-            if self.player_hand[i].is_some() {
-                let play = Action::Play(self.player_hints[i]);
-                let discard = Action::Discard(self.player_hints[i]);
-                if actions.iter().position(|&a| a == play).is_none() {
-                    actions.push(play);
-                }
-                if self.blue_tokens < 8 && actions.iter().position(|&a| a == discard).is_none() {
-                    actions.push(discard);
-                }
-            }
+    fn random_action<R: Rng>(&self, rng: &mut R) -> Self::Action {
+        *self.action_list().choose(rng).unwrap()
+    }
+
+    fn search_actions(&self) -> Vec<Self::Action> {
+        let rules = self.pruning.dominance;
+        let min_info = self.pruning.min_hint_info;
+        let mut actions = ActionList::new();
+        self.actions_into_with(&mut actions, self.pruning.collapse_slots);
+        if rules == DominanceRules::none() && min_info <= 0.0 {
+            return actions.to_vec();
+        }
+        let known = self.known_slots();
+        let mut kept: Vec<Action> = actions
+            .iter()
+            .filter(|a| !rules.prunes(a, &known))
+            .cloned()
+            .collect();
+        if min_info > 0.0 {
+            self.prune_uninformative_hints(&mut kept, min_info);
         }
+        // with every card known trash & tokens full there's nothing else to do
+        match kept.is_empty() {
+            true => actions.to_vec(),
+            false => kept,
+        }
+    }
 
-        if self.blue_tokens > 0 {
-            // color hint actions
-            for &color in COLORS.iter() {
-                let num_of_color = self
-                    .opponent_hand
-                    .iter()
-                    .filter(|c| c.is_some() && c.color() == color)
-                    .count();
-                if num_of_color > 0 {
-                    actions.push(Action::ColorHint(color));
-                }
+    fn step<R: Rng>(&mut self, action: &Self::Action, rng: &mut R) {
+        let outcome = self.resolve(action);
+        self.apply_chance(&outcome, rng);
+    }
+
+    fn apply_action(&self, action: &Self::Action) -> Self::Outcome {
+        self.resolve(action)
+    }
+
+    fn chance_outcomes(&self, outcome: &Self::Outcome) -> Vec<(Self::Chance, f32)> {
+        let remaining = self.deck.remaining() as f32;
+        self.draw_counts(outcome)
+            .into_iter()
+            .map(|(card, count)| (card, count as f32 / remaining))
+            .collect()
+    }
+
+    fn resolve_chance<R: Rng>(&mut self, outcome: &Self::Outcome, rng: &mut R) {
+        self.apply_chance(outcome, rng);
+    }
+
+    fn resolve_chance_with(
+        &mut self,
+        outcome: &Self::Outcome,
+        chance: &Self::Chance,
+    ) -> Result<(), String> {
+        self.apply_chance_with(outcome, *chance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::chance_expectation;
+    use crate::rand::prelude::SliceRandom;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+    #[test]
+    fn test_reward_shaping() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.fireworks.0[Color::Red as usize] = 2;
+        env.discard.add(Card::new(Color::White, Suit::Five));
+        env.max_score = max_score(&env.fireworks, &env.discard);
+        env.black_tokens = StrikeTokens::new(3);
+        let public_info = env.public_info();
+
+        assert_eq!(
+            public_info.shaped_reward(&RewardShaping::full()),
+            public_info.reward()
+        );
+        let off = RewardShaping {
+            score: false,
+            future: false,
+            tokens: false,
+        };
+        assert_eq!(public_info.shaped_reward(&off), 0.0);
+        let score_only = RewardShaping { score: true, ..off };
+        assert_eq!(public_info.shaped_reward(&score_only), 2.0 / 25.0);
+        let future_only = RewardShaping {
+            future: true,
+            ..off
+        };
+        assert_eq!(public_info.shaped_reward(&future_only), 22.0 / 25.0);
+        let tokens_only = RewardShaping {
+            tokens: true,
+            ..off
+        };
+        assert_eq!(public_info.shaped_reward(&tokens_only), 2.0 / 3.0);
+    }
+
+    #[test]
+    fn test_max_score_tracks_discards() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut env = HanabiEnv::random(&mut rng);
+            while !env.is_over() {
+                let action = env.random_action(&mut rng);
+                env.step(&action, &mut rng);
+                assert_eq!(env.max_score, max_score(&env.fireworks, &env.discard));
+                assert_eq!(env.public_info().max_score, env.max_score);
             }
+        }
+    }
 
-            // suit hint actions
-            for &suit in SUITS.iter() {
-                let num_in_suit = self
-                    .opponent_hand
-                    .iter()
-                    .filter(|c| c.is_some() && c.suit() == suit)
-                    .count();
-                if num_in_suit > 0 {
-                    actions.push(Action::SuitHint(suit));
-                }
+    #[test]
+    fn test_two_phase_step() {
+        // the deck in card order deals W1 W1 W1 W2 W2 & W3 W3 W4 W4 W5, so W5 can't be drawn
+        let mut order = Vec::new();
+        let copies = CardCollection::starting_deck();
+        for id in 0..25 {
+            for _ in 0..copies.counts[id] {
+                order.push(Card::from_id(id as u8));
             }
         }
+        let mut env = HanabiEnv::from_deck_order(&order);
+        env.deck = Deck::Multiset(env.deck.cards());
+        env.blue_tokens = ClueTokens::new(7);
+        let played = env.player_hand[1];
+        let play = Action::Play(1);
+
+        let outcome = env.resolve(&play);
+        assert_eq!(outcome.slot, Some(1));
+        assert_eq!(outcome.card, Some(played));
+        assert_eq!(outcome.success, Some(played.suit_id() == 0));
+        assert_eq!(env.resolve(&Action::SuitHint(Suit::One)).slot, None);
+        let outcomes = env.draw_counts(&outcome);
+        let total: u8 = outcomes.iter().map(|o| o.1).sum();
+        assert_eq!(total, env.deck.remaining());
+
+        // the same rng stream draws the same card either way
+        let mut stepped = env.clone();
+        stepped.step(&play, &mut StdRng::seed_from_u64(9));
+        let mut two_phase = env.clone();
+        two_phase.apply_chance(&outcome, &mut StdRng::seed_from_u64(9));
+        assert_eq!(two_phase.opponent_hand, stepped.opponent_hand);
+        assert_eq!(two_phase.public_info().key(), stepped.public_info().key());
+
+        // forcing the draw
+        let drawn = outcomes[0].0;
+        let mut forced = env.clone();
+        forced.apply_chance_with(&outcome, drawn).unwrap();
+        assert_eq!(forced.opponent_hand[1], drawn);
+        assert_eq!(forced.deck.remaining(), env.deck.remaining() - 1);
+        let missing = Card::new(Color::White, Suit::Five);
+        assert!(!outcomes.iter().any(|o| o.0 == missing));
+        let mut unchanged = env.clone();
+        assert!(unchanged.apply_chance_with(&outcome, missing).is_err());
+        assert_eq!(unchanged.player_hand, env.player_hand);
+        assert!(unchanged.apply_chance_with(&outcome, Card::none()).is_err());
+    }
 
-        actions
+    #[test]
+    fn test_hint_history() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.blue_tokens = ClueTokens::new(7);
+        let clue = Action::ColorHint(env.opponent_hand[2].color());
+        let touched = env.touched_slots(&clue);
+        env.step(&clue, &mut rng);
+
+        // the partner is to move and got the clue
+        let history = env.public_info().hint_history;
+        assert_eq!(history.turn, 1);
+        let event = *history.latest_received().unwrap();
+        assert_eq!((event.turn, event.clue), (0, clue));
+        assert_eq!(event.touched_slots(), touched);
+        assert_eq!(history.touch_on(touched[0]), Some(&event));
+
+        // a clue back the other way is received by the first player instead
+        let back = Action::SuitHint(env.opponent_hand[0].suit());
+        env.step(&back, &mut rng);
+        let history = env.hint_history.clone();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.latest_received().unwrap().clue, back);
+        assert_eq!(history.iter().nth(1).unwrap().clue, clue);
+        assert_eq!(history.turns_ago(history.iter().nth(1).unwrap()), 2);
+
+        // playing a touched card forgets the touch, the clue itself stays
+        env.step(&Action::Discard(touched[0]), &mut rng);
+        env.step(&Action::Play(touched[0]), &mut rng);
+        let history = env.hint_history.clone();
+        let first = history.iter().find(|e| e.clue == clue).unwrap();
+        assert_eq!(first.touched & (1 << touched[0]), 0);
+        assert_eq!(history.len(), 2);
+
+        // every clue is kept however many there are, and a clone records its own
+        let mut env = HanabiEnv::random(&mut rng);
+        for _ in 0..66 {
+            let clue = Action::ColorHint(env.opponent_hand[0].color());
+            env.blue_tokens = ClueTokens::new(8);
+            env.step(&clue, &mut rng);
+        }
+        let before = env.hint_history.clone();
+        env.blue_tokens = ClueTokens::new(8);
+        env.step(&Action::ColorHint(env.opponent_hand[0].color()), &mut rng);
+        assert_eq!(before.len(), 66);
+        assert_eq!(env.hint_history.len(), 67);
+        assert_eq!(env.hint_history.iter().last().unwrap().turn, 0);
+        // the events are out of line, the history doesn't grow the env
+        assert_eq!(std::mem::size_of::<HintHistory>(), 16);
     }
 
-    fn step<R: Rng>(&mut self, action: &Self::Action, mut rng: &mut R) {
-        match action {
-            &Action::ColorHint(color) => {
-                for i in 0..5 {
-                    if self.opponent_hand[i].is_some() {
-                        if self.opponent_hand[i].color() == color {
-                            self.opponent_hints[i].set_true_color(color);
-                        } else {
-                            self.opponent_hints[i].disable_color(color);
+    #[test]
+    fn test_hints_from_history() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (mut checked, mut lost) = (0, 0);
+        for _ in 0..20 {
+            let mut env = HanabiEnv::random(&mut rng);
+            while !env.is_over() {
+                let action = env.random_action(&mut rng);
+                let hand = env.opponent_hand;
+                env.step(&action, &mut rng);
+
+                // a clue rules its color or rank out for every card it didn't touch
+                let hints = env.player_hints;
+                for i in (0..5).filter(|&i| hand[i].is_some()) {
+                    match action {
+                        Action::ColorHint(color) if hand[i].color() != color => {
+                            assert_eq!(hints[i].color_mask() & (1 << color as u8), 0)
+                        }
+                        Action::SuitHint(suit) if hand[i].suit() != suit => {
+                            assert_eq!(hints[i].suit_mask() & (1 << suit as u8), 0)
                         }
+                        _ => {}
                     }
                 }
-                self.blue_tokens -= 1;
-            }
-            &Action::SuitHint(suit) => {
-                for i in 0..5 {
-                    if self.opponent_hand[i].is_some() {
-                        if self.opponent_hand[i].suit() == suit {
-                            self.opponent_hints[i].set_true_suit(suit);
-                        } else {
-                            self.opponent_hints[i].disable_suit(suit);
-                        }
+
+                let public_info = env.public_info();
+                match public_info.hints_from_history() {
+                    Some((player, opponent)) => {
+                        assert_eq!(player, public_info.player_hints);
+                        assert_eq!(opponent, public_info.opponent_hints);
+                        checked += 1;
                     }
+                    None => lost += 1,
                 }
-                self.blue_tokens -= 1;
             }
-            &Action::Play(hint) => {
-                let i = *self
-                    .hint_matches(&self.player_hints, &hint)
-                    .choose(&mut rng)
-                    .unwrap();
+        }
+        assert!(
+            checked > 100 && lost == 0,
+            "{} checked, {} lost",
+            checked,
+            lost
+        );
+    }
 
-                let card = self.player_hand[i];
+    #[test]
+    fn test_rule_set() {
+        assert_eq!(RuleSet::parse(""), Ok(RuleSet::default()));
+        let rules = RuleSet::parse("hand=4,clues=6,strikes=2,copies=2.2.2.2.1").unwrap();
+        assert_eq!(rules.deck_size(), 45);
+        assert!(!rules.black);
+        assert_eq!(rules.deck().counts[0], 2);
+        assert!(RuleSet::parse("hand=6").is_err());
+        assert!(RuleSet::parse("copies=3.2.2.2").is_err());
+        assert!(RuleSet::parse("colors=6").is_err());
 
-                if self.fireworks.accepts(card) {
-                    self.fireworks.add_card(card);
-                    if self.fireworks.is_color_complete(card.color()) {
-                        self.blue_tokens += 1;
-                        if self.blue_tokens > 8 {
-                            self.blue_tokens = 8;
-                        }
-                    }
-                } else {
-                    self.discard_at(i);
-                    self.black_tokens -= 1;
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut env = HanabiEnv::random_with(rules, &mut rng);
+            assert_eq!(env.player_hand[4], Card::none());
+            assert_eq!(env.blue_tokens, 6);
+            assert_eq!(env.black_tokens, 3);
+            let mut turns = 0;
+            while !env.is_over() {
+                assert!(env.blue_tokens <= 6);
+                assert!(env.player_hand[4..].iter().all(|c| !c.is_some()));
+                let action = env.random_action(&mut rng);
+                env.step(&action, &mut rng);
+                turns += 1;
+            }
+            assert!(env.black_tokens >= 1);
+            // with two strikes the quickest loss is two bombs in a row
+            assert!(turns >= 2);
+            assert!(env.public_info().deck_total() == 0 || env.black_tokens == 1);
+
+            // positions keep their rules through a save & load and a determinization
+            let loaded = HanabiEnv::from_json(&env.to_json()).unwrap();
+            assert_eq!(loaded.rules, rules);
+            let (sampled, _) = HanabiEnv::determinize(
+                &env.public_info(),
+                &env.private_info(env.current_player()),
+                &mut rng,
+            );
+            assert_eq!(sampled.rules, rules);
+        }
+    }
+
+    #[test]
+    fn test_black_suit() {
+        let rules = RuleSet::parse("black5").unwrap();
+        assert!(RuleSet::parse("black").is_err());
+        assert!(RuleSet::parse("black6").is_err());
+        let deck = rules.deck();
+        assert_eq!(rules.deck_size(), 45);
+        for suit in SUITS.iter() {
+            assert_eq!(deck.counts[Card::new(BLACK_COLOR, *suit).id() as usize], 1);
+        }
+        let black_clue = Action::ColorHint(BLACK_COLOR);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let mut env = HanabiEnv::random_with(rules, &mut rng);
+            while !env.is_over() {
+                assert!(!env.action_list().contains(&black_clue));
+                if env.opponent_hand.iter().any(|c| c.color() == BLACK_COLOR) {
+                    assert!(env.check_action(&black_clue).is_err());
                 }
-                self.draw_into(&mut rng, i);
+                let action = env.random_action(&mut rng);
+                env.step(&action, &mut rng);
             }
-            &Action::Discard(hint) => {
-                let i = *self
-                    .hint_matches(&self.player_hints, &hint)
-                    .choose(&mut rng)
-                    .unwrap();
-                self.discard_at(i);
-                self.draw_into(&mut rng, i);
-                self.blue_tokens += 1;
+            assert_eq!(
+                env.max_score,
+                max_score_with(&rules, &env.fireworks, &env.discard)
+            );
+        }
+    }
+
+    #[test]
+    fn test_collapse_slots() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.blue_tokens = ClueTokens::new(7);
+        let (mut every, mut collapsed) = (ActionList::new(), ActionList::new());
+        env.actions_into_with(&mut every, false);
+        env.actions_into_with(&mut collapsed, true);
+        for i in 0..5 {
+            assert!(every.contains(&Action::Play(i)));
+            assert!(every.contains(&Action::Discard(i)));
+        }
+        // only the searches collapse, every slot stays legal
+        assert_eq!(env.actions(), every.to_vec());
+        assert!(!env.search_actions().contains(&Action::Discard(1)));
+        // nothing is clued yet, so every slot looks like the first
+        assert!(collapsed.contains(&Action::Play(0)));
+        assert!(!collapsed.contains(&Action::Play(1)));
+        assert_eq!(env.collapsed(&Action::Discard(3)), Action::Discard(0));
+
+        env.player_hints[3] = Hint::from_masks(0b00001, 0b11111);
+        env.actions_into_with(&mut collapsed, true);
+        assert!(collapsed.contains(&Action::Play(3)));
+        assert!(!collapsed.contains(&Action::Play(4)));
+        assert_eq!(env.collapsed(&Action::Play(4)), Action::Play(0));
+        assert_eq!(env.collapsed(&Action::Play(3)), Action::Play(3));
+    }
+
+    #[test]
+    fn test_chance_nodes() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.blue_tokens = ClueTokens::new(5);
+        let discard = Action::Discard(0);
+        let outcome = env.apply_action(&discard);
+        assert_eq!(outcome.slot, Some(0));
+        let chances = Env::chance_outcomes(&env, &outcome);
+        let total: f32 = chances.iter().map(|c| c.1).sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        // the drawn card ends up in the opponent's hand once the hands swap
+        let ones = env.deck.cards().counts.iter().step_by(5).sum::<u8>();
+        let p_one = chance_expectation(&env, &discard, |next: &HanabiEnv| {
+            (next.opponent_hand[0].suit_id() == 0) as u8 as f32
+        });
+        assert!((p_one - ones as f32 / env.deck.remaining() as f32).abs() < 1e-5);
+
+        let clue = *env
+            .actions()
+            .iter()
+            .find(|a| matches!(a, Action::ColorHint(_) | Action::SuitHint(_)))
+            .unwrap();
+        let outcome = env.apply_action(&clue);
+        assert!(Env::chance_outcomes(&env, &outcome).is_empty());
+        let tokens = chance_expectation(&env, &clue, |next: &HanabiEnv| {
+            next.blue_tokens.get() as f32
+        });
+        assert_eq!(tokens, 4.0);
+    }
+
+    #[test]
+    fn test_reward_switch() {
+        let switch = RewardSwitch::parse("perfect:5").unwrap();
+        assert_eq!(switch.reward, EndgameReward::Perfect);
+        assert_eq!(RewardSwitch::parse("score:0").unwrap().deck_size, 0);
+        assert!(RewardSwitch::parse("score").is_err());
+        assert!(RewardSwitch::parse("future:3").is_err());
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        assert!(!switch.applies(&env.public_info()));
+        while env.public_info().deck_total() > 5 {
+            // clues & discards only, so the game can't end on black tokens first
+            let mut action = env.random_action(&mut rng);
+            while let Action::Play(_) = action {
+                action = env.random_action(&mut rng);
             }
+            env.step(&action, &mut rng);
         }
+        let public_info = env.public_info();
+        assert!(switch.applies(&public_info));
+        let score = public_info.fireworks.total() as f32 / 25.0;
+        assert_eq!(public_info.endgame_reward(EndgameReward::Score), score);
+        assert_eq!(
+            public_info.endgame_reward(EndgameReward::Perfect),
+            PERFECT_TIE_BREAK * score
+        );
+        assert!(public_info.endgame_reward(EndgameReward::Perfect) < 1.0);
+    }
 
-        if self.last_round {
-            self.last_round_turns_taken += 1;
+    #[test]
+    fn test_try_step_rejects_illegal_actions() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        let clue = Action::ColorHint(env.opponent_hand[0].color());
+
+        env.blue_tokens = ClueTokens::new(0);
+        let before = env.to_json();
+        assert_eq!(
+            env.try_step(&clue, &mut rng),
+            Err(IllegalAction::NoClueTokens(clue))
+        );
+        assert_eq!(env.to_json(), before);
+
+        env.blue_tokens = ClueTokens::new(8);
+        assert_eq!(
+            env.try_step(&Action::Discard(0), &mut rng),
+            Err(IllegalAction::DiscardAtMaxTokens)
+        );
+        assert_eq!(
+            env.try_step(&Action::Play(5), &mut rng),
+            Err(IllegalAction::EmptySlot(Action::Play(5)))
+        );
+        assert_eq!(
+            env.to_json(),
+            before.replace("\"blue_tokens\": 0", "\"blue_tokens\": 8")
+        );
+
+        let mut colors = env
+            .opponent_hand
+            .iter()
+            .map(|c| c.color() as u8)
+            .collect::<Vec<_>>();
+        colors.sort();
+        if let Some(missing) = (0..5).find(|c| colors.binary_search(c).is_err()) {
+            let untouched = Action::ColorHint(Color::from_id(missing));
+            assert_eq!(
+                env.try_step(&untouched, &mut rng),
+                Err(IllegalAction::NoCardOfColor(Color::from_id(missing)))
+            );
+        }
+        let ranks: Vec<Suit> = env.opponent_hand.iter().map(|c| c.suit()).collect();
+        if let Some(&missing) = SUITS.iter().find(|s| !ranks.contains(s)) {
+            let untouched = Action::SuitHint(missing);
+            assert_eq!(
+                env.try_step(&untouched, &mut rng).unwrap_err().to_string(),
+                format!("{:?} clue touches no cards", missing)
+            );
         }
 
-        std::mem::swap(&mut self.player_hand, &mut self.opponent_hand);
-        std::mem::swap(&mut self.player_hints, &mut self.opponent_hints);
+        assert_eq!(env.try_step(&clue, &mut rng), Ok(()));
+        assert_eq!(env.blue_tokens, 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "no clue tokens left")]
+    fn test_clue_without_tokens_panics() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.blue_tokens = ClueTokens::new(0);
+        let clue = Action::ColorHint(env.opponent_hand[0].color());
+        env.step(&clue, &mut rng);
+    }
+
+    #[test]
+    fn test_token_spending() {
+        assert_eq!(ClueTokens::new(1).spend(), Some(ClueTokens::new(0)));
+        assert_eq!(ClueTokens::new(0).spend(), None);
+        assert_eq!(ClueTokens::new(7).gain(8), ClueTokens::new(8));
+        assert_eq!(ClueTokens::new(8).gain(8), ClueTokens::new(8));
+        assert_eq!(StrikeTokens::new(0).spend(), None);
+        assert!(StrikeTokens::new(2) > 1 && StrikeTokens::new(1) == 1);
+    }
+
+    #[test]
+    fn test_display() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.fireworks.0[Color::Red as usize] = 2;
+        env.discard.add(Card::new(Color::Green, Suit::Four));
+        env.discard.add(Card::new(Color::White, Suit::One));
+        env.max_score = max_score(&env.fireworks, &env.discard);
+
+        let mut out = Vec::new();
+        env.describe_to(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), env.to_string());
+
+        assert_eq!(env.fireworks.to_string(), "W0 R2 B0 Y0 G0 (2/25)");
+        assert_eq!(env.discard.to_string(), "W1 G4");
+        assert_eq!(CardCollection::empty().to_string(), "-");
+        let public_info = env.public_info().to_string();
+        assert!(public_info.contains("Discarded: W1 G4"));
+        assert!(!public_info.contains(&format!("{:?}", env.player_hand)));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::rand::prelude::SliceRandom;
-    use crate::rand::rngs::StdRng;
-    use crate::rand::SeedableRng;
     #[test]
     fn test_future_reward() {
         let mut fireworks = Fireworks::empty();
         let mut discard = CardCollection::empty();
+        let copies = CardCollection::starting_deck();
 
-        assert_eq!(possible_future_rewards(&fireworks, &discard), 25);
+        assert_eq!(possible_future_rewards(&copies, &fireworks, &discard), 25);
 
         discard.add(Card::new(Color::White, Suit::One));
         discard.add(Card::new(Color::White, Suit::One));
         discard.add(Card::new(Color::White, Suit::One));
 
-        assert_eq!(possible_future_rewards(&fireworks, &discard), 20);
+        assert_eq!(possible_future_rewards(&copies, &fireworks, &discard), 20);
 
         discard.add(Card::new(Color::Green, Suit::One));
 
-        assert_eq!(possible_future_rewards(&fireworks, &discard), 20);
+        assert_eq!(possible_future_rewards(&copies, &fireworks, &discard), 20);
 
         discard.add(Card::new(Color::Yellow, Suit::Three));
         discard.add(Card::new(Color::Yellow, Suit::Three));
 
-        assert_eq!(possible_future_rewards(&fireworks, &discard), 17);
+        assert_eq!(possible_future_rewards(&copies, &fireworks, &discard), 17);
 
         discard.add(Card::new(Color::Red, Suit::Five));
 
-        assert_eq!(possible_future_rewards(&fireworks, &discard), 16);
+        assert_eq!(possible_future_rewards(&copies, &fireworks, &discard), 16);
 
         fireworks.add_card(Card::new(Color::Blue, Suit::One));
         fireworks.add_card(Card::new(Color::Blue, Suit::Two));
 
-        assert_eq!(possible_future_rewards(&fireworks, &discard), 14);
+        assert_eq!(possible_future_rewards(&copies, &fireworks, &discard), 14);
+    }
+
+    #[test]
+    fn test_ordered_deck() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let order = match Deck::shuffled(&mut rng) {
+            Deck::Ordered(cards) => cards.into_iter().rev().collect::<Vec<Card>>(),
+            Deck::Multiset(_) => unreachable!(),
+        };
+
+        let mut env = HanabiEnv::from_deck_order(&order);
+        assert_eq!(env.player_hand[..], order[0..5]);
+        assert_eq!(env.opponent_hand[..], order[5..10]);
+        assert_eq!(env.deck.remaining(), 40);
+        assert_eq!(env.deck.peek_known(), Some(order[10]));
+
+        env.step(&Action::Play(0), &mut rng);
+        assert_eq!(env.deck.remaining(), 39);
+        assert!(env.opponent_hand.contains(&order[10]));
+        assert_eq!(env.deck.peek_known(), Some(order[11]));
+
+        let mut deck = CardCollection::empty();
+        deck.add(Card::new(Color::Red, Suit::Two));
+        deck.add(Card::new(Color::Red, Suit::Two));
+        assert_eq!(
+            Deck::Multiset(deck).peek_known(),
+            Some(Card::new(Color::Red, Suit::Two))
+        );
+        deck.add(Card::new(Color::Red, Suit::Three));
+        assert_eq!(Deck::Multiset(deck).peek_known(), None);
+    }
+
+    #[test]
+    fn test_hint_card_mask() {
+        let mut hint = Hint::empty();
+        assert_eq!(hint.card_mask(), ALL_CARDS);
+        assert_eq!(Hint::none().card_mask(), 0);
+        hint.set_true_color(Color::Blue);
+        hint.disable_suit(Suit::One);
+        assert_eq!(hint.color_mask(), 0b00100);
+        assert_eq!(hint.suit_mask(), 0b11110);
+        for id in 0..25 {
+            let card = Card::from_id(id);
+            assert_eq!(hint.card_mask() & 1 << id != 0, hint.matches(card));
+        }
+        hint.set_true_suit(Suit::Three);
+        assert_eq!(
+            hint.card_mask(),
+            1 << Card::new(Color::Blue, Suit::Three).id()
+        );
+
+        let deck = CardCollection::starting_deck();
+        assert_eq!(deck.card_mask(), ALL_CARDS);
+        assert_eq!(deck.count_in(ALL_CARDS), 50);
+        assert_eq!(
+            deck.count_in(Hint::from_masks(0b00010, 0b11111).card_mask()),
+            10
+        );
+        assert_eq!(
+            deck.count_in(Hint::from_masks(0b11111, 0b00001).card_mask()),
+            15
+        );
+    }
+
+    #[test]
+    fn test_action_list() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut actions = ActionList::new();
+        for _ in 0..20 {
+            let mut env = HanabiEnv::random(&mut rng);
+            while !env.is_over() {
+                env.actions_into(&mut actions);
+                for (i, action) in actions.iter().enumerate() {
+                    assert!(!actions[..i].contains(action), "{:?} twice", action);
+                    assert!(env.check_action(action).is_ok());
+                }
+                for i in (0..5).filter(|&i| env.player_hand[i].is_some()) {
+                    assert!(actions.contains(&Action::Play(i)));
+                }
+                for card in env.opponent_hand.iter().filter(|c| c.is_some()) {
+                    let clue = Action::ColorHint(card.color());
+                    assert_eq!(actions.contains(&clue), env.blue_tokens > 0);
+                }
+
+                // the same draws pick the same action from the list and from Env::actions
+                let mut a = rng.clone();
+                let mut b = rng.clone();
+                assert_eq!(
+                    env.random_action(&mut a),
+                    *env.actions().choose(&mut b).unwrap()
+                );
+                let action = env.random_action(&mut rng);
+                env.step(&action, &mut rng);
+            }
+        }
+    }
+
+    #[test]
+    fn test_determinize_hints_gives_up() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut deck = CardCollection::starting_deck();
+        let r5 = Hint::from_masks(1 << Color::Red as u8, 1 << Suit::Five as u8);
+        let mut hints = [Hint::empty(); 5];
+        hints[2] = r5;
+        let (hand, log_weight, _) = determinize_hints(&mut deck, &hints, &mut rng).unwrap();
+        assert_eq!(hand[2], Card::new(Color::Red, Suit::Five));
+        assert_eq!(log_weight, 0.0);
+
+        // there's one R5, so no hand has two of them. dealing stops rather than trying forever
+        let mut deck = CardCollection::starting_deck();
+        hints[3] = r5;
+        assert!(determinize_hints(&mut deck, &hints, &mut rng).is_err());
+        assert_eq!(deck.total, CardCollection::starting_deck().total);
+    }
+
+    #[test]
+    fn test_undo() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let order = match Deck::shuffled(&mut rng) {
+            Deck::Ordered(order) => order,
+            Deck::Multiset(_) => unreachable!(),
+        };
+        // only the step's changes are kept, not a copy of the env
+        assert!(std::mem::size_of::<UndoToken>() <= 80);
+        for mut env in [
+            HanabiEnv::random(&mut rng),
+            HanabiEnv::from_deck_order(&order),
+        ] {
+            let mut tokens = Vec::new();
+            let mut states = Vec::new();
+            while !env.is_over() {
+                states.push(env.to_json());
+                let action = env.random_action(&mut rng);
+                // a step taken back leaves nothing behind
+                let token = env.step_with_undo(&action, &mut rng);
+                env.undo(token);
+                assert_eq!(&env.to_json(), states.last().unwrap());
+                tokens.push(env.step_with_undo(&action, &mut rng));
+            }
+            while let Some(token) = tokens.pop() {
+                env.undo(token);
+                assert_eq!(env.to_json(), states.pop().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_actions_prune_dominated_moves() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.fireworks.0[Color::Red as usize] = 2;
+        env.blue_tokens = ClueTokens::new(4);
+        // slot 0 is known to be R1 or R2, both played already. slot 1 is known to be R3
+        env.player_hints[0] = Hint::from_masks(1 << Color::Red as u8, 0b11);
+        env.player_hints[1] = Hint::from_masks(1 << Color::Red as u8, 1 << Suit::Three as u8);
+        // slot 2 is known to be R4, which can't be played yet, and slot 3 the last R5
+        env.player_hints[2] = Hint::from_masks(1 << Color::Red as u8, 1 << Suit::Four as u8);
+        env.player_hints[3] = Hint::from_masks(1 << Color::Red as u8, 1 << Suit::Five as u8);
+        let known = env.known_slots();
+        assert_eq!(
+            known,
+            KnownSlots {
+                trash: 0b1,
+                playable: 0b10,
+                unplayable: 0b1101,
+                critical: 0b1000,
+            }
+        );
+
+        let actions = env.actions();
+        let pruned = env.search_actions();
+        assert!(actions.contains(&Action::Play(0)) && actions.contains(&Action::Discard(1)));
+        assert!(!pruned.contains(&Action::Play(0)) && !pruned.contains(&Action::Discard(1)));
+        assert!(pruned.contains(&Action::Discard(0)) && pruned.contains(&Action::Play(1)));
+        assert!(!pruned.contains(&Action::Play(2)) && !pruned.contains(&Action::Play(3)));
+        // the R5 can only be thrown away while there's no known trash to throw instead
+        assert!(!pruned.contains(&Action::Discard(3)) && pruned.contains(&Action::Discard(2)));
+        assert_eq!(pruned.len() + 5, actions.len());
+        assert!(!DominanceRules::none().prunes(&Action::Play(0), &known));
+        let no_trash = KnownSlots { trash: 0, ..known };
+        assert!(!DominanceRules::default().prunes(&Action::Discard(3), &no_trash));
+        env.pruning.dominance = DominanceRules::none();
+        assert!(env.search_actions().contains(&Action::Play(0)));
+        env.pruning.dominance = DominanceRules::default();
+
+        // with tokens full and nothing to clue, playing known trash is all that's left
+        let trash = Hint::from_masks(1 << Color::Red as u8, 0b11);
+        env.player_hints = [trash; 5];
+        env.blue_tokens = ClueTokens::new(env.rules.clue_tokens);
+        env.opponent_hand = [Card::none(); 5];
+        assert_eq!(env.search_actions(), vec![Action::Play(0)]);
+    }
+
+    #[test]
+    fn test_prune_uninformative_hints() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        let clue = Action::ColorHint(env.opponent_hand[0].color());
+        assert!(env.hint_info_gain(&clue) > 0.0);
+        // the same clue again tells the partner nothing
+        let hand = env.opponent_hand;
+        apply_clue(&hand, &mut env.opponent_hints, &clue);
+        assert_eq!(env.hint_info_gain(&clue), 0.0);
+
+        let is_clue = |a: &Action| matches!(a, Action::ColorHint(_) | Action::SuitHint(_));
+        let actions = env.actions();
+        let mut pruned = actions.clone();
+        env.prune_uninformative_hints(&mut pruned, 0.01);
+        assert!(actions.contains(&clue) && !pruned.contains(&clue));
+        assert_eq!(pruned.len() + 1, actions.len());
+        assert!(env.search_actions().contains(&clue));
+        env.pruning.min_hint_info = 0.01;
+        assert!(!env.search_actions().contains(&clue));
+
+        // nothing is informative enough, the best clue is kept to stall with
+        let mut pruned = actions.clone();
+        env.prune_uninformative_hints(&mut pruned, 1000.0);
+        assert_eq!(pruned.iter().filter(|a| is_clue(a)).count(), 1);
+        assert_eq!(
+            pruned.len(),
+            actions.iter().filter(|a| !is_clue(a)).count() + 1
+        );
+
+        // the mover's own cards don't come into it, every world they could be in agrees
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        for _ in 0..10 {
+            let (world, _) = HanabiEnv::determinize(&public_info, &private_info, &mut rng);
+            for clue in actions.iter().filter(|a| is_clue(a)) {
+                assert_eq!(world.hint_info_gain(clue), env.hint_info_gain(clue));
+            }
+        }
+    }
+
+    #[test]
+    fn test_view_key_apart_from_public_key() {
+        // swapping a discard count for a card id in the partner's hand is the same state to a
+        // key whose features overlap
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut a = HanabiEnv::random(&mut rng);
+        let mut b = a.clone();
+        a.discard.counts[15] = 1;
+        a.opponent_hand[0] = Card::from_id(2);
+        b.discard.counts[15] = 2;
+        b.opponent_hand[0] = Card::from_id(1);
+        let key = |env: &HanabiEnv| {
+            env.public_info().key() ^ HanabiEnv::view_key(&env.private_info(env.current_player()))
+        };
+        assert_ne!(key(&a), key(&b));
+
+        // the same hints with the other seat to move
+        let public_info = a.public_info();
+        let mut swapped = public_info.clone();
+        swapped.hint_history.turn += 1;
+        assert_ne!(public_info.key(), swapped.key());
+    }
+
+    #[test]
+    fn test_determinize_matches_sample_then_new() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut env = HanabiEnv::random(&mut rng);
+        while !env.is_over() {
+            let (public_info, private_info) =
+                (env.public_info(), env.private_info(env.current_player()));
+            let (mut a, mut b) = (rng.clone(), rng.clone());
+            let (sampled, log_prob) = HanabiEnv::determinize(&public_info, &private_info, &mut a);
+            let (private_infos, expected_log_prob) =
+                HanabiEnv::sample_private_infos(&public_info, &private_info, &mut b);
+            let expected = HanabiEnv::new(&public_info, &private_infos);
+            assert_eq!(sampled.to_json(), expected.to_json());
+            assert_eq!(log_prob, expected_log_prob);
+
+            let action = env.random_action(&mut rng);
+            env.step(&action, &mut rng);
+        }
+    }
+
+    #[test]
+    fn test_fully_constrained_hints() {
+        let hand = [
+            Card::new(Color::Red, Suit::Five),
+            Card::new(Color::Blue, Suit::One),
+            Card::new(Color::Green, Suit::Three),
+            Card::new(Color::White, Suit::Two),
+            Card::new(Color::Yellow, Suit::Four),
+        ];
+        let mut player_hints = [Hint::empty(); 5];
+        for i in 0..5 {
+            player_hints[i].set_true_color(hand[i].color());
+            player_hints[i].set_true_suit(hand[i].suit());
+        }
+        let public_info = PublicInfo {
+            player_hints,
+            opponent_hints: [Hint::empty(); 5],
+            discard: CardCollection::empty(),
+            blue_tokens: ClueTokens::new(0),
+            black_tokens: StrikeTokens::new(4),
+            fireworks: Fireworks::empty(),
+            max_score: 25,
+            last_round: false,
+            last_round_turns_taken: 0,
+            hint_history: HintHistory::default(),
+            rules: RuleSet::default(),
+            pruning: SearchPruning::default(),
+        };
+        let private_info = PrivateInfo {
+            opponent_hand: [
+                Card::new(Color::Red, Suit::One),
+                Card::new(Color::Red, Suit::One),
+                Card::new(Color::Blue, Suit::Two),
+                Card::new(Color::Green, Suit::Two),
+                Card::new(Color::White, Suit::One),
+            ],
+        };
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let (private_infos, log_prob) =
+                HanabiEnv::sample_private_infos(&public_info, &private_info, &mut rng);
+            let op_info = &private_infos[1 - public_info.current_player()];
+            assert_eq!(op_info.opponent_hand, hand);
+            assert_eq!(log_prob, 0.0);
+        }
     }
 
     #[test]
     fn test_weird() {
         let public_info = PublicInfo {
             player_hints: [
-                Hint {
-                    color: 0b11110,
-                    suit: 0b00001,
-                },
-                Hint {
-                    color: 0b00001,
-                    suit: 0b01111,
-                },
-                Hint {
-                    color: 0b00001,
-                    suit: 0b10000,
-                },
-                Hint {
-                    color: 0b11111,
-                    suit: 0b01111,
-                },
-                Hint {
-                    color: 0b11111,
-                    suit: 0b01111,
-                },
+                Hint::from_masks(0b11110, 0b00001),
+                Hint::from_masks(0b00001, 0b01111),
+                Hint::from_masks(0b00001, 0b10000),
+                Hint::from_masks(0b11111, 0b01111),
+                Hint::from_masks(0b11111, 0b01111),
             ],
             opponent_hints: [
-                Hint {
-                    color: 0b00100,
-                    suit: 0b11111,
-                },
-                Hint {
-                    color: 0b11011,
-                    suit: 0b11111,
-                },
-                Hint {
-                    color: 0b00100,
-                    suit: 0b11111,
-                },
-                Hint {
-                    color: 0b11111,
-                    suit: 0b11111,
-                },
-                Hint {
-                    color: 0b11011,
-                    suit: 0b11111,
-                },
+                Hint::from_masks(0b00100, 0b11111),
+                Hint::from_masks(0b11011, 0b11111),
+                Hint::from_masks(0b00100, 0b11111),
+                Hint::from_masks(0b11111, 0b11111),
+                Hint::from_masks(0b11011, 0b11111),
             ],
             discard: CardCollection::empty(),
-            blue_tokens: 7,
-            black_tokens: 2,
+            blue_tokens: ClueTokens::new(7),
+            black_tokens: StrikeTokens::new(2),
             fireworks: Fireworks([1, 0, 0, 0, 1]),
+            max_score: 25,
             last_round: false,
             last_round_turns_taken: 0,
+            hint_history: HintHistory::default(),
+            rules: RuleSet::default(),
+            pruning: SearchPruning::default(),
         };
         let private_info = PrivateInfo {
             opponent_hand: [
@@ -891,19 +3177,17 @@ mod tests {
         };
         let mut rng = StdRng::seed_from_u64(0);
         let r = public_info.reward();
+        // slot 2 is known to be the W5 with only W1 played, so every world bombs out on it the
+        // same way, and losing the game scores below the position it was lost from
+        let mut lost = None;
         for _ in 0..1000 {
-            let (op_info, p) =
-                HanabiEnv::sample_opponent_info(&public_info, &private_info, &mut rng);
-            let mut env = HanabiEnv::new(&public_info, &private_info, &op_info);
-            env.step(
-                &Action::Play(Hint {
-                    color: 0b00001,
-                    suit: 0b10000,
-                }),
-                &mut rng,
-            );
+            let (private_infos, _) =
+                HanabiEnv::sample_private_infos(&public_info, &private_info, &mut rng);
+            let mut env = HanabiEnv::new(&public_info, &private_infos);
+            env.step(&Action::Play(2), &mut rng);
             assert!(env.is_over());
-            assert_eq!(env.reward(), r);
+            assert_eq!(env.reward(), *lost.get_or_insert(env.reward()));
         }
+        assert!(lost.unwrap() < r);
     }
 }
@@ -0,0 +1,240 @@
+use crate::env::HasSymmetry;
+use crate::hanabi_env::{
+    Action, Card, CardCollection, Color, Fireworks, Hint, PrivateInfo, PublicInfo, BLACK_COLOR,
+};
+
+// maps old color id -> new color id
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColorPermutation(pub [u8; 5]);
+
+impl ColorPermutation {
+    pub fn identity() -> Self {
+        Self([0, 1, 2, 3, 4])
+    }
+
+    pub fn inverse(&self) -> Self {
+        let mut inverse = [0; 5];
+        for color in 0..5 {
+            inverse[self.0[color] as usize] = color as u8;
+        }
+        Self(inverse)
+    }
+
+    pub fn color(&self, color: Color) -> Color {
+        Color::from_id(self.0[color as usize])
+    }
+
+    fn mask(&self, mask: u8) -> u8 {
+        // bit 5 marks a missing card and isn't a color
+        let mut permuted = mask & 0b100000;
+        for color in 0..5 {
+            if mask & (1 << color) != 0 {
+                permuted |= 1 << self.0[color];
+            }
+        }
+        permuted
+    }
+
+    pub fn hint(&self, hint: &Hint) -> Hint {
+        Hint::from_masks(self.mask(hint.color_mask()), hint.suit_mask())
+    }
+
+    pub fn card(&self, card: &Card) -> Card {
+        if card.is_some() {
+            Card::from_parts(self.0[card.color_id() as usize], card.suit_id())
+        } else {
+            *card
+        }
+    }
+
+    pub fn cards(&self, cards: &CardCollection) -> CardCollection {
+        let mut permuted = *cards;
+        for color in 0..5 {
+            for suit in 0..5 {
+                permuted.counts[5 * self.0[color] as usize + suit] = cards.counts[5 * color + suit];
+            }
+        }
+        permuted
+    }
+
+    pub fn fireworks(&self, fireworks: &Fireworks) -> Fireworks {
+        let mut permuted = *fireworks;
+        for color in 0..5 {
+            permuted.0[self.0[color] as usize] = fireworks.0[color];
+        }
+        permuted
+    }
+
+    pub fn action(&self, action: &Action) -> Action {
+        match *action {
+            Action::ColorHint(color) => Action::ColorHint(self.color(color)),
+            Action::SuitHint(suit) => Action::SuitHint(suit),
+            // slots don't move when colors are relabeled
+            Action::Play(slot) => Action::Play(slot),
+            Action::Discard(slot) => Action::Discard(slot),
+        }
+    }
+
+    pub fn private_info(&self, private_info: &PrivateInfo) -> PrivateInfo {
+        let mut opponent_hand = private_info.opponent_hand;
+        for card in opponent_hand.iter_mut() {
+            *card = self.card(card);
+        }
+        PrivateInfo { opponent_hand }
+    }
+}
+
+// everything in the public state that depends on a color, and the ranks of that color in
+// `seen`'s slots, so equal signatures mean the colors are interchangeable
+fn color_signature(
+    public_info: &PublicInfo,
+    seen: &[Card; 5],
+    color: usize,
+) -> (u8, [u8; 5], u8, u8, [u8; 5]) {
+    let mut discarded = [0; 5];
+    discarded.copy_from_slice(&public_info.discard.counts[5 * color..5 * color + 5]);
+    let mut player_hinted = 0;
+    let mut opponent_hinted = 0;
+    for i in 0..5 {
+        player_hinted |= ((public_info.player_hints[i].color_mask() >> color) & 1) << i;
+        opponent_hinted |= ((public_info.opponent_hints[i].color_mask() >> color) & 1) << i;
+    }
+    let mut ranks = [0; 5];
+    for (rank, card) in ranks.iter_mut().zip(seen.iter()) {
+        if card.is_some() && card.color_id() as usize == color {
+            *rank = card.suit_id() + 1;
+        }
+    }
+    (
+        public_info.fireworks.0[color],
+        discarded,
+        player_hinted,
+        opponent_hinted,
+        ranks,
+    )
+}
+
+// the canonical colors of the public state seen with the partner's hand `seen`
+pub fn canonical_symmetry_seeing(public_info: &PublicInfo, seen: &[Card; 5]) -> ColorPermutation {
+    let mut order = [0, 1, 2, 3, 4];
+    order.sort_by_key(|&color| color_signature(public_info, seen, color as usize));
+    if public_info.rules.black {
+        // the black suit isn't like the others, it keeps its color
+        let black = BLACK_COLOR as u8;
+        let others: Vec<u8> = order.iter().cloned().filter(|&c| c != black).collect();
+        let mut others = others.into_iter();
+        for (color, slot) in order.iter_mut().enumerate() {
+            *slot = match color as u8 == black {
+                true => black,
+                false => others.next().unwrap(),
+            };
+        }
+    }
+    ColorPermutation(order).inverse()
+}
+
+impl HasSymmetry<Action> for PublicInfo {
+    type Symmetry = ColorPermutation;
+
+    fn canonical_symmetry(&self) -> ColorPermutation {
+        canonical_symmetry_seeing(self, &[Card::none(); 5])
+    }
+
+    fn apply(&self, permutation: &ColorPermutation) -> Self {
+        let mut public_info = self.clone();
+        for i in 0..5 {
+            public_info.player_hints[i] = permutation.hint(&self.player_hints[i]);
+            public_info.opponent_hints[i] = permutation.hint(&self.opponent_hints[i]);
+        }
+        public_info.discard = permutation.cards(&self.discard);
+        public_info.fireworks = permutation.fireworks(&self.fireworks);
+        public_info.hint_history = self.hint_history.map_clues(|clue| permutation.action(clue));
+        public_info
+    }
+
+    fn apply_to_action(permutation: &ColorPermutation, action: &Action) -> Action {
+        permutation.action(action)
+    }
+
+    fn invert(permutation: &ColorPermutation) -> ColorPermutation {
+        permutation.inverse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasKey};
+    use crate::hanabi_env::HanabiEnv;
+    use crate::rand::prelude::SliceRandom;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_permuted_states_share_canonical_key() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        for _ in 0..10 {
+            let action = *env.actions().choose(&mut rng).unwrap();
+            env.step(&action, &mut rng);
+        }
+        let public_info = env.public_info();
+
+        let mut colors = [0, 1, 2, 3, 4];
+        for _ in 0..20 {
+            colors.shuffle(&mut rng);
+            let permutation = ColorPermutation(colors);
+            let permuted = public_info.apply(&permutation);
+            assert_eq!(permuted.canonical_key(), public_info.canonical_key());
+            assert_eq!(
+                permuted.apply(&permutation.inverse()).key(),
+                public_info.key()
+            );
+
+            let action = Action::ColorHint(Color::Red);
+            let round_trip = permutation.inverse().action(&permutation.action(&action));
+            assert_eq!(round_trip, action);
+        }
+
+        // the black suit stays where it is
+        let mut black = public_info.clone();
+        black.rules.black = true;
+        for _ in 0..20 {
+            colors.shuffle(&mut rng);
+            let permuted = black.apply(&ColorPermutation(colors));
+            let canonical = permuted.canonical_symmetry();
+            assert_eq!(canonical.color(BLACK_COLOR), BLACK_COLOR);
+        }
+    }
+
+    #[test]
+    fn test_partner_hand_orders_the_colors() {
+        // nothing public tells the colors apart at the deal, only the partner's hand does
+        let mut rng = StdRng::seed_from_u64(1);
+        let env = HanabiEnv::random(&mut rng);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let (symmetry, view_key) = HanabiEnv::canonical_view(&public_info, &private_info);
+
+        let mut colors = [0, 1, 2, 3, 4];
+        for _ in 0..20 {
+            colors.shuffle(&mut rng);
+            let permutation = ColorPermutation(colors);
+            let (public, private) = (
+                public_info.apply(&permutation),
+                permutation.private_info(&private_info),
+            );
+            let (permuted_symmetry, permuted_view_key) =
+                HanabiEnv::canonical_view(&public, &private);
+            assert_eq!(permuted_view_key, view_key);
+            // a clue of a color the partner holds lands on the same canonical clue
+            for card in private_info.opponent_hand.iter() {
+                let clue = Action::ColorHint(card.color());
+                assert_eq!(
+                    permuted_symmetry.action(&permutation.action(&clue)),
+                    symmetry.action(&clue)
+                );
+            }
+        }
+    }
+}
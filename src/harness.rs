@@ -0,0 +1,523 @@
+// plays evaluation games, one seed per game so a run is reproducible whatever the number of
+// workers or shards. the seed fixes the whole deck order (see `runner::deal`), so different
+// policies playing the same seed see the same deals and their scores can be compared game by game
+use crate::env::Env;
+use crate::hanabi_env::{Action, HanabiEnv, Hint, PrivateInfo, PublicInfo};
+use crate::rand::rngs::StdRng;
+use crate::record::GameRecord;
+use crate::registry::{make_agent, Agent, RewardModel};
+use crate::results::{
+    append_line, append_result, merge_results, read_checkpoint, remaining_seeds, summarize,
+    GameResult, Summary, Timings,
+};
+use crate::runner::{observing, GameRunner, RNG_SALT};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub struct GameOutcome {
+    pub result: GameResult,
+    pub reward: f32,
+    pub record: GameRecord,
+    pub move_millis: Vec<f32>,
+    pub game_millis: f32,
+    pub forfeited: bool,
+}
+
+pub fn play_game<P: FnMut(PublicInfo, PrivateInfo, &mut StdRng) -> Action>(
+    name: &str,
+    policy_fn: &mut P,
+    seed: u64,
+    game_time_limit: Option<Duration>,
+    reward_model: &dyn Fn(&HanabiEnv) -> f32,
+) -> GameOutcome {
+    // the same policy sits in both seats
+    let policy_fn = RefCell::new(policy_fn);
+    let runner = GameRunner::new(seed)
+        .seat(
+            0,
+            name,
+            observing(|p, q, rng| (policy_fn.borrow_mut())(p, q, rng)),
+        )
+        .seat(
+            1,
+            name,
+            observing(|p, q, rng| (policy_fn.borrow_mut())(p, q, rng)),
+        )
+        .time_limit(game_time_limit);
+    outcome(runner.run(), reward_model)
+}
+
+fn outcome(runner: GameRunner, reward_model: &dyn Fn(&HanabiEnv) -> f32) -> GameOutcome {
+    GameOutcome {
+        result: GameResult {
+            seed: runner.seed,
+            score: runner.env.fireworks.total(),
+        },
+        reward: reward_model(&runner.env),
+        record: runner.record,
+        move_millis: runner.move_millis,
+        game_millis: runner.game_millis,
+        forfeited: runner.forfeited,
+    }
+}
+// what a seat gets to see, named by a prefix on the agent, e.g. "no-ranks:heuristic"
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum View {
+    Full,
+    // "cheater:", its own hand revealed, an upper bound on what the agent could do with perfect
+    // information
+    Cheater,
+    // "no-ranks:", never learns what rank clues told either hand
+    NoRanks,
+    // "no-colors:", never learns what color clues told either hand
+    NoColors,
+}
+
+const ALL: u8 = 0b11111;
+
+impl View {
+    fn parse(name: &str) -> (Self, &str) {
+        for &(prefix, view) in [
+            ("cheater:", View::Cheater),
+            ("no-ranks:", View::NoRanks),
+            ("no-colors:", View::NoColors),
+        ]
+        .iter()
+        {
+            if let Some(inner) = name.strip_prefix(prefix) {
+                return (view, inner);
+            }
+        }
+        (View::Full, name)
+    }
+
+    fn degrade(&self, hints: &mut [Hint; 5]) {
+        for hint in hints.iter_mut().filter(|h| h.is_some()) {
+            *hint = match self {
+                View::NoRanks => Hint::from_masks(hint.color_mask(), ALL),
+                View::NoColors => Hint::from_masks(ALL, hint.suit_mask()),
+                View::Full | View::Cheater => *hint,
+            };
+        }
+    }
+
+    // the seat's observation of `env`
+    pub fn observe(&self, env: &HanabiEnv) -> (PublicInfo, PrivateInfo) {
+        let mut public_info = env.public_info();
+        match self {
+            View::Full => {}
+            View::Cheater => {
+                // every card's hint narrowed to the card itself
+                for i in 0..5 {
+                    let card = env.player_hand[i];
+                    if card.is_some() {
+                        public_info.player_hints[i] =
+                            Hint::from_masks(1 << card.color() as u8, 1 << card.suit() as u8);
+                    }
+                }
+            }
+            View::NoRanks | View::NoColors => {
+                self.degrade(&mut public_info.player_hints);
+                self.degrade(&mut public_info.opponent_hints);
+            }
+        }
+        (public_info, env.private_info(env.current_player()))
+    }
+}
+
+// a registered agent in one seat, seeing the game through its view
+pub struct Seat {
+    pub name: String,
+    agent: Agent,
+    view: View,
+}
+
+impl Seat {
+    pub fn new(name: &str) -> Result<Self, String> {
+        let (view, inner) = View::parse(name);
+        Ok(Self {
+            name: name.to_string(),
+            agent: make_agent(inner)?,
+            view,
+        })
+    }
+
+    fn act(&mut self, env: &HanabiEnv, rng: &mut StdRng) -> Action {
+        if self.view == View::Full {
+            return (self.agent)(
+                env.public_info(),
+                env.private_info(env.current_player()),
+                rng,
+            );
+        }
+        let (public_info, private_info) = self.view.observe(env);
+        (self.agent)(public_info, private_info, rng)
+    }
+}
+
+// plays `seeds` with the registered agents `names` in seats 0 and 1, on `num_workers` threads
+// that each build their own agents. outcomes come back in seed order
+pub fn play_games(
+    names: [&str; 2],
+    seeds: &[u64],
+    num_workers: usize,
+    game_time_limit: Option<Duration>,
+    reward_model: RewardModel,
+) -> Result<Vec<GameOutcome>, String> {
+    play_games_logged(
+        names,
+        seeds,
+        num_workers,
+        game_time_limit,
+        reward_model,
+        None,
+    )
+}
+
+// every game's rng streams are derived from its seed alone (see GameRunner::new), so a run can
+// stop anywhere and pick up again: the only state to keep is which seeds are done. finished games
+// are appended to `checkpoint` as they end, and the games already in it are skipped. its first
+// line is the session, see `session_line`, and a run of another session won't resume from it.
+// returns the outcomes played now along with the results of every seed, old & new
+pub fn resume_games(
+    names: [&str; 2],
+    seeds: &[u64],
+    num_workers: usize,
+    game_time_limit: Option<Duration>,
+    reward_model: RewardModel,
+    checkpoint: &str,
+) -> Result<(Vec<GameOutcome>, Vec<GameResult>), String> {
+    let session = session_line(names);
+    let done = match std::path::Path::new(checkpoint).exists() {
+        true => {
+            let (header, done) = read_checkpoint(checkpoint).map_err(|e| e.to_string())?;
+            if header.as_deref() != Some(session.as_str()) {
+                return Err(format!(
+                    "{} is from another session ({}), this one is '{}'",
+                    checkpoint,
+                    header.unwrap_or_else(|| "none recorded".to_string()),
+                    session
+                ));
+            }
+            done
+        }
+        false => {
+            append_line(checkpoint, &session).map_err(|e| e.to_string())?;
+            Vec::new()
+        }
+    };
+    let done: Vec<GameResult> = done
+        .into_iter()
+        .filter(|r| seeds.contains(&r.seed))
+        .collect();
+    let outcomes = play_games_logged(
+        names,
+        &remaining_seeds(seeds, &done),
+        num_workers,
+        game_time_limit,
+        reward_model,
+        Some(checkpoint.to_string()),
+    )?;
+    let played: Vec<GameResult> = outcomes.iter().map(|o| o.result).collect();
+    let results = merge_results(&[done, played])?;
+    Ok((outcomes, results))
+}
+
+// what a checkpoint's games were played with: the agents by seat, and how a game's rng streams
+// come from its seed, the deal from the seed itself and the agents' & env's from seed ^ salt
+pub fn session_line(names: [&str; 2]) -> String {
+    format!(
+        "# agents={},{} rng=seed^{:#x}",
+        names[0], names[1], RNG_SALT
+    )
+}
+
+fn play_games_logged(
+    names: [&str; 2],
+    seeds: &[u64],
+    num_workers: usize,
+    game_time_limit: Option<Duration>,
+    reward_model: RewardModel,
+    checkpoint: Option<String>,
+) -> Result<Vec<GameOutcome>, String> {
+    // fail before spawning anything if an agent doesn't exist
+    for name in names.iter() {
+        Seat::new(name)?;
+    }
+
+    let seeds = Arc::new(seeds.to_vec());
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let finished = Arc::new(Mutex::new(Vec::new()));
+    let workers: Vec<_> = (0..num_workers.max(1))
+        .map(|_| {
+            let names = [names[0].to_string(), names[1].to_string()];
+            let seeds = seeds.clone();
+            let next_game = next_game.clone();
+            let finished = finished.clone();
+            let reward_model = reward_model.clone();
+            let checkpoint = checkpoint.clone();
+            std::thread::spawn(move || {
+                let mut seats = [Seat::new(&names[0]).unwrap(), Seat::new(&names[1]).unwrap()];
+                let mut outcomes = Vec::new();
+                loop {
+                    let game = next_game.fetch_add(1, Ordering::SeqCst);
+                    if game >= seeds.len() {
+                        break;
+                    }
+                    let [first, second] = &mut seats;
+                    let runner = GameRunner::new(seeds[game])
+                        .seat(0, &names[0], Box::new(|env, rng| first.act(env, rng)))
+                        .seat(1, &names[1], Box::new(|env, rng| second.act(env, rng)))
+                        .time_limit(game_time_limit);
+                    let outcome = outcome(runner.run(), &*reward_model);
+                    let mut scores = finished.lock().unwrap();
+                    if let Some(path) = checkpoint.as_ref() {
+                        append_result(path, &outcome.result).expect("failed to write checkpoint");
+                    }
+                    scores.push(outcome.result.score as f32);
+                    println!(
+                        "{}/{} seed={} score={} running mean={:.3}",
+                        scores.len(),
+                        seeds.len(),
+                        outcome.result.seed,
+                        outcome.result.score,
+                        scores.iter().sum::<f32>() / scores.len() as f32
+                    );
+                    drop(scores);
+                    outcomes.push(outcome);
+                }
+                outcomes
+            })
+        })
+        .collect();
+
+    let mut outcomes: Vec<GameOutcome> = workers
+        .into_iter()
+        .flat_map(|worker| worker.join().unwrap())
+        .collect();
+    outcomes.sort_by_key(|o| o.result.seed);
+    Ok(outcomes)
+}
+
+// every agent paired with every agent, `matrix[i][j]` has `names[i]` in seat 0 and `names[j]`
+// in seat 1. the diagonal is self-play
+pub fn cross_play(
+    names: &[String],
+    seeds: &[u64],
+    num_workers: usize,
+    reward_model: RewardModel,
+) -> Result<Vec<Vec<Summary>>, String> {
+    let mut matrix = Vec::new();
+    for first in names.iter() {
+        let mut row = Vec::new();
+        for second in names.iter() {
+            let outcomes = play_games(
+                [first, second],
+                seeds,
+                num_workers,
+                None,
+                reward_model.clone(),
+            )?;
+            let results: Vec<GameResult> = outcomes.iter().map(|o| o.result).collect();
+            row.push(summarize(&results));
+        }
+        matrix.push(row);
+    }
+    Ok(matrix)
+}
+
+pub fn timings(outcomes: &[GameOutcome]) -> Timings {
+    Timings {
+        move_millis: outcomes
+            .iter()
+            .flat_map(|o| o.move_millis.iter().cloned())
+            .collect(),
+        game_millis: outcomes.iter().map(|o| o.game_millis).collect(),
+        forfeits: outcomes.iter().filter(|o| o.forfeited).count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hanabi_env::Card;
+    use crate::rand::SeedableRng;
+    use crate::registry::{register_builtins, reward_model};
+    use crate::runner::deal;
+
+    #[test]
+    fn test_resumed_run_matches_uninterrupted() {
+        register_builtins();
+        let seeds: Vec<u64> = (0..6).collect();
+        let play = |seeds: &[u64], checkpoint: &str| {
+            resume_games(
+                ["random", "heuristic"],
+                seeds,
+                2,
+                None,
+                reward_model("score").unwrap(),
+                checkpoint,
+            )
+            .unwrap()
+        };
+        let path = |name: &str| {
+            let file = format!("hanabi-resume-{}-{}.txt", name, std::process::id());
+            std::env::temp_dir()
+                .join(file)
+                .to_string_lossy()
+                .to_string()
+        };
+        let (whole, split) = (path("whole"), path("split"));
+
+        let (_, uninterrupted) = play(&seeds, &whole);
+        // stopped after the first half while writing the next game, then started again on the
+        // whole run
+        play(&seeds[..3], &split);
+        append_result(&split, &GameResult { seed: 3, score: 9 }).unwrap();
+        let text = std::fs::read_to_string(&split).unwrap();
+        std::fs::write(&split, &text[..text.len() - 2]).unwrap();
+        let (outcomes, resumed) = play(&seeds, &split);
+        let (header, checkpointed) = read_checkpoint(&split).unwrap();
+        // another pair of agents is another session
+        let other = resume_games(
+            ["heuristic", "random"],
+            &seeds,
+            1,
+            None,
+            reward_model("score").unwrap(),
+            &split,
+        );
+        std::fs::remove_file(&whole).unwrap();
+        std::fs::remove_file(&split).unwrap();
+
+        assert!(other.is_err());
+        assert_eq!(header, Some(session_line(["random", "heuristic"])));
+
+        assert_eq!(outcomes.len(), 3);
+        assert_eq!(resumed, uninterrupted);
+        assert_eq!(merge_results(&[checkpointed]).unwrap(), uninterrupted);
+        assert_eq!(
+            uninterrupted,
+            play_games(
+                ["random", "heuristic"],
+                &seeds,
+                1,
+                None,
+                reward_model("score").unwrap()
+            )
+            .unwrap()
+            .iter()
+            .map(|o| o.result)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_workers_play_the_same_games() {
+        register_builtins();
+        let seeds: Vec<u64> = (0..6).collect();
+        let serial = play_games(
+            ["heuristic", "heuristic"],
+            &seeds,
+            1,
+            None,
+            reward_model("score").unwrap(),
+        );
+        let parallel = play_games(
+            ["heuristic", "heuristic"],
+            &seeds,
+            3,
+            None,
+            reward_model("score").unwrap(),
+        );
+        let scores = |outcomes: Vec<GameOutcome>| -> Vec<GameResult> {
+            outcomes.iter().map(|o| o.result).collect()
+        };
+        assert_eq!(scores(serial.unwrap()), scores(parallel.unwrap()));
+
+        // whatever the policy does, cards come off the deck in the same order
+        let draws = |name: &str| -> Vec<Card> {
+            let outcomes = play_games(
+                [name, name],
+                &seeds[..1],
+                1,
+                None,
+                reward_model("score").unwrap(),
+            );
+            let record = &outcomes.unwrap()[0].record;
+            record.turns.iter().filter_map(|t| t.drawn).collect()
+        };
+        let (random, heuristic) = (draws("random"), draws("heuristic"));
+        let n = random.len().min(heuristic.len());
+        assert!(n > 0);
+        assert_eq!(random[..n], heuristic[..n]);
+        assert!(play_games(
+            ["heuristic", "no-such-agent"],
+            &seeds,
+            2,
+            None,
+            reward_model("score").unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_cross_play() {
+        register_builtins();
+        let names = vec![
+            "heuristic".to_string(),
+            "random".to_string(),
+            "cheater:heuristic".to_string(),
+        ];
+        let seeds: Vec<u64> = (0..8).collect();
+        let matrix = cross_play(&names, &seeds, 2, reward_model("score").unwrap()).unwrap();
+        assert_eq!(matrix.len(), 3);
+        assert!(matrix
+            .iter()
+            .all(|row| row.len() == 3 && row[0].num_games == 8));
+        // a random partner drags the heuristic down, seeing its own hand lifts it up
+        assert!(matrix[0][0].mean > matrix[0][1].mean);
+        assert!(matrix[2][2].mean > matrix[0][0].mean);
+    }
+
+    #[test]
+    fn test_handicapped_views() {
+        register_builtins();
+        let mut env = deal(0);
+        let mut rng = StdRng::seed_from_u64(0);
+        let hand = env.opponent_hand;
+        env.step(&Action::SuitHint(hand[0].suit()), &mut rng);
+        env.step(&Action::ColorHint(env.opponent_hand[1].color()), &mut rng);
+        // the seat to move got the color clue, its partner the rank clue
+        let touched = |hints: &[Hint; 5]| hints.iter().filter(|h| **h != Hint::empty()).count();
+        let (full, _) = View::Full.observe(&env);
+        assert!(touched(&full.player_hints) > 0 && touched(&full.opponent_hints) > 0);
+        let (no_ranks, _) = View::NoRanks.observe(&env);
+        assert!(touched(&no_ranks.player_hints) > 0);
+        assert_eq!(touched(&no_ranks.opponent_hints), 0);
+        assert!(no_ranks.opponent_hints.iter().all(|h| h.suit_mask() == ALL));
+        let (no_colors, _) = View::NoColors.observe(&env);
+        assert_eq!(touched(&no_colors.player_hints), 0);
+        let (cheater, _) = View::Cheater.observe(&env);
+        assert!(cheater
+            .player_hints
+            .iter()
+            .all(|h| h.color_mask().count_ones() == 1));
+
+        // handicapped seats still only make legal moves
+        let seeds: Vec<u64> = (0..4).collect();
+        for &name in ["no-ranks:heuristic", "no-colors:heuristic"].iter() {
+            let outcomes = play_games(
+                [name, "heuristic"],
+                &seeds,
+                1,
+                None,
+                reward_model("score").unwrap(),
+            );
+            assert_eq!(outcomes.unwrap().len(), 4);
+        }
+        assert_eq!(View::parse("no-ranks:mcts"), (View::NoRanks, "mcts"));
+    }
+}
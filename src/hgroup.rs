@@ -0,0 +1,372 @@
+// the basic H-Group conventions, the ones most players on hanab.live expect from a partner. a
+// clue's focus is the chop when the clue touches it, otherwise the newest card it newly touches,
+// and the focus of a play clue is playable. a clue that focuses the chop is a save instead when
+// it's a 5 or 2 clue, or when the focus can't be playable. nobody discards a touched card: the
+// discard is the chop, the first untouched card, as render::chop has it, so the newest cards are
+// at the other end. a slot counts as touched once a clue has touched its card, as
+// HintHistory::touched has it, whatever the negative information says about it
+use crate::abstraction::{is_critical_with, is_trash_with};
+use crate::env::HasReward;
+use crate::evaluator::{possible_cards, Evaluator};
+use crate::hanabi_env::{
+    apply_clue, Action, Card, HanabiEnv, Hint, PrivateInfo, PublicInfo, Suit, COLORS, SUITS,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Reading {
+    Play,
+    Save,
+}
+
+// the first of the `held` slots that isn't `touched`
+pub fn chop(held: u8, touched: u8) -> Option<usize> {
+    (0..5).find(|&i| held & !touched & 1 << i != 0)
+}
+
+fn may_be_playable(public_info: &PublicInfo, hint: &Hint) -> bool {
+    let mask = hint.card_mask();
+    (0..25).any(|id| mask & 1 << id != 0 && public_info.fireworks.accepts(Card::from_id(id)))
+}
+
+// the focus of a clue touching `touched` when `touched_before` had been clued already, and
+// whether it's a play or a save. `hints` are the receiver's after the clue
+pub fn read_clue(
+    public_info: &PublicInfo,
+    clue: &Action,
+    held: u8,
+    touched_before: u8,
+    touched: u8,
+    hints: &[Hint; 5],
+) -> Option<(usize, Reading)> {
+    if touched == 0 {
+        return None;
+    }
+    let newest = |bits: u8| 7 - bits.leading_zeros() as usize;
+    let chop = chop(held, touched_before);
+    let new = touched & !touched_before;
+    let focus = match chop {
+        Some(chop) if new & 1 << chop != 0 => chop,
+        _ if new != 0 => newest(new),
+        _ => newest(touched),
+    };
+    let save_clue = *clue == Action::SuitHint(Suit::Five) || *clue == Action::SuitHint(Suit::Two);
+    match Some(focus) == chop && (save_clue || !may_be_playable(public_info, &hints[focus])) {
+        true => Some((focus, Reading::Save)),
+        false => Some((focus, Reading::Play)),
+    }
+}
+
+// the slot the clue the player got on the turn just gone focused as a play, if it may still be
+// playable. older clues aren't read: the slots they touched are forgotten as the cards leave,
+// which moves their focus, and the fireworks may have moved on since
+pub fn play_clued(public_info: &PublicInfo) -> Option<usize> {
+    let history = &public_info.hint_history;
+    let hints = &public_info.player_hints;
+    let received: Vec<_> = history
+        .iter()
+        .filter(|e| history.received_by_player(e))
+        .collect();
+    let event = received.first().filter(|e| history.turns_ago(e) == 1)?;
+    // what was touched before this clue: what older clues touched
+    let touched_before = received[1..].iter().fold(0, |bits, e| bits | e.touched);
+    match read_clue(
+        public_info,
+        &event.clue,
+        event.held,
+        touched_before,
+        event.touched,
+        hints,
+    ) {
+        Some((slot, Reading::Play)) if may_be_playable(public_info, &hints[slot]) => Some(slot),
+        _ => None,
+    }
+}
+
+// the clues the rules allow on `hand`, with the slots each touches
+fn clues(public_info: &PublicInfo, hand: &[Card; 5]) -> Vec<(Action, u8)> {
+    COLORS
+        .iter()
+        .map(|&color| Action::ColorHint(color))
+        .filter(|clue| public_info.rules.allows_clue(clue))
+        .chain(SUITS.iter().map(|&suit| Action::SuitHint(suit)))
+        .map(|clue| {
+            let touched = (0..5)
+                .filter(|&i| match clue {
+                    Action::ColorHint(color) => hand[i].is_some() && hand[i].color() == color,
+                    Action::SuitHint(suit) => hand[i].is_some() && hand[i].suit() == suit,
+                    _ => false,
+                })
+                .fold(0, |bits, i| bits | 1 << i);
+            (clue, touched)
+        })
+        .filter(|&(_, touched)| touched != 0)
+        .collect()
+}
+
+// how the partner reads `clue`, touching `touched` of their cards
+fn partner_reading(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    clue: &Action,
+    touched: u8,
+) -> Option<(usize, Reading)> {
+    let hand = &private_info.opponent_hand;
+    let held = (0..5)
+        .filter(|&i| hand[i].is_some())
+        .fold(0, |bits, i| bits | 1 << i);
+    let mut hints = public_info.opponent_hints;
+    apply_clue(hand, &mut hints, clue);
+    let touched_before = public_info.hint_history.touched(false);
+    read_clue(public_info, clue, held, touched_before, touched, &hints)
+}
+
+// a clue that saves the partner's chop when it's a 5, a critical card or a 2 with no other copy
+// in their hand, and isn't playable
+fn save_clue(public_info: &PublicInfo, private_info: &PrivateInfo) -> Option<Action> {
+    let hand = &private_info.opponent_hand;
+    let (rules, fireworks, discard) = (
+        &public_info.rules,
+        &public_info.fireworks,
+        &public_info.discard,
+    );
+    let held = (0..5)
+        .filter(|&i| hand[i].is_some())
+        .fold(0, |bits, i| bits | 1 << i);
+    let slot = chop(held, public_info.hint_history.touched(false))?;
+    let card = hand[slot];
+    let two = card.suit() == Suit::Two
+        && !is_trash_with(rules, card, fireworks, discard)
+        && (0..5).all(|j| j == slot || hand[j] != card);
+    if fireworks.accepts(card) || !(is_critical_with(rules, card, fireworks, discard) || two) {
+        return None;
+    }
+    clues(public_info, hand)
+        .into_iter()
+        .find(|(clue, touched)| {
+            partner_reading(public_info, private_info, clue, *touched)
+                == Some((slot, Reading::Save))
+        })
+        .map(|(clue, _)| clue)
+}
+
+// a clue whose focus is a playable card of the partner's, lowest rank first, that touches no
+// trash and no second copy of a card, whether it's already touched or touched by the clue too
+fn play_clue(public_info: &PublicInfo, private_info: &PrivateInfo) -> Option<Action> {
+    let hand = &private_info.opponent_hand;
+    let (rules, fireworks, discard) = (
+        &public_info.rules,
+        &public_info.fireworks,
+        &public_info.discard,
+    );
+    let touched_before = public_info.hint_history.touched(false);
+    let good_touch = |touched: u8| {
+        (0..5)
+            .filter(|&i| touched & !touched_before & 1 << i != 0)
+            .all(|i| {
+                !is_trash_with(rules, hand[i], fireworks, discard)
+                    && (0..5).all(|j| {
+                        j == i || (touched_before | touched) & 1 << j == 0 || hand[j] != hand[i]
+                    })
+            })
+    };
+    let mut candidates = clues(public_info, hand)
+        .into_iter()
+        .filter_map(|(clue, touched)| {
+            match partner_reading(public_info, private_info, &clue, touched) {
+                Some((slot, Reading::Play))
+                    if fireworks.accepts(hand[slot])
+                        && touched & !touched_before != 0
+                        && good_touch(touched) =>
+                {
+                    Some((hand[slot].suit_id(), clue))
+                }
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|&(rank, _)| rank);
+    candidates.first().map(|&(_, clue)| clue)
+}
+
+pub fn hgroup_policy(public_info: &PublicInfo, private_info: &PrivateInfo) -> Action {
+    let hints = &public_info.player_hints;
+    let (rules, fireworks, discard) = (
+        &public_info.rules,
+        &public_info.fireworks,
+        &public_info.discard,
+    );
+    let held: Vec<usize> = (0..5).filter(|&i| hints[i].is_some()).collect();
+    let cards = |i: usize| possible_cards(&hints[i], public_info, private_info);
+    let known = |i: usize, f: &dyn Fn(Card) -> bool| {
+        let cards = cards(i);
+        !cards.is_empty() && cards.into_iter().all(f)
+    };
+
+    // the focus of a play clue, then known playable cards
+    let playable = |c: Card| fireworks.accepts(c);
+    if let Some(slot) = play_clued(public_info).filter(|&i| cards(i).into_iter().any(playable)) {
+        return Action::Play(slot);
+    }
+    if let Some(&slot) = held.iter().find(|&&i| known(i, &playable)) {
+        return Action::Play(slot);
+    }
+
+    if public_info.blue_tokens > 0 {
+        if let Some(clue) =
+            save_clue(public_info, private_info).or_else(|| play_clue(public_info, private_info))
+        {
+            return clue;
+        }
+    }
+
+    if public_info.blue_tokens < rules.clue_tokens {
+        let trash = |c: Card| is_trash_with(rules, c, fireworks, discard);
+        let mask = held.iter().fold(0, |bits, i| bits | 1 << i);
+        // a locked hand, every card touched, gives up the first card
+        let slot = held
+            .iter()
+            .cloned()
+            .find(|&i| known(i, &trash))
+            .or_else(|| chop(mask, public_info.hint_history.touched(true)))
+            .unwrap_or(held[0]);
+        return Action::Discard(slot);
+    }
+
+    // every token, so a clue that tells the partner nothing they'd act on wrongly: a save, a
+    // play on a playable card or on one they can tell isn't
+    let hand = &private_info.opponent_hand;
+    let clues = clues(public_info, hand);
+    clues
+        .iter()
+        .find(
+            |(clue, touched)| match partner_reading(public_info, private_info, clue, *touched) {
+                Some((slot, Reading::Play)) => {
+                    let mut hints = public_info.opponent_hints;
+                    apply_clue(hand, &mut hints, clue);
+                    fireworks.accepts(hand[slot]) || !may_be_playable(public_info, &hints[slot])
+                }
+                _ => true,
+            },
+        )
+        .or_else(|| clues.first())
+        .map_or(Action::Play(held[0]), |&(clue, _)| clue)
+}
+
+// puts `weight` of the prior on the move an H-Group player makes and spreads the rest evenly.
+// as MCTS::with_partner_prior, a search expects the partner to keep to the conventions. values
+// are the shaped reward
+pub struct HGroupEvaluator {
+    pub weight: f32,
+}
+
+impl Evaluator<HanabiEnv> for HGroupEvaluator {
+    fn evaluate(
+        &mut self,
+        public_info: &PublicInfo,
+        private_info: &PrivateInfo,
+        actions: &[Action],
+    ) -> (f32, Vec<f32>) {
+        let convention = hgroup_policy(public_info, private_info);
+        let weight = match actions.contains(&convention) {
+            true => self.weight,
+            false => 0.0,
+        };
+        let rest = (1.0 - weight) / actions.len() as f32;
+        let priors = actions
+            .iter()
+            .map(|&action| rest + (action == convention) as u8 as f32 * weight)
+            .collect();
+        (public_info.reward(), priors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasEnd};
+    use crate::hanabi_env::Color;
+    use crate::position::Position;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+    use crate::scenarios::game;
+
+    #[test]
+    fn test_hgroup_conventions() {
+        // the partner's chop is a 5, so it gets a 5 save over the playable R1
+        let position = Position::parse("W5.R1.B3.Y4.G2 ?.?.?.?.? ?.?.?.?.? 00000 - 8/3 -").unwrap();
+        let (public_info, private_info) = (&position.public_info, &position.private_info);
+        assert_eq!(
+            hgroup_policy(public_info, private_info),
+            Action::SuitHint(Suit::Five)
+        );
+
+        // nothing to save, and red focuses the R1 as a play
+        let position = Position::parse("B3.R1.Y4.W2.G3 ?.?.?.?.? ?.?.?.?.? 00000 - 8/3 -").unwrap();
+        let (public_info, private_info) = (&position.public_info, &position.private_info);
+        let clue = hgroup_policy(public_info, private_info);
+        assert_eq!(clue, Action::ColorHint(Color::Red));
+        assert_eq!(
+            partner_reading(public_info, private_info, &clue, 0b00010),
+            Some((1, Reading::Play))
+        );
+
+        // the partner played R1 to R4 off rank clues, which ruled every rank but 5 out for the
+        // card left in slot 0. no clue touched it, so it's still their chop
+        let env = game(
+            "B2.G4.Y2.W3.G4.W5.R1.R2.R3.R4.B3.G3.Y3.B4",
+            &[
+                Action::SuitHint(Suit::One),
+                Action::Play(1),
+                Action::SuitHint(Suit::Two),
+                Action::Play(2),
+                Action::SuitHint(Suit::Three),
+                Action::Play(3),
+                Action::SuitHint(Suit::Four),
+                Action::Play(4),
+            ],
+        );
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        assert_eq!(public_info.opponent_hints[0].suit_mask() & 0b11111, 0b10000);
+        let clue = hgroup_policy(&public_info, &private_info);
+        assert_eq!(clue, Action::ColorHint(Color::White));
+        assert_eq!(
+            partner_reading(&public_info, &private_info, &clue, 0b00001),
+            Some((0, Reading::Save))
+        );
+
+        // either clue on the partner's R1s would touch both copies
+        let env = game("B4.Y3.G2.W4.Y4.W3.R1.G2.R1.B4", &[]);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        assert_eq!(play_clue(&public_info, &private_info), None);
+
+        // a game keeps to legal moves, and the partner plays what a play clue focused
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        let mut plays = 0;
+        while !env.is_over() {
+            let (public_info, private_info) =
+                (env.public_info(), env.private_info(env.current_player()));
+            let action = hgroup_policy(&public_info, &private_info);
+            assert!(env.actions().contains(&action), "{:?}", action);
+            if let Action::Play(slot) = action {
+                plays += (play_clued(&public_info) == Some(slot)) as usize;
+            }
+            env.step(&action, &mut rng);
+        }
+        assert!(plays > 0);
+
+        // the prior leans on the convention's move
+        let (public_info, private_info) = (&position.public_info, &position.private_info);
+        let actions = [
+            Action::Play(0),
+            Action::Discard(0),
+            Action::ColorHint(Color::Red),
+        ];
+        let (_, priors) =
+            HGroupEvaluator { weight: 0.5 }.evaluate(public_info, private_info, &actions);
+        assert!((priors.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(priors[2] > priors[0]);
+    }
+}
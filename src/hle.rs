@@ -0,0 +1,350 @@
+use crate::hanabi_env::{Action, Card, CardCollection, Color, Hint, PrivateInfo, PublicInfo, Suit};
+use crate::record::TurnRecord;
+
+// layout of the Hanabi Learning Environment's canonical observation encoder for
+// 2 players, 5 colors, 5 ranks, hand size 5, 8 info tokens & 3 lives
+const NUM_PLAYERS: usize = 2;
+const HAND_SIZE: usize = 5;
+const NUM_CARDS: usize = 25;
+
+const HANDS_SIZE: usize = (NUM_PLAYERS - 1) * HAND_SIZE * NUM_CARDS + NUM_PLAYERS;
+const BOARD_SIZE: usize = (50 - NUM_PLAYERS * HAND_SIZE) + NUM_CARDS + 8 + 3;
+const DISCARDS_SIZE: usize = 50;
+const LAST_ACTION_SIZE: usize =
+    NUM_PLAYERS + 4 + NUM_PLAYERS + 5 + 5 + HAND_SIZE + HAND_SIZE + NUM_CARDS + 2;
+const KNOWLEDGE_SIZE: usize = NUM_PLAYERS * HAND_SIZE * (NUM_CARDS + 5 + 5);
+
+pub const HLE_OBSERVATION_SIZE: usize =
+    HANDS_SIZE + BOARD_SIZE + DISCARDS_SIZE + LAST_ACTION_SIZE + KNOWLEDGE_SIZE;
+
+// HLE orders colors R Y G W B
+fn hle_color(color: Color) -> usize {
+    match color {
+        Color::Red => 0,
+        Color::Yellow => 1,
+        Color::Green => 2,
+        Color::White => 3,
+        Color::Blue => 4,
+    }
+}
+
+fn color_from_hle(index: usize) -> Color {
+    [
+        Color::Red,
+        Color::Yellow,
+        Color::Green,
+        Color::White,
+        Color::Blue,
+    ][index]
+}
+
+fn hle_card(card: Card) -> usize {
+    hle_color(card.color()) * 5 + card.suit_id() as usize
+}
+
+pub const HLE_NUM_MOVES: usize = 20;
+
+// HLE move uids: discard 0-4, play 5-9, reveal color 10-14, reveal rank 15-19
+pub fn move_uid(action: &Action) -> usize {
+    match *action {
+        Action::Discard(i) => i,
+        Action::Play(i) => 5 + i,
+        Action::ColorHint(color) => 10 + hle_color(color),
+        Action::SuitHint(suit) => 15 + suit as usize,
+    }
+}
+
+// the uids of every move the player can't tell apart from `action`: a play/discard maps to each
+// slot with the same hint, the action's own uid first
+pub fn move_uids(public_info: &PublicInfo, action: &Action) -> Vec<usize> {
+    let uid = move_uid(action);
+    let hints = &public_info.player_hints;
+    let mut uids = vec![uid];
+    if let Some(slot) = action.slot() {
+        let first = uid - slot;
+        uids.extend(
+            (0..HAND_SIZE)
+                .filter(|&i| i != slot && hints[i] == hints[slot])
+                .map(|i| first + i),
+        );
+    }
+    uids
+}
+
+pub fn move_from_uid(uid: usize) -> Action {
+    match uid {
+        0..=4 => Action::Discard(uid),
+        5..=9 => Action::Play(uid - 5),
+        10..=14 => Action::ColorHint(color_from_hle(uid - 10)),
+        15..=19 => Action::SuitHint(Suit::from_id((uid - 15) as u8)),
+        _ => panic!("invalid move uid {}", uid),
+    }
+}
+
+// bit `uid` is set for every legal HLE move uid, worked out from the hints & the partner's hand
+// without building the actions. the same moves as Env::actions through `move_uids`
+pub fn legal_move_mask(public_info: &PublicInfo, private_info: &PrivateInfo) -> u32 {
+    let mut mask = 0;
+    for i in 0..HAND_SIZE {
+        if public_info.player_hints[i].is_some() {
+            mask |= 1 << (5 + i);
+            if public_info.blue_tokens < public_info.rules.clue_tokens {
+                mask |= 1 << i;
+            }
+        }
+    }
+    if public_info.blue_tokens > 0 {
+        for card in private_info.opponent_hand.iter().filter(|c| c.is_some()) {
+            mask |= 1 << (10 + hle_color(card.color()));
+            mask |= 1 << (15 + card.suit_id());
+        }
+    }
+    mask
+}
+
+pub fn legal_moves_from_mask(mask: u32) -> [bool; HLE_NUM_MOVES] {
+    let mut legal = [false; HLE_NUM_MOVES];
+    for (uid, legal) in legal.iter_mut().enumerate() {
+        *legal = mask & (1 << uid) != 0;
+    }
+    legal
+}
+
+fn thermometer(bits: &mut [u8], n: usize) {
+    for bit in bits.iter_mut().take(n) {
+        *bit = 1;
+    }
+}
+
+// the single color/rank a hint pins down, if any.
+// note: HLE only counts directly revealed values here, but hints don't remember whether
+// a value was revealed or deduced from negative information, so both are encoded
+fn revealed(mask: u8) -> Option<usize> {
+    let mask = mask & 0b11111;
+    if mask.count_ones() == 1 {
+        Some(mask.trailing_zeros() as usize)
+    } else {
+        None
+    }
+}
+
+fn encode_knowledge(hints: &[Hint; 5], bits: &mut [u8]) {
+    for (i, hint) in hints.iter().enumerate() {
+        if !hint.is_some() {
+            continue;
+        }
+        let offset = i * (NUM_CARDS + 10);
+        for id in 0..25 {
+            let card = Card::from_id(id);
+            if hint.matches(card) {
+                bits[offset + hle_card(card)] = 1;
+            }
+        }
+        if let Some(color_id) = revealed(hint.color_mask()) {
+            bits[offset + NUM_CARDS + hle_color(Color::from_id(color_id as u8))] = 1;
+        }
+        if let Some(rank) = revealed(hint.suit_mask()) {
+            bits[offset + NUM_CARDS + 5 + rank] = 1;
+        }
+    }
+}
+
+fn encode_last_action(last_turn: &TurnRecord, bits: &mut [u8]) {
+    // the last move was always made by the partner, relative offset 1
+    bits[1] = 1;
+    let move_type = match last_turn.action {
+        Action::Play(_) => 0,
+        Action::Discard(_) => 1,
+        Action::ColorHint(_) => 2,
+        Action::SuitHint(_) => 3,
+    };
+    bits[NUM_PLAYERS + move_type] = 1;
+
+    let target = NUM_PLAYERS + 4;
+    let color = target + NUM_PLAYERS;
+    let rank = color + 5;
+    let revealed = rank + 5;
+    let position = revealed + HAND_SIZE;
+    let card = position + HAND_SIZE;
+    let success = card + NUM_CARDS;
+    let added_token = success + 1;
+
+    match last_turn.action {
+        Action::ColorHint(c) => {
+            bits[target] = 1;
+            bits[color + hle_color(c)] = 1;
+        }
+        Action::SuitHint(s) => {
+            bits[target] = 1;
+            bits[rank + s as usize] = 1;
+        }
+        _ => {}
+    }
+    for &i in last_turn.touched.iter() {
+        bits[revealed + i] = 1;
+    }
+    if let Some(i) = last_turn.slot {
+        bits[position + i] = 1;
+    }
+    if let Some(c) = last_turn.card {
+        bits[card + hle_card(c)] = 1;
+    }
+    if last_turn.successful_play {
+        bits[success] = 1;
+    }
+    let completed_color =
+        last_turn.successful_play && last_turn.card.is_some_and(|c| c.suit() == Suit::Five);
+    let discarded = matches!(last_turn.action, Action::Discard(_));
+    if discarded || completed_color {
+        bits[added_token] = 1;
+    }
+}
+
+// the observing player is the player to move, `last_turn` is the partner's previous move.
+// note: slots keep their position when a card is replaced, whereas HLE shifts cards left
+// and draws into the last slot, so slot indices only line up between draws
+pub fn encode_observation(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    last_turn: Option<&TurnRecord>,
+) -> Vec<u8> {
+    let mut bits = vec![0; HLE_OBSERVATION_SIZE];
+    let (hands, rest) = bits.split_at_mut(HANDS_SIZE);
+    let (board, rest) = rest.split_at_mut(BOARD_SIZE);
+    let (discards, rest) = rest.split_at_mut(DISCARDS_SIZE);
+    let (last_action, knowledge) = rest.split_at_mut(LAST_ACTION_SIZE);
+
+    for (i, &card) in private_info.opponent_hand.iter().enumerate() {
+        if card.is_some() {
+            hands[i * NUM_CARDS + hle_card(card)] = 1;
+        }
+    }
+    let missing = HAND_SIZE * NUM_CARDS;
+    if public_info.player_hints.iter().any(|h| !h.is_some()) {
+        hands[missing] = 1;
+    }
+    if public_info.opponent_hints.iter().any(|h| !h.is_some()) {
+        hands[missing + 1] = 1;
+    }
+
+    let (deck, board) = board.split_at_mut(50 - NUM_PLAYERS * HAND_SIZE);
+    let (fireworks, tokens) = board.split_at_mut(NUM_CARDS);
+    thermometer(deck, public_info.deck_total() as usize);
+    for color_id in 0..5 {
+        let level = public_info.fireworks.0[color_id as usize];
+        if level > 0 {
+            fireworks[hle_card(Card::from_parts(color_id, level - 1))] = 1;
+        }
+    }
+    thermometer(&mut tokens[..8], public_info.blue_tokens.get() as usize);
+    // the game ends when black tokens reach 1, so the lives left are one less
+    thermometer(
+        &mut tokens[8..],
+        public_info.black_tokens.get() as usize - 1,
+    );
+
+    let counts = CardCollection::starting_deck().counts;
+    for id in 0..25 {
+        let card = Card::from_id(id);
+        // each color takes 10 bits, ranks thermometer encoded within: 3, 2, 2, 2, 1
+        let color_offset = hle_color(card.color()) * 10;
+        let rank_offset = (0..card.suit_id() as usize)
+            .map(|r| counts[r] as usize)
+            .sum::<usize>();
+        let start = color_offset + rank_offset;
+        thermometer(
+            &mut discards[start..start + counts[id as usize] as usize],
+            public_info.discard.counts[id as usize] as usize,
+        );
+    }
+
+    if let Some(last_turn) = last_turn {
+        encode_last_action(last_turn, last_action);
+    }
+
+    let (own, partner) = knowledge.split_at_mut(HAND_SIZE * (NUM_CARDS + 10));
+    encode_knowledge(&public_info.player_hints, own);
+    encode_knowledge(&public_info.opponent_hints, partner);
+
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::hanabi_env::ActionList;
+    use crate::hanabi_env::HanabiEnv;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+    use crate::record::GameRecord;
+
+    #[test]
+    fn test_legal_move_mask() {
+        use crate::env::HasEnd;
+        use crate::rand::prelude::SliceRandom;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10 {
+            let mut env = HanabiEnv::random(&mut rng);
+            while !env.is_over() {
+                let public_info = env.public_info();
+                let mut expected = 0;
+                let mut actions = ActionList::new();
+                env.actions_into(&mut actions);
+                for action in actions.iter() {
+                    for uid in move_uids(&public_info, action) {
+                        expected |= 1 << uid;
+                    }
+                }
+                let mask = legal_move_mask(&public_info, &env.private_info(env.current_player()));
+                assert_eq!(mask, expected);
+                assert_eq!(
+                    legal_moves_from_mask(mask).iter().filter(|&&l| l).count(),
+                    mask.count_ones() as usize
+                );
+                let action = *env.actions().choose(&mut rng).unwrap();
+                env.step(&action, &mut rng);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_observation() {
+        assert_eq!(HLE_OBSERVATION_SIZE, 658);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        let bits = encode_observation(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            None,
+        );
+        assert_eq!(bits.len(), HLE_OBSERVATION_SIZE);
+        // 5 visible cards, a full deck, 8 info tokens, 3 lives & every card plausible
+        assert_eq!(bits[..HANDS_SIZE].iter().sum::<u8>(), 5);
+        let board = &bits[HANDS_SIZE..HANDS_SIZE + BOARD_SIZE];
+        assert_eq!(board.iter().sum::<u8>(), 40 + 8 + 3);
+        let knowledge = &bits[HLE_OBSERVATION_SIZE - KNOWLEDGE_SIZE..];
+        assert_eq!(knowledge.iter().map(|&b| b as usize).sum::<usize>(), 250);
+
+        let mut record = GameRecord::new(["a".to_string(), "b".to_string()]);
+        let action = Action::ColorHint(env.opponent_hand[0].color());
+        record.step(&mut env, &action, &mut rng);
+        let bits = encode_observation(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            record.turns.last(),
+        );
+        let start = HANDS_SIZE + BOARD_SIZE + DISCARDS_SIZE;
+        let last_action = &bits[start..start + LAST_ACTION_SIZE];
+        assert_eq!(last_action[1], 1);
+        assert_eq!(last_action[NUM_PLAYERS + 2], 1);
+        assert_eq!(last_action[NUM_PLAYERS + 4], 1);
+        assert_eq!(
+            last_action[NUM_PLAYERS + 4 + NUM_PLAYERS + 10],
+            1,
+            "slot 0 was revealed"
+        );
+    }
+}
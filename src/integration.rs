@@ -0,0 +1,72 @@
+// end to end tripwires: whole seeded self-play games have to finish, only ever take legal
+// actions and reach a sane score. tests are built optimized, see [profile.test] in Cargo.toml
+use crate::env::{Env, HasEnd};
+use crate::evaluator::heuristic_policy;
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::mcts::MCTS;
+use crate::rand::rngs::StdRng;
+use crate::rand::{Rng, SeedableRng};
+use crate::search::Budget;
+
+const SEEDS: [u64; 4] = [1, 2, 3, 4];
+const NUM_EXPLORES: usize = 1_000;
+
+fn play<P: FnMut(&PublicInfo, &PrivateInfo, &mut StdRng) -> Action>(
+    seed: u64,
+    mut policy: P,
+) -> u8 {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut env = HanabiEnv::random(&mut rng);
+    let mut num_turns = 0;
+    while !env.is_over() {
+        let action = policy(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            &mut rng,
+        );
+        env.try_step(&action, &mut rng).unwrap();
+        num_turns += 1;
+        assert!(num_turns <= 100, "the game didn't end");
+    }
+    env.fireworks.total()
+}
+
+// the mean score over SEEDS
+fn mean_score<P: FnMut(&PublicInfo, &PrivateInfo, &mut StdRng) -> Action>(mut policy: P) -> f32 {
+    let total: u32 = SEEDS
+        .iter()
+        .map(|&seed| play(seed, &mut policy) as u32)
+        .sum();
+    total as f32 / SEEDS.len() as f32
+}
+
+#[test]
+fn test_search_game_within_budget() {
+    let search = mean_score(|public_info, private_info, rng| {
+        let mut mcts =
+            MCTS::<HanabiEnv>::with_capacity(public_info, private_info, NUM_EXPLORES, rng.gen());
+        mcts.explore_within(Budget::Iterations(NUM_EXPLORES));
+        // the budget is counted in simulations, each one a visit of the root
+        assert_eq!(mcts.nodes[mcts.root].num_visits as usize, NUM_EXPLORES);
+        mcts.best_action()
+    });
+    let random = mean_score(|public_info, private_info, rng| {
+        let (env, _) = HanabiEnv::determinize(public_info, private_info, rng);
+        env.random_action(rng)
+    });
+    // plain MCTS averages 6.5 on these deals & random play 1, so this trips when the search
+    // stops being clearly better than random
+    assert!(
+        search >= 5.0 && search >= random + 3.0,
+        "search {} random {}",
+        search,
+        random
+    );
+}
+
+#[test]
+fn test_heuristic_game_score() {
+    let score =
+        mean_score(|public_info, private_info, _| heuristic_policy(public_info, private_info));
+    assert!(score >= 15.0, "scored {}", score);
+}
@@ -0,0 +1,349 @@
+// keyed storage for the caches that outlive a run (endgame values, opening books, search
+// results), so each of them stores bytes through one trait instead of its own file format:
+// - MemoryStore: a sorted map, for tests and caches that are thrown away
+// - FileStore: a memory store backed by an append-only log, later records override earlier
+//   ones. the log is
+//
+//     "HKV1" | (key length u32 | value length u32 | key bytes | value bytes)*
+//
+//   with lengths little endian
+// - SledStore: a sled database, with the sled-store feature
+// - Table: the read-only tables in table.rs, values are f32 bits
+// `copy_store` moves entries between any two of them
+use crate::table::Table;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Write};
+
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+pub trait KvStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String>;
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), String>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // every entry, sorted by key
+    fn entries(&self) -> Result<Vec<Entry>, String>;
+
+    // makes every put so far durable
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// copies every entry of `from` into `to`, returning how many were copied
+pub fn copy_store(from: &dyn KvStore, to: &mut dyn KvStore) -> Result<usize, String> {
+    let entries = from.entries()?;
+    for (key, value) in entries.iter() {
+        to.put(key, value)?;
+    }
+    to.flush()?;
+    Ok(entries.len())
+}
+
+// "memory" for a MemoryStore, a path ending in ".sled" for a SledStore and any other path for a
+// FileStore
+pub fn open_store(spec: &str) -> Result<Box<dyn KvStore>, String> {
+    if spec == "memory" {
+        return Ok(Box::new(MemoryStore::new()));
+    }
+    if spec.ends_with(".sled") {
+        #[cfg(feature = "sled-store")]
+        return Ok(Box::new(SledStore::open(spec)?));
+        #[cfg(not(feature = "sled-store"))]
+        return Err(format!("{}: sled stores need the sled-store feature", spec));
+    }
+    Ok(Box::new(FileStore::open(spec)?))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    map: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvStore for MemoryStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        Ok(self.map.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), String> {
+        self.map.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn entries(&self) -> Result<Vec<Entry>, String> {
+        Ok(self
+            .map
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+}
+
+const LOG_MAGIC: &[u8; 4] = b"HKV1";
+
+pub struct FileStore {
+    path: String,
+    memory: MemoryStore,
+    log: BufWriter<File>,
+}
+
+// the records of a log, and how many of its bytes hold whole ones. a crash in the middle of a put
+// leaves a partial record at the end, which is dropped. a file without the magic is an error
+fn read_log(path: &str, bytes: &[u8]) -> Result<(MemoryStore, usize), String> {
+    let mut memory = MemoryStore::new();
+    if bytes.len() < 4 || &bytes[..4] != LOG_MAGIC {
+        return Err(format!("{}: not a store log", path));
+    }
+    let u32_at = |at: usize| {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(&bytes[at..at + 4]);
+        u32::from_le_bytes(buf) as usize
+    };
+    let mut at = 4;
+    while at < bytes.len() {
+        if at + 8 > bytes.len() {
+            break;
+        }
+        let (key_len, value_len) = (u32_at(at), u32_at(at + 4));
+        let end = at + 8 + key_len + value_len;
+        if end > bytes.len() {
+            break;
+        }
+        let key = &bytes[at + 8..at + 8 + key_len];
+        memory
+            .map
+            .insert(key.to_vec(), bytes[at + 8 + key_len..end].to_vec());
+        at = end;
+    }
+    Ok((memory, at))
+}
+
+impl FileStore {
+    // loads the log at `path`, creating it when there's none. a partial record left at the end
+    // by a crash is cut off, so the next put starts on a record boundary
+    pub fn open(path: &str) -> Result<Self, String> {
+        let io = |e: std::io::Error| format!("{}: {}", path, e);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(io)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(io)?;
+        // a log cut off before its magic was written through is as good as empty
+        let memory = if LOG_MAGIC.starts_with(&bytes) && bytes.len() < LOG_MAGIC.len() {
+            file.set_len(0).map_err(io)?;
+            file.write_all(LOG_MAGIC).map_err(io)?;
+            MemoryStore::new()
+        } else {
+            let (memory, whole) = read_log(path, &bytes)?;
+            if whole < bytes.len() {
+                file.set_len(whole as u64).map_err(io)?;
+            }
+            memory
+        };
+        Ok(Self {
+            path: path.to_string(),
+            memory,
+            log: BufWriter::new(file),
+        })
+    }
+}
+
+impl KvStore for FileStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        self.memory.get(key)
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), String> {
+        if self.memory.map.get(key).map(|v| v.as_slice()) == Some(value) {
+            return Ok(());
+        }
+        let mut write = || -> std::io::Result<()> {
+            self.log.write_all(&(key.len() as u32).to_le_bytes())?;
+            self.log.write_all(&(value.len() as u32).to_le_bytes())?;
+            self.log.write_all(key)?;
+            self.log.write_all(value)
+        };
+        write().map_err(|e| format!("{}: {}", self.path, e))?;
+        self.memory.put(key, value)
+    }
+
+    fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    fn entries(&self) -> Result<Vec<Entry>, String> {
+        self.memory.entries()
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.log
+            .flush()
+            .map_err(|e| format!("{}: {}", self.path, e))
+    }
+}
+
+impl Drop for FileStore {
+    fn drop(&mut self) {
+        let _ = self.log.flush();
+    }
+}
+
+#[cfg(feature = "sled-store")]
+pub struct SledStore {
+    db: sled::Db,
+}
+
+#[cfg(feature = "sled-store")]
+impl SledStore {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let db = sled::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self { db })
+    }
+}
+
+#[cfg(feature = "sled-store")]
+impl KvStore for SledStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        let value = self.db.get(key).map_err(|e| e.to_string())?;
+        Ok(value.map(|v| v.to_vec()))
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), String> {
+        self.db.insert(key, value).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.db.len()
+    }
+
+    fn entries(&self) -> Result<Vec<Entry>, String> {
+        self.db
+            .iter()
+            .map(|entry| {
+                let (k, v) = entry.map_err(|e| e.to_string())?;
+                Ok((k.to_vec(), v.to_vec()))
+            })
+            .collect()
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.db.flush().map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+impl KvStore for Table {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+        Ok(Table::get(self, key).map(|v| v.to_bits().to_le_bytes().to_vec()))
+    }
+
+    fn put(&mut self, _key: &[u8], _value: &[u8]) -> Result<(), String> {
+        Err("tables are read-only, see table::write_table".to_string())
+    }
+
+    fn len(&self) -> usize {
+        Table::len(self)
+    }
+
+    fn entries(&self) -> Result<Vec<Entry>, String> {
+        Ok((0..Table::len(self))
+            .map(|i| {
+                let (key, value) = self.record(i);
+                (key.to_vec(), value.to_bits().to_le_bytes().to_vec())
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::write_table;
+
+    #[test]
+    fn test_stores() {
+        let dir = std::env::temp_dir();
+        let log = dir.join(format!("hanabi-store-{}.log", std::process::id()));
+        let log = log.to_str().unwrap();
+        {
+            let mut store = open_store(log).unwrap();
+            assert!(store.is_empty());
+            store.put(b"b", b"2").unwrap();
+            store.put(b"a", b"1").unwrap();
+            store.put(b"b", b"3").unwrap();
+            assert_eq!(store.get(b"b").unwrap(), Some(b"3".to_vec()));
+        }
+        // later records win when the log is read back
+        let store = FileStore::open(log).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get(b"b").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(store.get(b"c").unwrap(), None);
+
+        let mut memory = open_store("memory").unwrap();
+        assert_eq!(copy_store(&store, &mut *memory).unwrap(), 2);
+        assert_eq!(memory.entries().unwrap(), store.entries().unwrap());
+        std::fs::remove_file(log).unwrap();
+
+        std::fs::write(log, b"HKV0").unwrap();
+        assert!(FileStore::open(log).is_err());
+        std::fs::remove_file(log).unwrap();
+
+        // a put cut off by a crash: the partial record is dropped, and the next put reads back
+        {
+            let mut store = FileStore::open(log).unwrap();
+            store.put(b"a", b"1").unwrap();
+        }
+        let whole = std::fs::read(log).unwrap();
+        let mut cut = whole.clone();
+        cut.extend_from_slice(&[1, 0, 0, 0, 5, 0, 0, 0, b'b', b'2']);
+        for len in whole.len() + 1..cut.len() {
+            std::fs::write(log, &cut[..len]).unwrap();
+            let mut store = FileStore::open(log).unwrap();
+            assert_eq!(store.len(), 1);
+            assert_eq!(std::fs::read(log).unwrap(), whole);
+            store.put(b"c", b"3").unwrap();
+            drop(store);
+            let store = FileStore::open(log).unwrap();
+            assert_eq!(store.get(b"c").unwrap(), Some(b"3".to_vec()));
+            assert_eq!(store.get(b"b").unwrap(), None);
+        }
+        // or by one before the log's magic was written
+        std::fs::write(log, b"HK").unwrap();
+        assert!(FileStore::open(log).unwrap().is_empty());
+        assert_eq!(std::fs::read(log).unwrap(), LOG_MAGIC);
+        std::fs::remove_file(log).unwrap();
+
+        // a table reads like any other store
+        let table = dir.join(format!("hanabi-store-{}.bin", std::process::id()));
+        let table = table.to_str().unwrap();
+        write_table(table, 1, vec![(vec![7], 0.5)]).unwrap();
+        let mut table_store = Table::open(table).unwrap();
+        std::fs::remove_file(table).unwrap();
+        assert_eq!(
+            KvStore::get(&table_store, &[7]).unwrap(),
+            Some(0.5f32.to_bits().to_le_bytes().to_vec())
+        );
+        assert!(table_store.put(&[8], &[0; 4]).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+// level-k reasoning about the partner. a level 0 player moves at random and a level 1 player
+// plays the heuristic. a level k player searches: each rollout tries one of its moves in a
+// sampled deal, then both seats play the rest of the game as level k - 1 players on a smaller
+// budget. so at level 2 the player assumes a heuristic partner, at level 3 a partner that
+// itself assumes a heuristic partner, and so on
+use crate::env::{Env, HasEnd, HasReward};
+use crate::evaluator::heuristic_policy;
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::rand::Rng;
+
+// each level down searches with this fraction of the budget above it
+pub const BUDGET_SHRINK: usize = 10;
+
+// `budget` is the number of rollouts at this level. a level whose share of the budget is less
+// than its number of moves still tries each of them once, rather than ranking moves it never
+// tried
+pub fn level_k_policy<R: Rng>(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    depth: usize,
+    budget: usize,
+    rng: &mut R,
+) -> Action {
+    if depth == 1 {
+        return heuristic_policy(public_info, private_info);
+    }
+    let (env, _) = HanabiEnv::determinize(public_info, private_info, rng);
+    if depth == 0 {
+        return env.random_action(rng);
+    }
+    let actions = env.actions();
+    let budget = budget.max(actions.len());
+
+    let mut totals = vec![0.0; actions.len()];
+    let mut visits = vec![0; actions.len()];
+    for r in 0..budget {
+        let i = r % actions.len();
+        let (mut world, _) = HanabiEnv::determinize(public_info, private_info, rng);
+        world.step(&actions[i], rng);
+        while !world.is_over() {
+            let action = level_k_policy(
+                &world.public_info(),
+                &world.private_info(world.current_player()),
+                depth - 1,
+                budget / BUDGET_SHRINK,
+                rng,
+            );
+            world.step(&action, rng);
+        }
+        totals[i] += world.reward();
+        visits[i] += 1;
+    }
+
+    let best = (0..actions.len())
+        .max_by(|&a, &b| {
+            let mean = |i: usize| totals[i] / visits[i] as f32;
+            mean(a)
+                .partial_cmp(&mean(b))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap();
+    actions[best]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_level_k_policy() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let legal = env.actions();
+        for depth in 0..4 {
+            let action = level_k_policy(&public_info, &private_info, depth, 30, &mut rng);
+            assert!(legal.contains(&action), "level {}: {:?}", depth, action);
+        }
+
+        let heuristic = heuristic_policy(&public_info, &private_info);
+        assert_eq!(
+            level_k_policy(&public_info, &private_info, 1, 1000, &mut rng),
+            heuristic
+        );
+        // too small a budget to try every move still tries each of them once
+        let action = level_k_policy(&public_info, &private_info, 2, 1, &mut rng);
+        assert!(legal.contains(&action));
+
+        let mut a = StdRng::seed_from_u64(1);
+        let mut b = StdRng::seed_from_u64(1);
+        assert_eq!(
+            level_k_policy(&public_info, &private_info, 2, 60, &mut a),
+            level_k_policy(&public_info, &private_info, 2, 60, &mut b)
+        );
+    }
+}
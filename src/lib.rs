@@ -0,0 +1,67 @@
+// the game, the agents and the search code, shared by the command line in main.rs and the
+// benchmarks in benches/
+extern crate rand;
+
+pub mod abstraction;
+pub mod action_info;
+pub mod action_stats;
+pub mod advisor;
+pub mod analysis;
+pub mod attribution;
+pub mod beliefs;
+pub mod dashboard;
+pub mod deals;
+pub mod endgame;
+pub mod env;
+pub mod evaluator;
+pub mod explain;
+pub mod features;
+pub mod finesse;
+pub mod flat;
+pub mod guess_env;
+pub mod gym;
+pub mod hanab_live;
+pub mod hanabi_distr;
+pub mod hanabi_env;
+pub mod hanabi_symmetry;
+pub mod harness;
+pub mod hgroup;
+pub mod hle;
+#[cfg(test)]
+mod integration;
+pub mod kv_store;
+pub mod level_k;
+pub mod mcts;
+pub mod migrate;
+pub mod mini;
+pub mod nmcs;
+pub mod notation;
+pub mod nrpa;
+#[cfg(feature = "onnx")]
+pub mod onnx_evaluator;
+pub mod opening_book;
+pub mod position;
+pub mod recommend;
+pub mod record;
+pub mod registry;
+#[cfg(feature = "color")]
+pub mod render;
+pub mod replay;
+pub mod results;
+pub mod rollout_rng;
+pub mod runner;
+pub mod sampler_check;
+#[cfg(test)]
+mod scenarios;
+pub mod search;
+pub mod selfcheck;
+pub mod selfplay;
+#[cfg(test)]
+mod snapshot;
+pub mod sparse;
+pub mod state_json;
+pub mod strength;
+pub mod table;
+pub mod telemetry;
+#[cfg(feature = "tui")]
+pub mod tui;
@@ -1,213 +1,1691 @@
 extern crate rand;
 
-mod env;
-mod hanabi_env;
-// mod mcts;
-mod hanabi_distr;
+use hanabi::abstraction::TrashDiscards;
+use hanabi::action_info::{suggestions_to_json, ActionInfo};
+use hanabi::action_stats::{learn_stats, with_stats_bias, ActionStats};
+use hanabi::advisor::advise;
+use hanabi::attribution::{attribute, summarize_attributions, Attribution};
+use hanabi::beliefs::{heatmaps_to_csv, heatmaps_to_json};
+use hanabi::dashboard::scan;
+use hanabi::deals::{DealConstraint, DealGenerator};
+use hanabi::endgame::{EndgameSolver, ENDGAME_DECK_SIZE};
+use hanabi::env::{Env, HasEnd, HasReward};
+use hanabi::evaluator::heuristic_policy;
+use hanabi::explain::explain;
+use hanabi::finesse::set_finesse_weight;
+use hanabi::flat::{
+    policy, policy_with_budget, rollout_shaped, rollout_single_determinization, rollout_switching,
+    OPENING_BOOK, REWARD_SWITCH, UTILITY,
+};
+use hanabi::hanab_live::{import_dump, parse_export, ImportFilter};
+use hanabi::hanabi_env::{
+    Action, Card, CardCollection, DominanceRules, EndgameReward, HanabiEnv, Hint, PrivateInfo,
+    PublicInfo, RewardShaping, RewardSwitch, RuleSet, SearchPruning, Utility,
+};
+use hanabi::harness::{cross_play, play_game, play_games, resume_games, timings, GameOutcome};
+use hanabi::hgroup::HGroupEvaluator;
+use hanabi::mcts::{best_merged_action, root_parallel, MCTS};
+use hanabi::mini::{mini_baseline, mini_rules};
+use hanabi::notation::{notate_turn, parse_legal_action, parse_line};
+#[cfg(feature = "onnx")]
+use hanabi::onnx_evaluator::OnnxEvaluator;
+use hanabi::opening_book::{build_book, hit_rate, OpeningBook};
+use hanabi::position::Position;
+use hanabi::record::{clue_stats_by_seat, GameRecord};
+use hanabi::registry::{
+    self, agent_names, make_agent, prune_agents, register_agent, register_builtins,
+    register_level_k, register_sparse, reward_model, reward_model_names, score_reward,
+    SPARSE_SAMPLES, SPARSE_WORLDS,
+};
+#[cfg(feature = "color")]
+use hanabi::render;
+use hanabi::replay::{write_replays, Replay};
+use hanabi::results::{
+    compare_paired, merge_results, read_results, summarize, write_csv, write_json, write_results,
+    GameResult, Shard, Summary,
+};
+use hanabi::rollout_rng::{set_rng_kind, RngKind, RolloutRng};
+use hanabi::runner::{deal, move_time_limit, observing, GameRunner};
+use hanabi::sampler_check::{check_sampler, POSITIONS};
+use hanabi::search::{
+    world_values, Budget, Criterion, EarlyStop, RootSampling, SampleOver, SearchMetrics,
+    VarianceReduction, WorldAllocation,
+};
+use hanabi::selfcheck::run_selfcheck;
+use hanabi::selfplay::{run_selfplay, write_positions, SelfPlayConfig};
+use hanabi::state_json::cards_to_json;
+use hanabi::strength::{strength_level, strength_policy, STRENGTH_LADDER};
+use hanabi::table::Table;
+use hanabi::telemetry::{self, ActionStat, Decision};
+#[cfg(feature = "tui")]
+use hanabi::tui;
 
-use env::{Env, HasEnd, HasReward};
-use hanabi_env::{Action, Card, CardCollection, HanabiEnv, Hint, PrivateInfo, PublicInfo};
-
-use crate::rand::prelude::SliceRandom;
 use crate::rand::rngs::StdRng;
-use crate::rand::SeedableRng;
+use crate::rand::{Rng, SeedableRng};
 
-use std::time::Instant;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-fn rollout_single_determinization(
-    public_info: PublicInfo,
-    my_private: PrivateInfo,
-    mut rng: &mut StdRng,
-) -> (Action, f32) {
-    let (mut env, prob) = HanabiEnv::determinize(&public_info, &my_private, &mut rng);
-    let action = *env.actions().choose(&mut rng).unwrap();
-    env.step(&action, &mut rng);
+fn open_endgame_table(path: &str) -> Arc<Table> {
+    let start = Instant::now();
+    let table = Table::open(path).unwrap();
+    println!(
+        "endgame table {}: {} states in {:.1} ms",
+        path,
+        table.len(),
+        start.elapsed().as_secs_f32() * 1000.0
+    );
+    Arc::new(table)
+}
+
+// solves the endgame of heuristic self-play games and saves every state reached along the way
+fn build_endgame_table(out: &str, num_games: usize, merge: Option<&Table>) {
+    let mut solver = EndgameSolver::new();
+    for seed in 0..num_games as u64 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut env = HanabiEnv::random(&mut rng);
+        while !env.is_over() && env.deck.remaining() > ENDGAME_DECK_SIZE {
+            let action =
+                heuristic_policy(&env.public_info(), &env.private_info(env.current_player()));
+            env.step(&action, &mut rng);
+        }
+        if !env.is_over() {
+            // the states are solved clairvoyantly, the table holds determinized upper bounds
+            solver.upper_bound_values(
+                &env.public_info(),
+                &env.private_info(env.current_player()),
+                &mut rng,
+            );
+        }
+        println!("game {}: {} states", seed, solver.cache_len());
+    }
+    solver.save_table(out, merge).unwrap();
+}
+
+// set once from the command line, `--ms-per-move`
+static MS_PER_MOVE: OnceLock<u64> = OnceLock::new();
+
+// set once from the command line, `--risk`
+static RISK: OnceLock<Criterion> = OnceLock::new();
+
+// set once from the command line, `--action-stats`
+static ACTION_STATS: OnceLock<Arc<ActionStats>> = OnceLock::new();
+
+// set once from the command line, `--widening`
+static WIDENING: OnceLock<f32> = OnceLock::new();
 
-    while !env.is_over() {
-        env.step(env.actions().choose(&mut rng).unwrap(), &mut rng);
+// set once from the command line, `--single-observer`
+static SINGLE_OBSERVER: OnceLock<bool> = OnceLock::new();
+
+// set once from the command line, `--rave`
+static RAVE: OnceLock<f32> = OnceLock::new();
+
+// set once from the command line, `--hgroup-partner`
+static HGROUP_PARTNER: OnceLock<f32> = OnceLock::new();
+
+// the value after the flag at `i`, a flag ending the command line is an error like an unknown one
+fn flag_value(args: &[String], i: usize) -> &str {
+    match args.get(i + 1) {
+        Some(value) => value,
+        None => panic!("flag {} needs a value", args[i]),
     }
+}
 
-    (action, prob * env.reward())
+// the flat mc & mcts agents search for `--ms-per-move` when it's given, `iterations` otherwise.
+// under evaluate's `--game-time-limit` they search for the move's share of the time left, or
+// for `--ms-per-move` when that's shorter
+fn move_budget(iterations: usize) -> Budget {
+    match (MS_PER_MOVE.get(), move_time_limit()) {
+        (Some(&millis), Some(limit)) => Budget::Millis(millis.min(limit)),
+        (Some(&millis), None) => Budget::Millis(millis),
+        (None, Some(limit)) => Budget::Millis(limit),
+        (None, None) => Budget::Iterations(iterations),
+    }
 }
 
-fn policy<F: Fn(PublicInfo, PrivateInfo, &mut StdRng) -> (Action, f32)>(
+fn mcts_policy(
     public_info: PublicInfo,
     private_info: PrivateInfo,
-    rollout_fn: &F,
-    num_rollouts: usize,
-    mut rng: &mut StdRng,
+    num_explores: usize,
+    rng: &mut StdRng,
 ) -> Action {
-    let mut actions = Vec::new();
-    let mut rewards = Vec::new();
-    let mut upper = std::f32::NEG_INFINITY;
-    let mut lower = std::f32::INFINITY;
-    let mut child_upper = Vec::new();
-    let mut child_lower = Vec::new();
-    let mut visits = Vec::new();
-
-    for _ in 0..num_rollouts {
-        let (action, reward) = rollout_fn(public_info.clone(), private_info.clone(), &mut rng);
-
-        if upper < reward {
-            upper = reward;
-        }
-        if lower > reward {
-            lower = reward;
-        }
-
-        match actions.iter().position(|&a| a == action) {
-            Some(i) => {
-                rewards[i] += reward;
-                visits[i] += 1;
-                if child_upper[i] < reward {
-                    child_upper[i] = reward;
-                }
-                if child_lower[i] > reward {
-                    child_lower[i] = reward;
-                }
-            }
-            None => {
-                actions.push(action);
-                rewards.push(reward);
-                child_lower.push(reward);
-                child_upper.push(reward);
-                visits.push(1);
-            }
-        }
-    }
-
-    let mut best_i = 0;
-    let mut best_score = std::f32::NEG_INFINITY;
-    for i in 0..rewards.len() {
-        let total_reward = rewards[i];
-        // let mean_reward = total_reward / visits[i] as f32;
-        // // let ugape = child_upper[i] - lower;
-        // let mut B = std::f32::NEG_INFINITY;
-        // for j in 0..rewards.len() {
-        //     if i == j {
-        //         continue;
-        //     }
-        //     let ugap = child_upper[j] - child_lower[i];
-        //     if ugap > B {
-        //         B = ugap;
-        //     }
-        // }
-        // println!(
-        //     "{:?}: {} / {} = {} | [{} {}]  | {}",
-        //     actions[i], total_reward, visits[i], mean_reward, child_lower[i], child_upper[i], B,
-        // );
-        if total_reward > best_score {
-            best_score = total_reward;
-            best_i = i;
-        }
-        // if ugape > best_score {
-        //     best_i = i;
-        //     best_score = ugape;
-        // }
-    }
-
-    actions[best_i]
-}
-
-fn describe_game<F: Fn(PublicInfo, PrivateInfo, &mut StdRng) -> (Action, f32)>(
-    rollout_fn: &F,
-    num_rollouts: usize,
-) {
+    if let Some(action) = OPENING_BOOK
+        .get()
+        .and_then(|book| book.lookup(&public_info, &private_info))
+    {
+        return action;
+    }
+    let start = Instant::now();
+    let seed = rng.gen();
+    let mut mcts =
+        MCTS::<HanabiEnv>::with_capacity(&public_info, &private_info, num_explores, seed);
+    if let Some(&utility) = UTILITY.get() {
+        mcts = mcts.with_utility(Box::new(move |env| env.public_info().utility(utility)));
+    }
+    if let Some(stats) = ACTION_STATS.get() {
+        mcts = with_stats_bias(mcts, stats.clone());
+    }
+    if let Some(&alpha) = WIDENING.get() {
+        mcts = mcts.with_widening(alpha);
+    }
+    if SINGLE_OBSERVER.get() == Some(&true) {
+        mcts = mcts.with_observer_key(Box::new(|_| 0));
+    }
+    if let Some(&k) = RAVE.get() {
+        mcts = mcts.with_rave(k);
+    }
+    if let Some(&weight) = HGROUP_PARTNER.get() {
+        mcts = mcts.with_partner_prior(Box::new(HGroupEvaluator { weight }));
+    }
+    mcts.explore_within(move_budget(num_explores));
+    let action = mcts.best_action();
+    if telemetry::enabled() {
+        telemetry::record(&Decision {
+            searcher: "mcts",
+            legal_actions: telemetry::legal_actions(&public_info, &private_info, seed),
+            budget: num_explores,
+            actions: mcts
+                .root_stats()
+                .into_iter()
+                .map(|(action, visits, value)| ActionStat {
+                    action,
+                    visits,
+                    value,
+                })
+                .collect(),
+            chosen: action,
+            millis: start.elapsed().as_secs_f32() * 1000.0,
+        });
+    }
+    action
+}
+
+#[cfg(feature = "onnx")]
+fn onnx_policy(
+    evaluator: &OnnxEvaluator,
+    public_info: PublicInfo,
+    private_info: PrivateInfo,
+    num_explores: usize,
+    rng: &mut StdRng,
+) -> Action {
+    let mut mcts =
+        MCTS::<HanabiEnv>::with_capacity(&public_info, &private_info, num_explores, rng.gen())
+            .with_evaluator(Box::new(evaluator.clone()));
+    mcts.explore_n(num_explores);
+    mcts.best_action()
+}
+
+fn describe_game<F>(rollout_fn: &F, num_rollouts: usize)
+where
+    F: Fn(&PublicInfo, &PrivateInfo, Option<Action>, &mut RolloutRng) -> (Action, f32, f32),
+{
     let mut rng = StdRng::seed_from_u64(0);
+    let env = HanabiEnv::random(&mut rng);
+    let policy_fn = |public_info, private_info, rng: &mut StdRng| {
+        policy(public_info, private_info, rollout_fn, num_rollouts, rng)
+    };
+    let runner = GameRunner::from_env(env, rng, 0)
+        .seat(0, "flat-mc", observing(policy_fn))
+        .seat(1, "flat-mc", observing(policy_fn))
+        .before_turn(|env| {
+            println!();
+            env.describe();
+        })
+        .after_turn(|env, _| {
+            env.describe();
+            println!();
+        })
+        .run();
+    println!("{} {}", runner.env.reward(), runner.env.fireworks.total());
+}
 
-    let mut env = HanabiEnv::random(&mut rng);
+fn evaluate<P: FnMut(PublicInfo, PrivateInfo, &mut StdRng) -> Action>(
+    name: &str,
+    policy_fn: &mut P,
+    num_games: usize,
+    shard: Shard,
+    output: Option<&str>,
+    game_time_limit: Option<Duration>,
+    reward_model: &dyn Fn(&HanabiEnv) -> f32,
+) -> Vec<GameResult> {
+    let mut outcomes = Vec::new();
+    let mut total_reward = 0.0;
 
-    while !env.is_over() {
-        let action = policy(
-            env.public_info(),
-            env.private_info(true),
-            rollout_fn,
-            num_rollouts,
-            &mut rng,
+    // every game gets its own seed so shards on different machines play the same deals as a single run
+    for seed in shard.seeds(num_games) {
+        let outcome = play_game(name, policy_fn, seed, game_time_limit, reward_model);
+        total_reward += outcome.reward;
+        outcomes.push(outcome);
+        println!(
+            "{} ({} / {})",
+            total_reward / outcomes.len() as f32,
+            total_reward,
+            outcomes.len()
         );
-        println!();
-        env.describe();
-        // println!();
-        // println!(">>> {:?}", action);
-        // println!();
-        env.step(&action, &mut rng);
-        env.describe();
-        println!();
     }
-    println!("{} {}", env.reward(), env.fireworks.total());
+
+    report(&outcomes, output, None);
+    outcomes.iter().map(|o| o.result).collect()
 }
 
-fn evaluate<F: Fn(PublicInfo, PrivateInfo, &mut StdRng) -> (Action, f32)>(
-    rollout_fn: &F,
-    num_rollouts: usize,
-) {
-    let mut rng = StdRng::seed_from_u64(0);
+// `review` is the explores per move to search every decision with for the attribution, without
+// it only bombs count as errors. few explores leave noisy values that overcount decision errors
+fn report(outcomes: &[GameOutcome], output: Option<&str>, review: Option<usize>) {
+    let results: Vec<GameResult> = outcomes.iter().map(|o| o.result).collect();
+    if let Some(path) = output {
+        write_results(path, &results).expect("failed to write results");
+    }
+    let summary = summarize(&results);
+    println!("{}", summary);
+    for line in summary.histogram_lines() {
+        println!("{}", line);
+    }
+    println!("{}", timings(outcomes));
 
-    let mut rewards = Vec::new();
+    let records: Vec<GameRecord> = outcomes.iter().map(|o| o.record.clone()).collect();
+    for (seat, stats) in clue_stats_by_seat(&records).iter().enumerate() {
+        println!(
+            "seat {}: clues given={} received={} | given efficiency={:.2} received efficiency={:.2}",
+            seat,
+            stats.clues_given,
+            stats.clues_received,
+            stats.given_efficiency(),
+            stats.received_efficiency(),
+        );
+    }
 
-    for _ in 0..100 {
-        let mut env = HanabiEnv::random(&mut rng);
+    let attributions: Vec<Attribution> = outcomes
+        .iter()
+        .map(|o| {
+            let initial = deal(o.result.seed);
+            let reviews = review.map(|num_explores| {
+                Replay::from_game(&initial, &o.record)
+                    .review(num_explores, o.result.seed)
+                    .unwrap()
+            });
+            attribute(&initial, &o.record, reviews.as_deref()).unwrap()
+        })
+        .collect();
+    println!("{}", summarize_attributions(&attributions));
+}
 
-        while !env.is_over() {
-            let action = policy(
-                env.public_info(),
-                env.private_info(true),
-                rollout_fn,
-                num_rollouts,
-                &mut rng,
-            );
-            env.step(&action, &mut rng);
+// mean scores with the row agent in seat 0 and the column agent in seat 1, then how each agent
+// does with copies of itself against how it does with everyone else
+fn print_cross_play(names: &[String], matrix: &[Vec<Summary>]) {
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0).max(8);
+    print!("{:<width$}", "", width = width);
+    for name in names.iter() {
+        print!(" {:>width$}", name, width = width);
+    }
+    println!();
+    for (name, row) in names.iter().zip(matrix.iter()) {
+        print!("{:<width$}", name, width = width);
+        for summary in row.iter() {
+            let cell = format!("{:.2}±{:.2}", summary.mean, summary.std_err);
+            print!(" {:>width$}", cell, width = width);
         }
-
-        rewards.push(env.fireworks.total() as f32);
-
-        let total_reward = rewards.iter().sum::<f32>();
+        println!();
+    }
+    println!();
+    for (i, name) in names.iter().enumerate() {
+        let others: Vec<f32> = (0..names.len())
+            .filter(|&j| j != i)
+            .flat_map(|j| vec![matrix[i][j].mean, matrix[j][i].mean])
+            .collect();
+        let cross = others.iter().sum::<f32>() / others.len().max(1) as f32;
         println!(
-            "{} ({} / {})",
-            total_reward / rewards.len() as f32,
-            total_reward,
-            rewards.len()
+            "{}: self-play={:.2} cross-play={:.2}",
+            name, matrix[i][i].mean, cross
         );
     }
 }
 
-fn rollout_speed<F: Fn(PublicInfo, PrivateInfo, &mut StdRng) -> (Action, f32)>(
-    rollout_fn: &F,
+fn flat_policy(public_info: PublicInfo, private_info: PrivateInfo, rng: &mut StdRng) -> Action {
+    flat_policy_with(public_info, private_info, VarianceReduction::None, rng)
+}
+
+fn flat_policy_with(
+    public_info: PublicInfo,
+    private_info: PrivateInfo,
+    variance: VarianceReduction,
+    rng: &mut StdRng,
+) -> Action {
+    policy_with_budget(
+        public_info,
+        private_info,
+        &rollout_single_determinization,
+        move_budget(50_000),
+        variance,
+        rng,
+    )
+}
+
+fn compare_world_allocation(
     num_rollouts: usize,
+    num_games: usize,
+    restart_alert: Option<usize>,
+    log_restarts: bool,
 ) {
-    let mut rng = StdRng::seed_from_u64(0);
-    let env = HanabiEnv::random(&mut rng);
-    let public_info = env.public_info();
-    let private_info = env.private_info(true);
-
-    let mut times = Vec::new();
-    loop {
-        let start = Instant::now();
+    let adaptive = WorldAllocation::Adaptive {
+        min_worlds: 10,
+        max_worlds: 1000,
+    };
+    let early_stop = EarlyStop {
+        min_worlds: 10,
+        delta: 0.05,
+    };
+    let allocations = [
+        ("fixed", WorldAllocation::Fixed(100), None),
+        ("adaptive", adaptive, None),
+        ("adaptive+early-stop", adaptive, Some(early_stop)),
+    ];
+    for &(name, allocation, early_stop) in allocations.iter() {
+        let mut metrics = SearchMetrics {
+            restart_alert,
+            log_restarts,
+            ..SearchMetrics::default()
+        };
+        let results = evaluate(
+            name,
+            &mut |public_info, private_info, rng| {
+                world_values(
+                    &public_info,
+                    &private_info,
+                    num_rollouts,
+                    allocation,
+                    early_stop,
+                    &mut metrics,
+                    rng,
+                )
+                .best(Criterion::Mean)
+            },
+            num_games,
+            Shard::all(),
+            None,
+            None,
+            &score_reward,
+        );
+        println!("{}: {} | {}", name, summarize(&results), metrics);
+    }
+}
 
-        for _ in 0..num_rollouts {
-            rollout_fn(public_info.clone(), private_info.clone(), &mut rng);
+// plays the flat monte carlo agent with every combination of the shaped reward's terms on the
+// same deals, and reports each one's score against the full reward
+fn ablate(num_rollouts: usize, num_games: usize) {
+    let mut rows = Vec::new();
+    for &score in [true, false].iter() {
+        for &future in [true, false].iter() {
+            for &tokens in [true, false].iter() {
+                let shaping = RewardShaping {
+                    score,
+                    future,
+                    tokens,
+                };
+                let name = format!("score={} future={} tokens={}", score, future, tokens);
+                let results = evaluate(
+                    &name,
+                    &mut |public_info, private_info, rng| {
+                        policy(
+                            public_info,
+                            private_info,
+                            &|public_info, private_info, first, rng| {
+                                rollout_shaped(public_info, private_info, first, &shaping, rng)
+                            },
+                            num_rollouts,
+                            rng,
+                        )
+                    },
+                    num_games,
+                    Shard::all(),
+                    None,
+                    None,
+                    &score_reward,
+                );
+                rows.push((name, summarize(&results)));
+            }
         }
+    }
+
+    // the first row has every term on
+    let full_mean = rows[0].1.mean;
+    println!(
+        "{:<40} {:>7} {:>7} {:>7}",
+        "terms", "mean", "stderr", "delta"
+    );
+    for (name, summary) in rows.iter() {
+        println!(
+            "{:<40} {:>7.3} {:>7.3} {:>+7.3}",
+            name,
+            summary.mean,
+            summary.std_err,
+            summary.mean - full_mean
+        );
+    }
+}
+
+// plays the same deals with the shaped reward throughout and with each endgame reward switched in
+// once the deck is down to `deck_size`, each against the unswitched run seed by seed
+fn compare_reward_switch(num_rollouts: usize, num_games: usize, deck_size: u8) {
+    let switches = [
+        None,
+        Some(RewardSwitch {
+            deck_size,
+            reward: EndgameReward::Score,
+        }),
+        Some(RewardSwitch {
+            deck_size,
+            reward: EndgameReward::Perfect,
+        }),
+    ];
+    let mut runs = Vec::new();
+    for switch in switches.iter() {
+        let name = match switch {
+            Some(switch) => format!("{:?}:{}", switch.reward, switch.deck_size),
+            None => "shaped".to_string(),
+        };
+        let results = evaluate(
+            &name,
+            &mut |public_info, private_info, rng| {
+                policy(
+                    public_info,
+                    private_info,
+                    &|public_info, private_info, first, rng| {
+                        rollout_switching(
+                            public_info,
+                            private_info,
+                            first,
+                            &RewardShaping::full(),
+                            switch.as_ref(),
+                            rng,
+                        )
+                    },
+                    num_rollouts,
+                    rng,
+                )
+            },
+            num_games,
+            Shard::all(),
+            None,
+            None,
+            &score_reward,
+        );
+        runs.push((name, results));
+    }
+
+    for (name, results) in runs.iter() {
+        println!("{}: {}", name, summarize(results));
+    }
+    for (name, results) in runs.iter().skip(1) {
+        println!(
+            "{} - shaped: {}",
+            name,
+            compare_paired(results, &runs[0].1).unwrap()
+        );
+    }
+}
+
+// plays the same deals choosing root actions by each criterion, reporting how often it disagrees
+// with the mean and how often games end on the last black token
+fn compare_criteria(criteria: &[Criterion], num_rollouts: usize, num_games: usize) {
+    for &criterion in criteria.iter() {
+        let mut metrics = SearchMetrics::default();
+        let mut disagreements = 0;
+        let bomb_outs = std::cell::Cell::new(0);
+        let results = evaluate(
+            &format!("{:?}", criterion),
+            &mut |public_info, private_info, rng| {
+                let values = world_values(
+                    &public_info,
+                    &private_info,
+                    num_rollouts,
+                    WorldAllocation::Fixed(100),
+                    None,
+                    &mut metrics,
+                    rng,
+                );
+                if values.best(Criterion::Mean) != values.best(criterion) {
+                    disagreements += 1;
+                }
+                values.best(criterion)
+            },
+            num_games,
+            Shard::all(),
+            None,
+            None,
+            &|env| {
+                if env.black_tokens == 1 {
+                    bomb_outs.set(bomb_outs.get() + 1);
+                }
+                score_reward(env)
+            },
+        );
+        let worst = results.iter().map(|r| r.score).min().unwrap_or(0);
+        println!(
+            "{:?}: {} | worst={} bomb-outs={} | disagrees with the mean on {}/{} decisions",
+            criterion,
+            summarize(&results),
+            worst,
+            bomb_outs.get(),
+            disagreements,
+            metrics.decisions
+        );
+    }
+}
 
-        let elapsed = start.elapsed().as_millis() as f32;
-        times.push(elapsed);
-        times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+fn compare_abstraction(num_explores: usize, num_games: usize) {
+    for &merge_trash in [false, true].iter() {
+        let name = if merge_trash {
+            "trash-discards"
+        } else {
+            "exact"
+        };
+        let mut num_nodes = 0;
+        let mut num_decisions = 0;
+        let results = evaluate(
+            name,
+            &mut |public_info, private_info, rng| {
+                let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+                    &public_info,
+                    &private_info,
+                    num_explores,
+                    rng.gen(),
+                );
+                if merge_trash {
+                    mcts = mcts.with_abstraction(Box::new(TrashDiscards));
+                }
+                mcts.explore_n(num_explores);
+                num_nodes += mcts.nodes.len();
+                num_decisions += 1;
+                mcts.best_action()
+            },
+            num_games,
+            Shard::all(),
+            None,
+            None,
+            &score_reward,
+        );
+        println!(
+            "{}: {} | nodes/decision={:.1}",
+            name,
+            summarize(&results),
+            num_nodes as f32 / num_decisions.max(1) as f32
+        );
+    }
+}
 
-        let mean_time = times.iter().sum::<f32>() / times.len() as f32;
-        let median_time = times[times.len() / 2];
-        println!("mean={}ms | median={}ms", mean_time, median_time);
+// --file f.json loads a saved replay, --seed S records a game played by the heuristic agent
+fn load_replay(file: Option<&str>, seed: Option<u64>) -> Replay {
+    match (file, seed) {
+        (Some(path), _) => Replay::from_json(&std::fs::read_to_string(path).unwrap()).unwrap(),
+        (None, Some(seed)) => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let env = HanabiEnv::random(&mut rng);
+            let runner = GameRunner::from_env(env, rng, seed).run();
+            Replay::from_game(&runner.initial, &runner.record)
+        }
+        (None, None) => panic!("expected --file f.json or --seed S"),
     }
 }
 
+// colored when built with the color feature
+fn show(env: &HanabiEnv) {
+    #[cfg(feature = "color")]
+    print!("{}", render::render_env(env));
+    #[cfg(not(feature = "color"))]
+    env.describe();
+}
+
 fn main() {
-    println!("Card {}", std::mem::size_of::<Card>());
-    println!("Hint {}", std::mem::size_of::<Hint>());
-    println!("CardCollection {}", std::mem::size_of::<CardCollection>());
-    println!("Env {}", std::mem::size_of::<HanabiEnv>());
-    println!("PublicInfo {}", std::mem::size_of::<PublicInfo>());
-    println!("PrivateInfo {}", std::mem::size_of::<PrivateInfo>());
-    println!();
+    register_builtins();
+    register_agent("flat-mc", || Box::new(flat_policy));
+    // the same budget spent rolling out every root action in each sampled world
+    register_agent("flat-mc-crn", || {
+        Box::new(|public_info, private_info, rng| {
+            let variance = VarianceReduction::CommonRandomNumbers;
+            flat_policy_with(public_info, private_info, variance, rng)
+        })
+    });
+    register_agent("mcts", || {
+        Box::new(|public_info, private_info, rng| {
+            mcts_policy(public_info, private_info, 10_000, rng)
+        })
+    });
+    // ten times the "mcts" budget, what build-opening-book searches with by default so the book
+    // is stronger than the agents reading it
+    register_agent("mcts-deep", || {
+        Box::new(|public_info, private_info, rng| {
+            mcts_policy(public_info, private_info, 100_000, rng)
+        })
+    });
+    // rollouts over sampled worlds, choosing by `--risk` so partners who don't expect bomb-risk
+    // plays can be given a safer bot
+    register_agent("world-mc", || {
+        Box::new(|public_info, private_info, rng| {
+            let criterion = RISK.get().copied().unwrap_or(Criterion::Mean);
+            world_values(
+                &public_info,
+                &private_info,
+                10_000,
+                WorldAllocation::Fixed(100),
+                None,
+                &mut SearchMetrics::default(),
+                rng,
+            )
+            .best(criterion)
+        })
+    });
+    // the same budget as "mcts" split over a tree per core
+    register_agent("mcts-parallel", || {
+        Box::new(|public_info, private_info, rng| {
+            let num_trees = std::thread::available_parallelism().map_or(4, |n| n.get());
+            let stats = root_parallel::<HanabiEnv>(
+                &public_info,
+                &private_info,
+                num_trees,
+                10_000 / num_trees,
+                rng.gen(),
+            );
+            best_merged_action(&stats)
+        })
+    });
+
+    let mut args: Vec<String> = std::env::args().collect();
+    // --rng std|small anywhere on the command line picks the rng rollouts draw from
+    if let Some(i) = args.iter().position(|a| a == "--rng") {
+        set_rng_kind(RngKind::parse(flag_value(&args, i)).unwrap());
+        args.drain(i..i + 2);
+    }
+    // --finesse <weight> weights sampled deals that read the partner's clues as finesses
+    if let Some(i) = args.iter().position(|a| a == "--finesse") {
+        set_finesse_weight(flag_value(&args, i).parse().unwrap());
+        args.drain(i..i + 2);
+    }
+    // --ms-per-move <ms> gives the flat-mc & mcts agents a wall clock budget per move instead of
+    // their fixed rollout & explore counts
+    if let Some(i) = args.iter().position(|a| a == "--ms-per-move") {
+        let _ = MS_PER_MOVE.set(flag_value(&args, i).parse().unwrap());
+        args.drain(i..i + 2);
+    }
+    // --reward-switch score:<n>|perfect:<n>|off scores flat rollouts by the final score, or by
+    // whether it's perfect, once the deck is down to n cards. off keeps the shaped reward to the
+    // end, without the flag the rollouts switch to the score at 5 cards
+    if let Some(i) = args.iter().position(|a| a == "--reward-switch") {
+        let switch = match flag_value(&args, i) {
+            "off" => None,
+            text => Some(RewardSwitch::parse(text).unwrap()),
+        };
+        let _ = REWARD_SWITCH.set(switch);
+        args.drain(i..i + 2);
+    }
+    // --utility expected|at-least:<score> has flat rollouts and mcts maximize the chance of
+    // finishing with at least the score, rather than the expected score
+    if let Some(i) = args.iter().position(|a| a == "--utility") {
+        let _ = UTILITY.set(Utility::parse(flag_value(&args, i)).unwrap());
+        args.drain(i..i + 2);
+    }
+    // --risk mean|maximin|mean-sigma:<k>|cvar:<alpha> picks the world-mc agent's root actions by
+    // the spread of their rollout rewards, not only the mean
+    if let Some(i) = args.iter().position(|a| a == "--risk") {
+        let _ = RISK.set(Criterion::parse(flag_value(&args, i)).unwrap());
+        args.drain(i..i + 2);
+    }
+    // --min-hint-info <bits> leaves clues telling the partner less than this out of the searches
+    let mut pruning = SearchPruning::default();
+    if let Some(i) = args.iter().position(|a| a == "--min-hint-info") {
+        pruning.min_hint_info = flag_value(&args, i).parse().unwrap();
+        args.drain(i..i + 2);
+    }
+    // --opening-book <path> plays the book's moves in the first turns instead of searching, see
+    // build-opening-book
+    if let Some(i) = args.iter().position(|a| a == "--opening-book") {
+        let book = OpeningBook::open(flag_value(&args, i)).unwrap();
+        println!(
+            "opening book {}: {} positions",
+            flag_value(&args, i),
+            book.len()
+        );
+        let _ = OPENING_BOOK.set(book);
+        args.drain(i..i + 2);
+    }
+    // --action-stats <path> biases mcts searches towards what the stats learned, see learn-stats
+    if let Some(i) = args.iter().position(|a| a == "--action-stats") {
+        let stats = ActionStats::open(flag_value(&args, i)).unwrap();
+        println!(
+            "action stats {}: {} entries",
+            flag_value(&args, i),
+            stats.len()
+        );
+        let _ = ACTION_STATS.set(Arc::new(stats));
+        args.drain(i..i + 2);
+    }
+    // --widening <alpha> has mcts nodes keep their determinizations, ceil((visits + 1)^alpha) of
+    // them, rather than sampling a new one every visit
+    if let Some(i) = args.iter().position(|a| a == "--widening") {
+        let _ = WIDENING.set(flag_value(&args, i).parse().unwrap());
+        args.drain(i..i + 2);
+    }
+    // --single-observer files every world of a public state under one mcts node below the root,
+    // rather than a node per information set of the mover
+    if let Some(i) = args.iter().position(|a| a == "--single-observer") {
+        let _ = SINGLE_OBSERVER.set(true);
+        args.remove(i);
+    }
+    // --rave <k> blends mcts edge values with their all-moves-as-first values, about equally at
+    // k visits
+    if let Some(i) = args.iter().position(|a| a == "--rave") {
+        let _ = RAVE.set(flag_value(&args, i).parse().unwrap());
+        args.drain(i..i + 2);
+    }
+    // --hgroup-partner <weight> has mcts expect H-Group conventions from the partner, putting
+    // that much of the prior at their nodes on the conventional move. ours keep their rollouts
+    if let Some(i) = args.iter().position(|a| a == "--hgroup-partner") {
+        let _ = HGROUP_PARTNER.set(flag_value(&args, i).parse().unwrap());
+        args.drain(i..i + 2);
+    }
+    // --every-slot has the searches branch on a play & discard for every slot, rather than one
+    // per distinct hint
+    if let Some(i) = args.iter().position(|a| a == "--every-slot") {
+        pruning.collapse_slots = false;
+        args.remove(i);
+    }
+    // --sparse-agents registers sparse-1 to sparse-3, which don't beat the heuristic yet
+    if let Some(i) = args.iter().position(|a| a == "--sparse-agents") {
+        for depth in 1..4 {
+            register_sparse(depth, SPARSE_SAMPLES, SPARSE_WORLDS);
+        }
+        args.remove(i);
+    }
+    // --search-dominated turns off the DominanceRules, letting searches play known trash or
+    // unplayable cards and discard known playable or critical ones
+    if let Some(i) = args.iter().position(|a| a == "--search-dominated") {
+        pruning.dominance = DominanceRules::none();
+        args.remove(i);
+    }
+    // the agents & advise search with what the flags above picked
+    if pruning != SearchPruning::default() {
+        prune_agents(pruning);
+    }
+    match args.get(1).map(|a| a.as_str()) {
+        Some("evaluate") => {
+            let mut shard = Shard::all();
+            let mut num_games = 100;
+            let mut output = None;
+            let mut model = None;
+            let mut game_time_limit = None;
+            let mut agent = "flat-mc";
+            let mut reward = reward_model("score").unwrap();
+            let mut num_workers = 1;
+            let mut json = None;
+            let mut csv = None;
+            let mut checkpoint = None;
+            let mut review = None;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--shard" => shard = Shard::parse(flag_value(&args, i)).unwrap(),
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--workers" => num_workers = flag_value(&args, i).parse().unwrap(),
+                    "--out" => output = Some(flag_value(&args, i)),
+                    "--json" => json = Some(flag_value(&args, i)),
+                    "--csv" => csv = Some(flag_value(&args, i)),
+                    "--checkpoint" => checkpoint = Some(flag_value(&args, i)),
+                    "--review" => review = Some(flag_value(&args, i).parse().unwrap()),
+                    "--telemetry" => telemetry::open(flag_value(&args, i)).unwrap(),
+                    "--model" => model = Some(flag_value(&args, i)),
+                    "--agent" => agent = flag_value(&args, i),
+                    "--reward" => reward = reward_model(flag_value(&args, i)).unwrap(),
+                    "--game-time-limit" => {
+                        game_time_limit = Some(Duration::from_secs_f32(
+                            flag_value(&args, i).parse().unwrap(),
+                        ))
+                    }
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            match model {
+                #[cfg(feature = "onnx")]
+                Some(path) => {
+                    let evaluator = OnnxEvaluator::load(path).unwrap();
+                    evaluate(
+                        "onnx-mcts",
+                        &mut |public_info, private_info, rng| {
+                            onnx_policy(&evaluator, public_info, private_info, 1_000, rng)
+                        },
+                        num_games,
+                        shard,
+                        output,
+                        game_time_limit,
+                        &*reward,
+                    );
+                }
+                #[cfg(not(feature = "onnx"))]
+                Some(_) => panic!("--model needs the onnx feature"),
+                // games already in the checkpoint are skipped and the summary covers them too
+                None if checkpoint.is_some() => {
+                    let (outcomes, results) = resume_games(
+                        [agent, agent],
+                        &shard.seeds(num_games),
+                        num_workers,
+                        game_time_limit,
+                        reward,
+                        checkpoint.unwrap(),
+                    )
+                    .unwrap();
+                    println!(
+                        "played {} of {} games this run",
+                        outcomes.len(),
+                        results.len()
+                    );
+                    if !outcomes.is_empty() {
+                        report(&outcomes, None, review);
+                    }
+                    if let Some(path) = output {
+                        write_results(path, &results).expect("failed to write results");
+                    }
+                    println!("all games: {}", summarize(&results));
+                    if let Some(path) = json {
+                        write_json(path, agent, &results).expect("failed to write json");
+                    }
+                    if let Some(path) = csv {
+                        write_csv(path, &results).expect("failed to write csv");
+                    }
+                }
+                None => {
+                    let outcomes = play_games(
+                        [agent, agent],
+                        &shard.seeds(num_games),
+                        num_workers,
+                        game_time_limit,
+                        reward,
+                    )
+                    .unwrap();
+                    report(&outcomes, output, review);
+                    let results: Vec<GameResult> = outcomes.iter().map(|o| o.result).collect();
+                    if let Some(path) = json {
+                        write_json(path, agent, &results).expect("failed to write json");
+                    }
+                    if let Some(path) = csv {
+                        write_csv(path, &results).expect("failed to write csv");
+                    }
+                }
+            }
+        }
+        Some("compare") => {
+            let (a, b) = (args[2].as_str(), args[3].as_str());
+            let mut shard = Shard::all();
+            let mut num_games = 100;
+            let mut num_workers = 1;
+            let mut i = 4;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--shard" => shard = Shard::parse(flag_value(&args, i)).unwrap(),
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--workers" => num_workers = flag_value(&args, i).parse().unwrap(),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let seeds = shard.seeds(num_games);
+            let results: Vec<Vec<GameResult>> = [a, b]
+                .iter()
+                .map(|name| {
+                    play_games(
+                        [*name, *name],
+                        &seeds,
+                        num_workers,
+                        None,
+                        reward_model("score").unwrap(),
+                    )
+                    .unwrap()
+                    .iter()
+                    .map(|o| o.result)
+                    .collect()
+                })
+                .collect();
+            println!("{}: {}", a, summarize(&results[0]));
+            println!("{}: {}", b, summarize(&results[1]));
+            println!(
+                "{} - {}: {}",
+                a,
+                b,
+                compare_paired(&results[0], &results[1]).unwrap()
+            );
+        }
+        Some("cross-play") => {
+            let mut names = Vec::new();
+            let mut num_games = 100;
+            let mut num_workers = 1;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--games" => {
+                        num_games = flag_value(&args, i).parse().unwrap();
+                        i += 1;
+                    }
+                    "--workers" => {
+                        num_workers = flag_value(&args, i).parse().unwrap();
+                        i += 1;
+                    }
+                    name => names.push(name.to_string()),
+                }
+                i += 1;
+            }
+            let seeds = Shard::all().seeds(num_games);
+            let matrix =
+                cross_play(&names, &seeds, num_workers, reward_model("score").unwrap()).unwrap();
+            print_cross_play(&names, &matrix);
+        }
+        Some("level-k") => {
+            // how the score moves with reasoning depth: level-0 up to --max-depth play each
+            // other with --budget rollouts at the top level
+            let mut max_depth = 3;
+            let mut budget = registry::LEVEL_K_BUDGET;
+            let mut num_games = 20;
+            let mut num_workers = 1;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--max-depth" => max_depth = flag_value(&args, i).parse().unwrap(),
+                    "--budget" => budget = flag_value(&args, i).parse().unwrap(),
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--workers" => num_workers = flag_value(&args, i).parse().unwrap(),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let names: Vec<String> = (0..=max_depth)
+                .map(|depth| {
+                    register_level_k(depth, budget);
+                    format!("level-{}", depth)
+                })
+                .collect();
+            let seeds = Shard::all().seeds(num_games);
+            let matrix =
+                cross_play(&names, &seeds, num_workers, reward_model("score").unwrap()).unwrap();
+            print_cross_play(&names, &matrix);
+        }
+        Some("verify-sampler") => {
+            let mut num_samples = 100_000;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--samples" => num_samples = flag_value(&args, i).parse().unwrap(),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut failures = 0;
+            for (name, text) in POSITIONS.iter() {
+                let position = Position::parse(text).unwrap();
+                for check in check_sampler(&position, num_samples, &mut rng) {
+                    // strict enough that a dozen slots rarely trip it by chance
+                    let biased = check.p_value < 0.001;
+                    failures += biased as usize;
+                    println!(
+                        "{} slot {}: chi2={:.1} dof={} p={:.4}{}",
+                        name,
+                        check.slot + 1,
+                        check.chi_squared,
+                        check.dof,
+                        check.p_value,
+                        if biased { " BIASED" } else { "" }
+                    );
+                }
+            }
+            println!("{} biased slots", failures);
+        }
+        Some("agents") => {
+            println!("agents: {}", agent_names().join(", "));
+            println!("reward models: {}", reward_model_names().join(", "));
+        }
+        Some("advise") => {
+            // deals the game for --seed, replays --line, and advises whoever is to move next.
+            // --position "<position string>" advises from a pasted position instead,
+            // --json prints the suggestions with each action's metadata, --explain why the top
+            // suggestion beat the next few. --strength <level> prints the move a level of the
+            // strength ladder makes instead, for a bot partnering someone in a live game
+            let mut seed = 0;
+            let mut strength = None;
+            let mut line = "";
+            let mut position = None;
+            let mut json = false;
+            let mut explanation = false;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--seed" => {
+                        seed = flag_value(&args, i).parse().unwrap();
+                        i += 1;
+                    }
+                    "--line" => {
+                        line = flag_value(&args, i);
+                        i += 1;
+                    }
+                    "--position" => {
+                        position = Some(Position::parse(flag_value(&args, i)).unwrap());
+                        i += 1;
+                    }
+                    "--strength" => {
+                        strength = Some(strength_level(flag_value(&args, i)).unwrap());
+                        i += 1;
+                    }
+                    "--json" => json = true,
+                    "--explain" => explanation = true,
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 1;
+            }
+            let mut position = position.unwrap_or_else(|| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut env = HanabiEnv::random(&mut rng);
+                for turn in parse_line(line).unwrap() {
+                    turn.apply(&mut env, &mut rng).unwrap();
+                }
+                Position::from_env(&env)
+            });
+            position.public_info.pruning = pruning;
+            let (public_info, private_info) = (&position.public_info, &position.private_info);
+            if let Some(level) = strength {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let action =
+                    strength_policy(level, public_info.clone(), private_info.clone(), &mut rng);
+                println!("{}", position);
+                println!("{}: {:?}", level.name, action);
+            } else if explanation {
+                let suggestions = advise(public_info, private_info, 10_000, seed);
+                println!("{}", position);
+                match explain(public_info, private_info, &suggestions) {
+                    Some(explanation) => println!("{}", explanation),
+                    None => println!("no moves to explain"),
+                }
+            } else if json {
+                let suggestions = advise(public_info, private_info, 10_000, seed);
+                let value = suggestions_to_json(public_info, private_info, &suggestions);
+                println!("{}", serde_json::to_string_pretty(&value).unwrap());
+            } else {
+                println!("{}", position);
+                for suggestion in advise(public_info, private_info, 10_000, seed) {
+                    println!(
+                        "{:?}: visits={} mean={:.3} {}",
+                        suggestion.action,
+                        suggestion.visits,
+                        suggestion.mean_reward,
+                        ActionInfo::new(public_info, private_info, &suggestion.action).summary()
+                    );
+                }
+            }
+        }
+        Some("play") => {
+            // a game against a level of the strength ladder, `--strength`, moves typed on stdin
+            // as notation::parse_action reads them. `--seat 2` moves second, `--seed` deals
+            let mut level = strength_level("intermediate").unwrap();
+            let mut human = 0;
+            let mut seed = 0;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--strength" => level = strength_level(flag_value(&args, i)).unwrap(),
+                    "--seat" => human = flag_value(&args, i).parse::<usize>().unwrap() - 1,
+                    "--seed" => seed = flag_value(&args, i).parse().unwrap(),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let read_move = |env: &HanabiEnv, _: &mut StdRng| loop {
+                // what the human sees: the bot's hand and what the clues told them
+                println!("{}", Position::from_env(env));
+                print!("your move: ");
+                std::io::Write::flush(&mut std::io::stdout()).unwrap();
+                let mut text = String::new();
+                if std::io::stdin().read_line(&mut text).unwrap() == 0 {
+                    std::process::exit(0);
+                }
+                match parse_legal_action(env, &text) {
+                    Ok(action) => return action,
+                    Err(e) => println!("{}", e),
+                }
+            };
+            let bot = observing(move |public_info, private_info, rng| {
+                strength_policy(level, public_info, private_info, rng)
+            });
+            let mut turn_number = 0;
+            let runner = GameRunner::new(seed)
+                .seat(human, "you", Box::new(read_move))
+                .seat(1 - human, level.name, bot)
+                .after_turn(|_, turn| {
+                    turn_number += 1;
+                    println!("{}", notate_turn(turn_number, turn));
+                })
+                .run();
+            println!("score {}", runner.record.score);
+        }
+        Some("beliefs") => {
+            // per slot color x rank probability grids for a pasted --position, ours & the
+            // partner's modeled ones, as json or with --csv <path> as rows
+            let mut position = None;
+            let mut csv = None;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--position" => position = Some(Position::parse(flag_value(&args, i)).unwrap()),
+                    "--csv" => csv = Some(flag_value(&args, i)),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let position = position.expect("beliefs needs --position");
+            let (public_info, private_info) = (&position.public_info, &position.private_info);
+            match csv {
+                Some(path) => std::fs::write(path, heatmaps_to_csv(public_info, private_info))
+                    .expect("failed to write csv"),
+                None => {
+                    let value = heatmaps_to_json(public_info, private_info);
+                    println!("{}", serde_json::to_string_pretty(&value).unwrap());
+                }
+            }
+        }
+        Some("selfplay") => {
+            let mut config = SelfPlayConfig {
+                num_games: 100,
+                max_positions: None,
+                num_workers: 4,
+                num_explores: 1_000,
+                sampling: RootSampling {
+                    sample_over: SampleOver::Visits,
+                    temperature: 1.0,
+                    dirichlet_alpha: 0.3,
+                    noise_fraction: 0.25,
+                    greedy_after: 20,
+                },
+                seed: 0,
+            };
+            let mut output = "selfplay.jsonl";
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--games" => config.num_games = flag_value(&args, i).parse().unwrap(),
+                    "--positions" => {
+                        config.max_positions = Some(flag_value(&args, i).parse().unwrap())
+                    }
+                    "--workers" => config.num_workers = flag_value(&args, i).parse().unwrap(),
+                    "--explores" => config.num_explores = flag_value(&args, i).parse().unwrap(),
+                    "--seed" => config.seed = flag_value(&args, i).parse().unwrap(),
+                    "--out" => output = flag_value(&args, i),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let positions = run_selfplay(&config);
+            write_positions(output, &positions).expect("failed to write positions");
+            println!("wrote {} positions to {}", positions.len(), output);
+        }
+        Some("describe") => {
+            // prints a position saved with HanabiEnv::to_json
+            let path = match args.get(2).map(|a| a.as_str()) {
+                Some("--state") => &args[3],
+                _ => panic!("usage: describe --state <file.json>"),
+            };
+            let text = std::fs::read_to_string(path).unwrap();
+            let env = HanabiEnv::from_json(&text).unwrap();
+            show(&env);
+            println!("{}", Position::from_env(&env));
+        }
+        Some("replay") => {
+            // --seed S --out f.json records a game played by the heuristic agent,
+            // --file f.json plays one back, re-running the search at --analyze <turn>
+            let mut seed = None;
+            let mut file = None;
+            let mut out = None;
+            let mut analyze = None;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--seed" => seed = Some(flag_value(&args, i).parse().unwrap()),
+                    "--file" => file = Some(flag_value(&args, i)),
+                    "--out" => out = Some(flag_value(&args, i)),
+                    "--analyze" => analyze = Some(flag_value(&args, i).parse::<usize>().unwrap()),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let replay = load_replay(file, seed);
+            if let Some(path) = out {
+                std::fs::write(path, replay.to_json()).unwrap();
+            }
+
+            for (i, turn) in replay.turns.iter().enumerate() {
+                let env = replay.env_at(i + 1).unwrap();
+                show(&env);
+                if analyze == Some(i + 1) {
+                    for suggestion in advise(
+                        &env.public_info(),
+                        &env.private_info(env.current_player()),
+                        10_000,
+                        0,
+                    ) {
+                        let info = ActionInfo::new(
+                            &env.public_info(),
+                            &env.private_info(env.current_player()),
+                            &suggestion.action,
+                        );
+                        println!(
+                            "  {:?}: visits={} mean={:.3} {}",
+                            suggestion.action,
+                            suggestion.visits,
+                            suggestion.mean_reward,
+                            info.summary()
+                        );
+                    }
+                }
+                println!(">>> {}", turn);
+                println!();
+            }
+            show(&replay.env_at(replay.turns.len() + 1).unwrap());
+        }
+        Some("import-hanablive-dump") => {
+            // converts a hanab.live database dump into a binary replay file
+            // usage: import-hanablive-dump <dump.json> --out <games.bin> [--variant name]
+            //        [--players N] [--min-score N] [--max-score N]
+            let path = args
+                .get(2)
+                .expect("usage: import-hanablive-dump <dump.json> --out <games.bin>");
+            let mut filter = ImportFilter::default();
+            let mut out = None;
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--out" => out = Some(flag_value(&args, i)),
+                    "--variant" => filter.variant = flag_value(&args, i).to_string(),
+                    "--players" => filter.num_players = flag_value(&args, i).parse().unwrap(),
+                    "--min-score" => filter.min_score = flag_value(&args, i).parse().unwrap(),
+                    "--max-score" => filter.max_score = flag_value(&args, i).parse().unwrap(),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let out = out.expect("--out <games.bin> is required");
+            let dump = std::io::BufReader::new(std::fs::File::open(path).unwrap());
+            let (games, stats) = import_dump(dump, &filter);
+            let replays: Vec<Replay> = games.into_iter().map(|g| g.replay).collect();
+            write_replays(out, &replays).unwrap();
+            println!(
+                "read {} games: imported {}, filtered {}, failed {}",
+                stats.num_read,
+                stats.num_imported,
+                stats.num_filtered,
+                stats.failed.len()
+            );
+            for (i, why) in stats.failed.iter().take(10) {
+                println!("game {}: {}", i, why);
+            }
+        }
+        Some("generate-deals") => {
+            // deck orders meeting every --constraint, one JSON list of cards per line
+            // usage: generate-deals [--count N] [--seed S] [--constraint spec]... [--out deals.jsonl]
+            let mut generator = DealGenerator::new();
+            let (mut count, mut seed, mut out) = (10, 0, None);
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--count" => count = flag_value(&args, i).parse().unwrap(),
+                    "--seed" => seed = flag_value(&args, i).parse().unwrap(),
+                    "--constraint" => {
+                        generator =
+                            generator.with(DealConstraint::parse(flag_value(&args, i)).unwrap())
+                    }
+                    "--out" => out = Some(flag_value(&args, i).to_string()),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let mut rng = StdRng::seed_from_u64(seed);
+            let lines: Vec<String> = (0..count)
+                .map(|_| cards_to_json(&generator.generate(&mut rng).unwrap()).to_string())
+                .collect();
+            match out {
+                Some(path) => std::fs::write(path, lines.join("\n") + "\n").unwrap(),
+                None => lines.iter().for_each(|line| println!("{}", line)),
+            }
+        }
+        Some("import-hanablive") => {
+            // reviews every move of a hanab.live export against the search
+            // usage: import-hanablive <export.json> [--explores N] [--out replay.json]
+            let path = args.get(2).expect("usage: import-hanablive <export.json>");
+            let mut num_explores = 10_000;
+            let mut out = None;
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--explores" => num_explores = flag_value(&args, i).parse().unwrap(),
+                    "--out" => out = Some(flag_value(&args, i)),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let game = parse_export(&std::fs::read_to_string(path).unwrap()).unwrap();
+            if let Some(path) = out {
+                std::fs::write(path, game.replay.to_json()).unwrap();
+            }
 
-    // describe_game(&rollout_single_determinization, 500_000);
-    evaluate(&rollout_single_determinization, 50_000);
-    // rollout_speed(&rollout_single_determinization, 50_000);
+            let reviews = game.replay.review(num_explores, 0).unwrap();
+            let mut agreed = [0; 2];
+            let mut moves = [0; 2];
+            for (i, review) in reviews.iter().enumerate() {
+                let seat = i % 2;
+                moves[seat] += 1;
+                if review.agrees() {
+                    agreed[seat] += 1;
+                } else {
+                    println!(
+                        "turn {} {}: played {:?} ({}), search prefers {:?} ({:.3})",
+                        review.turn,
+                        game.players[seat],
+                        review.played,
+                        review
+                            .played_value
+                            .map_or("untried".to_string(), |v| format!("{:.3}", v)),
+                        review.best,
+                        review.best_value
+                    );
+                }
+            }
+            for seat in 0..2 {
+                println!(
+                    "{}: {}/{} moves agree with the search ({:.1}%)",
+                    game.players[seat],
+                    agreed[seat],
+                    moves[seat],
+                    100.0 * agreed[seat] as f32 / moves[seat].max(1) as f32
+                );
+            }
+        }
+        #[cfg(feature = "tui")]
+        Some("analyze") => {
+            // interactive board for a replay, see tui.rs for the keys
+            let mut seed = None;
+            let mut file = None;
+            let mut turn = 1;
+            let mut max_explores = 200_000;
+            let mut max_nodes = None;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--seed" => seed = Some(flag_value(&args, i).parse().unwrap()),
+                    "--file" => file = Some(flag_value(&args, i)),
+                    "--turn" => turn = flag_value(&args, i).parse().unwrap(),
+                    "--explores" => max_explores = flag_value(&args, i).parse().unwrap(),
+                    "--max-nodes" => max_nodes = Some(flag_value(&args, i).parse().unwrap()),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            tui::run(load_replay(file, seed), turn, max_explores, max_nodes).unwrap();
+        }
+        Some("compare-allocation") => {
+            let mut restart_alert = None;
+            let mut log_restarts = false;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--restart-alert" => {
+                        restart_alert = Some(flag_value(&args, i).parse().unwrap());
+                        i += 1;
+                    }
+                    "--log-restarts" => log_restarts = true,
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 1;
+            }
+            compare_world_allocation(50_000, 100, restart_alert, log_restarts);
+        }
+        Some("compare-abstraction") => compare_abstraction(10_000, 100),
+        Some("compare-maximin") => {
+            compare_criteria(&[Criterion::Mean, Criterion::Maximin], 10_000, 100)
+        }
+        Some("compare-risk") => {
+            let mut num_games = 100;
+            let mut num_rollouts = 10_000;
+            let mut criteria = vec![Criterion::Mean];
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--rollouts" => num_rollouts = flag_value(&args, i).parse().unwrap(),
+                    "--criterion" => criteria.push(Criterion::parse(flag_value(&args, i)).unwrap()),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            if criteria.len() == 1 {
+                criteria.extend_from_slice(&[Criterion::MeanMinusSigma(1.0), Criterion::CVaR(0.2)]);
+            }
+            compare_criteria(&criteria, num_rollouts, num_games);
+        }
+        Some("ablate") => {
+            let mut num_games = 50;
+            let mut num_rollouts = 5_000;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--rollouts" => num_rollouts = flag_value(&args, i).parse().unwrap(),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            ablate(num_rollouts, num_games);
+        }
+        Some("calibrate-strength") => {
+            // self-play scores of every level of the strength ladder on seeds 0..
+            let num_games = match args.get(2).map(|a| a.as_str()) {
+                Some("--games") => args[3].parse().unwrap(),
+                _ => 50,
+            };
+            for level in STRENGTH_LADDER.iter() {
+                let results = evaluate(
+                    level.name,
+                    &mut |public_info, private_info, rng| {
+                        strength_policy(level, public_info, private_info, rng)
+                    },
+                    num_games,
+                    Shard::all(),
+                    None,
+                    None,
+                    &score_reward,
+                );
+                println!(
+                    "{}: {} (table says {:.2})",
+                    level.name,
+                    summarize(&results),
+                    level.calibrated_score
+                );
+            }
+        }
+        Some("compare-reward-switch") => {
+            let mut num_games = 50;
+            let mut num_rollouts = 5_000;
+            let mut deck_size = 5;
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--rollouts" => num_rollouts = flag_value(&args, i).parse().unwrap(),
+                    "--deck" => deck_size = flag_value(&args, i).parse().unwrap(),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            compare_reward_switch(num_rollouts, num_games, deck_size);
+        }
+        Some("build-endgame-table") => {
+            let out = &args[2];
+            let mut num_games = 10;
+            let mut merge = None;
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--merge" => merge = Some(open_endgame_table(flag_value(&args, i))),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            build_endgame_table(out, num_games, merge.as_deref());
+        }
+        Some("learn-stats") => {
+            // self-play mcts games recording each search's action values. with --merge the
+            // searches are biased by the stats there, and what they learn is added to them
+            let out = &args[2];
+            let mut num_games = 100;
+            let mut num_explores = 1000;
+            let mut seed = 0;
+            let mut merge: Option<String> = None;
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--explores" => num_explores = flag_value(&args, i).parse().unwrap(),
+                    "--seed" => seed = flag_value(&args, i).parse().unwrap(),
+                    "--merge" => merge = Some(flag_value(&args, i).to_string()),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let prior = merge.map(|path| Arc::new(ActionStats::open(&path).unwrap()));
+            let start = Instant::now();
+            let mut stats = learn_stats(num_games, num_explores, seed, prior.clone());
+            if let Some(prior) = prior {
+                stats.merge(&prior);
+            }
+            let saved = stats.save(out).unwrap();
+            println!(
+                "{}: {} entries from {} games in {:.1}s",
+                out,
+                saved,
+                num_games,
+                start.elapsed().as_secs_f32()
+            );
+        }
+        Some("build-opening-book") => {
+            // the moves `--agent` picks over the first turns of the evaluation deals, and how many
+            // of the positions of `--held-out` deals after them the book answers
+            let out = &args[2];
+            let mut agent = "mcts-deep".to_string();
+            let mut num_deals = 100;
+            let mut num_held_out = 100;
+            let mut turns = 4;
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--agent" => agent = flag_value(&args, i).to_string(),
+                    "--deals" => num_deals = flag_value(&args, i).parse().unwrap(),
+                    "--turns" => turns = flag_value(&args, i).parse().unwrap(),
+                    "--held-out" => num_held_out = flag_value(&args, i).parse().unwrap(),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            let start = Instant::now();
+            let book = build_book(&mut make_agent(&agent).unwrap(), num_deals, turns).unwrap();
+            let saved = book.save(out).unwrap();
+            println!(
+                "{}: {} positions from {} deals in {:.1}s",
+                out,
+                saved,
+                num_deals,
+                start.elapsed().as_secs_f32()
+            );
+            // the heuristic plays where the book has no move, a search would only be slower
+            let mut fallback = make_agent("heuristic").unwrap();
+            let held_out = num_deals..num_deals + num_held_out;
+            let (hits, positions) = hit_rate(&book, &mut fallback, held_out, turns);
+            println!(
+                "held out deals: the book answers {} of {} positions ({:.1}%)",
+                hits,
+                positions,
+                100.0 * hits as f32 / positions.max(1) as f32
+            );
+        }
+        Some("open-endgame-table") => {
+            open_endgame_table(&args[2]);
+        }
+        Some("report") => {
+            let dashboard = scan(args.get(2).map_or(".", |d| d.as_str())).unwrap();
+            for line in dashboard.lines() {
+                println!("{}", line);
+            }
+        }
+        Some("merge-results") => {
+            let shards: Vec<Vec<GameResult>> = args[2..]
+                .iter()
+                .map(|path| read_results(path).unwrap())
+                .collect();
+            let merged = merge_results(&shards).unwrap();
+            println!("{}", summarize(&merged));
+        }
+        Some("selfcheck") => {
+            // fast checks that this build behaves, exits with 1 when any of them fails
+            let results = run_selfcheck();
+            for result in results.iter() {
+                println!("{}", result);
+            }
+            let failed = results.iter().filter(|r| !r.passed).count();
+            println!(
+                "{} of {} checks passed",
+                results.len() - failed,
+                results.len()
+            );
+            if failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some("mini-baseline") => {
+            // agents on mini-Hanabi deals next to the exact best score of each deal. --rules
+            // takes a RuleSet spec, e.g. "hand=3,colors=2,ranks=3", and --agent can be repeated
+            let mut num_games = 100;
+            let mut rules = mini_rules();
+            let mut agents = Vec::new();
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--games" => num_games = flag_value(&args, i).parse().unwrap(),
+                    "--rules" => rules = RuleSet::parse(flag_value(&args, i)).unwrap(),
+                    "--agent" => agents.push(flag_value(&args, i).to_string()),
+                    flag => panic!("unknown flag {}", flag),
+                }
+                i += 2;
+            }
+            if agents.is_empty() {
+                agents.push("heuristic".to_string());
+            }
+            for agent in agents.iter() {
+                println!("{}", mini_baseline(agent, &rules, num_games).unwrap());
+            }
+        }
+        // the bytes each of the types searches copy around take up
+        Some("type-sizes") => {
+            println!("Card {}", std::mem::size_of::<Card>());
+            println!("Hint {}", std::mem::size_of::<Hint>());
+            println!("CardCollection {}", std::mem::size_of::<CardCollection>());
+            println!("Env {}", std::mem::size_of::<HanabiEnv>());
+            println!("PublicInfo {}", std::mem::size_of::<PublicInfo>());
+            println!("PrivateInfo {}", std::mem::size_of::<PrivateInfo>());
+        }
+        // prints every turn of one flat-mc self-play game
+        Some("describe-game") => describe_game(&rollout_single_determinization, 500_000),
+        _ => {
+            evaluate(
+                "flat-mc",
+                &mut flat_policy,
+                100,
+                Shard::all(),
+                None,
+                None,
+                &score_reward,
+            );
+        }
+    }
+    telemetry::close().expect("failed to flush telemetry");
 }
@@ -1,257 +1,1089 @@
-use crate::env::{Env, BLACK, WHITE};
-use crate::rand::rngs::StdRng;
-use crate::rand::SeedableRng;
+use crate::abstraction::Abstraction;
+use crate::env::{Env, HasEnd, HasKey, HasReward, HasSymmetry};
+use crate::evaluator::Evaluator;
+use crate::rand::seq::SliceRandom;
+use crate::rollout_rng::RolloutRng;
+use crate::search::{Budget, RootSampling};
+use std::collections::HashMap;
 use std::time::Instant;
 
-pub struct Node<E: Env + Clone> {
-    pub parent: usize,
+pub type Utility<E> = Box<dyn Fn(&E) -> f32>;
+pub type ProgressiveBias<E> = Box<dyn Fn(&<E as Env>::PublicInfo, &<E as Env>::Action) -> f32>;
+pub type ObserverKey<E> = Box<dyn Fn(&E) -> u64>;
+
+pub struct Edge<E: Env> {
+    pub action: E::Action,
+    pub child: Option<usize>,
+    pub prior: f32,
+    pub reward: f32,
+    pub num_visits: f32,
+    // all-moves-as-first: the rewards of simulations where the node's mover played the action
+    // at any later turn, see `rave`
+    pub amaf_reward: f32,
+    pub amaf_visits: f32,
+}
+
+pub struct Node<E: Env> {
     pub public_info: E::PublicInfo,
-    pub my_private_info: E::PrivateInfo,
+    // what the mover saw in the world that first reached the node. with the public info it fixes
+    // the node's canonical frame
+    pub private_info: E::PrivateInfo,
+    // what the node is filed under in the table
+    pub key: u64,
     pub terminal: bool,
-    pub expanded: bool,
-    pub my_action: bool,
-    pub children: Vec<(E::Action, usize)>,
-    pub reward: f32,
+    pub edges: Vec<Edge<E>>,
     pub num_visits: f32,
+    // the determinizations the node has kept, with progressive widening on
+    pub worlds: Vec<E>,
 }
 
-impl<E: Env + Clone> Node<E> {
-    pub fn new_root(
-        my_action: bool,
-        public_info: &E::PublicInfo,
-        my_private_info: &E::PrivateInfo,
-    ) -> Self {
+impl<E: Env> Node<E> {
+    pub fn new(public_info: &E::PublicInfo, private_info: &E::PrivateInfo, key: u64) -> Self {
         Node {
-            parent: 0,
             public_info: public_info.clone(),
-            my_private_info: my_private_info.clone(),
-            terminal: false,
-            expanded: false,
-            my_action: my_action,
-            children: Vec::new(),
-            num_visits: 0.0,
-            reward: 0.0,
-        }
-    }
-
-    pub fn new(parent_id: usize, node: &Self, action: &E::Action) -> Self {
-        let mut env = node.env.clone();
-        let is_over = env.step(action);
-        Node {
-            parent: parent_id,
-            env: env,
-            terminal: is_over,
-            expanded: is_over,
-            my_action: !node.my_action,
-            children: Vec::new(),
+            private_info: private_info.clone(),
+            key,
+            terminal: public_info.is_over(),
+            edges: Vec::new(),
             num_visits: 0.0,
-            reward: 0.0,
+            worlds: Vec::new(),
         }
     }
 }
 
-pub struct MCTS<E: Env + Clone> {
-    pub id: bool,
+pub struct MCTS<E: Env> {
     pub root: usize,
     pub nodes: Vec<Node<E>>,
-    pub rng: StdRng, // note: this is about the same performance as SmallRng or any of the XorShiftRngs that got moved to the xorshift crate
+    // transposition table: nodes reached by different action orders share the same public key
+    pub table: HashMap<u64, usize>,
+    // share nodes & edge statistics between states that are the same up to a symmetry (e.g. color permutations)
+    pub canonical: bool,
+    pub root_public_info: E::PublicInfo,
+    pub private_info: E::PrivateInfo,
+    // when set, leaves are valued by the evaluator instead of rollouts and edges are selected with PUCT
+    pub evaluator: Option<Box<dyn Evaluator<E>>>,
+    // when set, the partner's nodes get its priors and are selected with PUCT, a model of how
+    // they play. our own nodes keep UCT and leaves keep their rollouts, its values go unused
+    pub partner_prior: Option<Box<dyn Evaluator<E>>>,
+    // when set, nodes are looked up by the abstract key so states it considers equivalent are merged
+    pub abstraction: Option<Box<dyn Abstraction<E::PublicInfo>>>,
+    pub c_puct: f32,
+    pub rng: RolloutRng,
+    // when set, reaching this many live nodes recycles the least visited ones, see `make_room`
+    pub max_nodes: Option<usize>,
+    // slots of `nodes` that were recycled and can be reused
+    pub free: Vec<usize>,
+    // when set, finished games are valued by it instead of env.reward(), e.g. whether the final
+    // score reached a target
+    pub utility: Option<Utility<E>>,
+    // when set, an edge's UCT score gets what it says an action is worth in a node's position,
+    // divided by the edge's visits + 1 so the search's own values take over
+    pub progressive_bias: Option<ProgressiveBias<E>>,
+    // when set to alpha, a node visited n times keeps up to ceil((n + 1)^alpha) worlds: a visit
+    // adds the world it came in with while there's room, and plays on in a kept one otherwise.
+    // 1 is a new world every visit, 0 a single world per node
+    pub widening: Option<f32>,
+    // nodes are the information sets of the player to move (multiple observer IS-MCTS): a node
+    // is filed under the public key & Env::view_key of what the mover sees in the world. the
+    // partner's decisions then depend on their hints & our hand as they see it, never on their
+    // own cards, and ours on the partner's cards as drawn. when set, this key of the world
+    // replaces the view key below the root, e.g. `|_| 0` files every world of a public state
+    // under one node. an edge only links the last child it led to, so with max_nodes the other
+    // children count as unreachable
+    pub observer_key: Option<ObserverKey<E>>,
+    // when set to k, edges are selected on their value blended with their AMAF value, weighted
+    // by beta = sqrt(k / (3n + k)) for an edge visited n times. k is about the visits at which
+    // both count the same
+    pub rave: Option<f32>,
+    num_inserted: usize,
 }
 
-impl<E: Env + Clone> MCTS<E> {
-    pub fn with_capacity(id: bool, capacity: usize, seed: u64) -> Self {
-        let mut nodes = Vec::with_capacity(capacity);
-        let root = Node::new_root(id == WHITE);
-        nodes.push(root);
-        Self {
-            id: id,
+impl<E: Env> MCTS<E>
+where
+    E: HasReward<Reward = f32>,
+    E::Action: Copy + PartialEq,
+    E::PublicInfo: HasSymmetry<E::Action>,
+{
+    pub fn with_capacity(
+        public_info: &E::PublicInfo,
+        private_info: &E::PrivateInfo,
+        capacity: usize,
+        seed: u64,
+    ) -> Self {
+        Self::build(public_info, private_info, capacity, seed, false)
+    }
+
+    pub fn canonical(
+        public_info: &E::PublicInfo,
+        private_info: &E::PrivateInfo,
+        capacity: usize,
+        seed: u64,
+    ) -> Self {
+        Self::build(public_info, private_info, capacity, seed, true)
+    }
+
+    fn build(
+        public_info: &E::PublicInfo,
+        private_info: &E::PrivateInfo,
+        capacity: usize,
+        seed: u64,
+        canonical: bool,
+    ) -> Self {
+        let mut mcts = Self {
             root: 0,
-            nodes: nodes,
-            rng: StdRng::seed_from_u64(seed),
+            nodes: Vec::with_capacity(capacity),
+            table: HashMap::with_capacity(capacity),
+            canonical,
+            root_public_info: public_info.clone(),
+            private_info: private_info.clone(),
+            evaluator: None,
+            partner_prior: None,
+            abstraction: None,
+            c_puct: 1.5,
+            rng: RolloutRng::seed_from_u64(seed),
+            max_nodes: None,
+            free: Vec::new(),
+            utility: None,
+            progressive_bias: None,
+            widening: None,
+            observer_key: None,
+            rave: None,
+            num_inserted: 0,
+        };
+        mcts.root = mcts.get_or_insert(public_info, private_info);
+        mcts
+    }
+
+    pub fn with_evaluator(mut self, evaluator: Box<dyn Evaluator<E>>) -> Self {
+        self.evaluator = Some(evaluator);
+        self
+    }
+
+    pub fn with_partner_prior(mut self, prior: Box<dyn Evaluator<E>>) -> Self {
+        self.partner_prior = Some(prior);
+        self
+    }
+
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    pub fn with_utility(mut self, utility: Utility<E>) -> Self {
+        self.utility = Some(utility);
+        self
+    }
+
+    pub fn with_progressive_bias(mut self, bias: ProgressiveBias<E>) -> Self {
+        self.progressive_bias = Some(bias);
+        self
+    }
+
+    pub fn with_widening(mut self, alpha: f32) -> Self {
+        self.widening = Some(alpha);
+        self
+    }
+
+    pub fn with_observer_key(mut self, observer_key: ObserverKey<E>) -> Self {
+        self.observer_key = Some(observer_key);
+        self
+    }
+
+    pub fn with_rave(mut self, k: f32) -> Self {
+        self.rave = Some(k);
+        self
+    }
+
+    // what a finished game backs up
+    fn final_value(&self, env: &E) -> f32 {
+        match self.utility.as_ref() {
+            Some(utility) => utility(env),
+            None => env.reward(),
         }
     }
 
-    fn next_node_id(&self) -> usize {
-        self.nodes.len()
+    pub fn num_live_nodes(&self) -> usize {
+        self.nodes.len() - self.free.len()
     }
 
-    pub fn step_action(&mut self, action: &E::Action) {
-        // note: this function attempts to drop obviously unused nodes in order to reduce memory usage
-        self.root = match self.nodes[self.root]
-            .children
-            .iter()
-            .position(|(a, _)| a == action)
-        {
-            Some(action_index) => {
-                let (a, new_root) = self.nodes[self.root].children[action_index];
-                new_root
+    pub fn with_abstraction(mut self, abstraction: Box<dyn Abstraction<E::PublicInfo>>) -> Self {
+        // the root was inserted under its concrete key
+        self.table.clear();
+        self.abstraction = Some(abstraction);
+        let key = self.node_key(&self.root_public_info, &self.private_info);
+        self.nodes[self.root].key = key;
+        self.table.insert(key, self.root);
+        self
+    }
+
+    // the frame of the node the mover holding `private_info` is at. what they see breaks the ties
+    // between states the public info alone can't tell apart, unless an observer key stands in for
+    // it
+    fn symmetry(
+        &self,
+        public_info: &E::PublicInfo,
+        private_info: &E::PrivateInfo,
+    ) -> Option<<E::PublicInfo as HasSymmetry<E::Action>>::Symmetry> {
+        match (self.canonical, self.observer_key.is_some()) {
+            (false, _) => None,
+            (true, true) => Some(public_info.canonical_symmetry()),
+            (true, false) => Some(E::canonical_view(public_info, private_info).0),
+        }
+    }
+
+    fn public_key(
+        &self,
+        public_info: &E::PublicInfo,
+        symmetry: Option<&<E::PublicInfo as HasSymmetry<E::Action>>::Symmetry>,
+    ) -> u64 {
+        let key_of = |public_info: &E::PublicInfo| match self.abstraction.as_ref() {
+            Some(abstraction) => abstraction.key(public_info),
+            None => public_info.key(),
+        };
+        match symmetry {
+            Some(symmetry) => key_of(&public_info.apply(symmetry)),
+            None => key_of(public_info),
+        }
+    }
+
+    // the public key & the view key of what the mover holding `private_info` sees, both in the
+    // node's frame
+    fn node_key(&self, public_info: &E::PublicInfo, private_info: &E::PrivateInfo) -> u64 {
+        match self.canonical {
+            true => {
+                let (symmetry, view_key) = E::canonical_view(public_info, private_info);
+                self.public_key(public_info, Some(&symmetry)) ^ view_key
             }
+            false => self.public_key(public_info, None) ^ E::view_key(private_info),
+        }
+    }
+
+    // the root, seen with `private_info`
+    fn get_or_insert(
+        &mut self,
+        public_info: &E::PublicInfo,
+        private_info: &E::PrivateInfo,
+    ) -> usize {
+        let key = self.node_key(public_info, private_info);
+        self.get_or_insert_keyed(public_info, private_info, key)
+    }
+
+    // the node `env` reaches below the root
+    fn get_or_insert_child(&mut self, env: &E) -> usize {
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let key = match self.observer_key.as_ref() {
+            Some(observer_key) => {
+                let symmetry = self.symmetry(&public_info, &private_info);
+                self.public_key(&public_info, symmetry.as_ref()) ^ observer_key(env)
+            }
+            None => self.node_key(&public_info, &private_info),
+        };
+        self.get_or_insert_keyed(&public_info, &private_info, key)
+    }
+
+    fn get_or_insert_keyed(
+        &mut self,
+        public_info: &E::PublicInfo,
+        private_info: &E::PrivateInfo,
+        key: u64,
+    ) -> usize {
+        match self.table.get(&key) {
+            Some(&node_id) => node_id,
             None => {
-                let child_id = self.next_node_id();
-                let child_node = Node::new(child_id, &self.nodes[self.root], action);
-                self.nodes.push(child_node);
-                child_id
+                let node = Node::new(public_info, private_info, key);
+                let node_id = match self.free.pop() {
+                    Some(node_id) => {
+                        self.nodes[node_id] = node;
+                        node_id
+                    }
+                    None => {
+                        self.nodes.push(node);
+                        self.nodes.len() - 1
+                    }
+                };
+                self.table.insert(key, node_id);
+                self.num_inserted += 1;
+                node_id
+            }
+        }
+    }
+
+    pub fn set_root(&mut self, public_info: &E::PublicInfo, private_info: &E::PrivateInfo) {
+        // note: nodes from the previous search stay in the table, so any subtree we've already explored gets reused
+        self.root = self.get_or_insert(public_info, private_info);
+        self.root_public_info = public_info.clone();
+        self.private_info = private_info.clone();
+        if self.max_nodes.is_some() {
+            // the old root & whatever only it led to are garbage now
+            self.make_room(&[]);
+        }
+    }
+
+    // marks every node reachable from the root and `keep` through child links
+    fn reachable(&self, keep: &[usize]) -> Vec<bool> {
+        let mut live = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = keep.iter().cloned().chain(Some(self.root)).collect();
+        while let Some(node_id) = stack.pop() {
+            if live[node_id] {
+                continue;
+            }
+            live[node_id] = true;
+            stack.extend(self.nodes[node_id].edges.iter().filter_map(|e| e.child));
+        }
+        live
+    }
+
+    // frees unreachable nodes, then when more than half the budget is still live, the least
+    // visited nodes other than the root and `keep` until half is. edges into a freed node keep
+    // their statistics and just lose the child, which gets expanded again if it comes up
+    fn make_room(&mut self, keep: &[usize]) {
+        let max_nodes = self.max_nodes.unwrap_or(usize::MAX);
+        let mut live = self.reachable(keep);
+        let mut candidates: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| live[i] && i != self.root && !keep.contains(&i))
+            .collect();
+        let num_live = live.iter().filter(|&&l| l).count();
+        if num_live > max_nodes / 2 {
+            candidates.sort_by(|&a, &b| {
+                self.nodes[a]
+                    .num_visits
+                    .partial_cmp(&self.nodes[b].num_visits)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            for &node_id in candidates.iter().take(num_live - max_nodes / 2) {
+                live[node_id] = false;
             }
+            for node in self.nodes.iter_mut() {
+                for edge in node.edges.iter_mut() {
+                    if edge.child.is_some_and(|child| !live[child]) {
+                        edge.child = None;
+                    }
+                }
+            }
+            // subtrees hanging off an evicted node went with it
+            live = self.reachable(keep);
+        }
+
+        let already_free: Vec<bool> = {
+            let mut free = vec![false; self.nodes.len()];
+            for &node_id in self.free.iter() {
+                free[node_id] = true;
+            }
+            free
         };
+        for node_id in 0..self.nodes.len() {
+            if !live[node_id] && !already_free[node_id] {
+                let key = self.nodes[node_id].key;
+                if self.table.get(&key) == Some(&node_id) {
+                    self.table.remove(&key);
+                }
+                self.nodes[node_id].edges = Vec::new();
+                self.nodes[node_id].worlds = Vec::new();
+                self.free.push(node_id);
+            }
+        }
     }
 
     pub fn best_action(&self) -> E::Action {
         let root = &self.nodes[self.root];
 
         let mut best_action_ind = 0;
-        let mut best_value = -std::f32::INFINITY;
+        let mut best_value = -f32::INFINITY;
 
-        for (i, &(_, child_id)) in root.children.iter().enumerate() {
-            let child = &self.nodes[child_id];
-            let value = child.reward / child.num_visits;
+        for (i, edge) in root.edges.iter().enumerate() {
+            let value = edge.reward / edge.num_visits;
             if value > best_value {
                 best_value = value;
                 best_action_ind = i;
             }
         }
 
-        root.children[best_action_ind].0
+        match self.symmetry(&self.root_public_info, &self.private_info) {
+            Some(symmetry) => E::PublicInfo::apply_to_action(
+                &E::PublicInfo::invert(&symmetry),
+                &root.edges[best_action_ind].action,
+            ),
+            None => root.edges[best_action_ind].action,
+        }
+    }
+
+    // (action, visits, mean reward) for each explored root action
+    pub fn root_stats(&self) -> Vec<(E::Action, f32, f32)> {
+        let symmetry = self.symmetry(&self.root_public_info, &self.private_info);
+        self.nodes[self.root]
+            .edges
+            .iter()
+            .map(|edge| {
+                let action = match symmetry.as_ref() {
+                    Some(symmetry) => E::PublicInfo::apply_to_action(
+                        &E::PublicInfo::invert(symmetry),
+                        &edge.action,
+                    ),
+                    None => edge.action,
+                };
+                (action, edge.num_visits, edge.reward / edge.num_visits)
+            })
+            .collect()
+    }
+
+    // follows the most visited edge from the root.
+    // note: with `canonical` set, actions below the root are in the frame of each node's stored
+    // public & private info
+    pub fn principal_variation(&self, max_len: usize) -> Vec<E::Action> {
+        let mut line = Vec::new();
+        let mut node_id = self.root;
+        let mut public_info = self.root_public_info.clone();
+        let mut private_info = self.private_info.clone();
+        while line.len() < max_len {
+            let node = &self.nodes[node_id];
+            let edge = match node.edges.iter().max_by(|a, b| {
+                a.num_visits
+                    .partial_cmp(&b.num_visits)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            }) {
+                Some(edge) => edge,
+                None => break,
+            };
+            line.push(match self.symmetry(&public_info, &private_info) {
+                Some(symmetry) => {
+                    E::PublicInfo::apply_to_action(&E::PublicInfo::invert(&symmetry), &edge.action)
+                }
+                None => edge.action,
+            });
+            match edge.child {
+                Some(child_id) => {
+                    node_id = child_id;
+                    public_info = self.nodes[child_id].public_info.clone();
+                    private_info = self.nodes[child_id].private_info.clone();
+                }
+                None => break,
+            }
+        }
+        line
+    }
+
+    pub fn sample_action(&mut self, sampling: &RootSampling, move_number: usize) -> E::Action {
+        let stats = self.root_stats();
+        let visits: Vec<f32> = stats.iter().map(|s| s.1).collect();
+        let values: Vec<f32> = stats.iter().map(|s| s.2).collect();
+        let i = sampling.select(&visits, &values, move_number, &mut self.rng);
+        stats[i].0
     }
 
     fn explore(&mut self) {
+        let (mut env, _prob) =
+            E::determinize(&self.root_public_info, &self.private_info, &mut self.rng);
+
+        let root_player = env.current_player();
+        let num_players = env.num_players();
+        let mut path = Vec::new();
+        // with rave on, every action of the simulation & the frames of the path's nodes
+        let mut moves = Vec::new();
+        let mut frames = Vec::new();
         let mut node_id = self.root;
-        loop {
-            // assert!(node_id < self.nodes.len());
-            let node = &mut self.nodes[node_id];
-            if node.terminal {
-                let reward = node.public_info.reward();
-                self.backprop(node_id, reward, 1.0);
-                return;
-            } else if node.expanded {
-                node_id = self.select_best_child(node_id);
+        let reward = loop {
+            if let Some(alpha) = self.widening {
+                env = self.widen(node_id, env, alpha);
+            }
+            if self.nodes[node_id].terminal || env.is_over() {
+                break self.final_value(&env);
+            }
+
+            // edges are stored in the node's canonical frame
+            let symmetry =
+                self.symmetry(&env.public_info(), &env.private_info(env.current_player()));
+            let mut actions = env.search_actions();
+            if let Some(symmetry) = symmetry.as_ref() {
+                for action in actions.iter_mut() {
+                    *action = E::PublicInfo::apply_to_action(symmetry, action);
+                }
+            }
+            let partner = self.partner_prior.is_some() && env.current_player() != root_player;
+            let edge_ind = if self.evaluator.is_some() || partner {
+                self.add_prior_edges(node_id, &env, &actions, partner);
+                self.select_puct_edge(node_id, &actions)
             } else {
-                // expand all children at once
-                let (total_reward, total_visits) = self.expand_all_children(node_id);
+                match self.untried_action(node_id, &actions) {
+                    Some(action) => {
+                        self.nodes[node_id].edges.push(Edge {
+                            action,
+                            child: None,
+                            prior: 0.0,
+                            reward: 0.0,
+                            num_visits: 0.0,
+                            amaf_reward: 0.0,
+                            amaf_visits: 0.0,
+                        });
+                        self.nodes[node_id].edges.len() - 1
+                    }
+                    None => self.select_best_edge(node_id, &actions),
+                }
+            };
+            path.push((node_id, edge_ind));
 
-                // backprop all new children rewards back up
-                self.backprop(node_id, total_reward, total_visits);
+            let mut action = self.nodes[node_id].edges[edge_ind].action;
+            if let Some(symmetry) = symmetry.as_ref() {
+                action = E::PublicInfo::apply_to_action(&E::PublicInfo::invert(symmetry), &action);
+            }
+            if self.rave.is_some() {
+                moves.push(action);
+                frames.push(symmetry);
+            }
+            env.step(&action, &mut self.rng);
 
-                // we've expanded one node now, 1 round of exploring done!
-                return;
+            let expanded = self.nodes[node_id].edges[edge_ind].child.is_some();
+            if self
+                .max_nodes
+                .is_some_and(|max| self.num_live_nodes() >= max)
+            {
+                let keep: Vec<usize> = path.iter().map(|&(node_id, _)| node_id).collect();
+                self.make_room(&keep);
             }
+            let child_id = self.get_or_insert_child(&env);
+            self.nodes[node_id].edges[edge_ind].child = Some(child_id);
+            if !expanded {
+                // we've expanded one edge now, 1 round of exploring done!
+                break match self.rave.is_some() {
+                    true => self.leaf_value_recording(env, &mut moves),
+                    false => self.leaf_value(env),
+                };
+            }
+            node_id = child_id;
+        };
+
+        self.backprop(&path, reward);
+        if self.rave.is_some() {
+            self.backprop_amaf(&path, &frames, &moves, num_players, reward);
         }
     }
 
-    fn select_best_child(&mut self, node_id: usize) -> usize {
-        // assert!(node_id < self.nodes.len());
-        let node = &self.nodes[node_id];
+    // the world to carry on from `node_id` in, see `widening`
+    fn widen(&mut self, node_id: usize, env: E, alpha: f32) -> E {
+        let node = &mut self.nodes[node_id];
+        let room = (node.num_visits + 1.0).powf(alpha).ceil() as usize;
+        if node.worlds.len() < room.max(1) {
+            node.worlds.push(env.clone());
+            return env;
+        }
+        node.worlds.choose(&mut self.rng).unwrap().clone()
+    }
 
-        let visits = node.num_visits.log(2.0);
+    // `actions` are in the node's frame and line up with `env.search_actions()`. the priors are
+    // partner_prior's at the partner's nodes
+    fn add_prior_edges(&mut self, node_id: usize, env: &E, actions: &[E::Action], partner: bool) {
+        let missing = actions
+            .iter()
+            .any(|a| self.nodes[node_id].edges.iter().all(|e| e.action != *a));
+        if !missing {
+            return;
+        }
+        let evaluator = match partner {
+            true => self.partner_prior.as_mut(),
+            false => self.evaluator.as_mut(),
+        };
+        let (_, priors) = evaluator.unwrap().evaluate(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            &env.search_actions(),
+        );
+        let edges = &mut self.nodes[node_id].edges;
+        for (&action, &prior) in actions.iter().zip(priors.iter()) {
+            if edges.iter().all(|e| e.action != action) {
+                edges.push(Edge {
+                    action,
+                    child: None,
+                    prior,
+                    reward: 0.0,
+                    num_visits: 0.0,
+                    amaf_reward: 0.0,
+                    amaf_visits: 0.0,
+                });
+            }
+        }
+    }
+
+    fn select_puct_edge(&self, node_id: usize, actions: &[E::Action]) -> usize {
+        let node = &self.nodes[node_id];
 
-        let raw_first_child = node.children[0].1;
-        let first_child = raw_first_child - self.root;
-        let last_child = first_child + node.children.len();
+        let visits = node.num_visits.sqrt();
 
-        let best_child_ind = self.nodes[first_child..last_child]
+        node.edges
             .iter()
-            .map(|child| child.reward / child.num_visits + (2.0 * visits / child.num_visits).sqrt())
             .enumerate()
+            .filter(|(_, edge)| actions.contains(&edge.action))
+            .map(|(i, edge)| {
+                let value = if edge.num_visits > 0.0 {
+                    edge.reward / edge.num_visits
+                } else {
+                    0.0
+                };
+                (
+                    i,
+                    value + self.c_puct * edge.prior * visits / (1.0 + edge.num_visits),
+                )
+            })
             .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
             .map(|(i, _)| i)
-            .unwrap();
-
-        best_child_ind + raw_first_child
+            .unwrap()
     }
 
-    fn expand_all_children(&mut self, node_id: usize) -> (f32, f32) {
-        let mut node = &mut self.nodes[node_id];
+    fn leaf_value(&mut self, env: E) -> f32 {
+        if env.is_over() {
+            return self.final_value(&env);
+        }
+        match self.evaluator.as_mut() {
+            Some(evaluator) => {
+                let actions = env.actions();
+                evaluator
+                    .evaluate(
+                        &env.public_info(),
+                        &env.private_info(env.current_player()),
+                        &actions,
+                    )
+                    .0
+            }
+            None => self.rollout(env),
+        }
+    }
 
-        // we are adding all children at once, so this node is about to be expanded
-        node.expanded = true;
+    fn untried_action(&mut self, node_id: usize, actions: &[E::Action]) -> Option<E::Action> {
+        let edges = &self.nodes[node_id].edges;
+        let untried: Vec<&E::Action> = actions
+            .iter()
+            .filter(|&a| edges.iter().all(|e| e.action != *a))
+            .collect();
+        untried.choose(&mut self.rng).map(|&&a| a)
+    }
 
-        let mut total_reward = 0.0;
-        let mut total_visits = 0.0;
+    fn select_best_edge(&self, node_id: usize, actions: &[E::Action]) -> usize {
+        let node = &self.nodes[node_id];
 
-        // TODO sample env so we can create actions
-        let actions = Vec::new();
+        let visits = node.num_visits.log(2.0);
 
-        // reserve max number of actions for children to reduce allocations
-        node.children.reserve_exact(actions.len());
+        node.edges
+            .iter()
+            .enumerate()
+            .filter(|(_, edge)| actions.contains(&edge.action))
+            .map(|(i, edge)| {
+                let bias = match self.progressive_bias.as_ref() {
+                    Some(bias) => bias(&node.public_info, &edge.action) / (edge.num_visits + 1.0),
+                    None => 0.0,
+                };
+                let value = edge.reward / edge.num_visits;
+                let value = match self.rave {
+                    Some(k) if edge.amaf_visits > 0.0 => {
+                        let beta = (k / (3.0 * edge.num_visits + k)).sqrt();
+                        (1.0 - beta) * value + beta * edge.amaf_reward / edge.amaf_visits
+                    }
+                    _ => value,
+                };
+                (i, value + (2.0 * visits / edge.num_visits).sqrt() + bias)
+            })
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, _)| i)
+            .unwrap()
+    }
 
-        // iterate through all the children!
-        for action in actions {
-            // create the child node and sample a reward from it
-            let child_node = self.expand_single_child(node_id, action);
+    fn rollout(&mut self, mut env: E) -> f32 {
+        // note: checking if env.is_over() before cloning doesn't make much difference
+        while !env.is_over() {
+            let action = env.random_action(&mut self.rng);
+            env.step(&action, &mut self.rng);
+        }
+        self.final_value(&env)
+    }
 
-            // keep track of reward here so we can backprop 1 time for all the new children
-            total_reward += child_node.reward;
-            total_visits += 1.0;
+    // like leaf_value, adding the rollout's actions to `moves`
+    fn leaf_value_recording(&mut self, mut env: E, moves: &mut Vec<E::Action>) -> f32 {
+        if self.evaluator.is_some() {
+            return self.leaf_value(env);
+        }
+        while !env.is_over() {
+            let action = env.random_action(&mut self.rng);
+            moves.push(action);
+            env.step(&action, &mut self.rng);
+        }
+        self.final_value(&env)
+    }
 
-            self.nodes.push(child_node);
+    // credits each path edge's AMAF statistics with the simulation when its node's mover played
+    // its action then or at any later turn of theirs. `moves` starts with the path's actions, so
+    // the mover's own turns are every `num_players`th one from their node's
+    fn backprop_amaf(
+        &mut self,
+        path: &[(usize, usize)],
+        frames: &[Option<<E::PublicInfo as HasSymmetry<E::Action>>::Symmetry>],
+        moves: &[E::Action],
+        num_players: usize,
+        reward: f32,
+    ) {
+        for (depth, &(node_id, _)) in path.iter().enumerate() {
+            let played: Vec<E::Action> = moves[depth..]
+                .iter()
+                .step_by(num_players)
+                .map(|action| match frames[depth].as_ref() {
+                    Some(symmetry) => E::PublicInfo::apply_to_action(symmetry, action),
+                    None => *action,
+                })
+                .collect();
+            for edge in self.nodes[node_id].edges.iter_mut() {
+                if played.contains(&edge.action) {
+                    edge.amaf_visits += 1.0;
+                    edge.amaf_reward += reward;
+                }
+            }
         }
+    }
 
-        (total_reward, total_visits)
+    fn backprop(&mut self, path: &[(usize, usize)], reward: f32) {
+        for &(node_id, edge_ind) in path.iter() {
+            let node = &mut self.nodes[node_id];
+            node.num_visits += 1.0;
+            node.edges[edge_ind].num_visits += 1.0;
+            node.edges[edge_ind].reward += reward;
+        }
     }
 
-    fn expand_single_child(&mut self, node_id: usize, action: E::Action) -> Node<E> {
-        let child_id = self.next_node_id();
+    pub fn explore_for(&mut self, millis: u128) -> (usize, u128) {
+        let start = Instant::now();
+        let start_n = self.num_inserted;
+        while start.elapsed().as_millis() < millis {
+            self.explore();
+        }
+        (self.num_inserted - start_n, start.elapsed().as_millis())
+    }
 
-        let node = &mut self.nodes[node_id];
-        node.children.push((action, child_id));
+    pub fn explore_within(&mut self, budget: Budget) -> (usize, u128) {
+        let start = Instant::now();
+        let start_n = self.num_inserted;
+        let mut done = 0;
+        while !budget.exhausted(done, start) {
+            self.explore();
+            done += 1;
+        }
+        (self.num_inserted - start_n, start.elapsed().as_millis())
+    }
 
-        // create the child node... note we will be modifying num_visits and reward later, so mutable
-        let mut child_node = Node::new(node_id, &node, &action);
+    pub fn explore_n(&mut self, n: usize) -> (usize, u128) {
+        let start = Instant::now();
+        let start_n = self.num_inserted;
+        for _ in 0..n {
+            self.explore();
+        }
+        (self.num_inserted - start_n, start.elapsed().as_millis())
+    }
+}
 
-        // rollout child to get initial reward
-        // TODO sample rollout here
-        let reward = self.rollout(child_node.env.clone());
+// root parallelization: `num_trees` independent searches on their own threads, each seeded
+// differently so they sample their own determinizations, with the root statistics merged by
+// action afterwards. visits add up and the mean rewards are weighted by visits. no locking,
+// the trees never see each other
+pub fn root_parallel<E>(
+    public_info: &E::PublicInfo,
+    private_info: &E::PrivateInfo,
+    num_trees: usize,
+    explores_per_tree: usize,
+    seed: u64,
+) -> Vec<(E::Action, f32, f32)>
+where
+    E: Env + HasReward<Reward = f32>,
+    E::Action: Copy + PartialEq + Send,
+    E::PublicInfo: HasSymmetry<E::Action> + Sync,
+    E::PrivateInfo: Sync,
+{
+    let tree_stats: Vec<Vec<(E::Action, f32, f32)>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..num_trees.max(1) as u64)
+            .map(|tree| {
+                scope.spawn(move || {
+                    let mut mcts = MCTS::<E>::with_capacity(
+                        public_info,
+                        private_info,
+                        explores_per_tree,
+                        seed.wrapping_add(tree),
+                    );
+                    mcts.explore_n(explores_per_tree);
+                    mcts.root_stats()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
 
-        // store initial reward & 1 visit
-        child_node.num_visits = 1.0;
-        child_node.reward = reward;
+    let mut merged: Vec<(E::Action, f32, f32)> = Vec::new();
+    for (action, visits, mean) in tree_stats.into_iter().flatten() {
+        match merged.iter_mut().find(|m| m.0 == action) {
+            Some(m) => {
+                m.2 = (m.2 * m.1 + mean * visits) / (m.1 + visits);
+                m.1 += visits;
+            }
+            None => merged.push((action, visits, mean)),
+        }
+    }
+    merged
+}
 
-        child_node
+// the merged action with the best mean reward, the first on ties like MCTS::best_action
+pub fn best_merged_action<A: Copy>(stats: &[(A, f32, f32)]) -> A {
+    let mut best = 0;
+    for (i, stat) in stats.iter().enumerate() {
+        if stat.2 > stats[best].2 {
+            best = i;
+        }
     }
+    stats[best].0
+}
 
-    fn rollout(&mut self, mut env: E) -> f32 {
-        // assert!(node_id < self.nodes.len());
-        // note: checking if env.is_over() before cloning doesn't make much difference
-        let mut is_over = env.is_over();
-        while !is_over {
-            let action = env.get_random_action(&mut self.rng);
-            is_over = env.step(&action);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hanabi_env::{HanabiEnv, Utility};
+    use crate::hgroup::HGroupEvaluator;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_transpositions_share_nodes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            1000,
+            0,
+        );
+        mcts.explore_n(1000);
+
+        assert_eq!(mcts.table.len(), mcts.nodes.len());
+        for (&key, &node_id) in mcts.table.iter() {
+            assert_eq!(mcts.nodes[node_id].key, key);
         }
-        env.reward(self.id)
+        // the root is what we see, the public state & the partner's hand
+        let view = HanabiEnv::view_key(&env.private_info(env.current_player()));
+        assert_eq!(mcts.nodes[mcts.root].key, env.public_info().key() ^ view);
+        assert!(env.actions().contains(&mcts.best_action()));
     }
 
-    fn backprop(&mut self, leaf_node_id: usize, reward: f32, num_visits: f32) {
-        let mut node_id = leaf_node_id;
-        loop {
-            // assert!(node_id < self.nodes.len());
+    #[test]
+    fn test_utility_values_finished_games() {
+        assert_eq!(Utility::parse("at-least:20"), Ok(Utility::AtLeast(20)));
+        assert_eq!(Utility::parse("expected"), Ok(Utility::Expected));
+        assert!(Utility::parse("at-least").is_err());
+        assert_eq!(Utility::AtLeast(20).of(19, 25), 0.0);
+        assert_eq!(Utility::AtLeast(20).of(20, 25), 1.0);
+        assert_eq!(Utility::Expected.of(5, 25), 0.2);
 
-            let node = &mut self.nodes[node_id];
+        let mut rng = StdRng::seed_from_u64(1);
+        let env = HanabiEnv::random(&mut rng);
+        let search = |target: u8| {
+            let utility = Utility::AtLeast(target);
+            let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+                &env.public_info(),
+                &env.private_info(env.current_player()),
+                500,
+                0,
+            )
+            .with_utility(Box::new(move |env| env.public_info().utility(utility)));
+            mcts.explore_n(500);
+            mcts.root_stats()
+        };
+        // every game reaches 0 and none gets past a perfect score
+        assert!(search(0).iter().all(|s| s.2 == 1.0));
+        assert!(search(26).iter().all(|s| s.2 == 0.0));
+        // random rollouts hardly ever score 10, and the values are chances now
+        let values: Vec<f32> = search(10).iter().map(|s| s.2).collect();
+        assert!(values.iter().all(|&v| (0.0..=1.0).contains(&v)));
+        assert!(values.iter().any(|&v| v < 1.0), "{:?}", values);
+    }
+
+    #[test]
+    fn test_widening_reuses_worlds() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let env = HanabiEnv::random(&mut rng);
+        let search = |alpha: f32| {
+            let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+                &env.public_info(),
+                &env.private_info(env.current_player()),
+                1000,
+                0,
+            )
+            .with_widening(alpha);
+            mcts.explore_n(1000);
+            assert!(env.actions().contains(&mcts.best_action()));
+            mcts
+        };
+
+        let single = search(0.0);
+        assert!(single.nodes.iter().all(|node| node.worlds.len() <= 1));
+        // whoever's hand is sampled, the mover's partner is always who they are
+        let world = &single.nodes[single.root].worlds[0];
+        assert_eq!(world.opponent_hand, env.opponent_hand);
 
-            node.num_visits += num_visits;
+        let widened = search(0.5);
+        let root = &widened.nodes[widened.root];
+        assert!(root.worlds.len() > 1);
+        assert!(root.worlds.len() <= (root.num_visits + 1.0).sqrt().ceil() as usize);
+    }
 
-            node.reward += reward;
+    #[test]
+    fn test_observer_keys_split_information_sets() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let env = HanabiEnv::random(&mut rng);
+        let search = |single_observer: bool| {
+            let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+                &env.public_info(),
+                &env.private_info(env.current_player()),
+                2000,
+                0,
+            );
+            if single_observer {
+                mcts = mcts.with_observer_key(Box::new(|_| 0));
+            }
+            mcts.explore_n(2000);
+            assert!(env.actions().contains(&mcts.best_action()));
+            // the most nodes sharing a public state
+            let mut counts = HashMap::new();
+            for node in mcts.nodes.iter() {
+                *counts.entry(node.public_info.key()).or_insert(0) += 1;
+            }
+            *counts.values().max().unwrap()
+        };
+        assert_eq!(search(true), 1);
+        // the partner's nodes after our clue differ by the hand we were dealt in the world
+        assert!(search(false) > 1);
+    }
+
+    #[test]
+    fn test_partner_prior_leaves_our_nodes_alone() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let env = HanabiEnv::random(&mut rng);
+        let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            1000,
+            0,
+        )
+        .with_partner_prior(Box::new(HGroupEvaluator { weight: 0.5 }));
+        mcts.explore_n(1000);
+        assert!(env.actions().contains(&mcts.best_action()));
 
-            if node_id == self.root {
-                break;
+        // our nodes are UCT's, with no priors, and the partner's edges all have one
+        let mover = |node: &Node<HanabiEnv>| node.public_info.hint_history.turn % 2;
+        let mut partner_nodes = 0;
+        for node in mcts.nodes.iter().filter(|node| !node.edges.is_empty()) {
+            match mover(node) == mover(&mcts.nodes[mcts.root]) {
+                true => assert!(node.edges.iter().all(|e| e.prior == 0.0)),
+                false => {
+                    assert!(node.edges.iter().all(|e| e.prior > 0.0));
+                    partner_nodes += 1;
+                }
             }
+        }
+        assert!(partner_nodes > 0);
+    }
+
+    #[test]
+    fn test_rave_fills_amaf_statistics() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let env = HanabiEnv::random(&mut rng);
+        let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            1000,
+            0,
+        )
+        .with_rave(100.0);
+        mcts.explore_n(1000);
+        assert!(env.actions().contains(&mcts.best_action()));
 
-            node_id = node.parent;
+        let root = &mcts.nodes[mcts.root];
+        for edge in root.edges.iter() {
+            // an edge is credited on its own visits & whenever its action came up later
+            assert!(edge.amaf_visits >= edge.num_visits);
+            assert!(edge.amaf_visits <= root.num_visits);
+            assert!(edge.amaf_reward <= edge.amaf_visits);
         }
+        assert!(root.edges.iter().any(|e| e.amaf_visits > e.num_visits));
     }
 
-    pub fn explore_for(&mut self, millis: u128) -> (usize, u128) {
-        let start = Instant::now();
-        let start_n = self.nodes.len();
-        while start.elapsed().as_millis() < millis {
-            self.explore();
+    #[test]
+    fn test_max_nodes_bounds_the_tree() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let env = HanabiEnv::random(&mut rng);
+        let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            300,
+            0,
+        )
+        .with_max_nodes(300);
+        let (inserted, _) = mcts.explore_n(3000);
+
+        assert!(inserted > 300);
+        assert!(mcts.num_live_nodes() <= 300);
+        assert!(mcts.nodes.len() <= 300 + 1);
+        assert_eq!(mcts.table.len(), mcts.num_live_nodes());
+        for (&key, &node_id) in mcts.table.iter() {
+            assert!(!mcts.free.contains(&node_id));
+            assert_eq!(mcts.nodes[node_id].key, key);
+            for edge in mcts.nodes[node_id].edges.iter() {
+                assert!(edge.child.is_none_or(|child| !mcts.free.contains(&child)));
+            }
         }
-        (self.nodes.len() - start_n, start.elapsed().as_millis())
+        assert!(env.actions().contains(&mcts.best_action()));
+        let root_visits: f32 = mcts.root_stats().iter().map(|s| s.1).sum();
+        assert_eq!(root_visits, 3000.0);
     }
 
-    pub fn explore_n(&mut self, n: usize) -> (usize, u128) {
-        let start = Instant::now();
-        let start_n = self.nodes.len();
-        for _ in 0..n {
-            self.explore();
+    #[test]
+    fn test_root_parallel_merges_trees() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let env = HanabiEnv::random(&mut rng);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let stats = root_parallel::<HanabiEnv>(&public_info, &private_info, 4, 500, 7);
+
+        let visits: f32 = stats.iter().map(|s| s.1).sum();
+        assert_eq!(visits, 2000.0);
+        for (i, stat) in stats.iter().enumerate() {
+            assert!(env.actions().contains(&stat.0));
+            assert!(stats[i + 1..].iter().all(|s| s.0 != stat.0));
+        }
+        assert!(env.actions().contains(&best_merged_action(&stats)));
+
+        // one tree merged with nothing else is just that tree
+        let mut mcts = MCTS::<HanabiEnv>::with_capacity(&public_info, &private_info, 500, 7);
+        mcts.explore_n(500);
+        let single = root_parallel::<HanabiEnv>(&public_info, &private_info, 1, 500, 7);
+        assert_eq!(best_merged_action(&single), mcts.best_action());
+        assert_eq!(
+            stats,
+            root_parallel::<HanabiEnv>(&public_info, &private_info, 4, 500, 7)
+        );
+    }
+
+    #[test]
+    fn test_canonical_best_action_is_legal() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let env = HanabiEnv::random(&mut rng);
+        let mut mcts = MCTS::<HanabiEnv>::canonical(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            1000,
+            0,
+        );
+        mcts.explore_n(1000);
+
+        for (&key, &node_id) in mcts.table.iter() {
+            assert_eq!(mcts.nodes[node_id].key, key);
         }
-        (self.nodes.len() - start_n, start.elapsed().as_millis())
+        let public_info = env.public_info();
+        let (symmetry, view) =
+            HanabiEnv::canonical_view(&public_info, &env.private_info(env.current_player()));
+        assert_eq!(
+            mcts.nodes[mcts.root].key,
+            public_info.apply(&symmetry).key() ^ view
+        );
+        assert!(env.actions().contains(&mcts.best_action()));
     }
 }
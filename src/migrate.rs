@@ -0,0 +1,65 @@
+// saved games & positions carry a "version", and reading one upgrades it to the current version
+// first, one migration per version step. a format change bumps the version and adds the step
+// that fills in what older files lack, so files written by older builds keep loading
+use serde_json::Value;
+
+// takes a file from version n to n + 1
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+// the version written by a format whose steps are `migrations`
+pub const fn current_version(migrations: &[Migration]) -> u64 {
+    migrations.len() as u64 + 1
+}
+
+// `migrations[i]` takes version i + 1 to i + 2. `what` names the format in errors
+pub fn upgrade(mut value: Value, what: &str, migrations: &[Migration]) -> Result<Value, String> {
+    let current = current_version(migrations);
+    let version = match value["version"].as_u64() {
+        Some(version) if version >= 1 && version <= current => version,
+        version => return Err(format!("unsupported {} version {:?}", what, version)),
+    };
+    for migration in migrations[version as usize - 1..].iter() {
+        migration(&mut value).map_err(|e| format!("upgrading {}: {}", what, e))?;
+    }
+    value["version"] = Value::from(current);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn add_players(value: &mut Value) -> Result<(), String> {
+        value["players"] = json!(2);
+        Ok(())
+    }
+
+    fn rename_tokens(value: &mut Value) -> Result<(), String> {
+        let map = value.as_object_mut().ok_or("expected an object")?;
+        let tokens = map.remove("clues").ok_or("missing 'clues'")?;
+        map.insert("tokens".to_string(), tokens);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_applies_each_step() {
+        let migrations: &[Migration] = &[add_players, rename_tokens];
+        assert_eq!(current_version(migrations), 3);
+
+        let v1 = json!({"version": 1, "clues": 8});
+        assert_eq!(
+            upgrade(v1, "test", migrations).unwrap(),
+            json!({"version": 3, "players": 2, "tokens": 8})
+        );
+        let v2 = json!({"version": 2, "players": 3, "clues": 8});
+        assert_eq!(upgrade(v2, "test", migrations).unwrap()["players"], 3);
+        let v3 = json!({"version": 3, "tokens": 1});
+        assert_eq!(upgrade(v3.clone(), "test", migrations).unwrap(), v3);
+
+        assert!(upgrade(json!({"version": 1}), "test", migrations).is_err());
+        assert!(upgrade(json!({"version": 4}), "test", migrations).is_err());
+        assert!(upgrade(json!({"version": 0}), "test", migrations).is_err());
+        assert!(upgrade(json!({}), "test", migrations).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+// mini-Hanabi: the game cut down through the RuleSet to a couple of colors, low ranks and small
+// hands, so small that the best score for a deal can be found exactly. search agents are
+// checked against these scores, no policy can beat them and a good one should come close.
+// the exact score is the double-dummy one: both hands and the deck order are known, so clues
+// carry no information and only stall
+use crate::env::HasEnd;
+use crate::hanabi_env::{Action, ActionList, Card, HanabiEnv, RuleSet};
+use crate::rand::rngs::StdRng;
+use crate::rand::seq::SliceRandom;
+use crate::rand::SeedableRng;
+use crate::registry::make_agent;
+use crate::runner::{observing, GameRunner};
+use std::collections::HashMap;
+
+// 2 colors, ranks 1-3, 3 card hands: 14 cards and a perfect score of 6
+pub fn mini_rules() -> RuleSet {
+    RuleSet {
+        hand_size: 3,
+        colors: 2,
+        ranks: 3,
+        ..RuleSet::default()
+    }
+}
+
+// the deck order dealt for `seed`, hands first as HanabiEnv::from_deck_order_with reads it
+pub fn mini_deal(rules: &RuleSet, seed: u64) -> Vec<Card> {
+    let deck = rules.deck();
+    let mut order: Vec<Card> = (0..25)
+        .flat_map(|id| (0..deck.counts[id]).map(move |_| Card::from_id(id as u8)))
+        .collect();
+    order.shuffle(&mut StdRng::seed_from_u64(seed));
+    order
+}
+
+// hands, fireworks, tokens, cards left to draw & last round info. with the deck order fixed,
+// the cards left to draw say which cards have been discarded
+type StateKey = ([u8; 5], [u8; 5], [u8; 5], u8, u8, u8, bool, u8);
+
+fn state_key(env: &HanabiEnv) -> StateKey {
+    let mut player_hand = [0; 5];
+    let mut opponent_hand = [0; 5];
+    for i in 0..5 {
+        player_hand[i] = env.player_hand[i].id();
+        opponent_hand[i] = env.opponent_hand[i].id();
+    }
+    (
+        player_hand,
+        opponent_hand,
+        env.fireworks.0,
+        env.blue_tokens.get(),
+        env.black_tokens.get(),
+        env.deck.remaining(),
+        env.last_round,
+        env.last_round_turns_taken,
+    )
+}
+
+#[derive(Default)]
+pub struct ExactSolver {
+    cache: HashMap<StateKey, u8>,
+}
+
+impl ExactSolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.cache.len()
+    }
+
+    // the best final score from `env`, which must have an ordered deck
+    pub fn best_score(&mut self, env: &HanabiEnv) -> u8 {
+        self.search(&mut env.clone())
+    }
+
+    // steps are taken back rather than cloning the env for every child
+    fn search(&mut self, env: &mut HanabiEnv) -> u8 {
+        if env.is_over() {
+            return env.fireworks.total();
+        }
+        let key = state_key(env);
+        if let Some(&score) = self.cache.get(&key) {
+            return score;
+        }
+
+        let mut actions = ActionList::new();
+        env.actions_into(&mut actions);
+        let mut stalled = false;
+        let mut best = 0;
+        // the deck order is fixed, so the rng never decides anything
+        let mut rng = StdRng::seed_from_u64(0);
+        for action in actions.iter() {
+            if let Action::ColorHint(_) | Action::SuitHint(_) = action {
+                // one clue is as good as another
+                if stalled {
+                    continue;
+                }
+                stalled = true;
+            }
+            let token = env.step_with_undo(action, &mut rng);
+            best = best.max(self.search(env));
+            env.undo(token);
+            if best == env.max_score {
+                break;
+            }
+        }
+        self.cache.insert(key, best);
+        best
+    }
+}
+
+// the exact score of a deal
+pub fn exact_score(rules: &RuleSet, order: &[Card]) -> u8 {
+    ExactSolver::new().best_score(&HanabiEnv::from_deck_order_with(*rules, order))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MiniBaseline {
+    pub agent: String,
+    // (agent score, exact score) per deal
+    pub scores: Vec<(u8, u8)>,
+}
+
+impl MiniBaseline {
+    pub fn mean_score(&self) -> f32 {
+        self.scores.iter().map(|s| s.0 as f32).sum::<f32>() / self.scores.len().max(1) as f32
+    }
+
+    pub fn mean_exact(&self) -> f32 {
+        self.scores.iter().map(|s| s.1 as f32).sum::<f32>() / self.scores.len().max(1) as f32
+    }
+
+    // deals the agent scored the most it could on
+    pub fn optimal_rate(&self) -> f32 {
+        let optimal = self.scores.iter().filter(|s| s.0 == s.1).count();
+        optimal as f32 / self.scores.len().max(1) as f32
+    }
+}
+
+// plays the registered `agent` in both seats on the mini deals of seeds 0..num_games
+pub fn mini_baseline(
+    agent: &str,
+    rules: &RuleSet,
+    num_games: usize,
+) -> Result<MiniBaseline, String> {
+    let mut scores = Vec::with_capacity(num_games);
+    for seed in 0..num_games as u64 {
+        let order = mini_deal(rules, seed);
+        let env = HanabiEnv::from_deck_order_with(*rules, &order);
+        let (mut first, mut second) = (make_agent(agent)?, make_agent(agent)?);
+        let runner = GameRunner::from_env(env, StdRng::seed_from_u64(seed ^ 0x5eed), seed)
+            .seat(0, agent, observing(move |p, q, rng| first(p, q, rng)))
+            .seat(1, agent, observing(move |p, q, rng| second(p, q, rng)))
+            .run();
+        let score = runner.env.fireworks.total();
+        let exact = exact_score(rules, &order);
+        if score > exact {
+            return Err(format!(
+                "seed {}: {} scored {}, above the exact {}",
+                seed, agent, score, exact
+            ));
+        }
+        scores.push((score, exact));
+    }
+    Ok(MiniBaseline {
+        agent: agent.to_string(),
+        scores,
+    })
+}
+
+impl std::fmt::Display for MiniBaseline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: games={} mean={:.3} exact={:.3} ({:.1}% of exact) optimal={:.1}%",
+            self.agent,
+            self.scores.len(),
+            self.mean_score(),
+            self.mean_exact(),
+            100.0 * self.mean_score() / self.mean_exact().max(1e-6),
+            100.0 * self.optimal_rate()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attribution::double_dummy_score;
+    use crate::registry::register_builtins;
+
+    #[test]
+    fn test_exact_score() {
+        let rules = mini_rules();
+        assert_eq!(rules.deck_size(), 14);
+        assert_eq!(rules.perfect_score(), 6);
+        for seed in 0..20 {
+            let order = mini_deal(&rules, seed);
+            let exact = exact_score(&rules, &order);
+            assert!(exact <= 6);
+            // the greedy full information player is a lower bound
+            assert!(double_dummy_score(&rules, &order) <= exact);
+        }
+
+        // a deal sorted to play straight through scores everything
+        let mut sorted = mini_deal(&rules, 0);
+        sorted.sort_by_key(|c| (c.suit_id(), c.color_id()));
+        assert_eq!(exact_score(&rules, &sorted), 6);
+    }
+
+    #[test]
+    fn test_mini_baseline() {
+        register_builtins();
+        let baseline = mini_baseline("heuristic", &mini_rules(), 10).unwrap();
+        assert_eq!(baseline.scores.len(), 10);
+        assert!(baseline.mean_score() <= baseline.mean_exact());
+        assert!(baseline.mean_score() > 0.0, "{}", baseline);
+    }
+}
@@ -0,0 +1,114 @@
+// nested Monte Carlo search. the players are one team, so the game searches like a puzzle: a
+// level 0 search plays the heuristic in both seats to the end of the game, and a level n search
+// tries each move with a level n - 1 search after it, plays the first move of the best game any
+// of them found and repeats, keeping that game in case later tries do worse. a world is sampled
+// for the mover's own cards, and the draws come from an rng carried along with it, so replaying
+// a line gives the same game
+use crate::env::{Env, HasEnd, HasReward};
+use crate::evaluator::heuristic_policy;
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::rand::rngs::StdRng;
+use crate::rand::{Rng, SeedableRng};
+
+// each seat plays the heuristic from what it sees
+fn base_rollout(env: &HanabiEnv, rng: &StdRng) -> (f32, Vec<Action>) {
+    let (mut env, mut rng) = (env.clone(), rng.clone());
+    let mut line = Vec::new();
+    while !env.is_over() {
+        let action = heuristic_policy(&env.public_info(), &env.private_info(env.current_player()));
+        env.step(&action, &mut rng);
+        line.push(action);
+    }
+    (env.reward(), line)
+}
+
+// the reward of the best game a level `level` search found from `env`, and its moves
+pub fn nested(env: &HanabiEnv, rng: &StdRng, level: usize) -> (f32, Vec<Action>) {
+    if level == 0 {
+        return base_rollout(env, rng);
+    }
+    let (mut env, mut rng) = (env.clone(), rng.clone());
+    let mut played = Vec::new();
+    let mut best: Option<(f32, Vec<Action>)> = None;
+    while !env.is_over() {
+        for action in env.search_actions() {
+            let (mut child, mut child_rng) = (env.clone(), rng.clone());
+            child.step(&action, &mut child_rng);
+            let (reward, rest) = nested(&child, &child_rng, level - 1);
+            if best.as_ref().is_none_or(|b| reward > b.0) {
+                let mut line = played.clone();
+                line.push(action);
+                line.extend(rest);
+                best = Some((reward, line));
+            }
+        }
+        let action = best.as_ref().unwrap().1[played.len()];
+        env.step(&action, &mut rng);
+        played.push(action);
+    }
+    (env.reward(), played)
+}
+
+// a level `level` search in each of `num_worlds` sampled worlds, playing the move whose
+// searches found the best games on average
+pub fn nmcs_policy<R: Rng>(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    level: usize,
+    num_worlds: usize,
+    rng: &mut R,
+) -> Action {
+    if level == 0 {
+        return heuristic_policy(public_info, private_info);
+    }
+    let actions = HanabiEnv::determinize(public_info, private_info, rng)
+        .0
+        .search_actions();
+    let mut totals = vec![0.0; actions.len()];
+    for _ in 0..num_worlds {
+        let (world, _) = HanabiEnv::determinize(public_info, private_info, rng);
+        let world_rng = StdRng::seed_from_u64(rng.gen());
+        for (i, action) in actions.iter().enumerate() {
+            let (mut child, mut child_rng) = (world.clone(), world_rng.clone());
+            child.step(action, &mut child_rng);
+            totals[i] += nested(&child, &child_rng, level - 1).0;
+        }
+    }
+    let best = (0..actions.len())
+        .max_by(|&a, &b| {
+            totals[a]
+                .partial_cmp(&totals[b])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap();
+    actions[best]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_search() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let world_rng = StdRng::seed_from_u64(1);
+
+        // replaying the line a search found scores what it said
+        let (reward, line) = nested(&env, &world_rng, 1);
+        let (mut replay, mut replay_rng) = (env.clone(), world_rng.clone());
+        for action in line.iter() {
+            assert!(replay.actions().contains(action));
+            replay.step(action, &mut replay_rng);
+        }
+        assert!(replay.is_over());
+        assert_eq!(replay.reward(), reward);
+        // and it never does worse than the heuristic it starts from
+        assert!(reward >= nested(&env, &world_rng, 0).0);
+
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let action = nmcs_policy(&public_info, &private_info, 1, 2, &mut rng);
+        assert!(env.actions().contains(&action));
+    }
+}
@@ -0,0 +1,393 @@
+use crate::hanabi_env::{Action, Card, Color, HanabiEnv, Suit};
+use crate::rand::Rng;
+use crate::record::{GameRecord, TurnRecord};
+
+// one move of a line like "T12: clue P2 red → T13: P2 plays c3 (R2) ✓"
+#[derive(Clone, Debug, PartialEq)]
+pub enum Move {
+    Clue {
+        target: usize,
+        clue: Action,
+    },
+    Play {
+        seat: usize,
+        slot: Option<usize>,
+        card: Option<Card>,
+        success: Option<bool>,
+    },
+    Discard {
+        seat: usize,
+        slot: Option<usize>,
+        card: Option<Card>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NotatedTurn {
+    pub turn: usize,
+    pub mv: Move,
+}
+
+const SEPARATOR: &str = " → ";
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Red => "red",
+        Color::Blue => "blue",
+        Color::Yellow => "yellow",
+        Color::Green => "green",
+    }
+}
+
+// "red" or "3", the clue part of a clue move
+pub fn clue_text(clue: &Action) -> String {
+    match *clue {
+        Action::ColorHint(color) => color_name(color).to_string(),
+        Action::SuitHint(suit) => (suit as u8 + 1).to_string(),
+        _ => panic!("{:?} isn't a clue", clue),
+    }
+}
+
+pub fn parse_clue(text: &str) -> Option<Action> {
+    for color_id in 0..5 {
+        let color = Color::from_id(color_id);
+        if text == color_name(color) {
+            return Some(Action::ColorHint(color));
+        }
+    }
+    match text.parse::<u8>() {
+        Ok(rank) if (1..=5).contains(&rank) => Some(Action::SuitHint(Suit::from_id(rank - 1))),
+        _ => None,
+    }
+}
+
+pub fn parse_card(text: &str) -> Option<Card> {
+    let mut chars = text.chars();
+    let letter = chars.next()?.to_string();
+    let rank = chars.as_str().parse::<u8>().ok()?;
+    let color = (0..5)
+        .map(Color::from_id)
+        .find(|c| c.short_name() == letter)?;
+    if (1..=5).contains(&rank) {
+        Some(Card::new(color, Suit::from_id(rank - 1)))
+    } else {
+        None
+    }
+}
+
+fn parse_slot(text: &str) -> Option<usize> {
+    if !text.starts_with('c') {
+        return None;
+    }
+    match text[1..].parse::<usize>() {
+        Ok(slot) if (1..=5).contains(&slot) => Some(slot - 1),
+        _ => None,
+    }
+}
+
+fn parse_seat(text: &str) -> Option<usize> {
+    if !text.starts_with('P') {
+        return None;
+    }
+    match text[1..].parse::<usize>() {
+        Ok(seat) if seat >= 1 => Some(seat - 1),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for NotatedTurn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "T{}: ", self.turn)?;
+        match &self.mv {
+            Move::Clue { target, clue } => {
+                write!(f, "clue P{} ", target + 1)?;
+                match clue {
+                    Action::ColorHint(color) => write!(f, "{}", color_name(*color)),
+                    Action::SuitHint(suit) => write!(f, "{}", *suit as u8 + 1),
+                    _ => panic!("clue moves only hold hint actions"),
+                }
+            }
+            Move::Play {
+                seat,
+                slot,
+                card,
+                success,
+            } => {
+                write!(f, "P{} plays", seat + 1)?;
+                if let Some(slot) = slot {
+                    write!(f, " c{}", slot + 1)?;
+                }
+                if let Some(card) = card {
+                    write!(f, " ({:?})", card)?;
+                }
+                match success {
+                    Some(true) => write!(f, " ✓"),
+                    Some(false) => write!(f, " ✗"),
+                    None => Ok(()),
+                }
+            }
+            Move::Discard { seat, slot, card } => {
+                write!(f, "P{} discards", seat + 1)?;
+                if let Some(slot) = slot {
+                    write!(f, " c{}", slot + 1)?;
+                }
+                if let Some(card) = card {
+                    write!(f, " ({:?})", card)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl NotatedTurn {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let err = || format!("can't parse move '{}'", text);
+        let colon = text.find(':').ok_or_else(err)?;
+        let turn = text[..colon]
+            .trim()
+            .strip_prefix('T')
+            .and_then(|t| t.parse::<usize>().ok())
+            .ok_or_else(err)?;
+        let words: Vec<&str> = text[colon + 1..].split_whitespace().collect();
+
+        let mv = match words.as_slice() {
+            ["clue", target, clue] => Move::Clue {
+                target: parse_seat(target).ok_or_else(err)?,
+                clue: parse_clue(clue).ok_or_else(err)?,
+            },
+            [seat, verb, rest @ ..] if *verb == "plays" || *verb == "discards" => {
+                let seat = parse_seat(seat).ok_or_else(err)?;
+                let mut slot = None;
+                let mut card = None;
+                let mut success = None;
+                for word in rest.iter() {
+                    if word.starts_with('(') && word.ends_with(')') {
+                        card = Some(parse_card(&word[1..word.len() - 1]).ok_or_else(err)?);
+                    } else if *word == "✓" {
+                        success = Some(true);
+                    } else if *word == "✗" {
+                        success = Some(false);
+                    } else {
+                        slot = Some(parse_slot(word).ok_or_else(err)?);
+                    }
+                }
+                if *verb == "plays" {
+                    Move::Play {
+                        seat,
+                        slot,
+                        card,
+                        success,
+                    }
+                } else {
+                    Move::Discard { seat, slot, card }
+                }
+            }
+            _ => return Err(err()),
+        };
+        Ok(NotatedTurn { turn, mv })
+    }
+
+    // the action for this move
+    pub fn action(&self) -> Result<Action, String> {
+        let slot = |slot: &Option<usize>| slot.ok_or_else(|| format!("T{} has no slot", self.turn));
+        match &self.mv {
+            Move::Clue { clue, .. } => Ok(*clue),
+            Move::Play { slot: s, .. } => Ok(Action::Play(slot(s)?)),
+            Move::Discard { slot: s, .. } => Ok(Action::Discard(slot(s)?)),
+        }
+    }
+
+    // plays the move on `env`, which must be at this move's turn
+    pub fn apply<R: Rng>(&self, env: &mut HanabiEnv, rng: &mut R) -> Result<(), String> {
+        let action = self.action()?;
+        env.try_step(&action, rng)
+            .map_err(|e| format!("T{}: {}", self.turn, e))
+    }
+}
+
+// turns are numbered from 1 and seats are written P1 & P2
+pub fn notate_action(turn: usize, seat: usize, action: &Action) -> NotatedTurn {
+    let mv = match action {
+        &Action::ColorHint(_) | &Action::SuitHint(_) => Move::Clue {
+            target: 1 - seat,
+            clue: *action,
+        },
+        &Action::Play(_) => Move::Play {
+            seat,
+            slot: None,
+            card: None,
+            success: None,
+        },
+        &Action::Discard(_) => Move::Discard {
+            seat,
+            slot: None,
+            card: None,
+        },
+    };
+    NotatedTurn { turn, mv }
+}
+
+// a played turn, with the slot & card of a play or discard and whether a play went on
+pub fn notate_turn(turn_number: usize, turn: &TurnRecord) -> NotatedTurn {
+    let mut notated = notate_action(turn_number, turn.seat, &turn.action);
+    match &mut notated.mv {
+        Move::Play {
+            slot,
+            card,
+            success,
+            ..
+        } => {
+            *slot = turn.slot;
+            *card = turn.card;
+            *success = Some(turn.successful_play);
+        }
+        Move::Discard { slot, card, .. } => {
+            *slot = turn.slot;
+            *card = turn.card;
+        }
+        Move::Clue { .. } => {}
+    }
+    notated
+}
+
+pub fn notate_record(record: &GameRecord) -> Vec<NotatedTurn> {
+    record
+        .turns
+        .iter()
+        .enumerate()
+        .map(|(i, turn)| notate_turn(i + 1, turn))
+        .collect()
+}
+
+// a line of hint-addressed actions (e.g. a principal variation) starting at `first_turn`
+pub fn notate_line(first_turn: usize, first_seat: usize, actions: &[Action]) -> Vec<NotatedTurn> {
+    actions
+        .iter()
+        .enumerate()
+        .map(|(i, action)| notate_action(first_turn + i, (first_seat + i) % 2, action))
+        .collect()
+}
+
+pub fn format_line(turns: &[NotatedTurn]) -> String {
+    turns
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<String>>()
+        .join(SEPARATOR)
+}
+
+// a move as someone at the table types it: "play c3", "discard c1", "clue red" or "clue 4". the
+// seat & turn are whoever's turn it is
+pub fn parse_action(text: &str) -> Result<Action, String> {
+    let err = || {
+        format!(
+            "can't parse '{}', expected play c<n>, discard c<n> or clue <clue>",
+            text.trim()
+        )
+    };
+    match text.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["play", slot] => Ok(Action::Play(parse_slot(slot).ok_or_else(err)?)),
+        ["discard", slot] => Ok(Action::Discard(parse_slot(slot).ok_or_else(err)?)),
+        ["clue", clue] => parse_clue(clue).ok_or_else(err),
+        _ => Err(err()),
+    }
+}
+
+// parse_action, checked against whoever is to move in `env` with HanabiEnv::check_action
+pub fn parse_legal_action(env: &HanabiEnv, text: &str) -> Result<Action, String> {
+    let action = parse_action(text)?;
+    env.check_action(&action)?;
+    Ok(action)
+}
+
+pub fn parse_line(line: &str) -> Result<Vec<NotatedTurn>, String> {
+    line.split(SEPARATOR.trim())
+        .filter(|m| !m.trim().is_empty())
+        .map(|m| NotatedTurn::parse(m.trim()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasEnd};
+    use crate::rand::prelude::SliceRandom;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_parse_example() {
+        let turns = parse_line("T12: clue P2 red → T13: P2 plays c3 (R2) ✓").unwrap();
+        assert_eq!(
+            turns,
+            vec![
+                NotatedTurn {
+                    turn: 12,
+                    mv: Move::Clue {
+                        target: 1,
+                        clue: Action::ColorHint(Color::Red),
+                    },
+                },
+                NotatedTurn {
+                    turn: 13,
+                    mv: Move::Play {
+                        seat: 1,
+                        slot: Some(2),
+                        card: Some(Card::new(Color::Red, Suit::Two)),
+                        success: Some(true),
+                    },
+                },
+            ]
+        );
+        assert!(parse_line("T1: P1 juggles c1").is_err());
+
+        assert_eq!(parse_action("play c3"), Ok(Action::Play(2)));
+        assert_eq!(parse_action(" discard  c1 "), Ok(Action::Discard(0)));
+        assert_eq!(parse_action("clue red"), Ok(Action::ColorHint(Color::Red)));
+        assert_eq!(parse_action("clue 4"), Ok(Action::SuitHint(Suit::Four)));
+        assert!(parse_action("play c6").is_err());
+        assert!(parse_action("clue").is_err());
+    }
+
+    #[test]
+    fn test_parse_legal_action() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        // nothing is clued yet, so c4 carries the same hint as c1 and is still a legal play
+        assert_eq!(parse_legal_action(&env, "play c4"), Ok(Action::Play(3)));
+        let clue = clue_text(&Action::ColorHint(env.opponent_hand[0].color()));
+        assert!(parse_legal_action(&env, &format!("clue {}", clue)).is_ok());
+        // every clue token is still available
+        assert!(parse_legal_action(&env, "discard c2").is_err());
+        assert!(parse_legal_action(&env, "juggle c2").is_err());
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let order = match crate::hanabi_env::Deck::shuffled(&mut rng) {
+            crate::hanabi_env::Deck::Ordered(cards) => cards,
+            _ => unreachable!(),
+        };
+        let mut env = HanabiEnv::from_deck_order(&order);
+        let mut record = GameRecord::new(["a".to_string(), "b".to_string()]);
+        while !env.is_over() {
+            let action = *env.actions().choose(&mut rng).unwrap();
+            record.step(&mut env, &action, &mut rng);
+        }
+
+        let line = format_line(&notate_record(&record));
+        let turns = parse_line(&line).unwrap();
+        assert_eq!(turns, notate_record(&record));
+
+        // replaying the notation on the same deal reaches the same final state
+        let mut replayed = HanabiEnv::from_deck_order(&order);
+        for turn in turns.iter() {
+            turn.apply(&mut replayed, &mut rng).unwrap();
+        }
+        assert_eq!(replayed.fireworks.0, env.fireworks.0);
+        assert_eq!(replayed.discard.counts, env.discard.counts);
+    }
+}
@@ -0,0 +1,213 @@
+// nested rollout policy adaptation. rollouts pick moves from a softmax over a linear score of
+// each move's features, see features.rs. a level 0 search is one such rollout, and a level n
+// search runs `iterations` level n - 1 searches, each from the policy as adapted so far, moving
+// the weights towards the moves of the best game found after each: every move of it gains its
+// features less what the policy expected. like nmcs.rs the searches run in a sampled world with
+// its draws from an rng carried along, so the best game can be replayed to adapt on
+use crate::env::{Env, HasEnd, HasReward};
+use crate::evaluator::heuristic_policy;
+use crate::features::{action_features, ACTION_FEATURES};
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::rand::rngs::StdRng;
+use crate::rand::{Rng, SeedableRng};
+
+// how far one adaptation moves the weights
+pub const ALPHA: f32 = 1.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RolloutPolicy {
+    pub weights: [f32; ACTION_FEATURES],
+}
+
+impl Default for RolloutPolicy {
+    // a start roughly like the heuristic: play what's likely playable, discard trash over
+    // critical cards, clue playable cards and spend tokens before discarding
+    fn default() -> Self {
+        Self {
+            weights: [
+                -4.0, 0.0, -1.0, -1.0, 8.0, 3.0, -4.0, -3.0, -3.0, 0.0, 4.0, 2.0, 2.0,
+            ],
+        }
+    }
+}
+
+impl RolloutPolicy {
+    pub fn uniform() -> Self {
+        Self {
+            weights: [0.0; ACTION_FEATURES],
+        }
+    }
+}
+
+impl RolloutPolicy {
+    // the legal search moves from `env` and the policy's probability of each
+    fn distribution(
+        &self,
+        env: &HanabiEnv,
+    ) -> (Vec<Action>, Vec<[f32; ACTION_FEATURES]>, Vec<f32>) {
+        let actions = env.search_actions();
+        let features = action_features(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            &actions,
+        );
+        let logits: Vec<f32> = features
+            .iter()
+            .map(|f| f.iter().zip(self.weights.iter()).map(|(x, w)| x * w).sum())
+            .collect();
+        let max = logits.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let mut probs: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+        let total: f32 = probs.iter().sum();
+        for p in probs.iter_mut() {
+            *p /= total;
+        }
+        (actions, features, probs)
+    }
+
+    pub fn sample<R: Rng>(&self, env: &HanabiEnv, rng: &mut R) -> Action {
+        let (actions, _, probs) = self.distribution(env);
+        let mut r = rng.gen::<f32>();
+        for (action, p) in actions.iter().zip(probs.iter()) {
+            if r < *p {
+                return *action;
+            }
+            r -= p;
+        }
+        *actions.last().unwrap()
+    }
+
+    // replays `line` from `env`, moving the weights towards each of its moves
+    pub fn adapt(&mut self, env: &HanabiEnv, world_rng: &StdRng, line: &[Action], alpha: f32) {
+        let mut adapted = self.clone();
+        let (mut env, mut world_rng) = (env.clone(), world_rng.clone());
+        for action in line.iter() {
+            let (actions, features, probs) = self.distribution(&env);
+            if let Some(chosen) = actions.iter().position(|a| a == action) {
+                for k in 0..ACTION_FEATURES {
+                    let expected: f32 = features
+                        .iter()
+                        .zip(probs.iter())
+                        .map(|(f, p)| p * f[k])
+                        .sum();
+                    adapted.weights[k] += alpha * (features[chosen][k] - expected);
+                }
+            }
+            env.step(action, &mut world_rng);
+        }
+        *self = adapted;
+    }
+}
+
+// a game played out by `policy` from `env`, its reward and moves
+fn playout<R: Rng>(
+    env: &HanabiEnv,
+    world_rng: &StdRng,
+    policy: &RolloutPolicy,
+    rng: &mut R,
+) -> (f32, Vec<Action>) {
+    let (mut env, mut world_rng) = (env.clone(), world_rng.clone());
+    let mut line = Vec::new();
+    while !env.is_over() {
+        let action = policy.sample(&env, rng);
+        env.step(&action, &mut world_rng);
+        line.push(action);
+    }
+    (env.reward(), line)
+}
+
+// the reward of the best game a level `level` search from `env` found, and its moves
+pub fn nrpa<R: Rng>(
+    env: &HanabiEnv,
+    world_rng: &StdRng,
+    level: usize,
+    iterations: usize,
+    policy: &RolloutPolicy,
+    rng: &mut R,
+) -> (f32, Vec<Action>) {
+    if level == 0 {
+        return playout(env, world_rng, policy, rng);
+    }
+    let mut policy = policy.clone();
+    let mut best = (f32::NEG_INFINITY, Vec::new());
+    for _ in 0..iterations {
+        let found = nrpa(env, world_rng, level - 1, iterations, &policy, rng);
+        if found.0 >= best.0 {
+            best = found;
+        }
+        policy.adapt(env, world_rng, &best.1, ALPHA);
+    }
+    best
+}
+
+// a level `level` search in each of `num_worlds` sampled worlds, playing the first move of the
+// best games found, weighted by their rewards
+pub fn nrpa_policy<R: Rng>(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    level: usize,
+    iterations: usize,
+    num_worlds: usize,
+    rng: &mut R,
+) -> Action {
+    if level == 0 || num_worlds == 0 {
+        return heuristic_policy(public_info, private_info);
+    }
+    let mut votes: Vec<(Action, f32)> = Vec::new();
+    for _ in 0..num_worlds {
+        let (world, _) = HanabiEnv::determinize(public_info, private_info, rng);
+        let world_rng = StdRng::seed_from_u64(rng.gen());
+        let (reward, line) = nrpa(
+            &world,
+            &world_rng,
+            level,
+            iterations,
+            &RolloutPolicy::default(),
+            rng,
+        );
+        match votes.iter_mut().find(|v| v.0 == line[0]) {
+            Some(vote) => vote.1 += reward,
+            None => votes.push((line[0], reward)),
+        }
+    }
+    votes
+        .iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .unwrap()
+        .0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nrpa() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let world_rng = StdRng::seed_from_u64(1);
+
+        // adapting towards a move makes it likelier
+        let policy = RolloutPolicy::uniform();
+        let (actions, _, probs) = policy.distribution(&env);
+        let target = actions[0];
+        let mut adapted = policy.clone();
+        adapted.adapt(&env, &world_rng, &[target], ALPHA);
+        let (_, _, adapted_probs) = adapted.distribution(&env);
+        assert!(adapted_probs[0] > probs[0]);
+
+        // the best game a search found replays to the reward it reported
+        let (reward, line) = nrpa(&env, &world_rng, 1, 10, &policy, &mut rng);
+        let (mut replay, mut replay_rng) = (env.clone(), world_rng.clone());
+        for action in line.iter() {
+            assert!(replay.actions().contains(action));
+            replay.step(action, &mut replay_rng);
+        }
+        assert!(replay.is_over());
+        assert_eq!(replay.reward(), reward);
+
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let action = nrpa_policy(&public_info, &private_info, 1, 5, 2, &mut rng);
+        assert!(env.actions().contains(&action));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::evaluator::Evaluator;
+use crate::features::{encode, policy_indices, INPUT_SIZE, POLICY_SIZE};
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use std::sync::Arc;
+use tract_onnx::prelude::*;
+
+// a policy/value network taking `encode`d inputs of shape [1, INPUT_SIZE] and returning
+// a value of shape [1, 1] and policy logits of shape [1, POLICY_SIZE]
+#[derive(Clone)]
+pub struct OnnxEvaluator {
+    model: Arc<TypedRunnableModel<TypedModel>>,
+}
+
+impl OnnxEvaluator {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let model = tract_onnx::onnx()
+            .model_for_path(path)
+            .and_then(|m| {
+                m.with_input_fact(0, f32::fact(&[1, INPUT_SIZE]).into())?
+                    .into_optimized()?
+                    .into_runnable()
+            })
+            .map_err(|e| format!("can't load model '{}': {}", path, e))?;
+        Ok(Self {
+            model: Arc::new(model),
+        })
+    }
+
+    fn run(&self, features: Vec<f32>) -> TractResult<(f32, Vec<f32>)> {
+        let input = tract_ndarray::Array2::from_shape_vec((1, INPUT_SIZE), features)?;
+        let outputs = self.model.run(tvec!(Tensor::from(input).into()))?;
+        let value = *outputs[0].to_array_view::<f32>()?.iter().next().unwrap();
+        let logits = outputs[1].to_array_view::<f32>()?.iter().cloned().collect();
+        Ok((value, logits))
+    }
+}
+
+impl Evaluator<HanabiEnv> for OnnxEvaluator {
+    fn evaluate(
+        &mut self,
+        public_info: &PublicInfo,
+        private_info: &PrivateInfo,
+        actions: &[Action],
+    ) -> (f32, Vec<f32>) {
+        let (value, logits) = self
+            .run(encode(public_info, private_info))
+            .expect("onnx model failed to run");
+        assert_eq!(logits.len(), POLICY_SIZE);
+
+        // softmax over the legal actions only
+        let action_logits: Vec<f32> = actions
+            .iter()
+            .map(|action| {
+                policy_indices(public_info, action)
+                    .iter()
+                    .map(|&i| logits[i])
+                    .fold(std::f32::NEG_INFINITY, f32::max)
+            })
+            .collect();
+        let max = action_logits
+            .iter()
+            .cloned()
+            .fold(std::f32::NEG_INFINITY, f32::max);
+        let mut priors: Vec<f32> = action_logits.iter().map(|l| (l - max).exp()).collect();
+        let total = priors.iter().sum::<f32>();
+        for p in priors.iter_mut() {
+            *p /= total;
+        }
+        (value, priors)
+    }
+}
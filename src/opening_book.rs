@@ -0,0 +1,324 @@
+// an opening book: the moves a long search picked over the first turns of a set of deals, saved
+// to a store so agents play them straight away instead of searching. a book keyed on the
+// partner's exact cards would only answer the deals it was built on, so the partner's hand is
+// abstracted to what each slot is worth to the fireworks, see SlotClass, and the public state is
+// keyed up to relabeling the colors. moves are booked relative to the hand: a clue is booked by
+// its kind and the first slot it touches, and read back as that kind of clue on whatever card
+// holds the slot. a key several deals share books the move picked most often. entries are
+//
+//   key: canonical public key u64 | the partner's 5 slot classes   value: the action's code
+//
+// with the key little endian
+use crate::abstraction::{is_critical_with, is_trash_with};
+use crate::env::{Env, HasEnd, HasSymmetry};
+use crate::hanabi_env::{Action, Card, Color, PrivateInfo, PublicInfo, Suit};
+use crate::kv_store::{copy_store, FileStore, KvStore, MemoryStore};
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+use crate::registry::Agent;
+use crate::runner::deal;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+// book moves are only looked up this early in a game
+pub const MAX_BOOK_TURNS: u16 = 8;
+
+pub fn encode_action(action: &Action) -> u8 {
+    match *action {
+        Action::Play(slot) => slot as u8,
+        Action::Discard(slot) => 8 | slot as u8,
+        Action::ColorHint(color) => 16 | color as u8,
+        Action::SuitHint(suit) => 24 | suit as u8,
+    }
+}
+
+pub fn decode_action(code: u8) -> Result<Action, String> {
+    let index = code & 7;
+    if index > 4 || code >= 32 {
+        return Err(format!("invalid action code {}", code));
+    }
+    Ok(match code >> 3 {
+        0 => Action::Play(index as usize),
+        1 => Action::Discard(index as usize),
+        2 => Action::ColorHint(Color::from_id(index)),
+        _ => Action::SuitHint(Suit::from_id(index)),
+    })
+}
+
+// what a card in the partner's hand is worth to the fireworks
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlotClass {
+    Empty,
+    Playable,
+    // not playable yet, and the last copy outside the discard
+    Critical,
+    Trash,
+    // not playable yet, with another copy left
+    Later,
+}
+
+pub fn slot_class(public_info: &PublicInfo, card: Card) -> SlotClass {
+    let (rules, fireworks, discard) = (
+        &public_info.rules,
+        &public_info.fireworks,
+        &public_info.discard,
+    );
+    if !card.is_some() {
+        SlotClass::Empty
+    } else if fireworks.accepts(card) {
+        SlotClass::Playable
+    } else if is_trash_with(rules, card, fireworks, discard) {
+        SlotClass::Trash
+    } else if is_critical_with(rules, card, fireworks, discard) {
+        SlotClass::Critical
+    } else {
+        SlotClass::Later
+    }
+}
+
+fn book_key(public_info: &PublicInfo, private_info: &PrivateInfo) -> Vec<u8> {
+    let mut key = public_info.canonical_key().to_le_bytes().to_vec();
+    key.extend(
+        private_info
+            .opponent_hand
+            .iter()
+            .map(|&card| slot_class(public_info, card) as u8),
+    );
+    key
+}
+
+// a move as the book stores it: plays & discards as they are, a clue as the first slot of the
+// partner's it touches in place of the color or rank
+fn book_code(private_info: &PrivateInfo, action: &Action) -> Option<u8> {
+    let hand = &private_info.opponent_hand;
+    let first_touched =
+        |touches: &dyn Fn(Card) -> bool| (0..5).find(|&i| hand[i].is_some() && touches(hand[i]));
+    Some(match *action {
+        Action::Play(_) | Action::Discard(_) => encode_action(action),
+        Action::ColorHint(color) => 16 | first_touched(&|c| c.color() == color)? as u8,
+        Action::SuitHint(suit) => 24 | first_touched(&|c| c.suit() == suit)? as u8,
+    })
+}
+
+// the move a book code stands for in this position
+fn book_action(private_info: &PrivateInfo, code: u8) -> Option<Action> {
+    let hand = &private_info.opponent_hand;
+    let card = |slot: usize| Some(hand[slot]).filter(|c| c.is_some());
+    Some(match decode_action(code).ok()? {
+        Action::ColorHint(_) => Action::ColorHint(card(code as usize & 7)?.color()),
+        Action::SuitHint(_) => Action::SuitHint(card(code as usize & 7)?.suit()),
+        action => action,
+    })
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct OpeningBook {
+    store: MemoryStore,
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the whole book is read into memory, it's small
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mut book = Self::new();
+        copy_store(&FileStore::open(path)?, &mut book.store)?;
+        Ok(book)
+    }
+
+    pub fn save(&self, path: &str) -> Result<usize, String> {
+        copy_store(&self.store, &mut FileStore::open(path)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    // replaces whatever the position's key had booked
+    pub fn insert(
+        &mut self,
+        public_info: &PublicInfo,
+        private_info: &PrivateInfo,
+        action: &Action,
+    ) {
+        if let Some(code) = book_code(private_info, action) {
+            // a memory store can't fail
+            self.store
+                .put(&book_key(public_info, private_info), &[code])
+                .unwrap();
+        }
+    }
+
+    pub fn lookup(&self, public_info: &PublicInfo, private_info: &PrivateInfo) -> Option<Action> {
+        if public_info.hint_history.turn >= MAX_BOOK_TURNS {
+            return None;
+        }
+        let value = self
+            .store
+            .get(&book_key(public_info, private_info))
+            .unwrap()?;
+        book_action(private_info, *value.first()?)
+    }
+}
+
+// plays `agent` in both seats over the first `turns` turns of the deals of seeds 0..num_deals,
+// booking the move it picked most often for each key. the agent should search longer than the
+// ones the book is for, or the book only saves them time
+pub fn build_book(agent: &mut Agent, num_deals: u64, turns: u16) -> Result<OpeningBook, String> {
+    if turns > MAX_BOOK_TURNS {
+        return Err(format!(
+            "{} turns, the book is only read for the first {}",
+            turns, MAX_BOOK_TURNS
+        ));
+    }
+    let mut votes: BTreeMap<Vec<u8>, BTreeMap<u8, usize>> = BTreeMap::new();
+    for seed in 0..num_deals {
+        let mut env = deal(seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..turns {
+            if env.is_over() {
+                break;
+            }
+            let (public_info, private_info) =
+                (env.public_info(), env.private_info(env.current_player()));
+            let action = agent(public_info.clone(), private_info.clone(), &mut rng);
+            if let Some(code) = book_code(&private_info, &action) {
+                let key = book_key(&public_info, &private_info);
+                *votes.entry(key).or_default().entry(code).or_default() += 1;
+            }
+            env.step(&action, &mut rng);
+        }
+    }
+    let mut book = OpeningBook::new();
+    for (key, codes) in votes {
+        // the most votes, the lowest code on a tie
+        let code = codes
+            .iter()
+            .max_by_key(|&(&code, &n)| (n, !code))
+            .unwrap()
+            .0;
+        book.store.put(&key, &[*code]).unwrap();
+    }
+    Ok(book)
+}
+
+// how many of the positions over the first `turns` turns of the deals of `seeds` the book
+// answers, and how many there were. the book's moves are played where it has one, `agent`'s
+// elsewhere. seeds the book wasn't built on say how far it carries to new deals
+pub fn hit_rate(
+    book: &OpeningBook,
+    agent: &mut Agent,
+    seeds: Range<u64>,
+    turns: u16,
+) -> (usize, usize) {
+    let (mut hits, mut positions) = (0, 0);
+    for seed in seeds {
+        let mut env = deal(seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..turns.min(MAX_BOOK_TURNS) {
+            if env.is_over() {
+                break;
+            }
+            let (public_info, private_info) =
+                (env.public_info(), env.private_info(env.current_player()));
+            positions += 1;
+            let action = match book.lookup(&public_info, &private_info) {
+                Some(action) => {
+                    hits += 1;
+                    action
+                }
+                None => agent(public_info, private_info, &mut rng),
+            };
+            env.step(&action, &mut rng);
+        }
+    }
+    (hits, positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::heuristic_policy;
+    use crate::hanabi_symmetry::ColorPermutation;
+    use crate::position::Position;
+
+    #[test]
+    fn test_opening_book() {
+        for code in 0..32 {
+            match decode_action(code) {
+                Ok(action) => assert_eq!(encode_action(&action), code),
+                Err(_) => assert!(code & 7 > 4),
+            }
+        }
+
+        let mut agent: Agent = Box::new(|p, q, _| heuristic_policy(&p, &q));
+        let book = build_book(&mut agent, 5, 3).unwrap();
+        assert!(!book.is_empty() && book.len() <= 15);
+        assert!(build_book(&mut agent, 1, MAX_BOOK_TURNS + 1).is_err());
+
+        // the book answers the deals it was built on, color swapped or not
+        let env = deal(0);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let expected = heuristic_policy(&public_info, &private_info);
+        assert_eq!(book.lookup(&public_info, &private_info), Some(expected));
+        let swap = ColorPermutation([1, 0, 2, 3, 4]);
+        assert_eq!(
+            book.lookup(&public_info.apply(&swap), &swap.private_info(&private_info)),
+            Some(swap.action(&expected))
+        );
+
+        let path = std::env::temp_dir().join(format!("hanabi-book-{}.log", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(book.save(path).unwrap(), book.len());
+        let reopened = OpeningBook::open(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(reopened.len(), book.len());
+        assert_eq!(reopened.lookup(&public_info, &private_info), Some(expected));
+    }
+
+    #[test]
+    fn test_book_carries_to_new_deals() {
+        // the partner's cards differ but each slot is worth the same: a 1 in slots 1 & 3 and a 5
+        // in slot 4. a clue on the 1 in slot 1 reads back as the same kind of clue on the other
+        let position = |hand: &str| {
+            Position::parse(&format!("{} ?.?.?.?.? ?.?.?.?.? 00000 - 8/3 -", hand)).unwrap()
+        };
+        let (first, other) = (position("B3.R1.R2.Y1.W5"), position("G4.Y1.B2.G1.R5"));
+        let mut book = OpeningBook::new();
+        book.insert(
+            &first.public_info,
+            &first.private_info,
+            &Action::ColorHint(Color::Red),
+        );
+        assert_eq!(
+            book.lookup(&other.public_info, &other.private_info),
+            Some(Action::ColorHint(Color::Yellow))
+        );
+        book.insert(
+            &first.public_info,
+            &first.private_info,
+            &Action::SuitHint(Suit::Five),
+        );
+        assert_eq!(
+            book.lookup(&other.public_info, &other.private_info),
+            Some(Action::SuitHint(Suit::Five))
+        );
+        // a 1 where the 5 was isn't the same position
+        let moved = position("G4.Y1.B2.G5.R1");
+        assert_eq!(book.lookup(&moved.public_info, &moved.private_info), None);
+
+        // a book answers some of the opening positions of deals it wasn't built on
+        let mut agent: Agent = Box::new(|p, q, _| heuristic_policy(&p, &q));
+        let book = build_book(&mut agent, 100, 2).unwrap();
+        let (hits, positions) = hit_rate(&book, &mut agent, 1_000..1_100, 2);
+        assert_eq!(positions, 200);
+        assert!(hits > 0, "{} of {}", hits, positions);
+    }
+}
@@ -0,0 +1,297 @@
+// one-line position strings from the point of view of the player to move, e.g.
+//
+//   B3.Y1.B1.Y1.W1 RBYG1.W1234.W5.1234.1234 B.WRYG.B.?.WRYG 10001 - 7/2 -
+//
+// fields are separated by spaces:
+// - the opponent's hand, cards like "R3" separated by '.', an empty slot is '-'
+// - the player's hints then the opponent's hints: the colors then the ranks a card can still
+//   be, leaving out whichever part is fully open ('?' when both are), an empty slot is '-'
+// - fireworks levels in color order W R B Y G, e.g. "10001"
+// - the discard as cards run together, e.g. "R1R1B2", or '-' when empty
+// - blue tokens/black tokens
+// - '-', or "L<n>" in the last round after n turns of it were taken
+use crate::env::Env;
+use crate::hanabi_env::{
+    max_score, Card, CardCollection, ClueTokens, Color, Fireworks, HanabiEnv, Hint, HintHistory,
+    PrivateInfo, PublicInfo, RuleSet, SearchPruning, StrikeTokens,
+};
+use crate::notation::parse_card;
+
+#[derive(Clone)]
+pub struct Position {
+    pub public_info: PublicInfo,
+    pub private_info: PrivateInfo,
+}
+
+const ALL: u8 = 0b11111;
+
+fn write_hint(f: &mut std::fmt::Formatter<'_>, hint: &Hint) -> std::fmt::Result {
+    if !hint.is_some() {
+        return write!(f, "-");
+    }
+    if hint.color_mask() == ALL && hint.suit_mask() == ALL {
+        return write!(f, "?");
+    }
+    if hint.color_mask() != ALL {
+        for c in (0..5).filter(|&c| hint.color_mask() & (1 << c) != 0) {
+            write!(f, "{}", Color::from_id(c).short_name())?;
+        }
+    }
+    if hint.suit_mask() != ALL {
+        for r in (0..5).filter(|&r| hint.suit_mask() & (1 << r) != 0) {
+            write!(f, "{}", r + 1)?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_hint(text: &str) -> Result<Hint, String> {
+    match text {
+        "-" => return Ok(Hint::from_masks(0b100000, 0b100000)),
+        "?" => return Ok(Hint::from_masks(ALL, ALL)),
+        _ => {}
+    }
+    let mut color = 0;
+    let mut suit = 0;
+    for ch in text.chars() {
+        match ch.to_digit(10) {
+            Some(rank) if (1..=5).contains(&rank) => suit |= 1 << (rank - 1),
+            Some(_) => return Err(format!("invalid rank '{}' in hint '{}'", ch, text)),
+            None => {
+                let id = (0..5)
+                    .find(|&c| Color::from_id(c).short_name() == ch.to_string())
+                    .ok_or_else(|| format!("invalid color '{}' in hint '{}'", ch, text))?;
+                color |= 1 << id;
+            }
+        }
+    }
+    if color == 0 {
+        color = ALL;
+    }
+    if suit == 0 {
+        suit = ALL;
+    }
+    Ok(Hint::from_masks(color, suit))
+}
+
+fn parse_hints(text: &str) -> Result<[Hint; 5], String> {
+    let parts: Vec<&str> = text.split('.').collect();
+    if parts.len() != 5 {
+        return Err(format!("expected 5 hints, found '{}'", text));
+    }
+    let mut hints = [Hint::empty(); 5];
+    for (i, part) in parts.iter().enumerate() {
+        hints[i] = parse_hint(part)?;
+    }
+    Ok(hints)
+}
+
+fn parse_hand(text: &str) -> Result<[Card; 5], String> {
+    let parts: Vec<&str> = text.split('.').collect();
+    if parts.len() != 5 {
+        return Err(format!("expected 5 cards, found '{}'", text));
+    }
+    let mut hand = [Card::none(); 5];
+    for (i, part) in parts.iter().enumerate() {
+        if *part != "-" {
+            hand[i] = parse_card(part).ok_or_else(|| format!("invalid card '{}'", part))?;
+        }
+    }
+    Ok(hand)
+}
+
+fn parse_discard(text: &str) -> Result<CardCollection, String> {
+    let mut discard = CardCollection::empty();
+    if text == "-" {
+        return Ok(discard);
+    }
+    let chars: Vec<char> = text.chars().collect();
+    if !chars.len().is_multiple_of(2) {
+        return Err(format!("invalid discard '{}'", text));
+    }
+    for pair in chars.chunks(2) {
+        let card: String = pair.iter().collect();
+        discard.add(parse_card(&card).ok_or_else(|| format!("invalid card '{}'", card))?);
+    }
+    Ok(discard)
+}
+
+fn parse_fireworks(text: &str) -> Result<Fireworks, String> {
+    let levels: Vec<u32> = text.chars().filter_map(|ch| ch.to_digit(10)).collect();
+    if text.len() != 5 || levels.len() != 5 || levels.iter().any(|&l| l > 5) {
+        return Err(format!("invalid fireworks '{}'", text));
+    }
+    let mut fireworks = Fireworks([0; 5]);
+    for (c, &level) in levels.iter().enumerate() {
+        fireworks.0[c] = level as u8;
+    }
+    Ok(fireworks)
+}
+
+fn parse_count(text: &str) -> Result<u8, String> {
+    text.parse::<u8>()
+        .map_err(|_| format!("invalid count '{}'", text))
+}
+
+impl Position {
+    pub fn from_env(env: &HanabiEnv) -> Self {
+        Self {
+            public_info: env.public_info(),
+            private_info: env.private_info(env.current_player()),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        if fields.len() != 7 {
+            return Err(format!("expected 7 fields, found {}", fields.len()));
+        }
+        let (blue, black) = match fields[5].find('/') {
+            Some(i) => (&fields[5][..i], &fields[5][i + 1..]),
+            None => return Err(format!("invalid tokens '{}'", fields[5])),
+        };
+        let (last_round, last_round_turns_taken) = match fields[6] {
+            "-" => (false, 0),
+            turn if turn.starts_with('L') => (true, parse_count(&turn[1..])?),
+            turn => return Err(format!("invalid turn '{}'", turn)),
+        };
+        let discard = parse_discard(fields[4])?;
+        let fireworks = parse_fireworks(fields[3])?;
+        Ok(Self {
+            public_info: PublicInfo {
+                player_hints: parse_hints(fields[1])?,
+                opponent_hints: parse_hints(fields[2])?,
+                discard,
+                blue_tokens: ClueTokens::new(parse_count(blue)?),
+                black_tokens: StrikeTokens::new(parse_count(black)?),
+                fireworks,
+                max_score: max_score(&fireworks, &discard),
+                last_round,
+                last_round_turns_taken,
+                hint_history: HintHistory::default(),
+                rules: RuleSet::default(),
+                pruning: SearchPruning::default(),
+            },
+            private_info: PrivateInfo {
+                opponent_hand: parse_hand(fields[0])?,
+            },
+        })
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let public_info = &self.public_info;
+        for (i, card) in self.private_info.opponent_hand.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            if card.is_some() {
+                write!(f, "{:?}", card)?;
+            } else {
+                write!(f, "-")?;
+            }
+        }
+        for hints in [&public_info.player_hints, &public_info.opponent_hints].iter() {
+            write!(f, " ")?;
+            for (i, hint) in hints.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ".")?;
+                }
+                write_hint(f, hint)?;
+            }
+        }
+        write!(f, " ")?;
+        for level in public_info.fireworks.0.iter() {
+            write!(f, "{}", level)?;
+        }
+        write!(f, " ")?;
+        if public_info.discard.total == 0 {
+            write!(f, "-")?;
+        }
+        for id in 0..25 {
+            for _ in 0..public_info.discard.counts[id] {
+                write!(f, "{:?}", Card::from_id(id as u8))?;
+            }
+        }
+        write!(
+            f,
+            " {}/{} ",
+            public_info.blue_tokens, public_info.black_tokens
+        )?;
+        if public_info.last_round {
+            write!(f, "L{}", public_info.last_round_turns_taken)
+        } else {
+            write!(f, "-")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{HasEnd, HasKey};
+    use crate::hanabi_env::Suit;
+    use crate::rand::prelude::SliceRandom;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_position_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        while !env.is_over() {
+            let position = Position::from_env(&env);
+            let text = position.to_string();
+            let parsed = Position::parse(&text).unwrap();
+            // a position is written from the mover's seat, without saying which seat that is
+            let mut public_info = position.public_info.clone();
+            public_info.hint_history.turn = 0;
+            assert_eq!(parsed.public_info.key(), public_info.key());
+            assert_eq!(parsed.private_info, position.private_info);
+            assert_eq!(parsed.to_string(), text);
+
+            let action = *env.actions().choose(&mut rng).unwrap();
+            env.step(&action, &mut rng);
+        }
+    }
+
+    #[test]
+    fn test_parse_position() {
+        // the position of hanabi_env::tests::test_weird
+        let position = Position::parse(
+            "B3.Y1.B1.Y1.W1 RBYG1.W1234.W5.1234.1234 B.WRYG.B.?.WRYG 10001 - 7/2 -",
+        )
+        .unwrap();
+        let public_info = &position.public_info;
+        assert_eq!(public_info.player_hints[0].color_mask(), 0b11110);
+        assert_eq!(public_info.player_hints[0].suit_mask(), 0b00001);
+        assert_eq!(public_info.player_hints[3].color_mask(), 0b11111);
+        assert_eq!(public_info.player_hints[3].suit_mask(), 0b01111);
+        assert_eq!(public_info.opponent_hints[1].color_mask(), 0b11011);
+        assert_eq!(public_info.opponent_hints[3].suit_mask(), 0b11111);
+        assert_eq!(public_info.fireworks.0, [1, 0, 0, 0, 1]);
+        assert_eq!(public_info.discard.total, 0);
+        assert_eq!(
+            (
+                public_info.blue_tokens.get(),
+                public_info.black_tokens.get()
+            ),
+            (7, 2)
+        );
+        assert!(!public_info.last_round);
+        assert_eq!(
+            position.private_info.opponent_hand[0],
+            Card::new(Color::Blue, Suit::Three)
+        );
+
+        let late = Position::parse("-.R1.-.G5.W2 ?.-.?.?.? ?.?.?.?.? 55432 R1R1B2 0/1 L1").unwrap();
+        assert!(!late.private_info.opponent_hand[0].is_some());
+        assert!(!late.public_info.player_hints[1].is_some());
+        assert_eq!(late.public_info.discard.total, 3);
+        assert_eq!(late.public_info.last_round_turns_taken, 1);
+
+        assert!(Position::parse("B3.Y1 ? ? 00000 - 8/4 -").is_err());
+        assert!(Position::parse("B3.Y1.B1.Y1.W1 ?.?.?.?.? ?.?.?.?.? 00600 - 8/4 -").is_err());
+    }
+}
@@ -0,0 +1,221 @@
+// recommendation clues for the 2 player game: a clue doesn't mean what it says about the cards,
+// it carries a number naming a move for the partner. a recommendation is to play the lowest
+// playable card, else discard trash, else the highest card that isn't critical, else the first
+// card, and whoever gets one follows it on their next turn.
+// the numbering is hat guessing's, where a clue's number is the sum of the recommendations for
+// every hand the giver sees modulo the number of moves, and each receiver takes away the ones
+// for the hands they see. with 2 players there's one hand in the sum, so a clue just names the
+// partner's move: this is a direct recommendation agent, not the hat guessing strategy, whose
+// strength comes from clueing 2-4 other players at once. that agent for 4-5 players still needs
+// an env seating more than 2, HanabiEnv::new only takes 2. encode & decode already take any
+// number of hands for it
+use crate::abstraction::{is_critical_with, is_trash_with};
+use crate::beliefs::Beliefs;
+use crate::hanabi_env::{Action, Card, PrivateInfo, PublicInfo, COLORS, SUITS};
+
+// play or discard each of the 5 slots
+pub const NUM_RECOMMENDATIONS: u8 = 10;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Recommendation {
+    Play(usize),
+    Discard(usize),
+}
+
+impl Recommendation {
+    pub fn value(&self) -> u8 {
+        match *self {
+            Recommendation::Play(slot) => slot as u8,
+            Recommendation::Discard(slot) => 5 + slot as u8,
+        }
+    }
+
+    pub fn from_value(value: u8) -> Self {
+        match value % NUM_RECOMMENDATIONS {
+            v if v < 5 => Recommendation::Play(v as usize),
+            v => Recommendation::Discard(v as usize - 5),
+        }
+    }
+
+    pub fn action(&self) -> Action {
+        match *self {
+            Recommendation::Play(slot) => Action::Play(slot),
+            Recommendation::Discard(slot) => Action::Discard(slot),
+        }
+    }
+}
+
+// the move the holder of `hand` should make
+pub fn recommend(public_info: &PublicInfo, hand: &[Card; 5]) -> Recommendation {
+    let (rules, fireworks, discard) = (
+        &public_info.rules,
+        &public_info.fireworks,
+        &public_info.discard,
+    );
+    let held = || (0..5).filter(|&i| hand[i].is_some());
+    let playable = held()
+        .filter(|&i| fireworks.0[hand[i].color_id() as usize] == hand[i].suit_id())
+        .min_by_key(|&i| hand[i].suit_id());
+    if let Some(slot) = playable {
+        return Recommendation::Play(slot);
+    }
+    if let Some(slot) = held().find(|&i| is_trash_with(rules, hand[i], fireworks, discard)) {
+        return Recommendation::Discard(slot);
+    }
+    let expendable = held()
+        .filter(|&i| !is_critical_with(rules, hand[i], fireworks, discard))
+        .max_by_key(|&i| (hand[i].suit_id(), std::cmp::Reverse(i)));
+    Recommendation::Discard(expendable.unwrap_or(0))
+}
+
+// the number a clue carries for the recommendations of every hand the giver sees
+pub fn encode(recommendations: &[Recommendation]) -> u8 {
+    let sum: u32 = recommendations.iter().map(|r| r.value() as u32).sum();
+    (sum % NUM_RECOMMENDATIONS as u32) as u8
+}
+
+// the receiver's recommendation from a clue's number and the ones for the other hands they see
+pub fn decode(value: u8, seen: &[Recommendation]) -> Recommendation {
+    let n = NUM_RECOMMENDATIONS as u32;
+    let seen = encode(seen) as u32;
+    Recommendation::from_value(((value as u32 + n - seen) % n) as u8)
+}
+
+// the number a clue stands for: colors say play, ranks discard, in the first slot touched
+fn clue_value(clue: &Action, touched: u8) -> Option<u8> {
+    let slot = touched.trailing_zeros() as u8;
+    match clue {
+        _ if touched == 0 => None,
+        Action::ColorHint(_) => Some(slot),
+        Action::SuitHint(_) => Some(5 + slot),
+        _ => None,
+    }
+}
+
+// a clue to the partner carrying `value`, if any does
+fn clue_for(public_info: &PublicInfo, hand: &[Card; 5], value: u8) -> Option<Action> {
+    let touched_by = |clue: &Action| {
+        (0..5)
+            .filter(|&i| {
+                hand[i].is_some()
+                    && match clue {
+                        Action::ColorHint(color) => hand[i].color() == *color,
+                        Action::SuitHint(suit) => hand[i].suit() == *suit,
+                        _ => false,
+                    }
+            })
+            .fold(0u8, |bits, i| bits | 1 << i)
+    };
+    COLORS
+        .iter()
+        .map(|&color| Action::ColorHint(color))
+        .filter(|clue| public_info.rules.allows_clue(clue))
+        .chain(SUITS.iter().map(|&suit| Action::SuitHint(suit)))
+        .find(|clue| clue_value(clue, touched_by(clue)) == Some(value))
+}
+
+// the recommendation in the clue the partner gave on the turn just gone, if they gave one
+pub fn received_recommendation(public_info: &PublicInfo) -> Option<Recommendation> {
+    let history = &public_info.hint_history;
+    let event = history.latest_received()?;
+    if history.turns_ago(event) != 1 {
+        return None;
+    }
+    // no one else's hand to take away with 2 players
+    clue_value(&event.clue, event.touched).map(|value| decode(value, &[]))
+}
+
+pub fn recommendation_policy(public_info: &PublicInfo, private_info: &PrivateInfo) -> Action {
+    let can_discard = public_info.blue_tokens < public_info.rules.clue_tokens;
+    if let Some(recommendation) = received_recommendation(public_info) {
+        match recommendation {
+            Recommendation::Discard(_) if !can_discard => {}
+            _ => return recommendation.action(),
+        }
+    }
+
+    let partner = &private_info.opponent_hand;
+    let for_partner = recommend(public_info, partner);
+    let clue = clue_for(public_info, partner, encode(&[for_partner]));
+    if public_info.blue_tokens > 0 {
+        if let (Recommendation::Play(_), Some(clue)) = (for_partner, clue) {
+            return clue;
+        }
+    }
+
+    // cards the clues so far show are playable or trash, whatever they were meant to say
+    let summary = Beliefs::new(public_info, private_info).summary(public_info);
+    let held: Vec<usize> = (0..5)
+        .filter(|&i| public_info.player_hints[i].is_some())
+        .collect();
+    if let Some(&slot) = held.iter().find(|&&i| summary[i].playable >= 1.0) {
+        return Action::Play(slot);
+    }
+    if can_discard {
+        let slot = held
+            .iter()
+            .find(|&&i| summary[i].trash >= 1.0)
+            .or_else(|| held.iter().min_by_key(|&&i| (summary[i].critical > 0.0, i)))
+            .unwrap();
+        return Action::Discard(*slot);
+    }
+    // a full hand of tokens, so a clue has to be given. a rank clue says discard, which the
+    // partner can't do at full tokens either, and they fall back on the above
+    (5..NUM_RECOMMENDATIONS)
+        .find_map(|value| clue_for(public_info, partner, value))
+        .or(clue)
+        .unwrap_or(Action::Play(held[0]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasEnd};
+    use crate::hanabi_env::{Color, HanabiEnv};
+    use crate::position::Position;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_recommendations() {
+        // every receiver recovers their own recommendation from the sum
+        let recommendations = [
+            Recommendation::Play(3),
+            Recommendation::Discard(1),
+            Recommendation::Discard(4),
+        ];
+        let value = encode(&recommendations);
+        for i in 0..recommendations.len() {
+            let mut seen = recommendations.to_vec();
+            let own = seen.remove(i);
+            assert_eq!(decode(value, &seen), own);
+        }
+
+        // the partner's R1 & Y1 are the lowest playable cards, the first slot breaks the tie and
+        // a red clue touches it first
+        let position = Position::parse("B3.R1.R2.Y1.W5 ?.?.?.?.? ?.?.?.?.? 00000 - 8/3 -").unwrap();
+        let (public_info, private_info) = (&position.public_info, &position.private_info);
+        let partner = &private_info.opponent_hand;
+        assert_eq!(recommend(public_info, partner), Recommendation::Play(1));
+        assert_eq!(
+            recommendation_policy(public_info, private_info),
+            Action::ColorHint(Color::Red)
+        );
+
+        // a whole game keeps to legal moves, and the recommendation is followed
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        while !env.is_over() {
+            let (public_info, private_info) =
+                (env.public_info(), env.private_info(env.current_player()));
+            let action = recommendation_policy(&public_info, &private_info);
+            assert!(env.actions().contains(&action), "{:?}", action);
+            if let Some(recommendation) = received_recommendation(&public_info) {
+                if env.actions().contains(&recommendation.action()) {
+                    assert_eq!(action, recommendation.action());
+                }
+            }
+            env.step(&action, &mut rng);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::env::Env;
+use crate::hanabi_env::{Action, Card, HanabiEnv};
+use crate::rand::Rng;
+
+use std::collections::HashMap;
+
+#[derive(Clone, Debug)]
+pub struct TurnRecord {
+    pub seat: usize,
+    pub action: Action,
+    pub slot: Option<usize>,
+    pub card: Option<Card>,
+    pub touched: Vec<usize>,
+    pub successful_play: bool,
+    // the card drawn to replace a play/discard, None for clues & once the deck is empty
+    pub drawn: Option<Card>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ClueStats {
+    pub clues_given: u32,
+    pub clues_received: u32,
+    pub cards_touched_given: u32,
+    pub cards_touched_received: u32,
+    pub touched_played_given: u32,
+    pub touched_played_received: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    pub agents: [String; 2],
+    pub turns: Vec<TurnRecord>,
+    pub clue_stats: [ClueStats; 2],
+    pub score: u8,
+    // seat that clued each card currently in each seat's hand
+    touched_by: [[Option<usize>; 5]; 2],
+}
+
+impl ClueStats {
+    pub fn add(&mut self, other: &Self) {
+        self.clues_given += other.clues_given;
+        self.clues_received += other.clues_received;
+        self.cards_touched_given += other.cards_touched_given;
+        self.cards_touched_received += other.cards_touched_received;
+        self.touched_played_given += other.touched_played_given;
+        self.touched_played_received += other.touched_played_received;
+    }
+
+    // fraction of the cards this seat clued that its partner went on to play
+    pub fn given_efficiency(&self) -> f32 {
+        if self.cards_touched_given == 0 {
+            return 0.0;
+        }
+        self.touched_played_given as f32 / self.cards_touched_given as f32
+    }
+
+    // fraction of the cards clued to this seat that it went on to play
+    pub fn received_efficiency(&self) -> f32 {
+        if self.cards_touched_received == 0 {
+            return 0.0;
+        }
+        self.touched_played_received as f32 / self.cards_touched_received as f32
+    }
+}
+
+impl GameRecord {
+    pub fn new(agents: [String; 2]) -> Self {
+        Self {
+            agents,
+            turns: Vec::new(),
+            clue_stats: [ClueStats::default(); 2],
+            score: 0,
+            touched_by: [[None; 5]; 2],
+        }
+    }
+
+    pub fn current_seat(&self) -> usize {
+        self.turns.len() % 2
+    }
+
+    pub fn step<R: Rng>(&mut self, env: &mut HanabiEnv, action: &Action, rng: &mut R) {
+        let slot = action.slot();
+        let seat = self.current_seat();
+        let partner = 1 - seat;
+        let touched = env.touched_slots(action);
+        let fireworks_before = env.fireworks.total();
+        let hand = env.player_hand;
+
+        env.step(action, rng);
+        let successful_play = env.fireworks.total() > fireworks_before;
+
+        match action {
+            Action::ColorHint(_) | Action::SuitHint(_) => {
+                self.clue_stats[seat].clues_given += 1;
+                self.clue_stats[partner].clues_received += 1;
+                for &i in touched.iter() {
+                    if self.touched_by[partner][i].is_none() {
+                        self.clue_stats[seat].cards_touched_given += 1;
+                        self.clue_stats[partner].cards_touched_received += 1;
+                    }
+                    self.touched_by[partner][i] = Some(seat);
+                }
+            }
+            Action::Play(_) | Action::Discard(_) => {
+                let i = slot.unwrap();
+                if let Some(giver) = self.touched_by[seat][i] {
+                    if successful_play {
+                        self.clue_stats[giver].touched_played_given += 1;
+                        self.clue_stats[seat].touched_played_received += 1;
+                    }
+                }
+                self.touched_by[seat][i] = None;
+            }
+        }
+
+        self.score = env.fireworks.total();
+        // hands have swapped, so the drawn card is in the opponent's hand now
+        let drawn = slot
+            .map(|i| env.opponent_hand[i])
+            .filter(|card| card.is_some());
+        self.turns.push(TurnRecord {
+            seat,
+            action: *action,
+            slot,
+            card: slot.map(|i| hand[i]),
+            touched,
+            successful_play,
+            drawn,
+        });
+    }
+}
+
+pub fn clue_stats_by_seat(records: &[GameRecord]) -> [ClueStats; 2] {
+    let mut stats = [ClueStats::default(); 2];
+    for record in records.iter() {
+        stats[0].add(&record.clue_stats[0]);
+        stats[1].add(&record.clue_stats[1]);
+    }
+    stats
+}
+
+pub fn clue_stats_by_agent(records: &[GameRecord]) -> HashMap<String, ClueStats> {
+    let mut stats: HashMap<String, ClueStats> = HashMap::new();
+    for record in records.iter() {
+        for seat in 0..2 {
+            stats
+                .entry(record.agents[seat].clone())
+                .or_default()
+                .add(&record.clue_stats[seat]);
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasEnd};
+    use crate::rand::prelude::SliceRandom;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_clue_stats_are_mirrored() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        let mut record = GameRecord::new(["a".to_string(), "b".to_string()]);
+        while !env.is_over() {
+            let action = *env.actions().choose(&mut rng).unwrap();
+            record.step(&mut env, &action, &mut rng);
+        }
+
+        let [a, b] = record.clue_stats;
+        assert_eq!(a.clues_given, b.clues_received);
+        assert_eq!(b.clues_given, a.clues_received);
+        assert_eq!(a.cards_touched_given, b.cards_touched_received);
+        assert_eq!(a.touched_played_given, b.touched_played_received);
+        assert!(a.touched_played_received <= a.cards_touched_received);
+        assert_eq!(record.score, env.fireworks.total());
+
+        let by_agent = clue_stats_by_agent(&[record]);
+        assert_eq!(by_agent["a"], a);
+        assert_eq!(by_agent["b"], b);
+    }
+}
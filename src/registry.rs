@@ -0,0 +1,252 @@
+use crate::env::{Env, HasReward};
+use crate::evaluator::heuristic_policy;
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo, SearchPruning};
+use crate::hgroup::hgroup_policy;
+use crate::level_k::level_k_policy;
+use crate::nmcs::nmcs_policy;
+use crate::nrpa::nrpa_policy;
+use crate::rand::prelude::SliceRandom;
+use crate::rand::rngs::StdRng;
+use crate::recommend::recommendation_policy;
+use crate::sparse::sparse_endgame_policy;
+use crate::strength::register_strength_levels;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+// a policy for whoever is to move, the same shape `evaluate` takes
+pub type Agent = Box<dyn FnMut(PublicInfo, PrivateInfo, &mut StdRng) -> Action>;
+pub type AgentFactory = Box<dyn Fn() -> Agent + Send + Sync>;
+// scores a finished game
+pub type RewardModel = Arc<dyn Fn(&HanabiEnv) -> f32 + Send + Sync>;
+
+// agents and reward models addressable by name from the command line. registering a name
+// that is already taken replaces it
+static AGENTS: Mutex<BTreeMap<String, AgentFactory>> = Mutex::new(BTreeMap::new());
+static REWARD_MODELS: Mutex<BTreeMap<String, RewardModel>> = Mutex::new(BTreeMap::new());
+
+pub fn register_agent<F: Fn() -> Agent + Send + Sync + 'static>(name: &str, factory: F) {
+    AGENTS
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), Box::new(factory));
+}
+
+// has every agent registered so far search with `pruning`, agents registered later keep the
+// default SearchPruning
+pub fn prune_agents(pruning: SearchPruning) {
+    let mut agents = AGENTS.lock().unwrap();
+    let factories = std::mem::take(&mut *agents);
+    for (name, factory) in factories {
+        let pruned: AgentFactory = Box::new(move || {
+            let mut agent = factory();
+            Box::new(
+                move |mut public_info: PublicInfo, private_info, rng: &mut StdRng| {
+                    public_info.pruning = pruning;
+                    agent(public_info, private_info, rng)
+                },
+            )
+        });
+        agents.insert(name, pruned);
+    }
+}
+
+pub fn register_reward_model<F: Fn(&HanabiEnv) -> f32 + Send + Sync + 'static>(
+    name: &str,
+    model: F,
+) {
+    REWARD_MODELS
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), Arc::new(model));
+}
+
+pub fn make_agent(name: &str) -> Result<Agent, String> {
+    let agents = AGENTS.lock().unwrap();
+    match agents.get(name) {
+        Some(factory) => Ok(factory()),
+        None => Err(format!(
+            "unknown agent '{}', expected one of {:?}",
+            name,
+            agents.keys().collect::<Vec<_>>()
+        )),
+    }
+}
+
+pub fn reward_model(name: &str) -> Result<RewardModel, String> {
+    let models = REWARD_MODELS.lock().unwrap();
+    match models.get(name) {
+        Some(model) => Ok(model.clone()),
+        None => Err(format!(
+            "unknown reward model '{}', expected one of {:?}",
+            name,
+            models.keys().collect::<Vec<_>>()
+        )),
+    }
+}
+
+pub fn agent_names() -> Vec<String> {
+    AGENTS.lock().unwrap().keys().cloned().collect()
+}
+
+pub fn reward_model_names() -> Vec<String> {
+    REWARD_MODELS.lock().unwrap().keys().cloned().collect()
+}
+
+pub fn score_reward(env: &HanabiEnv) -> f32 {
+    env.fireworks.total() as f32
+}
+
+// rollouts a level-k agent gets per move unless registered with another budget
+pub const LEVEL_K_BUDGET: usize = 100;
+
+// "level-<depth>", see level_k.rs
+pub fn register_level_k(depth: usize, budget: usize) {
+    register_agent(&format!("level-{}", depth), move || {
+        Box::new(move |public_info, private_info, rng| {
+            level_k_policy(&public_info, &private_info, depth, budget, rng)
+        })
+    });
+}
+
+// worlds a nested search agent samples per move unless registered with another number
+pub const NMCS_WORLDS: usize = 16;
+
+// "nmcs-<level>", see nmcs.rs
+pub fn register_nmcs(level: usize, num_worlds: usize) {
+    register_agent(&format!("nmcs-{}", level), move || {
+        Box::new(move |public_info, private_info, rng| {
+            nmcs_policy(&public_info, &private_info, level, num_worlds, rng)
+        })
+    });
+}
+
+// searches per level and worlds per move an NRPA agent gets unless registered with others
+pub const NRPA_ITERATIONS: usize = 20;
+pub const NRPA_WORLDS: usize = 4;
+
+// "nrpa-<level>", see nrpa.rs
+pub fn register_nrpa(level: usize, iterations: usize, num_worlds: usize) {
+    register_agent(&format!("nrpa-{}", level), move || {
+        Box::new(move |public_info, private_info, rng| {
+            nrpa_policy(
+                &public_info,
+                &private_info,
+                level,
+                iterations,
+                num_worlds,
+                rng,
+            )
+        })
+    });
+}
+
+// chance samples per draw and worlds per move a sparse sampling agent gets unless registered
+// with others
+pub const SPARSE_SAMPLES: usize = 3;
+pub const SPARSE_WORLDS: usize = 8;
+
+// "sparse-<depth>", the heuristic with a sparse sampling endgame, see sparse.rs. these aren't
+// builtins: over 200 evaluation games sparse-1 to 3 average 17.6-17.8 against 18.6 for the
+// heuristic, so they're only registered on request, `--sparse-agents`
+pub fn register_sparse(depth: usize, samples: usize, num_worlds: usize) {
+    register_agent(&format!("sparse-{}", depth), move || {
+        Box::new(move |public_info, private_info, rng| {
+            sparse_endgame_policy(&public_info, &private_info, depth, samples, num_worlds, rng)
+        })
+    });
+}
+
+// the agents and reward models that don't need anything from main
+pub fn register_builtins() {
+    register_agent("heuristic", || {
+        Box::new(|public_info, private_info, _rng| heuristic_policy(&public_info, &private_info))
+    });
+    register_agent("recommend", || {
+        Box::new(|public_info, private_info, _rng| {
+            recommendation_policy(&public_info, &private_info)
+        })
+    });
+    register_agent("hgroup", || {
+        Box::new(|public_info, private_info, _rng| hgroup_policy(&public_info, &private_info))
+    });
+    register_agent("random", || {
+        Box::new(|public_info, private_info, rng| {
+            let (env, _) = HanabiEnv::determinize(&public_info, &private_info, rng);
+            *env.actions().choose(rng).unwrap()
+        })
+    });
+    for depth in 0..4 {
+        register_level_k(depth, LEVEL_K_BUDGET);
+    }
+    register_nmcs(1, NMCS_WORLDS);
+    // a level 2 search costs a level 1 search per move, so it gets fewer worlds
+    register_nmcs(2, NMCS_WORLDS / 4);
+    for level in 1..3 {
+        register_nrpa(level, NRPA_ITERATIONS, NRPA_WORLDS);
+    }
+    register_strength_levels();
+    register_reward_model("score", score_reward);
+    register_reward_model("perfect", |env| (env.fireworks.total() == 25) as u8 as f32);
+    register_reward_model("search", |env| env.reward());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::HasEnd;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_registered_agent_plays() {
+        register_builtins();
+        register_agent("first-action", || {
+            Box::new(|public_info, private_info, rng| {
+                let (env, _) = HanabiEnv::determinize(&public_info, &private_info, rng);
+                env.actions()[0]
+            })
+        });
+        register_reward_model("twice-score", |env| 2.0 * score_reward(env));
+        assert!(agent_names().contains(&"first-action".to_string()));
+        assert!(make_agent("no-such-agent").is_err());
+        assert!(reward_model("no-such-model").is_err());
+
+        let mut agent = make_agent("first-action").unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        while !env.is_over() {
+            let action = agent(
+                env.public_info(),
+                env.private_info(env.current_player()),
+                &mut rng,
+            );
+            assert!(env.actions().contains(&action));
+            env.step(&action, &mut rng);
+        }
+        let reward = reward_model("twice-score").unwrap();
+        assert_eq!(reward(&env), 2.0 * reward_model("score").unwrap()(&env));
+    }
+
+    #[test]
+    fn test_prune_agents() {
+        let seen = Arc::new(Mutex::new(None));
+        let probe = seen.clone();
+        register_agent("pruning-probe", move || {
+            let probe = probe.clone();
+            Box::new(move |public_info, _private_info, _rng| {
+                *probe.lock().unwrap() = Some(public_info.pruning);
+                Action::Play(0)
+            })
+        });
+        let every_slot = SearchPruning {
+            collapse_slots: false,
+            ..SearchPruning::default()
+        };
+        prune_agents(every_slot);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let mut agent = make_agent("pruning-probe").unwrap();
+        agent(env.public_info(), env.private_info(0), &mut rng);
+        assert_eq!(*seen.lock().unwrap(), Some(every_slot));
+    }
+}
@@ -0,0 +1,208 @@
+// ANSI colored rendering of a state for the terminal. hints are drawn as grids of the colors and
+// ranks each card can still be, with ruled out values dotted, and the chop is marked
+use crate::hanabi_env::{Card, Color, Fireworks, HanabiEnv, Hint, COLORS, SUITS};
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const SLOT_WIDTH: usize = 7;
+
+fn ansi_code(color: Color) -> &'static str {
+    match color {
+        Color::White => "\x1b[97m",
+        Color::Red => "\x1b[31m",
+        Color::Blue => "\x1b[34m",
+        Color::Yellow => "\x1b[33m",
+        Color::Green => "\x1b[32m",
+    }
+}
+
+pub fn paint(text: &str, color: Color) -> String {
+    format!("{}{}{}", ansi_code(color), text, RESET)
+}
+
+fn dim(text: &str) -> String {
+    format!("{}{}{}", DIM, text, RESET)
+}
+
+// pads `cell` (holding `visible` printable characters) to a slot column
+fn pad(cell: String, visible: usize) -> String {
+    format!("{}{}", cell, " ".repeat(SLOT_WIDTH.saturating_sub(visible)))
+}
+
+pub fn render_card(card: Card) -> String {
+    if card.is_some() {
+        paint(&format!("{:?}", card), card.color())
+    } else {
+        dim("--")
+    }
+}
+
+pub fn render_fireworks(fireworks: &Fireworks) -> String {
+    let stacks: Vec<String> = COLORS
+        .iter()
+        .map(|&color| {
+            let level = fireworks.0[color as usize];
+            paint(&format!("{}{}", color.short_name(), level), color)
+        })
+        .collect();
+    format!("{} ({}/25)", stacks.join(" "), fireworks.total())
+}
+
+fn render_colors(hint: &Hint) -> String {
+    COLORS
+        .iter()
+        .map(|&color| {
+            if hint.color_mask() & (1 << color as u8) != 0 {
+                paint(color.short_name(), color)
+            } else {
+                dim("·")
+            }
+        })
+        .collect()
+}
+
+fn render_ranks(hint: &Hint) -> String {
+    let known = hint.suit_mask().count_ones() == 1;
+    SUITS
+        .iter()
+        .map(|&suit| {
+            if hint.suit_mask() & (1 << suit as u8) != 0 {
+                let rank = (suit as u8 + 1).to_string();
+                if known {
+                    format!("{}{}{}", BOLD, rank, RESET)
+                } else {
+                    rank
+                }
+            } else {
+                dim("·")
+            }
+        })
+        .collect()
+}
+
+// the slot the heuristic agent would discard: the first card no clue has touched. `touched` has
+// the hand's bits from HintHistory::touched, as negative information leaves a hint non-empty
+pub fn chop(hints: &[Hint; 5], touched: u8) -> Option<usize> {
+    (0..5).find(|&i| hints[i].is_some() && touched & 1 << i == 0)
+}
+
+// a hand as a grid of slots, `hand` is None when the cards aren't visible to the viewer
+pub fn render_hand(hand: Option<&[Card; 5]>, hints: &[Hint; 5], touched: u8) -> String {
+    let mut cards = String::from("cards   ");
+    let mut colors = String::from("colors  ");
+    let mut ranks = String::from("ranks   ");
+    let mut marks = String::from("        ");
+    let chop = chop(hints, touched);
+    for i in 0..5 {
+        if !hints[i].is_some() {
+            for row in [&mut cards, &mut colors, &mut ranks].iter_mut() {
+                row.push_str(&pad(dim("-"), 1));
+            }
+            marks.push_str(&pad(String::new(), 0));
+            continue;
+        }
+        let card = match hand {
+            Some(hand) => render_card(hand[i]),
+            None => dim("??"),
+        };
+        cards.push_str(&pad(card, 2));
+        colors.push_str(&pad(render_colors(&hints[i]), 5));
+        ranks.push_str(&pad(render_ranks(&hints[i]), 5));
+        if chop == Some(i) {
+            marks.push_str(&pad(format!("{}chop{}", BOLD, RESET), 4));
+        } else {
+            marks.push_str(&pad(String::new(), 0));
+        }
+    }
+    format!(
+        "{}\n{}\n{}\n{}\n",
+        cards.trim_end(),
+        colors.trim_end(),
+        ranks.trim_end(),
+        marks.trim_end()
+    )
+}
+
+pub fn render_env(env: &HanabiEnv) -> String {
+    let mut text = format!(
+        "{} | blue={} black={} deck={} | discard: {}\n",
+        render_fireworks(&env.fireworks),
+        env.blue_tokens,
+        env.black_tokens,
+        env.deck.remaining(),
+        env.discard,
+    );
+    text += "----- Me -----\n";
+    text += &render_hand(None, &env.player_hints, env.hint_history.touched(true));
+    text += "----- Op -----\n";
+    text += &render_hand(
+        Some(&env.opponent_hand),
+        &env.opponent_hints,
+        env.hint_history.touched(false),
+    );
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Env;
+    use crate::hanabi_env::{Action, Suit};
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    fn strip_ansi(text: &str) -> String {
+        let mut plain = String::new();
+        let mut in_escape = false;
+        for ch in text.chars() {
+            match ch {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if in_escape => {}
+                _ => plain.push(ch),
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn test_render_hand() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        let red = Action::ColorHint(Color::Red);
+        env.opponent_hand = [
+            Card::new(Color::Red, Suit::One),
+            Card::new(Color::Blue, Suit::Two),
+            Card::new(Color::Red, Suit::Three),
+            Card::new(Color::Green, Suit::Four),
+            Card::new(Color::White, Suit::Five),
+        ];
+        env.step(&red, &mut rng);
+
+        // after the clue the clued hand belongs to the player to move. the untouched cards hold
+        // negative information, but the chop is still the first of them
+        let touched = env.hint_history.touched(true);
+        assert_eq!(touched, 0b101);
+        let plain = strip_ansi(&render_hand(None, &env.player_hints, touched));
+        let lines: Vec<&str> = plain.lines().collect();
+        assert_eq!(lines[0], "cards   ??     ??     ??     ??     ??");
+        assert_eq!(lines[1], "colors  ·R···  W·BYG  ·R···  W·BYG  W·BYG");
+        assert_eq!(lines[2], "ranks   12345  12345  12345  12345  12345");
+        assert_eq!(lines[3], "               chop");
+        assert_eq!(chop(&env.player_hints, touched), Some(1));
+
+        let touched = env.hint_history.touched(false);
+        let plain = strip_ansi(&render_hand(
+            Some(&env.player_hand),
+            &env.opponent_hints,
+            touched,
+        ));
+        assert!(plain.lines().last().unwrap().starts_with("        chop"));
+        assert!(render_card(Card::new(Color::Red, Suit::One)).contains("\x1b[31mR1"));
+        assert_eq!(
+            strip_ansi(&render_fireworks(&env.fireworks)),
+            "W0 R0 B0 Y0 G0 (0/25)"
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use crate::advisor::advise;
+use crate::env::Env;
+use crate::hanabi_env::{Action, Card, CardCollection, Color, HanabiEnv, Suit};
+use crate::migrate::{current_version, upgrade, Migration};
+use crate::notation::{notate_record, Move, NotatedTurn};
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+use crate::record::GameRecord;
+use crate::state_json::{cards_from_json, cards_to_json, parse};
+use serde_json::{json, Value};
+
+// versions: 1 didn't record who played
+fn add_agents(value: &mut Value) -> Result<(), String> {
+    value["agents"] = json!(["unknown", "unknown"]);
+    Ok(())
+}
+
+const REPLAY_MIGRATIONS: &[Migration] = &[add_agents];
+pub const REPLAY_VERSION: u64 = current_version(REPLAY_MIGRATIONS);
+
+// what the search thinks of one move of a replay
+#[derive(Clone, Debug)]
+pub struct DecisionReview {
+    pub turn: usize,
+    pub played: Action,
+    pub best: Action,
+    // None when the search never tried the played move
+    pub played_value: Option<f32>,
+    pub best_value: f32,
+}
+
+impl DecisionReview {
+    pub fn agrees(&self) -> bool {
+        self.played == self.best
+    }
+}
+
+// a whole game: the deck in draw order (the first 10 cards are the deal, see
+// HanabiEnv::from_deck_order), the agent in each seat and every move in line notation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub deck: Vec<Card>,
+    pub agents: [String; 2],
+    pub turns: Vec<NotatedTurn>,
+}
+
+impl Replay {
+    // `initial` is the env before the first move of `record`
+    pub fn from_game(initial: &HanabiEnv, record: &GameRecord) -> Self {
+        let mut deck: Vec<Card> = initial
+            .player_hand
+            .iter()
+            .chain(initial.opponent_hand.iter())
+            .cloned()
+            .collect();
+        deck.extend(record.turns.iter().filter_map(|turn| turn.drawn));
+
+        // cards that were never drawn go on the bottom in a fixed order
+        let mut undrawn = CardCollection::starting_deck();
+        for &card in deck.iter() {
+            undrawn.remove(card);
+        }
+        for id in 0..25 {
+            for _ in 0..undrawn.counts[id] {
+                deck.push(Card::from_id(id as u8));
+            }
+        }
+
+        Self {
+            deck,
+            agents: record.agents.clone(),
+            turns: notate_record(record),
+        }
+    }
+
+    // the position before turn `turn` (numbered from 1) is played, env_at(turns.len() + 1) is the final position
+    pub fn env_at(&self, turn: usize) -> Result<HanabiEnv, String> {
+        // the deck order is fixed, so the rng is never used to draw
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::from_deck_order(&self.deck);
+        for notated in self.turns.iter().take(turn.saturating_sub(1)) {
+            notated.apply(&mut env, &mut rng)?;
+        }
+        Ok(env)
+    }
+
+    // runs the search at every decision point from the mover's point of view
+    pub fn review(&self, num_explores: usize, seed: u64) -> Result<Vec<DecisionReview>, String> {
+        let mut reviews = Vec::new();
+        for (i, turn) in self.turns.iter().enumerate() {
+            let env = self.env_at(i + 1)?;
+            let played = turn.action()?;
+            let suggestions = advise(
+                &env.public_info(),
+                &env.private_info(env.current_player()),
+                num_explores,
+                seed + i as u64,
+            );
+            let value_of = |action: &Action| {
+                suggestions
+                    .iter()
+                    .find(|s| s.action == *action)
+                    .map(|s| s.mean_reward)
+            };
+            reviews.push(DecisionReview {
+                turn: turn.turn,
+                played,
+                best: suggestions[0].action,
+                played_value: value_of(&env.collapsed(&played)),
+                best_value: suggestions[0].mean_reward,
+            });
+        }
+        Ok(reviews)
+    }
+
+    pub fn to_json(&self) -> String {
+        let turns: Vec<String> = self.turns.iter().map(|t| t.to_string()).collect();
+        let value = json!({
+            "version": REPLAY_VERSION,
+            "deck": cards_to_json(&self.deck),
+            "agents": self.agents,
+            "turns": turns,
+        });
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let value = upgrade(parse(text)?, "replay", REPLAY_MIGRATIONS)?;
+        let deck = cards_from_json(&value["deck"])?;
+        if deck.len() != 50 || deck.iter().any(|c| !c.is_some()) {
+            return Err("a replay deck must list all 50 cards".to_string());
+        }
+        let turns = value["turns"]
+            .as_array()
+            .ok_or("expected a list of turns")?
+            .iter()
+            .map(|turn| match turn {
+                Value::String(text) => NotatedTurn::parse(text),
+                _ => Err(format!("expected a turn, found {}", turn)),
+            })
+            .collect::<Result<Vec<NotatedTurn>, String>>()?;
+        let agent = |seat: usize| {
+            value["agents"][seat]
+                .as_str()
+                .map(|name| name.to_string())
+                .ok_or_else(|| format!("missing agent for seat {}", seat))
+        };
+        Ok(Self {
+            deck,
+            agents: [agent(0)?, agent(1)?],
+            turns,
+        })
+    }
+
+    // the compact form written by `write_replays`: the 50 card ids of the deck, each agent as a
+    // length byte & its name, a little endian u16 turn count and a byte per turn, the kind of move
+    // (play, discard, color clue, rank clue) times 8 plus the slot, color or rank. plays & discards
+    // need their slot, the cards & outcomes are worked out again when reading
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let mut bytes: Vec<u8> = self.deck.iter().map(|c| c.id()).collect();
+        for agent in self.agents.iter() {
+            // names past 255 bytes are cut at the last whole character that fits
+            let len = (0..=agent.len().min(255))
+                .rev()
+                .find(|&i| agent.is_char_boundary(i))
+                .unwrap();
+            let name = &agent.as_bytes()[..len];
+            bytes.push(name.len() as u8);
+            bytes.extend_from_slice(name);
+        }
+        bytes.extend_from_slice(&(self.turns.len() as u16).to_le_bytes());
+        for turn in self.turns.iter() {
+            let no_slot = || format!("T{} has no slot", turn.turn);
+            bytes.push(match &turn.mv {
+                Move::Play { slot, .. } => PLAY_BYTE + slot.ok_or_else(no_slot)? as u8,
+                Move::Discard { slot, .. } => DISCARD_BYTE + slot.ok_or_else(no_slot)? as u8,
+                Move::Clue {
+                    clue: Action::ColorHint(color),
+                    ..
+                } => COLOR_CLUE_BYTE + *color as u8,
+                Move::Clue {
+                    clue: Action::SuitHint(suit),
+                    ..
+                } => RANK_CLUE_BYTE + *suit as u8,
+                Move::Clue { .. } => return Err(format!("T{} isn't a clue", turn.turn)),
+            });
+        }
+        Ok(bytes)
+    }
+
+    // reads one replay from the front of `bytes`, returning it & how many bytes it took
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, usize), String> {
+        let truncated = || "truncated replay".to_string();
+        let deck: Vec<Card> = bytes
+            .get(..50)
+            .ok_or_else(truncated)?
+            .iter()
+            .map(|&id| Card::from_id(id))
+            .collect();
+        if deck.iter().any(|c| c.id() >= 25) {
+            return Err("a replay deck must list all 50 cards".to_string());
+        }
+        let mut at = 50;
+        let mut agent = || {
+            let len = *bytes.get(at).ok_or_else(truncated)? as usize;
+            let name = bytes.get(at + 1..at + 1 + len).ok_or_else(truncated)?;
+            at += 1 + len;
+            String::from_utf8(name.to_vec()).map_err(|e| e.to_string())
+        };
+        let agents = [agent()?, agent()?];
+        let count = bytes.get(at..at + 2).ok_or_else(truncated)?;
+        let num_turns = u16::from_le_bytes([count[0], count[1]]) as usize;
+        at += 2;
+        let moves = bytes.get(at..at + num_turns).ok_or_else(truncated)?;
+
+        // the deck order is fixed, so the rng is never used to draw
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::from_deck_order(&deck);
+        let mut record = GameRecord::new(agents.clone());
+        for (i, &byte) in moves.iter().enumerate() {
+            let index = byte % 8;
+            if index >= 5 {
+                return Err(format!("T{}: invalid move byte {}", i + 1, byte));
+            }
+            let action = match byte - index {
+                PLAY_BYTE => Action::Play(index as usize),
+                DISCARD_BYTE => Action::Discard(index as usize),
+                COLOR_CLUE_BYTE => Action::ColorHint(Color::from_id(index)),
+                RANK_CLUE_BYTE => Action::SuitHint(Suit::from_id(index)),
+                _ => return Err(format!("T{}: invalid move byte {}", i + 1, byte)),
+            };
+            env.check_action(&action)
+                .map_err(|e| format!("T{}: {}", i + 1, e))?;
+            record.step(&mut env, &action, &mut rng);
+        }
+        let replay = Self {
+            deck,
+            agents,
+            turns: notate_record(&record),
+        };
+        Ok((replay, at + num_turns))
+    }
+}
+
+const PLAY_BYTE: u8 = 0;
+const DISCARD_BYTE: u8 = 8;
+const COLOR_CLUE_BYTE: u8 = 16;
+const RANK_CLUE_BYTE: u8 = 24;
+
+// starts every file of binary replays, the last byte is the format version
+const REPLAYS_MAGIC: &[u8; 5] = b"HNBR\x01";
+
+// many replays in one file, see Replay::to_bytes
+pub fn write_replays(path: &str, replays: &[Replay]) -> Result<(), String> {
+    let mut bytes = REPLAYS_MAGIC.to_vec();
+    for replay in replays.iter() {
+        bytes.extend(replay.to_bytes()?);
+    }
+    std::fs::write(path, bytes).map_err(|e| format!("{}: {}", path, e))
+}
+
+pub fn read_replays(path: &str) -> Result<Vec<Replay>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    if !bytes.starts_with(REPLAYS_MAGIC) {
+        return Err(format!("{} isn't a replay file", path));
+    }
+    let mut replays = Vec::new();
+    let mut at = REPLAYS_MAGIC.len();
+    while at < bytes.len() {
+        let (replay, len) = Replay::from_bytes(&bytes[at..])
+            .map_err(|e| format!("replay {}: {}", replays.len(), e))?;
+        replays.push(replay);
+        at += len;
+    }
+    Ok(replays)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasEnd, HasKey};
+    use crate::rand::prelude::SliceRandom;
+
+    #[test]
+    fn test_replay_reproduces_game() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut env = HanabiEnv::random(&mut rng);
+        let initial = env.clone();
+        let mut record = GameRecord::new(["a".to_string(), "b".to_string()]);
+        let mut keys = vec![env.public_info().key()];
+        while !env.is_over() {
+            let action = *env.actions().choose(&mut rng).unwrap();
+            record.step(&mut env, &action, &mut rng);
+            keys.push(env.public_info().key());
+        }
+
+        let replay = Replay::from_game(&initial, &record);
+        assert_eq!(replay.deck.len(), 50);
+        for turn in 1..=replay.turns.len() + 1 {
+            let replayed = replay.env_at(turn).unwrap();
+            assert_eq!(replayed.public_info().key(), keys[turn - 1]);
+        }
+        let last = replay.env_at(replay.turns.len() + 1).unwrap();
+        assert_eq!(last.player_hand, env.player_hand);
+        assert!(last.is_over());
+
+        assert_eq!(Replay::from_json(&replay.to_json()).unwrap(), replay);
+        assert_eq!(replay.agents, ["a".to_string(), "b".to_string()]);
+
+        let bytes = replay.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 50 + 2 * 2 + 2 + replay.turns.len());
+        assert_eq!(
+            Replay::from_bytes(&bytes).unwrap(),
+            (replay.clone(), bytes.len())
+        );
+        assert!(Replay::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // 2 byte characters over the 255 byte limit keep as many whole ones as fit
+        let mut named = replay.clone();
+        named.agents[0] = "é".repeat(200);
+        let (read, _) = Replay::from_bytes(&named.to_bytes().unwrap()).unwrap();
+        assert_eq!(read.agents[0], "é".repeat(127));
+
+        let path = std::env::temp_dir().join(format!("hanabi-replays-{}.bin", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        write_replays(&path, &[replay.clone(), replay.clone()]).unwrap();
+        let read = read_replays(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap(), vec![replay.clone(), replay]);
+    }
+
+    #[test]
+    fn test_reads_version_1() {
+        let v1 = include_str!("../fixtures/replay_v1.json");
+        let replay = Replay::from_json(v1).unwrap();
+        assert_eq!(
+            replay.agents,
+            ["unknown".to_string(), "unknown".to_string()]
+        );
+        assert_eq!(replay.deck.len(), 50);
+        let last = replay.env_at(replay.turns.len() + 1).unwrap();
+        assert!(last.is_over());
+
+        let saved = replay.to_json();
+        assert!(saved.contains("\"version\": 2"));
+        assert_eq!(Replay::from_json(&saved).unwrap(), replay);
+        assert!(Replay::from_json("{\"version\": 3}").is_err());
+    }
+}
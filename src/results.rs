@@ -0,0 +1,468 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GameResult {
+    pub seed: u64,
+    pub score: u8,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Summary {
+    pub num_games: usize,
+    pub mean: f32,
+    pub std_err: f32,
+    pub perfect_games: usize,
+    // games per final score, 0 through 25
+    pub histogram: [usize; 26],
+}
+
+// two policies' scores on the same seeds
+#[derive(Clone, Debug)]
+pub struct PairedComparison {
+    pub num_games: usize,
+    // mean of a - b over the games
+    pub mean_diff: f32,
+    pub std_err: f32,
+    pub z: f32,
+    // two sided, from the normal approximation of the mean difference
+    pub p_value: f32,
+    pub wins: usize,
+    pub losses: usize,
+    pub ties: usize,
+}
+
+// wall clock spent per move & per game, and games that ran out of time
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+    pub move_millis: Vec<f32>,
+    pub game_millis: Vec<f32>,
+    pub forfeits: usize,
+}
+
+impl Shard {
+    pub fn all() -> Self {
+        Self { index: 0, count: 1 }
+    }
+
+    // parses "i/N", e.g. "0/4" is the first of four shards
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.split('/');
+        let index = parts.next().and_then(|p| p.parse::<usize>().ok());
+        let count = parts.next().and_then(|p| p.parse::<usize>().ok());
+        match (index, count, parts.next()) {
+            (Some(index), Some(count), None) if index < count => Ok(Self { index, count }),
+            _ => Err(format!("invalid shard '{}', expected i/N with i < N", text)),
+        }
+    }
+
+    pub fn seeds(&self, num_games: usize) -> Vec<u64> {
+        (0..num_games as u64)
+            .filter(|seed| *seed as usize % self.count == self.index)
+            .collect()
+    }
+}
+
+// one "seed score" line per game, so shard files can simply be concatenated
+pub fn write_results(path: &str, results: &[GameResult]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for result in results.iter() {
+        writeln!(writer, "{} {}", result.seed, result.score)?;
+    }
+    Ok(())
+}
+
+// adds one game to a results file as it finishes, so an interrupted run keeps what it played
+pub fn append_result(path: &str, result: &GameResult) -> std::io::Result<()> {
+    append_line(path, &format!("{} {}", result.seed, result.score))
+}
+
+// the line goes out in a single write, so workers appending at once can't interleave theirs
+pub fn append_line(path: &str, line: &str) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(format!("{}\n", line).as_bytes())
+}
+
+// a results file written by append_result, with the "# " line describing the session it was
+// written by when there is one. a run killed mid write can leave the last line cut short, it's
+// truncated away so the next append starts on a line of its own
+pub fn read_checkpoint(path: &str) -> std::io::Result<(Option<String>, Vec<GameResult>)> {
+    let text = std::fs::read_to_string(path)?;
+    let complete = text.rfind('\n').map_or(0, |i| i + 1);
+    if complete < text.len() {
+        let file = std::fs::OpenOptions::new().write(true).open(path)?;
+        file.set_len(complete as u64)?;
+    }
+    let header = text[..complete]
+        .lines()
+        .next()
+        .filter(|line| line.starts_with("# "))
+        .map(|line| line.to_string());
+    Ok((header, parse_results(path, &text[..complete])?))
+}
+
+// the seeds a resumed run still has to play
+pub fn remaining_seeds(seeds: &[u64], done: &[GameResult]) -> Vec<u64> {
+    seeds
+        .iter()
+        .cloned()
+        .filter(|seed| done.iter().all(|r| r.seed != *seed))
+        .collect()
+}
+
+pub fn read_results(path: &str) -> std::io::Result<Vec<GameResult>> {
+    parse_results(path, &std::fs::read_to_string(path)?)
+}
+
+// "seed score" lines, skipping blank ones & "#" comments
+fn parse_results(path: &str, text: &str) -> std::io::Result<Vec<GameResult>> {
+    let mut results = Vec::new();
+    for (line_no, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let seed = parts.next().and_then(|p| p.parse().ok());
+        let score = parts.next().and_then(|p| p.parse().ok());
+        match (seed, score) {
+            (Some(seed), Some(score)) => results.push(GameResult { seed, score }),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{}:{}: expected 'seed score'", path, line_no + 1),
+                ))
+            }
+        }
+    }
+    Ok(results)
+}
+
+// combines shards, rejecting seeds that show up in more than one file
+pub fn merge_results(shards: &[Vec<GameResult>]) -> Result<Vec<GameResult>, String> {
+    let mut merged: Vec<GameResult> = shards.iter().flatten().cloned().collect();
+    merged.sort_by_key(|r| r.seed);
+    for pair in merged.windows(2) {
+        if pair[0].seed == pair[1].seed {
+            return Err(format!(
+                "seed {} appears in more than one shard",
+                pair[0].seed
+            ));
+        }
+    }
+    Ok(merged)
+}
+
+// no games summarize to a mean & standard error of 0
+pub fn summarize(results: &[GameResult]) -> Summary {
+    let n = results.len().max(1) as f32;
+    let mean = results.iter().map(|r| r.score as f32).sum::<f32>() / n;
+    let variance = results
+        .iter()
+        .map(|r| (r.score as f32 - mean).powi(2))
+        .sum::<f32>()
+        / (n - 1.0).max(1.0);
+    let mut histogram = [0; 26];
+    for result in results.iter() {
+        histogram[result.score.min(25) as usize] += 1;
+    }
+    Summary {
+        num_games: results.len(),
+        mean,
+        std_err: (variance / n).sqrt(),
+        perfect_games: results.iter().filter(|r| r.score == 25).count(),
+        histogram,
+    }
+}
+
+impl Summary {
+    // normal approximation, the mean of a hundred or more games is close enough to normal
+    pub fn ci95(&self) -> (f32, f32) {
+        (
+            self.mean - 1.96 * self.std_err,
+            self.mean + 1.96 * self.std_err,
+        )
+    }
+
+    pub fn perfect_rate(&self) -> f32 {
+        self.perfect_games as f32 / self.num_games.max(1) as f32
+    }
+
+    // one row per score that came up, e.g. "21   12 ############"
+    pub fn histogram_lines(&self) -> Vec<String> {
+        let max = self.histogram.iter().cloned().max().unwrap_or(0).max(1);
+        (0..26)
+            .filter(|&score| self.histogram[score] > 0)
+            .map(|score| {
+                let count = self.histogram[score];
+                format!(
+                    "{:>2} {:>5} {}",
+                    score,
+                    count,
+                    "#".repeat((50 * count).div_ceil(max))
+                )
+            })
+            .collect()
+    }
+
+    pub fn to_json_value(&self) -> serde_json::Value {
+        let (low, high) = self.ci95();
+        serde_json::json!({
+            "num_games": self.num_games,
+            "mean": self.mean,
+            "std_err": self.std_err,
+            "ci95": [low, high],
+            "perfect_games": self.perfect_games,
+            "perfect_rate": self.perfect_rate(),
+            "histogram": self.histogram.to_vec(),
+        })
+    }
+}
+
+// the summary and every game's score
+pub fn write_json(path: &str, name: &str, results: &[GameResult]) -> std::io::Result<()> {
+    let games: Vec<serde_json::Value> = results
+        .iter()
+        .map(|r| serde_json::json!({"seed": r.seed, "score": r.score}))
+        .collect();
+    let value = serde_json::json!({
+        "agent": name,
+        "summary": summarize(results).to_json_value(),
+        "games": games,
+    });
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", value)
+}
+
+pub fn write_csv(path: &str, results: &[GameResult]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "seed,score")?;
+    for result in results.iter() {
+        writeln!(writer, "{},{}", result.seed, result.score)?;
+    }
+    Ok(())
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "games={} mean={:.3} stderr={:.3} ci95=[{:.3}, {:.3}] perfect={} ({:.1}%)",
+            self.num_games,
+            self.mean,
+            self.std_err,
+            self.ci95().0,
+            self.ci95().1,
+            self.perfect_games,
+            100.0 * self.perfect_rate()
+        )
+    }
+}
+
+// Abramowitz & Stegun 7.1.26, good to about 1e-7
+pub fn erf(x: f32) -> f32 {
+    let sign = x.signum();
+    let x = x.abs() as f64;
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    sign * (1.0 - poly * (-x * x).exp()) as f32
+}
+
+// pairs `a` and `b` by seed, both must cover exactly the same seeds
+pub fn compare_paired(a: &[GameResult], b: &[GameResult]) -> Result<PairedComparison, String> {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort_by_key(|r| r.seed);
+    b.sort_by_key(|r| r.seed);
+    if a.len() != b.len() || a.iter().zip(b.iter()).any(|(x, y)| x.seed != y.seed) {
+        return Err("both result sets must cover the same seeds".to_string());
+    }
+    if a.is_empty() {
+        return Err("no games to compare".to_string());
+    }
+
+    let diffs: Vec<f32> = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| x.score as f32 - y.score as f32)
+        .collect();
+    let n = diffs.len() as f32;
+    let mean_diff = diffs.iter().sum::<f32>() / n;
+    let variance = diffs.iter().map(|d| (d - mean_diff).powi(2)).sum::<f32>() / (n - 1.0).max(1.0);
+    let std_err = (variance / n).sqrt();
+    let z = if std_err > 0.0 {
+        mean_diff / std_err
+    } else if mean_diff == 0.0 {
+        0.0
+    } else {
+        mean_diff.signum() * f32::INFINITY
+    };
+    Ok(PairedComparison {
+        num_games: diffs.len(),
+        mean_diff,
+        std_err,
+        z,
+        p_value: 1.0 - erf(z.abs() / std::f32::consts::SQRT_2),
+        wins: diffs.iter().filter(|&&d| d > 0.0).count(),
+        losses: diffs.iter().filter(|&&d| d < 0.0).count(),
+        ties: diffs.iter().filter(|&&d| d == 0.0).count(),
+    })
+}
+
+impl std::fmt::Display for PairedComparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "games={} diff={:+.3} stderr={:.3} ci95=[{:+.3}, {:+.3}] z={:.2} p={:.4} | wins={} losses={} ties={}",
+            self.num_games,
+            self.mean_diff,
+            self.std_err,
+            self.mean_diff - 1.96 * self.std_err,
+            self.mean_diff + 1.96 * self.std_err,
+            self.z,
+            self.p_value,
+            self.wins,
+            self.losses,
+            self.ties
+        )
+    }
+}
+
+// nearest rank percentile, `q` in [0, 1]
+pub fn percentile(values: &[f32], q: f32) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let rank = (q * sorted.len() as f32).ceil() as usize;
+    sorted[rank.max(1).min(sorted.len()) - 1]
+}
+
+impl std::fmt::Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "move ms p50={:.1} p90={:.1} p99={:.1} max={:.1} | game ms p50={:.1} p90={:.1} max={:.1} | forfeits={}",
+            percentile(&self.move_millis, 0.5),
+            percentile(&self.move_millis, 0.9),
+            percentile(&self.move_millis, 0.99),
+            percentile(&self.move_millis, 1.0),
+            percentile(&self.game_millis, 0.5),
+            percentile(&self.game_millis, 0.9),
+            percentile(&self.game_millis, 1.0),
+            self.forfeits,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shards_partition_seeds() {
+        let shards: Vec<Vec<GameResult>> = (0..3)
+            .map(|i| {
+                Shard::parse(&format!("{}/3", i))
+                    .unwrap()
+                    .seeds(10)
+                    .into_iter()
+                    .map(|seed| GameResult {
+                        seed,
+                        score: seed as u8,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let merged = merge_results(&shards).unwrap();
+        assert_eq!(merged.len(), 10);
+        assert_eq!(summarize(&merged).mean, 4.5);
+
+        let duplicated = vec![shards[0].clone(), shards[0].clone()];
+        assert!(merge_results(&duplicated).is_err());
+        assert!(Shard::parse("3/3").is_err());
+        assert!(Shard::parse("1").is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        let results: Vec<GameResult> = [25, 25, 20, 15]
+            .iter()
+            .enumerate()
+            .map(|(seed, &score)| GameResult {
+                seed: seed as u64,
+                score,
+            })
+            .collect();
+        let summary = summarize(&results);
+        assert_eq!(summary.mean, 21.25);
+        assert_eq!(summary.histogram[25], 2);
+        assert_eq!(summary.histogram[20], 1);
+        assert_eq!(summary.perfect_rate(), 0.5);
+        let (low, high) = summary.ci95();
+        assert!(low < summary.mean && high > summary.mean);
+        assert!((high - summary.mean - 1.96 * summary.std_err).abs() < 1e-5);
+        assert_eq!(summary.histogram_lines().len(), 3);
+        assert!(summary.histogram_lines()[2].starts_with("25     2 #"));
+
+        let empty = summarize(&[]);
+        assert_eq!((empty.num_games, empty.mean, empty.std_err), (0, 0.0, 0.0));
+        assert_eq!(empty.perfect_rate(), 0.0);
+
+        let value = summary.to_json_value();
+        assert_eq!(value["num_games"], 4);
+        assert_eq!(value["histogram"][25], 2);
+    }
+
+    #[test]
+    fn test_compare_paired() {
+        let results = |scores: &[u8]| -> Vec<GameResult> {
+            scores
+                .iter()
+                .enumerate()
+                .map(|(seed, &score)| GameResult {
+                    seed: seed as u64,
+                    score,
+                })
+                .collect()
+        };
+        // b is a point behind on every deal, wildly different deals don't hide that
+        let a = results(&[25, 3, 17, 11, 20, 6, 24, 9]);
+        let b = results(&[24, 2, 16, 10, 19, 5, 23, 8]);
+        let comparison = compare_paired(&a, &b).unwrap();
+        assert_eq!(comparison.mean_diff, 1.0);
+        assert_eq!(comparison.wins, 8);
+        assert!(comparison.p_value < 1e-3);
+
+        let noisy = results(&[24, 4, 16, 12, 19, 7, 23, 10]);
+        let comparison = compare_paired(&a, &noisy).unwrap();
+        assert_eq!(comparison.mean_diff, 0.0);
+        assert_eq!((comparison.wins, comparison.losses), (4, 4));
+        assert!((comparison.p_value - 1.0).abs() < 1e-6);
+
+        assert!((erf(1.0) - 0.8427008).abs() < 1e-6);
+        assert!(compare_paired(&a, &a[1..]).is_err());
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values: Vec<f32> = (1..=100).rev().map(|v| v as f32).collect();
+        assert_eq!(percentile(&values, 0.5), 50.0);
+        assert_eq!(percentile(&values, 0.99), 99.0);
+        assert_eq!(percentile(&values, 1.0), 100.0);
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&[], 0.5), 0.0);
+    }
+}
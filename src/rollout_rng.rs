@@ -0,0 +1,111 @@
+// the rng searches draw from inside rollouts. StdRng (ChaCha) gives the same stream on every
+// platform and rand version, SmallRng is cheaper per draw but its algorithm may change
+// between rand versions. searches seed a RolloutRng of whichever kind is configured, Small by
+// default, `--rng std` keeps ChaCha for runs that have to reproduce across builds
+use crate::rand::rngs::{SmallRng, StdRng};
+use crate::rand::{Error, RngCore, SeedableRng};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RngKind {
+    Std,
+    Small,
+}
+
+static USE_STD: AtomicBool = AtomicBool::new(false);
+
+pub fn set_rng_kind(kind: RngKind) {
+    USE_STD.store(kind == RngKind::Std, Ordering::Relaxed);
+}
+
+pub fn rng_kind() -> RngKind {
+    match USE_STD.load(Ordering::Relaxed) {
+        true => RngKind::Std,
+        false => RngKind::Small,
+    }
+}
+
+impl RngKind {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "std" => Ok(RngKind::Std),
+            "small" => Ok(RngKind::Small),
+            _ => Err(format!("unknown rng '{}', expected std or small", text)),
+        }
+    }
+}
+
+// the std rng's state is a few hundred bytes, boxed so a small rng doesn't carry that around
+#[derive(Clone, Debug)]
+pub enum RolloutRng {
+    Std(Box<StdRng>),
+    Small(SmallRng),
+}
+
+impl RolloutRng {
+    // the configured kind, see `set_rng_kind`
+    pub fn seed_from_u64(seed: u64) -> Self {
+        Self::with_kind(rng_kind(), seed)
+    }
+
+    pub fn with_kind(kind: RngKind, seed: u64) -> Self {
+        match kind {
+            RngKind::Std => RolloutRng::Std(Box::new(StdRng::seed_from_u64(seed))),
+            RngKind::Small => RolloutRng::Small(SmallRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RngCore for RolloutRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            RolloutRng::Std(rng) => rng.next_u32(),
+            RolloutRng::Small(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            RolloutRng::Std(rng) => rng.next_u64(),
+            RolloutRng::Small(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            RolloutRng::Std(rng) => rng.fill_bytes(dest),
+            RolloutRng::Small(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match self {
+            RolloutRng::Std(rng) => rng.try_fill_bytes(dest),
+            RolloutRng::Small(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::Rng;
+
+    #[test]
+    fn test_rollout_rng_kinds() {
+        let draws = |mut rng: RolloutRng| (0..8).map(|_| rng.gen::<u32>()).collect::<Vec<_>>();
+        let std = draws(RolloutRng::with_kind(RngKind::Std, 3));
+        let mut reference = StdRng::seed_from_u64(3);
+        assert_eq!(
+            std,
+            (0..8).map(|_| reference.gen::<u32>()).collect::<Vec<_>>()
+        );
+
+        let small = draws(RolloutRng::with_kind(RngKind::Small, 3));
+        assert_eq!(small, draws(RolloutRng::with_kind(RngKind::Small, 3)));
+        assert_ne!(small, std);
+
+        assert_eq!(RngKind::parse("std"), Ok(RngKind::Std));
+        assert!(RngKind::parse("chacha").is_err());
+    }
+}
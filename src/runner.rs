@@ -0,0 +1,239 @@
+// drives one game from the deal to the end: which agent sits in which seat, the record of every
+// turn, the rng streams and hooks around each turn. evaluation, replays and `describe-game` all
+// play their games through it
+use crate::env::{Env, HasEnd};
+use crate::evaluator::heuristic_policy;
+use crate::hanabi_env::{Action, Deck, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+use crate::record::{GameRecord, TurnRecord};
+use crate::telemetry;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+// picks the move for the seat about to act
+pub type SeatAgent<'a> = Box<dyn FnMut(&HanabiEnv, &mut StdRng) -> Action + 'a>;
+
+type BeforeTurn<'a> = Box<dyn FnMut(&HanabiEnv) + 'a>;
+type AfterTurn<'a> = Box<dyn FnMut(&HanabiEnv, &TurnRecord) + 'a>;
+
+thread_local! {
+    static MOVE_MILLIS: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// the share of the game's time limit the move the calling thread is deciding can spend, None
+// without a limit. searches take it as their budget, see `move_budget` in main
+pub fn move_time_limit() -> Option<u64> {
+    MOVE_MILLIS.with(|millis| millis.get())
+}
+
+// GameRunner::new seeds the agents' & env's rng with the game's seed ^ this
+pub const RNG_SALT: u64 = 0x5eed;
+
+// the seed fixes the whole deck order, so different agents playing the same seed see the same deals
+pub fn deal(seed: u64) -> HanabiEnv {
+    let mut rng = StdRng::seed_from_u64(seed);
+    match Deck::shuffled(&mut rng) {
+        Deck::Ordered(order) => HanabiEnv::from_deck_order(&order),
+        Deck::Multiset(_) => unreachable!(),
+    }
+}
+
+// a policy that only sees the mover's observation, as a seat agent
+pub fn observing<'a, P: FnMut(PublicInfo, PrivateInfo, &mut StdRng) -> Action + 'a>(
+    mut policy_fn: P,
+) -> SeatAgent<'a> {
+    Box::new(move |env, rng| {
+        policy_fn(
+            env.public_info(),
+            env.private_info(env.current_player()),
+            rng,
+        )
+    })
+}
+
+pub struct GameRunner<'a> {
+    pub seed: u64,
+    pub initial: HanabiEnv,
+    pub env: HanabiEnv,
+    pub record: GameRecord,
+    // what the agents and the env draw from, apart from the deal
+    pub rng: StdRng,
+    agents: [SeatAgent<'a>; 2],
+    before_turn: Vec<BeforeTurn<'a>>,
+    after_turn: Vec<AfterTurn<'a>>,
+    // past the limit the rest of the game is played by the heuristic agent
+    time_limit: Option<Duration>,
+    started: Option<Instant>,
+    pub move_millis: Vec<f32>,
+    pub game_millis: f32,
+    pub forfeited: bool,
+}
+
+impl<'a> GameRunner<'a> {
+    // the game dealt for `seed`, kept apart from the rng the policies use so their draws can't
+    // change the deck
+    pub fn new(seed: u64) -> Self {
+        Self::from_env(deal(seed), StdRng::seed_from_u64(seed ^ RNG_SALT), seed)
+    }
+
+    // an already dealt game, e.g. one whose deck is drawn from `rng` as the game goes
+    pub fn from_env(env: HanabiEnv, rng: StdRng, seed: u64) -> Self {
+        Self {
+            seed,
+            initial: env.clone(),
+            env,
+            record: GameRecord::new(["heuristic".to_string(), "heuristic".to_string()]),
+            rng,
+            agents: [
+                observing(|p, q, _| heuristic_policy(&p, &q)),
+                observing(|p, q, _| heuristic_policy(&p, &q)),
+            ],
+            before_turn: Vec::new(),
+            after_turn: Vec::new(),
+            time_limit: None,
+            started: None,
+            move_millis: Vec::new(),
+            game_millis: 0.0,
+            forfeited: false,
+        }
+    }
+
+    pub fn seat(mut self, seat: usize, name: &str, agent: SeatAgent<'a>) -> Self {
+        self.record.agents[seat] = name.to_string();
+        self.agents[seat] = agent;
+        self
+    }
+
+    pub fn time_limit(mut self, limit: Option<Duration>) -> Self {
+        self.time_limit = limit;
+        self
+    }
+
+    pub fn before_turn<F: FnMut(&HanabiEnv) + 'a>(mut self, hook: F) -> Self {
+        self.before_turn.push(Box::new(hook));
+        self
+    }
+
+    pub fn after_turn<F: FnMut(&HanabiEnv, &TurnRecord) + 'a>(mut self, hook: F) -> Self {
+        self.after_turn.push(Box::new(hook));
+        self
+    }
+
+    // plays one turn, false once the game is over
+    pub fn step(&mut self) -> bool {
+        if self.env.is_over() {
+            return false;
+        }
+        let game_start = *self.started.get_or_insert_with(Instant::now);
+        // what's left of the limit split over the turns the game has at least still to go, a
+        // search that keeps to it can't run the clock out
+        let move_limit = self.time_limit.map(|limit| {
+            let left = limit.checked_sub(game_start.elapsed()).unwrap_or_default();
+            let public_info = self.env.public_info();
+            let turns = public_info.deck_total() as u32 + public_info.num_players() as u32;
+            (left / turns).as_millis() as u64
+        });
+        if let Some(limit) = self.time_limit {
+            if game_start.elapsed() > limit {
+                self.forfeited = true;
+            }
+        }
+        for hook in self.before_turn.iter_mut() {
+            hook(&self.env);
+        }
+
+        let move_start = Instant::now();
+        telemetry::set_turn(self.seed, self.record.turns.len() + 1);
+        let action = if self.forfeited {
+            heuristic_policy(
+                &self.env.public_info(),
+                &self.env.private_info(self.env.current_player()),
+            )
+        } else {
+            let seat = self.record.current_seat();
+            MOVE_MILLIS.with(|millis| millis.set(move_limit));
+            let action = (self.agents[seat])(&self.env, &mut self.rng);
+            MOVE_MILLIS.with(|millis| millis.set(None));
+            action
+        };
+        self.move_millis
+            .push(move_start.elapsed().as_secs_f32() * 1000.0);
+        self.record.step(&mut self.env, &action, &mut self.rng);
+
+        let turn = self.record.turns.last().unwrap();
+        for hook in self.after_turn.iter_mut() {
+            hook(&self.env, turn);
+        }
+        self.game_millis = game_start.elapsed().as_secs_f32() * 1000.0;
+        true
+    }
+
+    pub fn run(mut self) -> Self {
+        while self.step() {}
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::prelude::SliceRandom;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_runner_plays_to_the_end() {
+        let turns = Cell::new(0);
+        let before = Cell::new(0);
+        let runner = GameRunner::new(3)
+            .seat(
+                1,
+                "random",
+                Box::new(|env: &HanabiEnv, rng: &mut StdRng| *env.actions().choose(rng).unwrap()),
+            )
+            .before_turn(|_| before.set(before.get() + 1))
+            .after_turn(|env, turn| {
+                assert_eq!(turns.get() % 2, turn.seat);
+                assert!(!turn.successful_play || env.fireworks.total() > 0);
+                turns.set(turns.get() + 1);
+            })
+            .run();
+
+        assert!(runner.env.is_over());
+        assert_eq!(
+            runner.record.agents,
+            ["heuristic".to_string(), "random".to_string()]
+        );
+        assert_eq!(runner.record.turns.len(), turns.get());
+        assert_eq!(before.get(), turns.get());
+        assert_eq!(runner.move_millis.len(), turns.get());
+        assert_eq!(runner.initial.player_hand, deal(3).player_hand);
+
+        // the same seed and agents play the same game again
+        let again = GameRunner::new(3).run();
+        let first = GameRunner::new(3).run();
+        assert_eq!(first.record.score, again.record.score);
+    }
+
+    #[test]
+    fn test_moves_see_their_share_of_the_time_limit() {
+        let limits = std::cell::RefCell::new(Vec::new());
+        let agent = |env: &HanabiEnv, _: &mut StdRng| {
+            limits.borrow_mut().push(move_time_limit());
+            heuristic_policy(&env.public_info(), &env.private_info(env.current_player()))
+        };
+        GameRunner::new(3).seat(0, "timed", Box::new(agent)).run();
+        assert!(limits.borrow().iter().all(|limit| limit.is_none()));
+
+        limits.borrow_mut().clear();
+        GameRunner::new(3)
+            .seat(0, "timed", Box::new(agent))
+            .time_limit(Some(Duration::from_secs(60)))
+            .run();
+        // 40 cards left to draw & 2 seats at the first move
+        let first = limits.borrow()[0].unwrap();
+        assert!(first <= 60_000 / 42 && first > 1_000, "{}", first);
+        assert!(limits.borrow().iter().all(|limit| limit.is_some()));
+        assert_eq!(move_time_limit(), None);
+    }
+}
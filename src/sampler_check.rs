@@ -0,0 +1,172 @@
+// checks the hands `sample_private_infos` draws for the player against the exact distribution
+// from enumerating every hand consistent with the hints. a chi-squared test per slot catches
+// samplers that are biased towards some cards, which otherwise only shows up as weaker play.
+// each hand counts with the weight the sampler gives it, so importance sampling passes too
+use crate::endgame::{enumerate_hands, unseen_cards};
+use crate::hanabi_env::HanabiEnv;
+use crate::position::Position;
+use crate::rand::Rng;
+use crate::results::erf;
+
+// positions small enough to enumerate, in the one-line position format
+pub const POSITIONS: &[(&str, &str)] = &[
+    (
+        "open-late",
+        "R1.B2.Y3.G4.W5 ?.?.?.-.- ?.?.?.?.? 33333 R4R4B4B4 5/1 -",
+    ),
+    (
+        "color-clued",
+        "R1.B2.Y3.G4.W5 R.RB.?.B.- ?.?.?.?.? 22222 - 8/3 -",
+    ),
+    (
+        "rank-clued",
+        "R3.B3.Y3.G3.W3 1.12.1.5.? ?.?.?.?.? 10000 W1W1 6/2 -",
+    ),
+];
+
+// expected counts below this are pooled into one cell, the test is unreliable with small cells
+const MIN_EXPECTED: f32 = 5.0;
+
+#[derive(Clone, Debug)]
+pub struct SlotCheck {
+    pub slot: usize,
+    pub chi_squared: f32,
+    pub dof: usize,
+    pub p_value: f32,
+}
+
+// per slot probability of each card id over all hands consistent with the hints
+pub fn exact_marginals(position: &Position) -> [[f32; 25]; 5] {
+    let unseen = unseen_cards(&position.public_info, &position.private_info);
+    let hands = enumerate_hands(&position.public_info, &unseen);
+    let total = hands.iter().map(|(_, w)| w).sum::<f32>();
+    let mut marginals = [[0.0; 25]; 5];
+    for (hand, weight) in hands.iter() {
+        for i in 0..5 {
+            if hand[i].is_some() {
+                marginals[i][hand[i].id() as usize] += weight / total;
+            }
+        }
+    }
+    marginals
+}
+
+// Wilson-Hilferty: the cube root of a chi-squared variable is close to normal
+fn chi_squared_p_value(x: f32, dof: usize) -> f32 {
+    if dof == 0 {
+        return 1.0;
+    }
+    let k = dof as f32;
+    let z = ((x / k).powf(1.0 / 3.0) - (1.0 - 2.0 / (9.0 * k))) / (2.0 / (9.0 * k)).sqrt();
+    0.5 * (1.0 - erf(z / std::f32::consts::SQRT_2))
+}
+
+fn chi_squared(observed: &[f32; 25], expected: &[f32; 25]) -> (f32, usize) {
+    let mut statistic = 0.0;
+    let mut cells = 0;
+    let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+    for id in 0..25 {
+        if expected[id] >= MIN_EXPECTED {
+            statistic += (observed[id] - expected[id]).powi(2) / expected[id];
+            cells += 1;
+        } else {
+            pooled_observed += observed[id];
+            pooled_expected += expected[id];
+        }
+    }
+    if pooled_expected > 0.0 {
+        statistic += (pooled_observed - pooled_expected).powi(2) / pooled_expected.max(1e-6);
+        cells += 1;
+    }
+    (statistic, cells.max(1) - 1)
+}
+
+pub fn check_sampler<R: Rng>(
+    position: &Position,
+    num_samples: usize,
+    rng: &mut R,
+) -> Vec<SlotCheck> {
+    let exact = exact_marginals(position);
+    let mut counts = [[0.0; 25]; 5];
+    let mut total_weight = 0.0;
+    for _ in 0..num_samples {
+        let (hand, log_weight, _) =
+            HanabiEnv::sample_counting_restarts(&position.public_info, &position.private_info, rng);
+        let weight = log_weight.exp();
+        total_weight += weight;
+        for i in 0..5 {
+            if hand.opponent_hand[i].is_some() {
+                counts[i][hand.opponent_hand[i].id() as usize] += weight;
+            }
+        }
+    }
+    // scaled back to samples, the test is on counts
+    for slot in counts.iter_mut() {
+        for count in slot.iter_mut() {
+            *count *= num_samples as f32 / total_weight;
+        }
+    }
+
+    (0..5)
+        .filter(|&i| position.public_info.player_hints[i].is_some())
+        .map(|i| {
+            let mut expected = [0.0; 25];
+            for id in 0..25 {
+                expected[id] = exact[i][id] * num_samples as f32;
+            }
+            let (chi_squared, dof) = chi_squared(&counts[i], &expected);
+            SlotCheck {
+                slot: i,
+                chi_squared,
+                dof,
+                p_value: chi_squared_p_value(chi_squared, dof),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_positions_enumerate() {
+        for (name, text) in POSITIONS.iter() {
+            let position = Position::parse(text).unwrap();
+            let marginals = exact_marginals(&position);
+            for (i, marginal) in marginals.iter().enumerate() {
+                let total = marginal.iter().sum::<f32>();
+                let expected = position.public_info.player_hints[i].is_some() as u8 as f32;
+                assert!((total - expected).abs() < 1e-4, "{} slot {}", name, i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_sampler() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // with no clues every slot sees the same cards, so drawing slot by slot is exact
+        let open = Position::parse(POSITIONS[0].1).unwrap();
+        let checks = check_sampler(&open, 20_000, &mut rng);
+        assert_eq!(checks.len(), 3);
+        assert!(checks.iter().all(|c| c.p_value > 1e-4), "{:?}", checks);
+
+        // once the clues differ between slots, drawing slot by slot favors cards few of the
+        // hints allow unless the sampler makes up for it
+        for (name, text) in POSITIONS[1..].iter() {
+            let position = Position::parse(text).unwrap();
+            let checks = check_sampler(&position, 20_000, &mut rng);
+            assert!(
+                checks.iter().all(|c| c.p_value > 1e-4),
+                "{} {:?}",
+                name,
+                checks
+            );
+        }
+
+        assert!((chi_squared_p_value(3.84, 1) - 0.05).abs() < 0.01);
+        assert!((chi_squared_p_value(18.31, 10) - 0.05).abs() < 0.01);
+    }
+}
@@ -0,0 +1,49 @@
+// a library of test positions reached by dealing a known deck and playing real moves, so their
+// hints & hint history are ones a game can produce. a deal lists the hand of the player who
+// moves first, the other hand and then the draws, all as "<color><rank>" joined by dots
+use crate::env::HasEnd;
+use crate::hanabi_env::{Action, Card, Color, HanabiEnv, Suit};
+use crate::notation::parse_card;
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+
+pub fn game(deal: &str, moves: &[Action]) -> HanabiEnv {
+    let order: Vec<Card> = deal.split('.').map(|c| parse_card(c).unwrap()).collect();
+    let mut env = HanabiEnv::from_deck_order(&order);
+    let mut rng = StdRng::seed_from_u64(0);
+    for action in moves.iter() {
+        env.try_step(action, &mut rng).unwrap();
+    }
+    assert!(!env.is_over());
+    env
+}
+
+// duplicates. the partner holds an R1 in slot 3 that red touched, and drew a second R1 into
+// slot 1 after. it's our move
+pub fn touched_and_drawn_copy() -> HanabiEnv {
+    game(
+        "B4.Y3.G2.W4.Y4.W3.Y4.G2.R1.B4.R1",
+        &[Action::ColorHint(Color::Red), Action::Discard(1)],
+    )
+}
+
+// the partner clued 2s onto our R2 & W2 in slots 0 & 3, and everything else we hold has
+// negative information from it. it's our move, with nothing to play or clue and tokens spent
+pub fn clued_twos() -> HanabiEnv {
+    game(
+        "R2.B3.G4.W2.Y3.B4.Y3.G2.W4.Y4",
+        &[Action::ColorHint(Color::Blue), Action::SuitHint(Suit::Two)],
+    )
+}
+
+// the partner clued red onto our slots 0 & 2, which a partner keeping to good touch wouldn't do
+// were they the same card. it's our move
+pub fn two_touched_reds() -> HanabiEnv {
+    game(
+        "R2.B3.R1.W4.Y3.B4.Y3.G2.W4.Y4",
+        &[
+            Action::ColorHint(Color::Blue),
+            Action::ColorHint(Color::Red),
+        ],
+    )
+}
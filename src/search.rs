@@ -0,0 +1,819 @@
+use crate::beliefs::Beliefs;
+use crate::env::{Env, HasEnd, HasReward};
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::rand::Rng;
+use crate::rollout_rng::RolloutRng;
+use rand_distr::{Dirichlet, Distribution};
+use std::time::Instant;
+
+#[derive(Copy, Clone, Debug)]
+pub enum WorldAllocation {
+    // always sample this many determinizations and split the budget evenly between them
+    Fixed(usize),
+    // scale the number of determinizations with the entropy of the player's beliefs about their own hand
+    Adaptive {
+        min_worlds: usize,
+        max_worlds: usize,
+    },
+}
+
+// how long a search gets for one move. a fixed count takes far longer early in the game, when
+// rollouts are long, than near the end
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Budget {
+    // rollouts for flat mc, explores for mcts
+    Iterations(usize),
+    // wall clock, whatever is best when it runs out is played
+    Millis(u64),
+}
+
+impl Budget {
+    // whether to stop after `done` iterations started at `start`. at least one always runs, so
+    // there is an action to return
+    pub fn exhausted(&self, done: usize, start: Instant) -> bool {
+        match *self {
+            Budget::Iterations(n) => done >= n.max(1),
+            Budget::Millis(millis) => done > 0 && start.elapsed().as_millis() >= millis as u128,
+        }
+    }
+}
+
+// how flat mc samples the root actions
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VarianceReduction {
+    // every rollout deals its own world and picks its own random root action
+    None,
+    // every root action is rolled out in each sampled world with the same rng stream, so the
+    // difference between two actions' values comes from the actions rather than the deals
+    CommonRandomNumbers,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SearchMetrics {
+    pub decisions: usize,
+    pub rollouts: usize,
+    pub worlds: usize,
+    pub entropy: f32,
+    // decisions that stopped sampling worlds early because one action dominated
+    pub early_stops: usize,
+    // times determinize_hints threw away a partial hand and started over
+    pub determinize_restarts: usize,
+    pub max_determinize_restarts: usize,
+    // determinizations that restarted more than `restart_alert` times
+    pub restart_alerts: usize,
+    // config: warn on stderr when a single determinization restarts more than this many times
+    pub restart_alert: Option<usize>,
+    // config: log every determinization that needed a restart
+    pub log_restarts: bool,
+}
+
+impl SearchMetrics {
+    pub fn mean_worlds(&self) -> f32 {
+        self.worlds as f32 / self.decisions.max(1) as f32
+    }
+
+    pub fn mean_entropy(&self) -> f32 {
+        self.entropy / self.decisions.max(1) as f32
+    }
+
+    pub fn mean_restarts(&self) -> f32 {
+        self.determinize_restarts as f32 / self.worlds.max(1) as f32
+    }
+
+    pub fn record_restarts(&mut self, public_info: &PublicInfo, restarts: usize) {
+        self.determinize_restarts += restarts;
+        self.max_determinize_restarts = self.max_determinize_restarts.max(restarts);
+        if self.log_restarts && restarts > 0 {
+            eprintln!(
+                "determinization restarted {} times, hints {:?}",
+                restarts, public_info.player_hints
+            );
+        }
+        if let Some(threshold) = self.restart_alert {
+            if restarts > threshold {
+                self.restart_alerts += 1;
+                eprintln!(
+                    "warning: determinization restarted {} times (> {}), hints {:?} discard {:?} fireworks {:?}",
+                    restarts,
+                    threshold,
+                    public_info.player_hints,
+                    public_info.discard.counts,
+                    public_info.fireworks
+                );
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for SearchMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "decisions={} rollouts={} worlds/decision={:.1} entropy/decision={:.3} early_stops={} restarts/world={:.2} max_restarts={} restart_alerts={}",
+            self.decisions,
+            self.rollouts,
+            self.mean_worlds(),
+            self.mean_entropy(),
+            self.early_stops,
+            self.mean_restarts(),
+            self.max_determinize_restarts,
+            self.restart_alerts,
+        )
+    }
+}
+
+// sum of exp(log_weight) * reward, kept relative to the largest weight so tiny weights don't underflow to 0
+#[derive(Copy, Clone, Debug)]
+pub struct WeightedReward {
+    pub max_log_weight: f32,
+    pub weight: f32,
+    pub weighted_reward: f32,
+    pub visits: usize,
+}
+
+impl Default for WeightedReward {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WeightedReward {
+    pub fn new() -> Self {
+        Self {
+            max_log_weight: f32::NEG_INFINITY,
+            weight: 0.0,
+            weighted_reward: 0.0,
+            visits: 0,
+        }
+    }
+
+    pub fn add(&mut self, log_weight: f32, reward: f32) {
+        self.visits += 1;
+        if log_weight == f32::NEG_INFINITY {
+            return;
+        }
+        if log_weight > self.max_log_weight {
+            let scale = (self.max_log_weight - log_weight).exp();
+            self.weight *= scale;
+            self.weighted_reward *= scale;
+            self.max_log_weight = log_weight;
+        }
+        let weight = (log_weight - self.max_log_weight).exp();
+        self.weight += weight;
+        self.weighted_reward += weight * reward;
+    }
+
+    // self-normalized mean reward
+    pub fn mean(&self) -> f32 {
+        self.weighted_reward / self.weight
+    }
+
+    // ln(sum of weight * reward), the log of the plain weighted sum
+    pub fn log_total(&self) -> f32 {
+        self.max_log_weight + self.weighted_reward.ln()
+    }
+}
+
+// every rollout reward of a root action with the log weight of the world it was rolled out in, so
+// selection can look at the spread of outcomes and not just the mean. the statistics are weighted
+// the way WeightedReward is, so mean-sigma:0 picks what mean does
+#[derive(Clone, Debug, Default)]
+pub struct ScoreDistribution {
+    // (log weight, reward)
+    pub samples: Vec<(f32, f32)>,
+}
+
+impl ScoreDistribution {
+    pub fn add(&mut self, log_weight: f32, reward: f32) {
+        self.samples.push((log_weight, reward));
+    }
+
+    // (weight, reward) pairs with the weights relative to the heaviest, as WeightedReward keeps them
+    fn weighted(&self) -> Vec<(f32, f32)> {
+        let max_log_weight = self
+            .samples
+            .iter()
+            .map(|s| s.0)
+            .fold(f32::NEG_INFINITY, f32::max);
+        self.samples
+            .iter()
+            .filter(|s| s.0 != f32::NEG_INFINITY)
+            .map(|&(log_weight, reward)| ((log_weight - max_log_weight).exp(), reward))
+            .collect()
+    }
+
+    // the weighted mean of `f` over the samples, 0 without any
+    fn expectation(weighted: &[(f32, f32)], f: impl Fn(f32) -> f32) -> f32 {
+        let total: f32 = weighted.iter().map(|s| s.0).sum();
+        match total > 0.0 {
+            true => weighted.iter().map(|&(w, r)| w * f(r)).sum::<f32>() / total,
+            false => 0.0,
+        }
+    }
+
+    pub fn mean(&self) -> f32 {
+        Self::expectation(&self.weighted(), |r| r)
+    }
+
+    pub fn std_dev(&self) -> f32 {
+        let weighted = self.weighted();
+        let mean = Self::expectation(&weighted, |r| r);
+        Self::expectation(&weighted, |r| (r - mean) * (r - mean)).sqrt()
+    }
+
+    // the mean of the worst `alpha` of the weight, at least the worst outcome's
+    pub fn cvar(&self, alpha: f32) -> f32 {
+        let mut weighted = self.weighted();
+        weighted.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        let total: f32 = weighted.iter().map(|s| s.0).sum();
+        let mut budget = alpha * total;
+        let (mut weight, mut sum) = (0.0, 0.0);
+        for &(w, r) in weighted.iter() {
+            // the outcome that crosses the cutoff counts for the part of it that's below
+            let take = w.min(budget);
+            if take <= 0.0 {
+                break;
+            }
+            weight += take;
+            sum += take * r;
+            budget -= take;
+        }
+        match weight > 0.0 {
+            true => sum / weight,
+            false => weighted.first().map_or(0.0, |s| s.1),
+        }
+    }
+
+    // the lowest reward with at least `q` of the weight at or below it, 0 without any samples
+    pub fn quantile(&self, q: f32) -> f32 {
+        let mut weighted = self.weighted();
+        weighted.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        let total: f32 = weighted.iter().map(|s| s.0).sum();
+        let mut below = 0.0;
+        for &(w, r) in weighted.iter() {
+            below += w;
+            if below >= q * total {
+                return r;
+            }
+        }
+        weighted.last().map_or(0.0, |s| s.1)
+    }
+}
+
+// stop sampling worlds once the best action's mean beats every other action's upper confidence bound
+#[derive(Copy, Clone, Debug)]
+pub struct EarlyStop {
+    // no check happens before this many worlds have been sampled
+    pub min_worlds: usize,
+    // probability each hoeffding bound fails
+    pub delta: f32,
+}
+
+// rewards are the score fraction plus at most as much again from the future reward term
+const REWARD_RANGE: f32 = 2.0;
+
+impl EarlyStop {
+    pub fn radius(&self, visits: usize) -> f32 {
+        REWARD_RANGE * ((1.0 / self.delta).ln() / (2.0 * visits.max(1) as f32)).sqrt()
+    }
+
+    // the index of the action that dominates every other action, if there is one
+    pub fn dominant(&self, rewards: &[WeightedReward]) -> Option<usize> {
+        let means: Vec<f32> = rewards.iter().map(|r| r.mean()).collect();
+        let best = argmax(&means);
+        let dominates = rewards
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != best)
+            .all(|(i, r)| means[best] > means[i] + self.radius(r.visits));
+        if dominates && rewards.len() > 1 {
+            Some(best)
+        } else {
+            None
+        }
+    }
+}
+
+impl WorldAllocation {
+    // returns (number of worlds, rollouts per world) for a total budget of `num_rollouts`
+    pub fn allocate(&self, entropy_fraction: f32, num_rollouts: usize) -> (usize, usize) {
+        let worlds = match *self {
+            WorldAllocation::Fixed(worlds) => worlds,
+            WorldAllocation::Adaptive {
+                min_worlds,
+                max_worlds,
+            } => {
+                let scale = entropy_fraction.clamp(0.0, 1.0);
+                min_worlds + ((max_worlds - min_worlds) as f32 * scale).round() as usize
+            }
+        };
+        let worlds = worlds.max(1).min(num_rollouts.max(1));
+        (worlds, (num_rollouts / worlds).max(1))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SampleOver {
+    // p ∝ visits^(1/temperature)
+    Visits,
+    // p = softmax(mean value / temperature)
+    Values,
+}
+
+// stochastic root action selection for self-play
+#[derive(Copy, Clone, Debug)]
+pub struct RootSampling {
+    pub sample_over: SampleOver,
+    pub temperature: f32,
+    pub dirichlet_alpha: f64,
+    pub noise_fraction: f32,
+    // moves at or past this number pick the best action deterministically
+    pub greedy_after: usize,
+}
+
+impl RootSampling {
+    pub fn greedy() -> Self {
+        Self {
+            sample_over: SampleOver::Visits,
+            temperature: 0.0,
+            dirichlet_alpha: 0.3,
+            noise_fraction: 0.0,
+            greedy_after: 0,
+        }
+    }
+
+    pub fn is_greedy(&self, move_number: usize) -> bool {
+        move_number >= self.greedy_after || self.temperature <= 0.0
+    }
+
+    pub fn probabilities(&self, visits: &[f32], values: &[f32]) -> Vec<f32> {
+        let mut probs: Vec<f32> = match self.sample_over {
+            SampleOver::Visits => visits
+                .iter()
+                .map(|v| v.powf(1.0 / self.temperature))
+                .collect(),
+            SampleOver::Values => {
+                let max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                values
+                    .iter()
+                    .map(|v| ((v - max) / self.temperature).exp())
+                    .collect()
+            }
+        };
+        let total = probs.iter().sum::<f32>();
+        for p in probs.iter_mut() {
+            *p /= total;
+        }
+        probs
+    }
+
+    // returns the index of the chosen action
+    pub fn select<R: Rng>(
+        &self,
+        visits: &[f32],
+        values: &[f32],
+        move_number: usize,
+        rng: &mut R,
+    ) -> usize {
+        if self.is_greedy(move_number) {
+            return argmax(visits);
+        }
+
+        let mut probs = self.probabilities(visits, values);
+        if self.noise_fraction > 0.0 && probs.len() > 1 {
+            let noise = Dirichlet::new_with_size(self.dirichlet_alpha, probs.len())
+                .unwrap()
+                .sample(rng);
+            for (p, n) in probs.iter_mut().zip(noise.iter()) {
+                *p = (1.0 - self.noise_fraction) * *p + self.noise_fraction * *n as f32;
+            }
+        }
+
+        let mut r = rng.gen::<f32>();
+        for (i, p) in probs.iter().enumerate() {
+            if r < *p {
+                return i;
+            }
+            r -= p;
+        }
+        probs.len() - 1
+    }
+}
+
+fn argmax(xs: &[f32]) -> usize {
+    xs.iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(i, _)| i)
+        .unwrap()
+}
+
+fn rollout_world<R: Rng>(env: &HanabiEnv, rng: &mut R) -> (Action, f32) {
+    let mut env = env.clone();
+    let action = env.random_action(rng);
+    env.step(&action, rng);
+
+    while !env.is_over() {
+        let action = env.random_action(rng);
+        env.step(&action, rng);
+    }
+
+    (action, env.reward())
+}
+
+// how the rollouts over sampled worlds are turned into a choice of root action
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Criterion {
+    // the highest likelihood weighted mean reward
+    Mean,
+    // the highest WORST_QUANTILE of the rollout rewards, for never bombing out over the average
+    // score. early stopping only applies to Mean
+    Maximin,
+    // mean - k standard deviations of the rollout rewards, trading score for fewer blowups
+    MeanMinusSigma(f32),
+    // the mean of the worst alpha fraction of rollouts
+    CVaR(f32),
+}
+
+impl Criterion {
+    // "mean", "maximin", "mean-sigma:<k>" or "cvar:<alpha>"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = text.splitn(2, ':');
+        let invalid = || {
+            format!(
+                "invalid criterion '{}', expected mean, maximin, mean-sigma:<k> or cvar:<alpha>",
+                text
+            )
+        };
+        let name = parts.next().unwrap_or("");
+        let param = parts.next().map(|p| p.parse::<f32>());
+        match (name, param) {
+            ("mean", None) => Ok(Criterion::Mean),
+            ("maximin", None) => Ok(Criterion::Maximin),
+            ("mean-sigma", Some(Ok(k))) => Ok(Criterion::MeanMinusSigma(k)),
+            ("cvar", Some(Ok(alpha))) if alpha > 0.0 && alpha <= 1.0 => Ok(Criterion::CVaR(alpha)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// the share of an action's rollouts Maximin counts as its worst case. the lowest reward outright
+// would favor the actions rolled out least, they've had fewer chances to find a bad world
+pub const WORST_QUANTILE: f32 = 0.1;
+
+// what the rollouts of one decision found for each root action
+#[derive(Clone, Debug)]
+pub struct RootValues {
+    pub actions: Vec<Action>,
+    pub rewards: Vec<WeightedReward>,
+    // the WORST_QUANTILE quantile of the action's rollout rewards
+    pub worst: Vec<f32>,
+    pub distributions: Vec<ScoreDistribution>,
+    // set when sampling stopped early because this action dominated
+    pub dominant: Option<usize>,
+}
+
+impl RootValues {
+    pub fn best(&self, criterion: Criterion) -> Action {
+        match (criterion, self.dominant) {
+            (Criterion::Mean, Some(i)) => self.actions[i],
+            (Criterion::Mean, None) => {
+                // the weighted mean, a total would favor the actions rolled out most
+                let means: Vec<f32> = self.rewards.iter().map(|r| r.mean()).collect();
+                self.actions[argmax(&means)]
+            }
+            (Criterion::Maximin, _) => self.actions[argmax(&self.worst)],
+            (Criterion::MeanMinusSigma(k), _) => {
+                let values: Vec<f32> = self
+                    .distributions
+                    .iter()
+                    .map(|d| d.mean() - k * d.std_dev())
+                    .collect();
+                self.actions[argmax(&values)]
+            }
+            (Criterion::CVaR(alpha), _) => {
+                let values: Vec<f32> = self.distributions.iter().map(|d| d.cvar(alpha)).collect();
+                self.actions[argmax(&values)]
+            }
+        }
+    }
+}
+
+// early stopping compares means, choosing by any other criterion it would cut the sampling short
+// on the wrong evidence. pass None for `early_stop` unless the values are read with Criterion::Mean
+pub fn world_values<R: Rng>(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    num_rollouts: usize,
+    allocation: WorldAllocation,
+    early_stop: Option<EarlyStop>,
+    metrics: &mut SearchMetrics,
+    rng: &mut R,
+) -> RootValues {
+    let entropy_fraction = Beliefs::new(public_info, private_info).entropy_fraction();
+    let (num_worlds, rollouts_per_world) = allocation.allocate(entropy_fraction, num_rollouts);
+
+    metrics.decisions += 1;
+    metrics.entropy += entropy_fraction;
+
+    let mut values = RootValues {
+        actions: Vec::new(),
+        rewards: Vec::new(),
+        worst: Vec::new(),
+        distributions: Vec::new(),
+        dominant: None,
+    };
+    let mut rollout_rng = RolloutRng::seed_from_u64(rng.gen());
+    for world in 0..num_worlds {
+        let (env, log_prob, restarts) =
+            HanabiEnv::determinize_counting_restarts(public_info, private_info, rng);
+        metrics.record_restarts(public_info, restarts);
+        for _ in 0..rollouts_per_world {
+            let (action, reward) = rollout_world(&env, &mut rollout_rng);
+            let i = match values.actions.iter().position(|&a| a == action) {
+                Some(i) => i,
+                None => {
+                    values.actions.push(action);
+                    values.rewards.push(WeightedReward::new());
+                    values.distributions.push(ScoreDistribution::default());
+                    values.actions.len() - 1
+                }
+            };
+            values.rewards[i].add(log_prob, reward);
+            values.distributions[i].add(log_prob, reward);
+        }
+        metrics.worlds += 1;
+        metrics.rollouts += rollouts_per_world;
+
+        // every legal action has to have been tried before any of them can dominate
+        if let Some(early_stop) = early_stop.as_ref() {
+            if world + 1 >= early_stop.min_worlds && values.actions.len() == env.actions().len() {
+                if let Some(i) = early_stop.dominant(&values.rewards) {
+                    metrics.early_stops += 1;
+                    values.dominant = Some(i);
+                    break;
+                }
+            }
+        }
+    }
+    values.worst = values
+        .distributions
+        .iter()
+        .map(|d| d.quantile(WORST_QUANTILE))
+        .collect();
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_adaptive_allocation() {
+        let allocation = WorldAllocation::Adaptive {
+            min_worlds: 10,
+            max_worlds: 1000,
+        };
+        assert_eq!(allocation.allocate(0.0, 10_000), (10, 1000));
+        assert_eq!(allocation.allocate(1.0, 10_000), (1000, 10));
+        assert_eq!(allocation.allocate(1.0, 100), (100, 1));
+        assert_eq!(WorldAllocation::Fixed(50).allocate(0.3, 1000), (50, 20));
+    }
+
+    #[test]
+    fn test_budget() {
+        let start = Instant::now();
+        assert!(!Budget::Iterations(3).exhausted(2, start));
+        assert!(Budget::Iterations(3).exhausted(3, start));
+        assert!(!Budget::Iterations(0).exhausted(0, start));
+        assert!(!Budget::Millis(0).exhausted(0, start));
+        assert!(Budget::Millis(0).exhausted(1, start));
+        assert!(!Budget::Millis(60_000).exhausted(1_000_000, start));
+    }
+
+    #[test]
+    fn test_weighted_reward_survives_underflow() {
+        // exp(-200) underflows f32, but the relative weights are still fine
+        let mut a = WeightedReward::new();
+        a.add(-200.0, 1.0);
+        a.add(-201.0, 0.5);
+        let mut b = WeightedReward::new();
+        b.add(-200.0, 0.2);
+        b.add(-201.0, 0.1);
+
+        assert_eq!((-200f32).exp(), 0.0);
+        assert!(a.log_total().is_finite());
+        assert!(a.log_total() > b.log_total());
+        let expected = (1.0 + 0.5 * (-1f32).exp()) / (1.0 + (-1f32).exp());
+        assert!((a.mean() - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_early_stop_dominance() {
+        let early_stop = EarlyStop {
+            min_worlds: 1,
+            delta: 0.05,
+        };
+        let rewards_with = |means: &[f32], visits: usize| -> Vec<WeightedReward> {
+            means
+                .iter()
+                .map(|&mean| {
+                    let mut r = WeightedReward::new();
+                    for _ in 0..visits {
+                        r.add(0.0, mean);
+                    }
+                    r
+                })
+                .collect()
+        };
+
+        // a clear gap with plenty of samples dominates, the same gap with few samples doesn't
+        assert_eq!(
+            early_stop.dominant(&rewards_with(&[0.2, 0.9, 0.3], 1000)),
+            Some(1)
+        );
+        assert_eq!(
+            early_stop.dominant(&rewards_with(&[0.2, 0.9, 0.3], 5)),
+            None
+        );
+        assert_eq!(early_stop.dominant(&rewards_with(&[0.5, 0.5], 1000)), None);
+        assert!(early_stop.radius(100) > early_stop.radius(1000));
+    }
+
+    #[test]
+    fn test_restart_metrics() {
+        use crate::rand::rngs::StdRng;
+        use crate::rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let mut metrics = SearchMetrics {
+            restart_alert: Some(3),
+            ..SearchMetrics::default()
+        };
+        metrics.record_restarts(&env.public_info(), 1);
+        metrics.record_restarts(&env.public_info(), 5);
+        metrics.record_restarts(&env.public_info(), 0);
+        assert_eq!(metrics.determinize_restarts, 6);
+        assert_eq!(metrics.max_determinize_restarts, 5);
+        assert_eq!(metrics.restart_alerts, 1);
+    }
+
+    #[test]
+    fn test_root_sampling() {
+        use crate::rand::rngs::StdRng;
+        use crate::rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let visits = [10.0, 80.0, 10.0];
+        let values = [0.1, 0.5, 0.2];
+        let sampling = RootSampling {
+            sample_over: SampleOver::Visits,
+            temperature: 1.0,
+            dirichlet_alpha: 0.3,
+            noise_fraction: 0.25,
+            greedy_after: 10,
+        };
+
+        let probs = sampling.probabilities(&visits, &values);
+        assert!((probs[1] - 0.8).abs() < 1e-6);
+
+        let mut counts = [0; 3];
+        for _ in 0..1000 {
+            counts[sampling.select(&visits, &values, 0, &mut rng)] += 1;
+        }
+        assert!(counts[0] > 0 && counts[2] > 0);
+        assert!(counts[1] > counts[0] && counts[1] > counts[2]);
+
+        for _ in 0..100 {
+            assert_eq!(sampling.select(&visits, &values, 10, &mut rng), 1);
+        }
+    }
+
+    #[test]
+    fn test_maximin_prefers_the_safe_action() {
+        let mut risky = WeightedReward::new();
+        risky.add(0.0, 1.0);
+        risky.add(0.0, 0.0);
+        let mut safe = WeightedReward::new();
+        safe.add(0.0, 0.4);
+        safe.add(0.0, 0.4);
+        let values = RootValues {
+            actions: vec![Action::Play(0), Action::Discard(0)],
+            rewards: vec![risky, safe],
+            worst: vec![0.0, 0.4],
+            distributions: Vec::new(),
+            dominant: None,
+        };
+        assert_eq!(values.best(Criterion::Mean), values.actions[0]);
+        assert_eq!(values.best(Criterion::Maximin), values.actions[1]);
+
+        let mut rng = crate::rand::rngs::StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let mut metrics = SearchMetrics::default();
+        let values = world_values(
+            &env.public_info(),
+            &env.private_info(env.current_player()),
+            500,
+            WorldAllocation::Fixed(10),
+            None,
+            &mut metrics,
+            &mut rng,
+        );
+        for (distribution, &worst) in values.distributions.iter().zip(values.worst.iter()) {
+            let rewards = distribution.samples.iter().map(|s| s.1);
+            assert!(worst >= rewards.clone().fold(f32::INFINITY, f32::min));
+            assert!(worst <= rewards.fold(f32::NEG_INFINITY, f32::max));
+        }
+        assert!(env.actions().contains(&values.best(Criterion::Maximin)));
+    }
+
+    #[test]
+    fn test_risk_criteria_avoid_high_variance() {
+        let distribution = |samples: &[f32]| ScoreDistribution {
+            samples: samples.iter().map(|&r| (0.0, r)).collect(),
+        };
+        // a play that bombs out 1 time in 10 against a steady discard
+        let risky = distribution(&[0.9, 0.9, 0.9, 0.9, 0.9, 0.9, 0.9, 0.9, 0.9, 0.1]);
+        let safe = distribution(&[0.75; 10]);
+        assert!((risky.mean() - 0.82).abs() < 1e-6);
+        assert!((risky.std_dev() - 0.24).abs() < 1e-5);
+        assert!((risky.cvar(0.1) - 0.1).abs() < 1e-6);
+        assert!((risky.cvar(0.2) - 0.5).abs() < 1e-6);
+        assert_eq!(risky.quantile(0.1), 0.1);
+        assert_eq!(risky.quantile(0.2), 0.9);
+        assert_eq!(ScoreDistribution::default().quantile(0.1), 0.0);
+        assert_eq!(safe.std_dev(), 0.0);
+
+        let mut rewards = Vec::new();
+        for d in [&risky, &safe].iter() {
+            let mut reward = WeightedReward::new();
+            d.samples.iter().for_each(|&(w, r)| reward.add(w, r));
+            rewards.push(reward);
+        }
+        let values = RootValues {
+            actions: vec![Action::Play(0), Action::Discard(0)],
+            rewards,
+            worst: vec![0.1, 0.75],
+            distributions: vec![risky, safe],
+            dominant: None,
+        };
+        assert_eq!(values.best(Criterion::Mean), values.actions[0]);
+        assert_eq!(
+            values.best(Criterion::MeanMinusSigma(0.1)),
+            values.actions[0]
+        );
+        assert_eq!(
+            values.best(Criterion::MeanMinusSigma(1.0)),
+            values.actions[1]
+        );
+        assert_eq!(values.best(Criterion::CVaR(0.2)), values.actions[1]);
+
+        assert_eq!(Criterion::parse("cvar:0.1"), Ok(Criterion::CVaR(0.1)));
+        assert_eq!(
+            Criterion::parse("mean-sigma:2"),
+            Ok(Criterion::MeanMinusSigma(2.0))
+        );
+        assert_eq!(Criterion::parse("maximin"), Ok(Criterion::Maximin));
+        assert!(Criterion::parse("cvar:0").is_err());
+        assert!(Criterion::parse("mean:1").is_err());
+    }
+
+    #[test]
+    fn test_weighted_criteria_agree_with_mean() {
+        // the first action's good outcome came from a world 4 times as likely as its bad one, so
+        // it's the better bet weighted, and the worse one counting rollouts
+        let mut values = RootValues {
+            actions: vec![Action::Play(0), Action::Discard(0)],
+            rewards: vec![WeightedReward::new(), WeightedReward::new()],
+            worst: vec![0.0, 0.0],
+            distributions: vec![ScoreDistribution::default(), ScoreDistribution::default()],
+            dominant: None,
+        };
+        let samples = [
+            (0, 4f32.ln(), 0.8),
+            (0, 0.0, 0.0),
+            (0, 0.0, 0.0),
+            (1, 0.0, 0.5),
+            (1, 0.0, 0.5),
+        ];
+        for &(i, log_weight, reward) in samples.iter() {
+            values.rewards[i].add(log_weight, reward);
+            values.distributions[i].add(log_weight, reward);
+        }
+        let first = &values.distributions[0];
+        assert!((first.mean() - 3.2 / 6.0).abs() < 1e-6);
+        assert!((first.mean() - values.rewards[0].mean()).abs() < 1e-6);
+        assert!((first.std_dev() - (2.0f32 / 9.0).sqrt() * 0.8).abs() < 1e-5);
+        // the worst third of the weight is the two bad worlds, half takes in some of the good one
+        assert!(first.cvar(1.0 / 3.0).abs() < 1e-6);
+        assert!((first.cvar(0.5) - 0.8 / 3.0).abs() < 1e-5);
+        assert_eq!(
+            values.best(Criterion::MeanMinusSigma(0.0)),
+            values.best(Criterion::Mean)
+        );
+        assert_eq!(values.best(Criterion::Mean), values.actions[0]);
+        assert!(ScoreDistribution::default().mean() == 0.0);
+    }
+}
@@ -0,0 +1,238 @@
+// a quick battery of checks that a build behaves the way the engine expects, for users to run
+// before filing a bug report. each check is small enough that the whole run takes seconds:
+// - sampler: the chi-squared test of sampler_check on positions the sampler draws exactly
+// - invariants: random games checked after every move for card conservation, token bounds and
+//   legal action lists
+// - replay: heuristic self-play on fixed seeds hashed against the hash this tree produces
+// - timing: random rollouts finish within a generous budget
+use crate::env::{Env, HasEnd};
+use crate::hanabi_env::{Card, CardCollection, Deck, HanabiEnv};
+use crate::position::Position;
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+use crate::runner::GameRunner;
+use crate::sampler_check::check_sampler;
+use std::time::Instant;
+
+// the replay hash of heuristic self-play on seeds 0..REPLAY_SEEDS, changes when the env, the
+// dealing or the heuristic changes on purpose
+pub const REPLAY_HASH: u64 = 0xb1e0b27bacdde205;
+const REPLAY_SEEDS: u64 = 3;
+
+// the sampler fills slots in order, so it's only exact while no clued slot follows one it could
+// take cards from. verify-sampler also covers the clued positions it's known to be biased on
+const SAMPLER_POSITIONS: &[(&str, &str)] = &[
+    (
+        "open-late",
+        "R1.B2.Y3.G4.W5 ?.?.?.-.- ?.?.?.?.? 33333 R4R4B4B4 5/1 -",
+    ),
+    (
+        "first-clued",
+        "R1.B2.Y3.G4.W5 R.?.?.-.- ?.?.?.?.? 22222 - 8/3 -",
+    ),
+    (
+        "color-clued",
+        "R1.B2.Y3.G4.W5 R.RB.?.B.- ?.?.?.?.? 22222 - 8/3 -",
+    ),
+];
+
+#[derive(Clone, Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+    pub millis: f32,
+}
+
+impl std::fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} ({:.0}ms): {}",
+            if self.passed { "PASS" } else { "FAIL" },
+            self.name,
+            self.millis,
+            self.detail
+        )
+    }
+}
+
+fn timed(name: &'static str, check: impl FnOnce() -> Result<String, String>) -> CheckResult {
+    let start = Instant::now();
+    let (passed, detail) = match check() {
+        Ok(detail) => (true, detail),
+        Err(detail) => (false, detail),
+    };
+    CheckResult {
+        name,
+        passed,
+        detail,
+        millis: start.elapsed().as_secs_f32() * 1000.0,
+    }
+}
+
+fn check_sampler_positions() -> Result<String, String> {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut num_slots = 0;
+    for (name, text) in SAMPLER_POSITIONS.iter() {
+        let position = Position::parse(text)?;
+        for check in check_sampler(&position, 20_000, &mut rng) {
+            num_slots += 1;
+            if check.p_value < 0.001 {
+                return Err(format!(
+                    "{} slot {} is biased: chi2={:.1} dof={} p={:.4}",
+                    name,
+                    check.slot + 1,
+                    check.chi_squared,
+                    check.dof,
+                    check.p_value
+                ));
+            }
+        }
+    }
+    Ok(format!("{} slots unbiased", num_slots))
+}
+
+// every card of the rules' deck is in exactly one of the hands, the deck, the discard or the
+// fireworks, and the counters stay in range
+pub fn check_invariants(env: &HanabiEnv) -> Result<(), String> {
+    let mut seen = CardCollection::empty();
+    let hands = env.player_hand.iter().chain(env.opponent_hand.iter());
+    hands.filter(|c| c.is_some()).for_each(|&c| seen.add(c));
+    match &env.deck {
+        Deck::Multiset(cards) => {
+            for id in 0..25 {
+                (0..cards.counts[id]).for_each(|_| seen.add(Card::from_id(id as u8)));
+            }
+        }
+        Deck::Ordered(cards) => cards.iter().for_each(|&c| seen.add(c)),
+    }
+    for id in 0..25 {
+        (0..env.discard.counts[id]).for_each(|_| seen.add(Card::from_id(id as u8)));
+    }
+    for color in 0..5u8 {
+        for suit in 0..env.fireworks.0[color as usize] {
+            seen.add(Card::from_parts(color, suit));
+        }
+    }
+    if seen.counts != env.rules.deck().counts {
+        return Err(format!(
+            "cards aren't conserved: {:?} vs {:?}",
+            seen.counts,
+            env.rules.deck().counts
+        ));
+    }
+    if env.blue_tokens > env.rules.clue_tokens || env.black_tokens > env.rules.strikes + 1 {
+        return Err(format!(
+            "tokens out of range: {} clues, {} black",
+            env.blue_tokens, env.black_tokens
+        ));
+    }
+    if env.fireworks.total() > env.max_score || env.max_score > env.rules.perfect_score() {
+        return Err(format!(
+            "score {} above max score {}",
+            env.fireworks.total(),
+            env.max_score
+        ));
+    }
+    if !env.is_over() {
+        let actions = env.actions();
+        if actions.is_empty() {
+            return Err("no legal actions".to_string());
+        }
+        for action in actions.iter() {
+            env.check_action(action)
+                .map_err(|e| format!("{:?} is listed but illegal: {}", action, e))?;
+        }
+    }
+    Ok(())
+}
+
+fn check_random_games(num_games: u64) -> Result<String, String> {
+    let mut num_moves = 0;
+    for seed in 0..num_games {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut env = HanabiEnv::random(&mut rng);
+        check_invariants(&env).map_err(|e| format!("seed {} at the start: {}", seed, e))?;
+        while !env.is_over() {
+            let action = env.random_action(&mut rng);
+            env.step(&action, &mut rng);
+            num_moves += 1;
+            check_invariants(&env)
+                .map_err(|e| format!("seed {} after {:?}: {}", seed, action, e))?;
+        }
+    }
+    Ok(format!("{} games, {} moves", num_games, num_moves))
+}
+
+// fnv-1a, unlike std's hasher it's the same on every platform & release
+fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+pub fn replay_hash() -> u64 {
+    let mut hash = 0xcbf29ce484222325;
+    for seed in 0..REPLAY_SEEDS {
+        let runner = GameRunner::new(seed).run();
+        for turn in runner.record.turns.iter() {
+            hash = fnv(hash, format!("{:?}", turn.action).as_bytes());
+        }
+        hash = fnv(hash, &[runner.env.fireworks.total()]);
+    }
+    hash
+}
+
+fn check_replay() -> Result<String, String> {
+    match replay_hash() {
+        REPLAY_HASH => Ok(format!("{:016x}", REPLAY_HASH)),
+        hash => Err(format!("hash {:016x}, expected {:016x}", hash, REPLAY_HASH)),
+    }
+}
+
+fn check_timing() -> Result<String, String> {
+    let num_rollouts = 200;
+    let mut rng = StdRng::seed_from_u64(0);
+    let root = HanabiEnv::random(&mut rng);
+    let start = Instant::now();
+    for _ in 0..num_rollouts {
+        let mut env = root.clone();
+        while !env.is_over() {
+            let action = env.random_action(&mut rng);
+            env.step(&action, &mut rng);
+        }
+    }
+    let millis = start.elapsed().as_secs_f32() * 1000.0 / num_rollouts as f32;
+    // debug builds run a rollout in well under a millisecond, this only catches the pathological
+    match millis < 20.0 {
+        true => Ok(format!("{:.3}ms per rollout", millis)),
+        false => Err(format!("{:.3}ms per rollout, expected under 20ms", millis)),
+    }
+}
+
+pub fn run_selfcheck() -> Vec<CheckResult> {
+    vec![
+        timed("sampler", check_sampler_positions),
+        timed("invariants", || check_random_games(20)),
+        timed("replay", check_replay),
+        timed("timing", check_timing),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selfcheck() {
+        for result in run_selfcheck() {
+            assert!(result.passed, "{}", result);
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        env.discard.add(env.player_hand[0]);
+        assert!(check_invariants(&env).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::env::{Env, HasEnd};
+use crate::hanabi_env::HanabiEnv;
+use crate::hle::{encode_observation, move_uid, move_uids, HLE_NUM_MOVES};
+use crate::mcts::MCTS;
+use crate::rand::rngs::StdRng;
+use crate::rand::{Rng, SeedableRng};
+use crate::record::GameRecord;
+use crate::search::RootSampling;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+pub struct SelfPlayConfig {
+    pub num_games: usize,
+    // stop starting new games once this many positions have been generated
+    pub max_positions: Option<usize>,
+    pub num_workers: usize,
+    pub num_explores: usize,
+    pub sampling: RootSampling,
+    pub seed: u64,
+}
+
+// one training example, from the point of view of the player to move
+#[derive(Clone, Debug)]
+pub struct Position {
+    pub game: usize,
+    pub turn: usize,
+    // HLE observation bits
+    pub observation: Vec<u8>,
+    // HLE move uid of the action that was played
+    pub action: usize,
+    // root visit fractions indexed by HLE move uid
+    pub policy: Vec<f32>,
+    // final score of the game this position is from
+    pub score: u8,
+}
+
+fn play_game(game: usize, config: &SelfPlayConfig) -> Vec<Position> {
+    let mut rng = StdRng::seed_from_u64(config.seed + game as u64);
+    let mut env = HanabiEnv::random(&mut rng);
+    let mut record = GameRecord::new(["selfplay".to_string(), "selfplay".to_string()]);
+    let mut positions = Vec::new();
+
+    while !env.is_over() {
+        let public_info = env.public_info();
+        let private_info = env.private_info(env.current_player());
+        let mut mcts = MCTS::<HanabiEnv>::with_capacity(
+            &public_info,
+            &private_info,
+            config.num_explores,
+            rng.gen(),
+        );
+        mcts.explore_n(config.num_explores);
+
+        let stats = mcts.root_stats();
+        let total_visits = stats.iter().map(|s| s.1).sum::<f32>();
+        let mut policy = vec![0.0; HLE_NUM_MOVES];
+        for (action, visits, _) in stats.iter() {
+            let uids = move_uids(&public_info, action);
+            for &uid in uids.iter() {
+                policy[uid] += visits / total_visits / uids.len() as f32;
+            }
+        }
+
+        let action = mcts.sample_action(&config.sampling, record.turns.len());
+        let observation = encode_observation(&public_info, &private_info, record.turns.last());
+        record.step(&mut env, &action, &mut rng);
+        let action_uid = move_uid(&action);
+
+        positions.push(Position {
+            game,
+            turn: record.turns.len(),
+            observation,
+            action: action_uid,
+            policy,
+            score: 0,
+        });
+    }
+
+    for position in positions.iter_mut() {
+        position.score = record.score;
+    }
+    positions
+}
+
+// games are seeded by index, so the dataset doesn't depend on the number of workers
+pub fn run_selfplay(config: &SelfPlayConfig) -> Vec<Position> {
+    let next_game = Arc::new(AtomicUsize::new(0));
+    let num_positions = Arc::new(AtomicUsize::new(0));
+
+    let workers: Vec<_> = (0..config.num_workers.max(1))
+        .map(|_| {
+            let config = config.clone();
+            let next_game = next_game.clone();
+            let num_positions = num_positions.clone();
+            std::thread::spawn(move || {
+                let mut positions = Vec::new();
+                loop {
+                    if let Some(max) = config.max_positions {
+                        if num_positions.load(Ordering::SeqCst) >= max {
+                            break;
+                        }
+                    }
+                    let game = next_game.fetch_add(1, Ordering::SeqCst);
+                    if game >= config.num_games {
+                        break;
+                    }
+                    let game_positions = play_game(game, &config);
+                    num_positions.fetch_add(game_positions.len(), Ordering::SeqCst);
+                    positions.extend(game_positions);
+                }
+                positions
+            })
+        })
+        .collect();
+
+    let mut positions: Vec<Position> = workers
+        .into_iter()
+        .flat_map(|worker| worker.join().unwrap())
+        .collect();
+    positions.sort_by_key(|p| (p.game, p.turn));
+    if let Some(max) = config.max_positions {
+        positions.truncate(max);
+    }
+    positions
+}
+
+// one JSON object per line, the observation is written as a string of 0/1 characters
+pub fn write_positions(path: &str, positions: &[Position]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for position in positions.iter() {
+        let observation: String = position
+            .observation
+            .iter()
+            .map(|&b| if b == 1 { '1' } else { '0' })
+            .collect();
+        let policy: Vec<String> = position.policy.iter().map(|p| format!("{}", p)).collect();
+        writeln!(
+            writer,
+            "{{\"game\":{},\"turn\":{},\"observation\":\"{}\",\"action\":{},\"policy\":[{}],\"score\":{}}}",
+            position.game,
+            position.turn,
+            observation,
+            position.action,
+            policy.join(","),
+            position.score,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hle::HLE_OBSERVATION_SIZE;
+
+    #[test]
+    fn test_selfplay_is_independent_of_workers() {
+        let config = SelfPlayConfig {
+            num_games: 4,
+            max_positions: Some(40),
+            num_workers: 1,
+            num_explores: 20,
+            sampling: RootSampling::greedy(),
+            seed: 0,
+        };
+        let single = run_selfplay(&config);
+        let parallel = run_selfplay(&SelfPlayConfig {
+            num_workers: 3,
+            ..config.clone()
+        });
+
+        assert_eq!(single.len(), 40);
+        assert_eq!(parallel.len(), 40);
+        for (a, b) in single.iter().zip(parallel.iter()) {
+            assert_eq!((a.game, a.turn, a.action), (b.game, b.turn, b.action));
+            assert_eq!(a.observation.len(), HLE_OBSERVATION_SIZE);
+            assert!((a.policy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+            assert!(a.policy[a.action] > 0.0);
+        }
+    }
+}
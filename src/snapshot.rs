@@ -0,0 +1,87 @@
+// golden master tests for the text the CLI prints.
+// run with UPDATE_SNAPSHOTS=1 to accept intentional changes or write a new snapshot, the files
+// live in snapshots/ and are only ever written then
+use std::path::PathBuf;
+
+fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("snapshots")
+        .join(format!("{}.txt", name))
+}
+
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = snapshot_path(name);
+    if std::env::var("UPDATE_SNAPSHOTS").is_ok() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    // a missing file fails too, a run that quietly writes one would check nothing
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "no snapshot '{}' at {}, rerun with UPDATE_SNAPSHOTS=1 to write it",
+            name,
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "snapshot '{}' changed, rerun with UPDATE_SNAPSHOTS=1 if this is intended\n--- expected\n{}\n--- actual\n{}",
+        name,
+        expected,
+        actual
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasEnd};
+    use crate::hanabi_env::HanabiEnv;
+    use crate::notation::{format_line, notate_record};
+    use crate::rand::prelude::SliceRandom;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+    use crate::record::GameRecord;
+    use crate::results::{summarize, GameResult, Timings};
+
+    fn seeded_game(seed: u64, max_turns: usize) -> (HanabiEnv, GameRecord) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut env = HanabiEnv::random(&mut rng);
+        let mut record = GameRecord::new(["a".to_string(), "b".to_string()]);
+        while !env.is_over() && record.turns.len() < max_turns {
+            let action = *env.actions().choose(&mut rng).unwrap();
+            record.step(&mut env, &action, &mut rng);
+        }
+        (env, record)
+    }
+
+    #[test]
+    fn test_describe_snapshot() {
+        let (env, _) = seeded_game(7, 12);
+        assert_snapshot("describe_seed7_turn12", &env.to_string());
+    }
+
+    #[test]
+    fn test_notation_snapshot() {
+        let (_, record) = seeded_game(7, 1000);
+        assert_snapshot("notation_seed7", &format_line(&notate_record(&record)));
+    }
+
+    #[test]
+    fn test_summary_snapshot() {
+        let results: Vec<GameResult> = (0..20)
+            .map(|seed| GameResult {
+                seed,
+                score: (seed * 7 % 26) as u8,
+            })
+            .collect();
+        let timings = Timings {
+            move_millis: (0..100).map(|i| i as f32 * 1.5).collect(),
+            game_millis: vec![120.0, 80.0, 300.0],
+            forfeits: 1,
+        };
+        let text = format!("{}\n{}\n", summarize(&results), timings);
+        assert_snapshot("summary", &text);
+    }
+}
@@ -0,0 +1,235 @@
+// sparse sampling: a depth limited expectimax. the mover takes the best of their moves, and a
+// draw is averaged over `samples` cards sampled by how likely they are, or over all of them when
+// there are that few. at the depth limit positions are valued by an evaluator, by default the
+// score & what the fireworks can still get, or by a rollout. with a seeded rng the search gives
+// the same answer every time, and depth & samples trade its cost against its accuracy.
+// the mover's own hand is sampled into a few worlds searched with every card known, so within
+// the depth limit clues are worth nothing to it. the agents value the depth limit by heuristic
+// rollouts, which do read clues, but the worlds are still sampled without what a clue implies
+// under the heuristic's conventions, and they play worse than the heuristic on its own. it's
+// meant for the endgame, where there's little left to tell
+use crate::endgame::ENDGAME_DECK_SIZE;
+use crate::env::{Env, HasEnd, HasReward};
+use crate::evaluator::{heuristic_policy, Evaluator};
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::rand::rngs::StdRng;
+use crate::rand::{Rng, SeedableRng};
+
+// values a position by the fully shaped reward: the score, and the points the fireworks can
+// still get (possible_future_rewards) weighed by the strikes left. the bound on its own doesn't
+// tell a play from a stall, nor a bomb from a safe move
+pub struct FutureRewards;
+
+impl Evaluator<HanabiEnv> for FutureRewards {
+    fn evaluate(
+        &mut self,
+        public_info: &PublicInfo,
+        _private_info: &PrivateInfo,
+        actions: &[Action],
+    ) -> (f32, Vec<f32>) {
+        (
+            public_info.reward(),
+            vec![1.0 / actions.len() as f32; actions.len()],
+        )
+    }
+}
+
+// plays the heuristic out from a world to the end of the game. each seat moves off what it sees,
+// so unlike the search above it, a rollout doesn't play with its own cards known, and clues are
+// worth what the heuristic makes of them
+pub fn heuristic_rollout(env: &HanabiEnv, rng: &mut StdRng) -> f32 {
+    let mut env = env.clone();
+    while !env.is_over() {
+        let private_info = env.private_info(env.current_player());
+        let action = heuristic_policy(&env.public_info(), &private_info);
+        env.step(&action, rng);
+    }
+    env.reward()
+}
+
+type Rollout<E> = Box<dyn FnMut(&E, &mut StdRng) -> f32>;
+
+pub struct SparseSampling<E: Env> {
+    pub depth: usize,
+    pub samples: usize,
+    pub evaluator: Box<dyn Evaluator<E>>,
+    // values the positions at the depth limit in place of the evaluator
+    rollout: Option<Rollout<E>>,
+    rng: StdRng,
+}
+
+impl<E> SparseSampling<E>
+where
+    E: Env + HasReward<Reward = f32>,
+    E::Action: Copy,
+{
+    pub fn new(depth: usize, samples: usize, evaluator: Box<dyn Evaluator<E>>, seed: u64) -> Self {
+        Self {
+            depth,
+            samples,
+            evaluator,
+            rollout: None,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn with_rollout(mut self, rollout: Rollout<E>) -> Self {
+        self.rollout = Some(rollout);
+        self
+    }
+
+    // the value of each of the mover's search actions in `env`, with every card known
+    pub fn action_values(&mut self, env: &E) -> Vec<(E::Action, f32)> {
+        env.search_actions()
+            .into_iter()
+            .map(|action| (action, self.q(env, &action, self.depth)))
+            .collect()
+    }
+
+    fn value(&mut self, env: &E, depth: usize) -> f32 {
+        if env.is_over() {
+            return env.reward();
+        }
+        if depth == 0 {
+            if let Some(rollout) = self.rollout.as_mut() {
+                return rollout(env, &mut self.rng);
+            }
+            let private_info = env.private_info(env.current_player());
+            return self
+                .evaluator
+                .evaluate(&env.public_info(), &private_info, &[])
+                .0;
+        }
+        env.search_actions()
+            .iter()
+            .map(|action| self.q(env, action, depth))
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    // the value of `action` in `env` with `depth` moves to go, this one included
+    fn q(&mut self, env: &E, action: &E::Action, depth: usize) -> f32 {
+        let outcome = env.apply_action(action);
+        let chances = env.chance_outcomes(&outcome);
+        if chances.is_empty() {
+            let mut next = env.clone();
+            next.resolve_chance(&outcome, &mut self.rng);
+            return self.value(&next, depth - 1);
+        }
+        let step = |chance: &E::Chance, this: &mut Self| {
+            let mut next = env.clone();
+            next.resolve_chance_with(&outcome, chance).unwrap();
+            this.value(&next, depth - 1)
+        };
+        if chances.len() <= self.samples {
+            return chances
+                .iter()
+                .map(|(chance, p)| p * step(chance, self))
+                .sum();
+        }
+        let mut total = 0.0;
+        for _ in 0..self.samples {
+            let mut r = self.rng.gen::<f32>();
+            let (chance, _) = chances
+                .iter()
+                .find(|(_, p)| {
+                    r -= p;
+                    r < 0.0
+                })
+                .unwrap_or_else(|| chances.last().unwrap());
+            total += step(chance, self);
+        }
+        total / self.samples as f32
+    }
+}
+
+// the move with the best mean value over `num_worlds` sampled worlds, each searched `depth`
+// moves deep
+pub fn sparse_policy<R: Rng>(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    depth: usize,
+    samples: usize,
+    num_worlds: usize,
+    rng: &mut R,
+) -> Action {
+    let mut search =
+        SparseSampling::<HanabiEnv>::new(depth, samples, Box::new(FutureRewards), rng.gen())
+            .with_rollout(Box::new(heuristic_rollout));
+    let mut totals: Vec<(Action, f32)> = Vec::new();
+    for _ in 0..num_worlds.max(1) {
+        let (world, _) = HanabiEnv::determinize(public_info, private_info, rng);
+        for (action, value) in search.action_values(&world) {
+            match totals.iter_mut().find(|t| t.0 == action) {
+                Some(total) => total.1 += value,
+                None => totals.push((action, value)),
+            }
+        }
+    }
+    totals
+        .iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .unwrap()
+        .0
+}
+
+// the heuristic, then sparse_policy once the deck is down to ENDGAME_DECK_SIZE cards
+pub fn sparse_endgame_policy<R: Rng>(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    depth: usize,
+    samples: usize,
+    num_worlds: usize,
+    rng: &mut R,
+) -> Action {
+    match public_info.deck_total() <= ENDGAME_DECK_SIZE {
+        true => sparse_policy(public_info, private_info, depth, samples, num_worlds, rng),
+        false => heuristic_policy(public_info, private_info),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hanabi_env::{Card, Color, Suit};
+    use crate::position::Position;
+
+    #[test]
+    fn test_sparse_sampling() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let env = HanabiEnv::random(&mut rng);
+        let (public_info, private_info) =
+            (env.public_info(), env.private_info(env.current_player()));
+        let action = sparse_policy(&public_info, &private_info, 2, 2, 2, &mut rng);
+        assert!(env.actions().contains(&action));
+        // the same seed searches the same way
+        let search = |seed: u64| {
+            let mut search = SparseSampling::<HanabiEnv>::new(2, 2, Box::new(FutureRewards), seed);
+            search.action_values(&env)
+        };
+        assert_eq!(search(1), search(1));
+        let rollouts = |seed: u64| {
+            SparseSampling::<HanabiEnv>::new(1, 2, Box::new(FutureRewards), seed)
+                .with_rollout(Box::new(heuristic_rollout))
+                .action_values(&env)
+        };
+        assert_eq!(rollouts(1), rollouts(1));
+
+        // the mover knows they hold the R4 red needs, and nothing beats playing it. discarding
+        // the last copy is never searched
+        let position =
+            Position::parse("B1.B1.B2.B2.Y1 R4.?.?.?.? ?.?.?.?.? 03000 R4 4/3 -").unwrap();
+        let (world, _) =
+            HanabiEnv::determinize(&position.public_info, &position.private_info, &mut rng);
+        assert_eq!(
+            world.player_hand[0].id(),
+            Card::new(Color::Red, Suit::Four).id()
+        );
+        let mut search = SparseSampling::<HanabiEnv>::new(1, 3, Box::new(FutureRewards), 0);
+        let values = search.action_values(&world);
+        assert!(values.iter().all(|v| v.0 != Action::Discard(0)));
+        let best = values.iter().map(|v| v.1).fold(f32::NEG_INFINITY, f32::max);
+        let play = values.iter().find(|v| v.0 == Action::Play(0)).unwrap().1;
+        assert_eq!(play, best);
+        assert!(values.iter().any(|v| v.1 < best));
+    }
+}
@@ -0,0 +1,523 @@
+// JSON schema for saved positions (version 4). cards are "<color><rank>" using the short color
+// names W R B Y G, e.g. "R3", and empty hand slots are null. a hint is the set of colors & ranks
+// the card can still be, e.g. {"colors": "RB", "ranks": "12345"}, and null for empty slots.
+//
+// PrivateInfo: {"opponent_hand": [card x5]}
+// PublicInfo:  {"player_hints": [hint x5], "opponent_hints": [hint x5], "discard": [card...],
+//               "fireworks": {"W": 0, "R": 2, ...}, "blue_tokens": 8, "black_tokens": 4,
+//               "last_round": false, "last_round_turns_taken": 0,
+//               "hint_history": {"turn": 12, "lost": false, "clues": [{"turn": 11,
+//                                "clue": "red", "touched": [0, 3], "held": [0, 1, 3, 4]}, ...]},
+//               "rules": {"hand_size": 5, "clue_tokens": 8, "strikes": 3,
+//                         "copies": [3, 2, 2, 2, 1], "final_turns": 2, "black": false,
+//                         "colors": 5, "ranks": 5}}
+//              clues are newest first, a missing "hint_history" reads as none given yet.
+//              "held" are the slots still holding the cards the clue was given to. a missing
+//              "rules" reads as the standard game
+// HanabiEnv:   {"version": 4, "num_players": 2, every PublicInfo field, "player_hand": [card x5],
+//               "opponent_hand": [card x5], "deck": {"ordered": bool, "cards": [card...]}}
+// the player is always the one to move. an ordered deck lists cards in draw order, an unordered
+// deck is drawn from at random
+//
+// versions: 1 had no "num_players", every game was two player. 2 had no "hint_history". 3 had
+// no "rules"
+use crate::hanabi_env::{
+    max_score_with, Card, CardCollection, ClueTokens, Color, Deck, Fireworks, HanabiEnv, Hint,
+    HintEvent, HintHistory, PrivateInfo, PublicInfo, RuleSet, SearchPruning, StrikeTokens,
+};
+use crate::migrate::{current_version, upgrade, Migration};
+use crate::notation::{clue_text, parse_card, parse_clue};
+use serde_json::{json, Map, Value};
+
+fn add_num_players(value: &mut Value) -> Result<(), String> {
+    value["num_players"] = json!(2);
+    Ok(())
+}
+
+// the clues given before the upgrade are gone, so the hints can't be worked out from them
+fn add_hint_history(value: &mut Value) -> Result<(), String> {
+    value["hint_history"] = json!({"turn": 0, "clues": [], "lost": true});
+    Ok(())
+}
+
+fn add_rules(value: &mut Value) -> Result<(), String> {
+    value["rules"] = rules_to_json(&RuleSet::default());
+    Ok(())
+}
+
+const STATE_MIGRATIONS: &[Migration] = &[add_num_players, add_hint_history, add_rules];
+pub const STATE_VERSION: u64 = current_version(STATE_MIGRATIONS);
+
+pub fn card_to_json(card: Card) -> Value {
+    if card.is_some() {
+        Value::String(format!("{:?}", card))
+    } else {
+        Value::Null
+    }
+}
+
+pub fn card_from_json(value: &Value) -> Result<Card, String> {
+    match value {
+        Value::Null => Ok(Card::none()),
+        Value::String(text) => parse_card(text).ok_or_else(|| format!("invalid card '{}'", text)),
+        _ => Err(format!("expected a card, found {}", value)),
+    }
+}
+
+fn hand_to_json(hand: &[Card; 5]) -> Value {
+    Value::Array(hand.iter().map(|&c| card_to_json(c)).collect())
+}
+
+fn hand_from_json(value: &Value) -> Result<[Card; 5], String> {
+    let cards = value.as_array().ok_or("expected a hand")?;
+    if cards.len() != 5 {
+        return Err(format!("expected 5 cards in a hand, found {}", cards.len()));
+    }
+    let mut hand = [Card::none(); 5];
+    for (i, card) in cards.iter().enumerate() {
+        hand[i] = card_from_json(card)?;
+    }
+    Ok(hand)
+}
+
+pub fn cards_to_json(cards: &[Card]) -> Value {
+    Value::Array(cards.iter().map(|&c| card_to_json(c)).collect())
+}
+
+pub fn cards_from_json(value: &Value) -> Result<Vec<Card>, String> {
+    value
+        .as_array()
+        .ok_or("expected a list of cards")?
+        .iter()
+        .map(card_from_json)
+        .collect()
+}
+
+fn collection_to_json(collection: &CardCollection) -> Value {
+    let mut cards = Vec::new();
+    for id in 0..25 {
+        for _ in 0..collection.counts[id] {
+            cards.push(Card::from_id(id as u8));
+        }
+    }
+    cards_to_json(&cards)
+}
+
+fn collection_from_json(value: &Value) -> Result<CardCollection, String> {
+    let mut collection = CardCollection::empty();
+    for card in cards_from_json(value)? {
+        collection.add(card);
+    }
+    Ok(collection)
+}
+
+fn hint_to_json(hint: &Hint) -> Value {
+    if !hint.is_some() {
+        return Value::Null;
+    }
+    let colors: String = (0..5)
+        .filter(|&c| hint.color_mask() & (1 << c) != 0)
+        .map(|c| Color::from_id(c).short_name().to_string())
+        .collect();
+    let ranks: String = (0..5)
+        .filter(|&r| hint.suit_mask() & (1 << r) != 0)
+        .map(|r| (r + 1).to_string())
+        .collect();
+    json!({ "colors": colors, "ranks": ranks })
+}
+
+fn hint_from_json(value: &Value) -> Result<Hint, String> {
+    if value.is_null() {
+        return Ok(Hint::from_masks(0b100000, 0b100000));
+    }
+    let field = |name: &str| -> Result<&str, String> {
+        value[name]
+            .as_str()
+            .ok_or_else(|| format!("hint is missing '{}'", name))
+    };
+    let mut color = 0;
+    for letter in field("colors")?.chars() {
+        let id = (0..5)
+            .find(|&c| Color::from_id(c).short_name() == letter.to_string())
+            .ok_or_else(|| format!("invalid color '{}'", letter))?;
+        color |= 1 << id;
+    }
+    let mut suit = 0;
+    for digit in field("ranks")?.chars() {
+        match digit.to_digit(10) {
+            Some(rank) if (1..=5).contains(&rank) => suit |= 1 << (rank - 1),
+            _ => return Err(format!("invalid rank '{}'", digit)),
+        }
+    }
+    Ok(Hint::from_masks(color, suit))
+}
+
+fn hints_to_json(hints: &[Hint; 5]) -> Value {
+    Value::Array(hints.iter().map(hint_to_json).collect())
+}
+
+fn hints_from_json(value: &Value) -> Result<[Hint; 5], String> {
+    let values = value.as_array().ok_or("expected a list of hints")?;
+    if values.len() != 5 {
+        return Err(format!("expected 5 hints, found {}", values.len()));
+    }
+    let mut hints = [Hint::empty(); 5];
+    for (i, hint) in values.iter().enumerate() {
+        hints[i] = hint_from_json(hint)?;
+    }
+    Ok(hints)
+}
+
+fn fireworks_to_json(fireworks: &Fireworks) -> Value {
+    let mut map = Map::new();
+    for c in 0..5 {
+        map.insert(
+            Color::from_id(c).short_name().to_string(),
+            json!(fireworks.0[c as usize]),
+        );
+    }
+    Value::Object(map)
+}
+
+fn fireworks_from_json(value: &Value) -> Result<Fireworks, String> {
+    let mut fireworks = Fireworks([0; 5]);
+    for c in 0..5 {
+        let color = Color::from_id(c);
+        let name = color.short_name();
+        match value[name].as_u64() {
+            Some(level) if level <= 5 => fireworks.0[c as usize] = level as u8,
+            _ => return Err(format!("invalid fireworks level for {}", name)),
+        }
+    }
+    Ok(fireworks)
+}
+
+fn hint_history_to_json(history: &HintHistory) -> Value {
+    let clues: Vec<Value> = history
+        .iter()
+        .map(|e| {
+            json!({
+                "turn": e.turn,
+                "clue": clue_text(&e.clue),
+                "touched": e.touched_slots(),
+                "held": e.held_slots(),
+            })
+        })
+        .collect();
+    json!({"turn": history.turn, "clues": clues, "lost": history.lost})
+}
+
+fn rules_to_json(rules: &RuleSet) -> Value {
+    json!({
+        "hand_size": rules.hand_size,
+        "clue_tokens": rules.clue_tokens,
+        "strikes": rules.strikes,
+        "copies": rules.copies,
+        "final_turns": rules.final_turns,
+        "black": rules.black,
+        "colors": rules.colors,
+        "ranks": rules.ranks,
+    })
+}
+
+fn rules_from_json(value: &Value) -> Result<RuleSet, String> {
+    if value.is_null() {
+        return Ok(RuleSet::default());
+    }
+    let copies = value["copies"]
+        .as_array()
+        .filter(|copies| copies.len() == 5)
+        .ok_or("expected 5 'copies'")?;
+    let mut rules = RuleSet {
+        hand_size: u8_field(value, "hand_size")? as usize,
+        clue_tokens: u8_field(value, "clue_tokens")?,
+        strikes: u8_field(value, "strikes")?,
+        copies: [0; 5],
+        final_turns: u8_field(value, "final_turns")?,
+        black: value["black"].as_bool().unwrap_or(false),
+        colors: value["colors"].as_u64().unwrap_or(5).min(255) as u8,
+        ranks: value["ranks"].as_u64().unwrap_or(5).min(255) as u8,
+    };
+    for (i, n) in copies.iter().enumerate() {
+        rules.copies[i] = n
+            .as_u64()
+            .filter(|&n| n <= u8::MAX as u64)
+            .ok_or("invalid 'copies'")? as u8;
+    }
+    rules.check()?;
+    Ok(rules)
+}
+
+fn hint_history_from_json(value: &Value) -> Result<HintHistory, String> {
+    let mut history = HintHistory::default();
+    if value.is_null() {
+        return Ok(history);
+    }
+    let turn = |v: &Value| {
+        v.as_u64()
+            .filter(|&t| t <= u16::MAX as u64)
+            .ok_or("missing or invalid clue turn")
+    };
+    history.turn = turn(&value["turn"])? as u16;
+    history.lost = value["lost"].as_bool().unwrap_or(false);
+    let slots = |value: &Value| -> Result<u8, String> {
+        let mut mask = 0;
+        for slot in value.as_array().ok_or("expected a list of slots")? {
+            match slot.as_u64() {
+                Some(i) if i < 5 => mask |= 1 << i,
+                _ => return Err(format!("invalid slot {}", slot)),
+            }
+        }
+        Ok(mask)
+    };
+    for clue in value["clues"]
+        .as_array()
+        .ok_or("expected a list of clues")?
+    {
+        let text = clue["clue"].as_str().ok_or("missing clue")?;
+        let touched = slots(&clue["touched"])?;
+        history.push_event(HintEvent {
+            turn: turn(&clue["turn"])? as u16,
+            clue: parse_clue(text).ok_or_else(|| format!("invalid clue '{}'", text))?,
+            touched,
+            // what the untouched cards learned isn't known without it
+            held: match clue["held"] {
+                Value::Null => touched,
+                ref held => slots(held)?,
+            },
+        })?;
+    }
+    Ok(history)
+}
+
+fn u8_field(value: &Value, name: &str) -> Result<u8, String> {
+    value[name]
+        .as_u64()
+        .filter(|&v| v <= u8::MAX as u64)
+        .map(|v| v as u8)
+        .ok_or_else(|| format!("missing or invalid '{}'", name))
+}
+
+fn bool_field(value: &Value, name: &str) -> Result<bool, String> {
+    value[name]
+        .as_bool()
+        .ok_or_else(|| format!("missing or invalid '{}'", name))
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    serde_json::from_str(text).map_err(|e| format!("invalid json: {}", e))
+}
+
+impl PrivateInfo {
+    pub fn to_json_value(&self) -> Value {
+        json!({ "opponent_hand": hand_to_json(&self.opponent_hand) })
+    }
+
+    pub fn from_json_value(value: &Value) -> Result<Self, String> {
+        Ok(PrivateInfo {
+            opponent_hand: hand_from_json(&value["opponent_hand"])?,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_json_value()).unwrap()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        Self::from_json_value(&parse(text)?)
+    }
+}
+
+impl PublicInfo {
+    pub fn to_json_value(&self) -> Value {
+        json!({
+            "player_hints": hints_to_json(&self.player_hints),
+            "opponent_hints": hints_to_json(&self.opponent_hints),
+            "discard": collection_to_json(&self.discard),
+            "fireworks": fireworks_to_json(&self.fireworks),
+            "blue_tokens": self.blue_tokens.get(),
+            "black_tokens": self.black_tokens.get(),
+            "last_round": self.last_round,
+            "last_round_turns_taken": self.last_round_turns_taken,
+            "hint_history": hint_history_to_json(&self.hint_history),
+            "rules": rules_to_json(&self.rules),
+        })
+    }
+
+    pub fn from_json_value(value: &Value) -> Result<Self, String> {
+        let discard = collection_from_json(&value["discard"])?;
+        let fireworks = fireworks_from_json(&value["fireworks"])?;
+        let rules = rules_from_json(&value["rules"])?;
+        Ok(PublicInfo {
+            player_hints: hints_from_json(&value["player_hints"])?,
+            opponent_hints: hints_from_json(&value["opponent_hints"])?,
+            discard,
+            fireworks,
+            max_score: max_score_with(&rules, &fireworks, &discard),
+            blue_tokens: ClueTokens::new(u8_field(value, "blue_tokens")?),
+            black_tokens: StrikeTokens::new(u8_field(value, "black_tokens")?),
+            last_round: bool_field(value, "last_round")?,
+            last_round_turns_taken: u8_field(value, "last_round_turns_taken")?,
+            hint_history: hint_history_from_json(&value["hint_history"])?,
+            rules,
+            pruning: SearchPruning::default(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_json_value()).unwrap()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        Self::from_json_value(&parse(text)?)
+    }
+}
+
+impl HanabiEnv {
+    pub fn to_json(&self) -> String {
+        let deck = match &self.deck {
+            Deck::Ordered(cards) => {
+                let order: Vec<Card> = cards.iter().rev().cloned().collect();
+                json!({ "ordered": true, "cards": cards_to_json(&order) })
+            }
+            Deck::Multiset(cards) => {
+                json!({ "ordered": false, "cards": collection_to_json(cards) })
+            }
+        };
+        let mut value = json!({
+            "version": STATE_VERSION,
+            "num_players": 2,
+            "player_hand": hand_to_json(&self.player_hand),
+            "opponent_hand": hand_to_json(&self.opponent_hand),
+            "deck": deck,
+        });
+        let public = PublicInfo {
+            player_hints: self.player_hints,
+            opponent_hints: self.opponent_hints,
+            discard: self.discard,
+            blue_tokens: self.blue_tokens,
+            black_tokens: self.black_tokens,
+            fireworks: self.fireworks,
+            max_score: self.max_score,
+            last_round: self.last_round,
+            last_round_turns_taken: self.last_round_turns_taken,
+            hint_history: self.hint_history.clone(),
+            rules: self.rules,
+            pruning: self.pruning,
+        };
+        if let (Value::Object(map), Value::Object(public)) = (&mut value, public.to_json_value()) {
+            map.extend(public);
+        }
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let value = upgrade(parse(text)?, "state", STATE_MIGRATIONS)?;
+        match value["num_players"].as_u64() {
+            Some(2) => {}
+            players => return Err(format!("unsupported number of players {:?}", players)),
+        }
+        let public = PublicInfo::from_json_value(&value)?;
+        let deck_cards = cards_from_json(&value["deck"]["cards"])?;
+        let deck = if bool_field(&value["deck"], "ordered")? {
+            Deck::from_order(&deck_cards)
+        } else {
+            let mut cards = CardCollection::empty();
+            for card in deck_cards {
+                cards.add(card);
+            }
+            Deck::Multiset(cards)
+        };
+        Ok(HanabiEnv {
+            player_hand: hand_from_json(&value["player_hand"])?,
+            player_hints: public.player_hints,
+            opponent_hand: hand_from_json(&value["opponent_hand"])?,
+            opponent_hints: public.opponent_hints,
+            deck,
+            discard: public.discard,
+            blue_tokens: public.blue_tokens,
+            black_tokens: public.black_tokens,
+            fireworks: public.fireworks,
+            max_score: public.max_score,
+            last_round: public.last_round,
+            last_round_turns_taken: public.last_round_turns_taken,
+            hint_history: public.hint_history,
+            rules: public.rules,
+            pruning: public.pruning,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::{Env, HasEnd, HasKey};
+    use crate::rand::prelude::SliceRandom;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let order = match Deck::shuffled(&mut rng) {
+            Deck::Ordered(cards) => cards,
+            _ => unreachable!(),
+        };
+        let mut env = HanabiEnv::from_deck_order(&order);
+        for _ in 0..20 {
+            if env.is_over() {
+                break;
+            }
+            let action = *env.actions().choose(&mut rng).unwrap();
+            env.step(&action, &mut rng);
+        }
+
+        let loaded = HanabiEnv::from_json(&env.to_json()).unwrap();
+        assert_eq!(loaded.to_json(), env.to_json());
+        assert_eq!(loaded.public_info().key(), env.public_info().key());
+        assert_eq!(loaded.player_hand, env.player_hand);
+        assert_eq!(loaded.deck.peek_known(), env.deck.peek_known());
+        assert_eq!(loaded.hint_history, env.hint_history);
+
+        let public = PublicInfo::from_json(&env.public_info().to_json()).unwrap();
+        assert_eq!(public.key(), env.public_info().key());
+        assert_eq!(public.hint_history, env.hint_history);
+        let private =
+            PrivateInfo::from_json(&env.private_info(env.current_player()).to_json()).unwrap();
+        assert_eq!(private, env.private_info(env.current_player()));
+
+        assert!(HanabiEnv::from_json("{\"version\": 2}").is_err());
+        assert!(HanabiEnv::from_json(
+            &env.to_json()
+                .replace("\"num_players\": 2", "\"num_players\": 3")
+        )
+        .is_err());
+        assert!(PublicInfo::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn test_reads_version_1() {
+        let v1 = include_str!("../fixtures/state_v1.json");
+        let env = HanabiEnv::from_json(v1).unwrap();
+        let saved = env.to_json();
+        assert!(saved.contains("\"version\": 4"));
+        assert!(saved.contains("\"num_players\": 2"));
+        assert_eq!(HanabiEnv::from_json(&saved).unwrap().to_json(), saved);
+        // the clues given before the upgrade aren't known
+        assert!(env.public_info().hints_from_history().is_none());
+
+        // the v3 file holds the same game as the v1 file
+        let (old, new) = (parse(v1).unwrap(), parse(&saved).unwrap());
+        for field in [
+            "player_hand",
+            "opponent_hand",
+            "deck",
+            "discard",
+            "fireworks",
+        ]
+        .iter()
+        {
+            assert_eq!(old[field], new[field], "{}", field);
+        }
+    }
+}
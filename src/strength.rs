@@ -0,0 +1,131 @@
+// named strength levels for partnering people of different skill. each level blends a search on
+// a rollout budget with the heuristic and adds random moves further down the ladder. the search is
+// flat::improvement_policy, which only leaves the heuristic's move for one its rollouts show is
+// better, so more rollouts make a stronger level.
+// `calibrated_score` is the self-play mean a level scored over seeds 0..200, `calibrate-strength`
+// measures it again
+use crate::env::Env;
+use crate::evaluator::heuristic_policy;
+use crate::flat::improvement_policy;
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::rand::rngs::StdRng;
+use crate::rand::Rng;
+use crate::registry::register_agent;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StrengthLevel {
+    pub name: &'static str,
+    // improvement_policy rollouts per move, 0 never searches
+    pub rollouts: usize,
+    // chance of playing the heuristic's move instead of searching
+    pub heuristic_blend: f32,
+    // chance of a uniformly random legal move, checked first
+    pub blunder_rate: f32,
+    pub calibrated_score: f32,
+}
+
+// weakest first. the lower levels are the heuristic with blunders, the upper ones search. the
+// heuristic on its own scores 18.6
+pub const STRENGTH_LADDER: [StrengthLevel; 4] = [
+    StrengthLevel {
+        name: "beginner",
+        rollouts: 0,
+        heuristic_blend: 1.0,
+        blunder_rate: 0.3,
+        calibrated_score: 8.05,
+    },
+    StrengthLevel {
+        name: "casual",
+        rollouts: 0,
+        heuristic_blend: 1.0,
+        blunder_rate: 0.1,
+        calibrated_score: 16.12,
+    },
+    StrengthLevel {
+        name: "intermediate",
+        rollouts: 200,
+        heuristic_blend: 0.5,
+        blunder_rate: 0.0,
+        calibrated_score: 19.32,
+    },
+    StrengthLevel {
+        name: "expert",
+        rollouts: 1_000,
+        heuristic_blend: 0.0,
+        blunder_rate: 0.0,
+        calibrated_score: 20.35,
+    },
+];
+
+pub fn strength_level(name: &str) -> Result<&'static StrengthLevel, String> {
+    STRENGTH_LADDER
+        .iter()
+        .find(|level| level.name == name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = STRENGTH_LADDER.iter().map(|l| l.name).collect();
+            format!("unknown strength '{}', expected one of {:?}", name, names)
+        })
+}
+
+pub fn strength_policy(
+    level: &StrengthLevel,
+    public_info: PublicInfo,
+    private_info: PrivateInfo,
+    rng: &mut StdRng,
+) -> Action {
+    let roll: f32 = rng.gen();
+    if roll < level.blunder_rate {
+        let (env, _) = HanabiEnv::determinize(&public_info, &private_info, rng);
+        return env.random_action(rng);
+    }
+    if level.rollouts == 0 || roll < level.blunder_rate + level.heuristic_blend {
+        return heuristic_policy(&public_info, &private_info);
+    }
+    improvement_policy(&public_info, &private_info, level.rollouts, rng)
+}
+
+// "strength-<name>" for every level of the ladder
+pub fn register_strength_levels() {
+    for level in STRENGTH_LADDER.iter() {
+        register_agent(&format!("strength-{}", level.name), move || {
+            Box::new(move |public_info, private_info, rng| {
+                strength_policy(level, public_info, private_info, rng)
+            })
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::HasEnd;
+    use crate::rand::SeedableRng;
+
+    #[test]
+    fn test_strength_levels() {
+        assert_eq!(strength_level("casual").unwrap().blunder_rate, 0.1);
+        assert!(strength_level("grandmaster").is_err());
+        for pair in STRENGTH_LADDER.windows(2) {
+            assert!(pair[0].calibrated_score <= pair[1].calibrated_score);
+        }
+
+        // a level that never searches or blunders plays the heuristic's moves
+        let plain = StrengthLevel {
+            name: "plain",
+            rollouts: 0,
+            heuristic_blend: 1.0,
+            blunder_rate: 0.0,
+            calibrated_score: 0.0,
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        while !env.is_over() {
+            let (public_info, private_info) =
+                (env.public_info(), env.private_info(env.current_player()));
+            let action =
+                strength_policy(&plain, public_info.clone(), private_info.clone(), &mut rng);
+            assert_eq!(action, heuristic_policy(&public_info, &private_info));
+            env.step(&action, &mut rng);
+        }
+    }
+}
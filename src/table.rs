@@ -0,0 +1,189 @@
+// read-only lookup tables of fixed size keys to f32 values, for opening books & endgame caches
+// too large to rebuild or copy into memory on every start. the file is a header followed by
+// the records sorted by key, so a lookup is a binary search over the file's bytes:
+//
+//   "HTBL" | version u32 | key length u32 | record count u64 | (key bytes, f32 value)*
+//
+// all integers and values are little endian. with the mmap feature the file is mapped instead
+// of read, so opening costs the same whatever its size
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+const MAGIC: &[u8; 4] = b"HTBL";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 20;
+
+enum Bytes {
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+    #[cfg_attr(feature = "mmap", allow(dead_code))]
+    Owned(Vec<u8>),
+}
+
+impl std::ops::Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "mmap")]
+            Bytes::Mapped(map) => map,
+            Bytes::Owned(bytes) => bytes,
+        }
+    }
+}
+
+pub struct Table {
+    bytes: Bytes,
+    key_len: usize,
+    len: usize,
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[at..at + 4]);
+    u32::from_le_bytes(buf)
+}
+
+#[cfg(feature = "mmap")]
+fn load(path: &str) -> std::io::Result<Bytes> {
+    let file = File::open(path)?;
+    // safety: tables are written once and never modified while a reader has them open
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(Bytes::Mapped(map))
+}
+
+#[cfg(not(feature = "mmap"))]
+fn load(path: &str) -> std::io::Result<Bytes> {
+    Ok(Bytes::Owned(std::fs::read(path)?))
+}
+
+impl Table {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let bytes = load(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::from_bytes(bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn from_bytes(bytes: Bytes) -> Result<Self, String> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err("not a table file".to_string());
+        }
+        let version = read_u32(&bytes, 4);
+        if version != VERSION {
+            return Err(format!("unsupported table version {}", version));
+        }
+        let key_len = read_u32(&bytes, 8) as usize;
+        let mut count = [0; 8];
+        count.copy_from_slice(&bytes[12..20]);
+        let len = u64::from_le_bytes(count) as usize;
+        if bytes.len() != HEADER_LEN + len * (key_len + 4) {
+            return Err(format!(
+                "expected {} records of {} bytes, file is {} bytes",
+                len,
+                key_len + 4,
+                bytes.len()
+            ));
+        }
+        Ok(Self {
+            bytes,
+            key_len,
+            len,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn key_len(&self) -> usize {
+        self.key_len
+    }
+
+    pub fn record(&self, i: usize) -> (&[u8], f32) {
+        let start = HEADER_LEN + i * (self.key_len + 4);
+        let key = &self.bytes[start..start + self.key_len];
+        let value = f32::from_bits(read_u32(&self.bytes, start + self.key_len));
+        (key, value)
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<f32> {
+        if key.len() != self.key_len {
+            return None;
+        }
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let mid = (low + high) / 2;
+            let (mid_key, value) = self.record(mid);
+            match mid_key.cmp(key) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(value),
+            }
+        }
+        None
+    }
+}
+
+// sorts `entries` by key and writes them out, every key must be `key_len` bytes
+pub fn write_table(
+    path: &str,
+    key_len: usize,
+    mut entries: Vec<(Vec<u8>, f32)>,
+) -> Result<(), String> {
+    if let Some((key, _)) = entries.iter().find(|(key, _)| key.len() != key_len) {
+        return Err(format!(
+            "key of {} bytes in a table of {} byte keys",
+            key.len(),
+            key_len
+        ));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.dedup_by(|a, b| a.0 == b.0);
+
+    let write = || -> std::io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(key_len as u32).to_le_bytes())?;
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for (key, value) in entries.iter() {
+            writer.write_all(key)?;
+            writer.write_all(&value.to_bits().to_le_bytes())?;
+        }
+        writer.flush()
+    };
+    write().map_err(|e| format!("{}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_lookups() {
+        let path = std::env::temp_dir().join(format!("hanabi-table-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let entries: Vec<(Vec<u8>, f32)> = (0..200u16)
+            .rev()
+            .map(|i| (vec![(i % 7) as u8, (i / 7) as u8, 3], i as f32 / 2.0))
+            .collect();
+        write_table(path, 3, entries.clone()).unwrap();
+
+        let table = Table::open(path).unwrap();
+        assert_eq!(table.len(), 200);
+        assert!(!table.is_empty());
+        for (key, value) in entries.iter() {
+            assert_eq!(table.get(key), Some(*value));
+        }
+        assert_eq!(table.get(&[9, 0, 3]), None);
+        assert_eq!(table.get(&[0, 0]), None);
+
+        assert!(write_table(path, 2, entries).is_err());
+        std::fs::write(path, b"HTBL").unwrap();
+        assert!(Table::open(path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
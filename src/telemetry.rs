@@ -0,0 +1,133 @@
+// a JSON object per search decision, appended to the log opened with `open`. searches call
+// `record` whether a log is open or not, so the harness decides what gets logged:
+//
+//   {"seed":3,"turn":12,"searcher":"mcts","legal_actions":[...],"budget":10000,
+//    "actions":[{"action":"Play(...)","visits":812.0,"value":0.61},...],"chosen":"...","millis":41.2}
+//
+// seed & turn come from whatever game the current thread is playing, see `set_turn`
+use crate::env::Env;
+use crate::hanabi_env::{Action, HanabiEnv, PrivateInfo, PublicInfo};
+use crate::rand::rngs::StdRng;
+use crate::rand::SeedableRng;
+use serde_json::json;
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+
+static LOG: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+
+thread_local! {
+    static TURN: Cell<Option<(u64, usize)>> = const { Cell::new(None) };
+}
+
+#[derive(Clone, Debug)]
+pub struct ActionStat {
+    pub action: Action,
+    pub visits: f32,
+    pub value: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Decision {
+    pub searcher: &'static str,
+    pub legal_actions: Vec<Action>,
+    // rollouts or explores the search was given
+    pub budget: usize,
+    pub actions: Vec<ActionStat>,
+    pub chosen: Action,
+    pub millis: f32,
+}
+
+pub fn open(path: &str) -> std::io::Result<()> {
+    *LOG.lock().unwrap() = Some(BufWriter::new(File::create(path)?));
+    Ok(())
+}
+
+// flushes and stops logging
+pub fn close() -> std::io::Result<()> {
+    match LOG.lock().unwrap().take() {
+        Some(mut writer) => writer.flush(),
+        None => Ok(()),
+    }
+}
+
+pub fn enabled() -> bool {
+    LOG.lock().unwrap().is_some()
+}
+
+// the game & 1-based turn the calling thread is deciding for
+pub fn set_turn(seed: u64, turn: usize) {
+    TURN.with(|t| t.set(Some((seed, turn))));
+}
+
+// the mover's legal moves, read off a world sampled with an rng of its own. `seed` is one the
+// search already drew from the game's rng: drawing from that rng here would make a logged game
+// play out differently from the same game unlogged
+pub fn legal_actions(
+    public_info: &PublicInfo,
+    private_info: &PrivateInfo,
+    seed: u64,
+) -> Vec<Action> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let (env, _) = HanabiEnv::determinize(public_info, private_info, &mut rng);
+    env.actions()
+}
+
+fn to_json(decision: &Decision, turn: Option<(u64, usize)>) -> serde_json::Value {
+    let action_name = |a: &Action| format!("{:?}", a);
+    json!({
+        "seed": turn.map(|(seed, _)| seed),
+        "turn": turn.map(|(_, turn)| turn),
+        "searcher": decision.searcher,
+        "legal_actions": decision.legal_actions.iter().map(action_name).collect::<Vec<_>>(),
+        "budget": decision.budget,
+        "actions": decision
+            .actions
+            .iter()
+            .map(|s| json!({"action": action_name(&s.action), "visits": s.visits, "value": s.value}))
+            .collect::<Vec<_>>(),
+        "chosen": action_name(&decision.chosen),
+        "millis": decision.millis,
+    })
+}
+
+pub fn record(decision: &Decision) {
+    let mut log = LOG.lock().unwrap();
+    if let Some(writer) = log.as_mut() {
+        let line = to_json(decision, TURN.with(|t| t.get()));
+        writeln!(writer, "{}", line).expect("failed to write telemetry");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hanabi_env::Color;
+
+    #[test]
+    fn test_decision_json() {
+        let decision = Decision {
+            searcher: "flat-mc",
+            legal_actions: vec![Action::Play(0), Action::ColorHint(Color::Red)],
+            budget: 100,
+            actions: vec![ActionStat {
+                action: Action::ColorHint(Color::Red),
+                visits: 60.0,
+                value: 0.5,
+            }],
+            chosen: Action::ColorHint(Color::Red),
+            millis: 1.5,
+        };
+        let value = to_json(&decision, Some((7, 3)));
+        assert_eq!(value["seed"], 7);
+        assert_eq!(value["turn"], 3);
+        assert_eq!(value["legal_actions"].as_array().unwrap().len(), 2);
+        assert_eq!(value["actions"][0]["visits"], 60.0);
+        assert_eq!(
+            value["chosen"],
+            format!("{:?}", Action::ColorHint(Color::Red))
+        );
+        assert!(to_json(&decision, None)["seed"].is_null());
+    }
+}
@@ -0,0 +1,319 @@
+// interactive analysis board for a replay: the position, the mover's beliefs about their own
+// hand and search statistics that keep updating while the search runs in the background.
+// left/right step through the replay, home/end jump to either end, q quits
+use crate::advisor::suggestions;
+use crate::beliefs::Beliefs;
+use crate::env::{Env, HasEnd};
+use crate::hanabi_env::{Card, Color, HanabiEnv};
+use crate::mcts::MCTS;
+use crate::replay::Replay;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color as TermColor, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use ratatui::Frame;
+use std::time::Duration;
+
+// explores run between checks for key presses
+const EXPLORES_PER_TICK: usize = 500;
+
+struct Analysis {
+    replay: Replay,
+    // numbered like Replay::env_at, turns.len() + 1 is the final position
+    turn: usize,
+    env: HanabiEnv,
+    mcts: Option<MCTS<HanabiEnv>>,
+    explores: usize,
+    max_explores: usize,
+    // bounds the search tree's memory, see MCTS::max_nodes
+    max_nodes: Option<usize>,
+}
+
+impl Analysis {
+    fn new(
+        replay: Replay,
+        turn: usize,
+        max_explores: usize,
+        max_nodes: Option<usize>,
+    ) -> Result<Self, String> {
+        let env = replay.env_at(turn)?;
+        let mut analysis = Self {
+            replay,
+            turn,
+            env,
+            mcts: None,
+            explores: 0,
+            max_explores,
+            max_nodes,
+        };
+        analysis.goto(turn)?;
+        Ok(analysis)
+    }
+
+    fn last_turn(&self) -> usize {
+        self.replay.turns.len() + 1
+    }
+
+    // moves to another position and starts its search over
+    fn goto(&mut self, turn: usize) -> Result<(), String> {
+        self.turn = turn.max(1).min(self.last_turn());
+        self.env = self.replay.env_at(self.turn)?;
+        self.explores = 0;
+        self.mcts = if self.env.is_over() {
+            None
+        } else {
+            let mcts = MCTS::with_capacity(
+                &self.env.public_info(),
+                &self.env.private_info(self.env.current_player()),
+                self.max_nodes.unwrap_or(self.max_explores),
+                self.turn as u64,
+            );
+            Some(match self.max_nodes {
+                Some(max_nodes) => mcts.with_max_nodes(max_nodes),
+                None => mcts,
+            })
+        };
+        Ok(())
+    }
+
+    fn search(&mut self, n: usize) {
+        if let Some(mcts) = self.mcts.as_mut() {
+            let n = n.min(self.max_explores - self.explores);
+            mcts.explore_n(n);
+            self.explores += n;
+        }
+    }
+}
+
+fn term_color(color: Color) -> TermColor {
+    match color {
+        Color::White => TermColor::White,
+        Color::Red => TermColor::Red,
+        Color::Blue => TermColor::Blue,
+        Color::Yellow => TermColor::Yellow,
+        Color::Green => TermColor::Green,
+    }
+}
+
+fn card_span(card: Card) -> Span<'static> {
+    if card.is_some() {
+        Span::styled(
+            format!("{:?} ", card),
+            Style::default().fg(term_color(card.color())),
+        )
+    } else {
+        Span::raw("-- ")
+    }
+}
+
+fn board_lines(analysis: &Analysis) -> Vec<Line<'static>> {
+    let env = &analysis.env;
+    let mut fireworks = vec![Span::raw("fireworks ")];
+    for c in 0..5 {
+        let color = Color::from_id(c);
+        fireworks.push(Span::styled(
+            format!("{}{} ", color.short_name(), env.fireworks.0[c as usize]),
+            Style::default().fg(term_color(color)),
+        ));
+    }
+    fireworks.push(Span::raw(format!(
+        "| blue={} black={} deck={}",
+        env.blue_tokens,
+        env.black_tokens,
+        env.deck.remaining()
+    )));
+
+    let mut partner = vec![Span::raw("partner   ")];
+    partner.extend(env.opponent_hand.iter().map(|&c| card_span(c)));
+    let next = match analysis.replay.turns.get(analysis.turn - 1) {
+        Some(turn) => format!("next      {}", turn),
+        None => "next      (game over)".to_string(),
+    };
+    vec![
+        Line::from(format!(
+            "position {}/{}",
+            analysis.turn,
+            analysis.last_turn()
+        )),
+        Line::from(fireworks),
+        Line::from(format!("discard   {}", env.discard)),
+        Line::from(partner),
+        Line::from(format!("          {:?}", env.opponent_hints)),
+        Line::from(format!("own hints {:?}", env.player_hints)),
+        Line::from(next),
+    ]
+}
+
+fn belief_lines(analysis: &Analysis) -> Vec<Line<'static>> {
+    let env = &analysis.env;
+    let beliefs = Beliefs::new(&env.public_info(), &env.private_info(env.current_player()));
+    (0..5)
+        .map(|i| {
+            if !env.player_hints[i].is_some() {
+                return Line::from(format!("c{}  empty", i + 1));
+            }
+            let mut ids: Vec<usize> = (0..25).filter(|&id| beliefs.slots[i][id] > 0.0).collect();
+            ids.sort_by(|&a, &b| {
+                beliefs.slots[i][b]
+                    .partial_cmp(&beliefs.slots[i][a])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            let mut spans = vec![Span::raw(format!(
+                "c{}  {:.1} bits  ",
+                i + 1,
+                beliefs.slot_entropy(i)
+            ))];
+            for &id in ids.iter().take(4) {
+                let card = Card::from_id(id as u8);
+                spans.push(card_span(card));
+                spans.push(Span::raw(format!(
+                    "{:>3.0}%  ",
+                    100.0 * beliefs.slots[i][id]
+                )));
+            }
+            Line::from(spans)
+        })
+        .collect()
+}
+
+fn search_table(analysis: &Analysis) -> Table<'static> {
+    let rows: Vec<Row> = match analysis.mcts.as_ref() {
+        Some(mcts) => suggestions(mcts)
+            .iter()
+            .map(|s| {
+                Row::new(vec![
+                    format!("{:?}", s.action),
+                    format!("{:.0}", s.visits),
+                    format!("{:.3}", s.mean_reward),
+                ])
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    Table::new(
+        rows,
+        [
+            Constraint::Min(28),
+            Constraint::Length(8),
+            Constraint::Length(8),
+        ],
+    )
+    .header(
+        Row::new(vec!["action", "visits", "mean"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(Block::bordered().title(format!(
+        "search ({}/{} explores)",
+        analysis.explores, analysis.max_explores
+    )))
+}
+
+fn draw(frame: &mut Frame, analysis: &Analysis) {
+    let rows = Layout::vertical([
+        Constraint::Length(9),
+        Constraint::Min(7),
+        Constraint::Length(1),
+    ])
+    .split(frame.area());
+    let columns =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).split(rows[1]);
+
+    frame.render_widget(
+        Paragraph::new(board_lines(analysis)).block(Block::bordered().title("board")),
+        rows[0],
+    );
+    frame.render_widget(
+        Paragraph::new(belief_lines(analysis)).block(Block::bordered().title("own hand beliefs")),
+        columns[0],
+    );
+    frame.render_widget(search_table(analysis), columns[1]);
+    frame.render_widget(Paragraph::new("←/→ step  home/end jump  q quit"), rows[2]);
+}
+
+pub fn run(
+    replay: Replay,
+    turn: usize,
+    max_explores: usize,
+    max_nodes: Option<usize>,
+) -> Result<(), String> {
+    let mut analysis = Analysis::new(replay, turn, max_explores, max_nodes)?;
+    let mut terminal = ratatui::init();
+    let result = (|| -> Result<(), String> {
+        loop {
+            terminal
+                .draw(|frame| draw(frame, &analysis))
+                .map_err(|e| e.to_string())?;
+
+            // search while there's nothing to react to
+            if !event::poll(Duration::from_millis(10)).map_err(|e| e.to_string())? {
+                analysis.search(EXPLORES_PER_TICK);
+                continue;
+            }
+            match event::read().map_err(|e| e.to_string())? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Right | KeyCode::Char('l') => analysis.goto(analysis.turn + 1)?,
+                    KeyCode::Left | KeyCode::Char('h') => analysis.goto(analysis.turn - 1)?,
+                    KeyCode::Home => analysis.goto(1)?,
+                    KeyCode::End => analysis.goto(analysis.last_turn())?,
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::heuristic_policy;
+    use crate::rand::rngs::StdRng;
+    use crate::rand::SeedableRng;
+    use crate::record::GameRecord;
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    #[test]
+    fn test_draw_analysis() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut env = HanabiEnv::random(&mut rng);
+        let initial = env.clone();
+        let mut record = GameRecord::new(["a".to_string(), "b".to_string()]);
+        while !env.is_over() {
+            let action =
+                heuristic_policy(&env.public_info(), &env.private_info(env.current_player()));
+            record.step(&mut env, &action, &mut rng);
+        }
+        let replay = Replay::from_game(&initial, &record);
+
+        let mut analysis = Analysis::new(replay, 3, 1_000, None).unwrap();
+        analysis.search(600);
+        analysis.search(600);
+        assert_eq!(analysis.explores, 1_000);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|frame| draw(frame, &analysis)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("position 3/"));
+        assert!(screen.contains("search (1000/1000 explores)"));
+        assert!(screen.contains("visits"));
+
+        // stepping past either end stays on the replay, the final position has nothing to search
+        analysis.goto(0).unwrap();
+        assert_eq!(analysis.turn, 1);
+        let last = analysis.last_turn();
+        analysis.goto(last + 5).unwrap();
+        assert_eq!(analysis.turn, last);
+        assert!(analysis.mcts.is_none());
+    }
+}